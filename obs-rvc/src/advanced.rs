@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+pub(crate) const ADVANCED_CONFIG_FILE: &str = "advanced.toml";
+
+/// Knobs deliberately kept out of the properties view. Read from `advanced.toml` in the
+/// plugin data directory; every key is optional and falls back to the defaults below.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct AdvancedConfig {
+    /// Default SOLA search window of new filters, rounded to whole 10 ms blocks.
+    pub sola_search_ms: f64,
+    /// How long the worker sleeps waiting for input before checking its state again.
+    pub worker_wait_timeout_ms: u64,
+    /// Audio blocks buffered between the audio callback and the worker, in either direction.
+    pub input_queue_capacity: usize,
+    pub output_queue_capacity: usize,
+    /// Splits the worker into a thread that prepares and sends each frame and one that waits for
+    /// the engine and finishes it, so that a frame converts while the next is prepared. The
+    /// stages within the engine still convert one frame at a time.
+    pub pipelined_worker: bool,
+    /// Threads onnxruntime uses within an operator, 0 leaves the choice to onnxruntime.
+    pub intra_threads: usize,
+    /// Neighbours blended per frame by index retrieval, and the power of the inverse squared
+    /// distance they are weighted by.
+    pub index_top_k: usize,
+    pub index_weight_exponent: f32,
+    /// Port of the localhost metrics endpoint, 0 keeps it off.
+    pub metrics_port: u16,
+    /// The least important messages written to the OBS log: error, warn, info or debug.
+    pub log_level: String,
+}
+
+impl Default for AdvancedConfig {
+    fn default() -> Self {
+        AdvancedConfig {
+            sola_search_ms: 10.0,
+            worker_wait_timeout_ms: 1000,
+            input_queue_capacity: 120,
+            output_queue_capacity: 200,
+            pipelined_worker: false,
+            intra_threads: 0,
+            index_top_k: 8,
+            index_weight_exponent: 2.0,
+            metrics_port: 0,
+            log_level: "info".to_string(),
+        }
+    }
+}
+
+impl AdvancedConfig {
+    /// A missing file is not an error, a malformed one is.
+    pub fn load(data_path: &Path) -> Result<Self, String> {
+        let path = data_path.join(ADVANCED_CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&path).map_err(|e| format!("{:?}: {}", path, e))?;
+        Self::parse(&content).map_err(|e| format!("{:?}: {}", path, e))
+    }
+
+    fn parse(content: &str) -> Result<Self, toml::de::Error> {
+        let config: AdvancedConfig = toml::from_str(content)?;
+        Ok(AdvancedConfig {
+            sola_search_ms: config.sola_search_ms.max(10.0),
+            input_queue_capacity: config.input_queue_capacity.max(1),
+            output_queue_capacity: config.output_queue_capacity.max(1),
+            index_top_k: config.index_top_k.max(1),
+            index_weight_exponent: config.index_weight_exponent.max(0.0),
+            ..config
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_advanced_config() {
+        let config = AdvancedConfig::parse("sola_search_ms = 30.0\ninput_queue_capacity = 0\n").unwrap();
+        assert_eq!(config.sola_search_ms, 30.0);
+        assert_eq!(config.input_queue_capacity, 1);
+        assert_eq!(config.output_queue_capacity, 200);
+        assert_eq!(config.worker_wait_timeout_ms, 1000);
+        assert!(!config.pipelined_worker);
+        assert_eq!(config.index_top_k, 8);
+        assert_eq!(config.log_level, "info");
+
+        let config = AdvancedConfig::parse("index_top_k = 0\nindex_weight_exponent = -1.0\n").unwrap();
+        assert_eq!(config.index_top_k, 1);
+        assert_eq!(config.index_weight_exponent, 0.0);
+
+        assert!(AdvancedConfig::parse("sola_search_ms = \"long\"").is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use std::time::{Duration, Instant};
+
+use ndarray::Array1;
+use parking_lot::Mutex;
+
+use crate::{
+    locale::{text, text_with},
+    rvcadapter::{RvcAdapterError, RvcInfer, StageTimes},
+};
+
+pub(crate) const BENCHMARK_FRAMES: usize = 50;
+// converted first and left out of the times, the first runs compile kernels and fill caches
+const WARMUP_FRAMES: usize = 3;
+// share of its frame a conversion may take to keep up with some room for the rest of the system,
+// a little under where the adaptive sample length starts growing the frames
+const SAFE_LOAD: f64 = 0.8;
+const LOAD_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum BenchmarkStatus {
+    Loading,
+    Running { done: usize },
+    Finished(BenchmarkReport),
+    Failed(String),
+}
+
+impl BenchmarkStatus {
+    pub fn describe(&self) -> String {
+        match self {
+            BenchmarkStatus::Loading => text("Benchmark.Loading"),
+            BenchmarkStatus::Running { done } => text_with("Benchmark.Running", &[done, &BENCHMARK_FRAMES]),
+            BenchmarkStatus::Finished(report) => report.describe(),
+            BenchmarkStatus::Failed(reason) => text_with("Benchmark.Failed", &[reason]),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct BenchmarkReport {
+    pub frames: usize,
+    pub average: Duration,
+    pub median: Duration,
+    pub p95: Duration,
+    pub slowest: Duration,
+    pub frame_duration: Duration,
+    /// The shortest sample length, in whole 10 ms, whose frames last long enough for the 95th
+    /// percentile of the conversions. The window the model sees hardly depends on the sample
+    /// length, so neither does the time a conversion takes.
+    pub min_sample_length: f64,
+}
+
+impl BenchmarkReport {
+    pub fn new(mut times: Vec<Duration>, frame_duration: Duration) -> Self {
+        times.sort();
+        let percentile = |share: f64| times[((times.len() - 1) as f64 * share).round() as usize];
+        let (median, p95, slowest) = (percentile(0.5), percentile(0.95), times[times.len() - 1]);
+        BenchmarkReport {
+            frames: times.len(),
+            average: times.iter().sum::<Duration>() / times.len() as u32,
+            median,
+            p95,
+            slowest,
+            frame_duration,
+            min_sample_length: (p95.as_secs_f64() / SAFE_LOAD * 100.0).ceil() / 100.0,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let milliseconds = |duration: Duration| format!("{:.1}", duration.as_secs_f64() * 1000.0);
+        text_with(
+            "Benchmark.Finished",
+            &[
+                &self.frames,
+                &milliseconds(self.average),
+                &milliseconds(self.median),
+                &milliseconds(self.p95),
+                &milliseconds(self.slowest),
+                &self.frame_duration.as_millis(),
+                &format!("{:.2}", self.min_sample_length),
+            ],
+        )
+    }
+}
+
+/// A voiced test signal at 16 kHz: a tone gliding around 200 Hz with a few harmonics and some
+/// noise, so that pitch extraction and retrieval have something to work on, unlike in silence.
+pub(crate) fn test_signal(len: usize) -> Array1<f32> {
+    let mut phase = 0.0f64;
+    let mut noise = 0x2545f491u32;
+    Array1::from_shape_fn(len, |i| {
+        let t = i as f64 / 16000.0;
+        phase += std::f64::consts::TAU * (200.0 + 40.0 * (std::f64::consts::TAU * 0.5 * t).sin()) / 16000.0;
+        noise ^= noise << 13;
+        noise ^= noise >> 17;
+        noise ^= noise << 5;
+        let harmonics = (1..=4).map(|n| (phase * n as f64).sin() / n as f64).sum::<f64>();
+        (0.2 * harmonics + 0.01 * (noise as f64 / u32::MAX as f64 - 0.5)) as f32
+    })
+}
+
+/// Waits for `engine` to load, then converts `input` through `convert` and reports into
+/// `status` as it goes. Runs on its own thread with an engine of its own, so that the filter
+/// keeps converting meanwhile.
+pub(crate) fn run_benchmark(
+    status: &Mutex<Option<BenchmarkStatus>>,
+    engine: &mut RvcInfer,
+    input: &Array1<f32>,
+    frame_duration: Duration,
+    mut convert: impl FnMut(&mut RvcInfer, &Array1<f32>, &mut Vec<f32>) -> Result<StageTimes, RvcAdapterError>,
+) {
+    *status.lock() = Some(BenchmarkStatus::Loading);
+    while !engine.is_ready() {
+        if engine.has_failed() {
+            let reason = engine.load_error().unwrap_or_else(|| text("Benchmark.LoadFailed"));
+            *status.lock() = Some(BenchmarkStatus::Failed(reason));
+            return;
+        }
+        std::thread::sleep(LOAD_POLL_INTERVAL);
+    }
+
+    let mut output = Vec::new();
+    let mut times = Vec::with_capacity(BENCHMARK_FRAMES);
+    for frame in 0..WARMUP_FRAMES + BENCHMARK_FRAMES {
+        let start = Instant::now();
+        if let Err(e) = convert(engine, input, &mut output) {
+            *status.lock() = Some(BenchmarkStatus::Failed(format!("{:?}", e)));
+            return;
+        }
+        if frame >= WARMUP_FRAMES {
+            times.push(start.elapsed());
+            *status.lock() = Some(BenchmarkStatus::Running { done: times.len() });
+        }
+    }
+    *status.lock() = Some(BenchmarkStatus::Finished(BenchmarkReport::new(times, frame_duration)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_benchmark_report() {
+        let times = (1..=100).rev().map(Duration::from_millis).collect();
+        let report = BenchmarkReport::new(times, Duration::from_millis(300));
+        assert_eq!(report.frames, 100);
+        assert_eq!(report.average, Duration::from_micros(50500));
+        assert_eq!(report.median, Duration::from_millis(51));
+        assert_eq!(report.p95, Duration::from_millis(95));
+        assert_eq!(report.slowest, Duration::from_millis(100));
+        // 95 ms at 80 % load
+        assert_eq!(report.min_sample_length, 0.12);
+
+        let signal = test_signal(1600);
+        assert!(signal.iter().all(|sample| sample.abs() < 1.0));
+        assert!(signal.iter().map(|sample| sample * sample).sum::<f32>() > 1.0);
+    }
+}
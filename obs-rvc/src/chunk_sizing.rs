@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+// share of its frame a conversion may take before the frames are made longer, and below which
+// they are made shorter again
+const GROW_LOAD: f64 = 0.9;
+const SHRINK_LOAD: f64 = 0.5;
+// how much the sample length changes per step
+const STEP_FACTOR: f64 = 1.25;
+const MAX_SAMPLE_LENGTH: f64 = 1.5;
+// frames measured at a length before it is changed again, the first ones after a change are slow
+const SETTLE_FRAMES: usize = 8;
+// weight of the newest frame in the averaged load
+const LOAD_SMOOTHING: f64 = 0.25;
+
+/// Lengthens the frames while the worker can't convert them in real time and brings them back
+/// towards the configured length once there is room again, so that a slow machine gets a longer
+/// latency instead of an output that keeps running dry.
+pub(crate) struct ChunkSizer {
+    load: f64,
+    frames: usize,
+}
+
+impl ChunkSizer {
+    pub fn new() -> Self {
+        Self { load: 0.0, frames: 0 }
+    }
+
+    /// Takes the time the last frame took and returns the sample length to switch to, if any.
+    /// It never goes below `configured`, the length the user set.
+    pub fn record(&mut self, elapsed: Duration, frame_duration: Duration, current: f64, configured: f64) -> Option<f64> {
+        let load = elapsed.as_secs_f64() / frame_duration.as_secs_f64();
+        self.load = if self.frames == 0 { load } else { self.load + LOAD_SMOOTHING * (load - self.load) };
+        self.frames += 1;
+        if self.frames < SETTLE_FRAMES {
+            return None;
+        }
+
+        let length = if self.load > GROW_LOAD && current < MAX_SAMPLE_LENGTH {
+            f64::min(current * STEP_FACTOR, MAX_SAMPLE_LENGTH)
+        } else if self.load < SHRINK_LOAD && current > configured {
+            f64::max(current / STEP_FACTOR, configured)
+        } else {
+            return None;
+        };
+        // in whole 10 ms, like the slider
+        let length = (length * 100.0).round() / 100.0;
+        if length == current {
+            return None;
+        }
+        self.reset();
+        Some(length)
+    }
+
+    pub fn reset(&mut self) {
+        self.load = 0.0;
+        self.frames = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_sizer() {
+        let frame = Duration::from_millis(100);
+        let mut sizer = ChunkSizer::new();
+
+        // too slow, grows once it has settled
+        let grown = (0..SETTLE_FRAMES).filter_map(|_| sizer.record(frame, frame, 0.1, 0.1)).collect::<Vec<_>>();
+        assert_eq!(grown, vec![0.13]);
+
+        // plenty of room, back down but not below what was configured
+        let quick = Duration::from_millis(10);
+        let shrunk = (0..SETTLE_FRAMES).filter_map(|_| sizer.record(quick, frame, 0.13, 0.12)).collect::<Vec<_>>();
+        assert_eq!(shrunk, vec![0.12]);
+        assert!((0..2 * SETTLE_FRAMES).all(|_| sizer.record(quick, frame, 0.12, 0.12).is_none()));
+    }
+}
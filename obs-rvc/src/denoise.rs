@@ -0,0 +1,94 @@
+use nnnoiseless::DenoiseState;
+use rubato::{FftFixedInOut, Resampler};
+
+// the rate RNNoise was trained for, it works on 10 ms frames of it
+const DENOISE_SAMPLE_RATE: usize = 48000;
+// RNNoise expects 16-bit sample values
+const SAMPLE_SCALE: f32 = 32767.0;
+
+/// Removes steady background noise like fans and hum with RNNoise before the input is encoded,
+/// as ContentVec picks it up as part of the voice. Adds 10 ms of latency, plus the resampling
+/// when the device doesn't run at 48 kHz.
+pub(crate) struct Denoiser {
+    state: Box<DenoiseState<'static>>,
+    chunk_size: usize,
+    // to 48 kHz and back, `None` at 48 kHz
+    resamplers: Option<(FftFixedInOut<f32>, FftFixedInOut<f32>)>,
+    frame: Vec<f32>,
+    denoised: Vec<f32>,
+}
+
+impl Denoiser {
+    /// `None` for sample rates whose 10 ms can't be resampled to RNNoise's frame in one go.
+    pub fn new(sample_rate: usize) -> Option<Self> {
+        let chunk_size = sample_rate / 100;
+        let resamplers = if sample_rate == DENOISE_SAMPLE_RATE {
+            None
+        } else {
+            let to_48k = FftFixedInOut::new(sample_rate, DENOISE_SAMPLE_RATE, chunk_size, 1).ok()?;
+            let from_48k = FftFixedInOut::new(DENOISE_SAMPLE_RATE, sample_rate, DenoiseState::FRAME_SIZE, 1).ok()?;
+            if to_48k.input_frames_next() != chunk_size
+                || to_48k.output_frames_next() != DenoiseState::FRAME_SIZE
+                || from_48k.output_frames_next() != chunk_size
+            {
+                return None;
+            }
+            Some((to_48k, from_48k))
+        };
+
+        Some(Self {
+            state: DenoiseState::new(),
+            chunk_size,
+            resamplers,
+            frame: vec![0.0; DenoiseState::FRAME_SIZE],
+            denoised: vec![0.0; DenoiseState::FRAME_SIZE],
+        })
+    }
+
+    /// Denoises `samples` in place, a whole number of 10 ms chunks as the worker frames are.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for chunk in samples.chunks_exact_mut(self.chunk_size) {
+            match self.resamplers.as_mut() {
+                Some((to_48k, _)) => {
+                    to_48k.process_into_buffer(&[&*chunk], &mut [&mut self.frame[..]], None).unwrap();
+                }
+                None => self.frame.copy_from_slice(chunk),
+            }
+            self.frame.iter_mut().for_each(|sample| *sample *= SAMPLE_SCALE);
+            self.state.process_frame(&mut self.denoised, &self.frame);
+            self.denoised.iter_mut().for_each(|sample| *sample /= SAMPLE_SCALE);
+
+            match self.resamplers.as_mut() {
+                Some((_, from_48k)) => {
+                    from_48k.process_into_buffer(&[&self.denoised], &mut [chunk], None).unwrap();
+                }
+                None => chunk.copy_from_slice(&self.denoised),
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.state = DenoiseState::new();
+        if let Some((to_48k, from_48k)) = self.resamplers.as_mut() {
+            to_48k.reset();
+            from_48k.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_denoiser_frames() {
+        assert!(Denoiser::new(48000).is_some());
+        assert!(Denoiser::new(44100).is_some());
+
+        let mut denoiser = Denoiser::new(44100).unwrap();
+        let mut samples = vec![0.0f32; 441 * 3];
+        denoiser.process(&mut samples);
+        assert_eq!(samples.len(), 441 * 3);
+        assert!(samples.iter().all(|sample| sample.abs() < 1e-3));
+    }
+}
@@ -0,0 +1,170 @@
+use std::sync::Arc;
+
+use ndarray::ArrayViewMut1;
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+// shifts smaller than this are left alone, in semitones
+const MIN_SHIFT: f64 = 0.01;
+// frames of about 21 ms, long enough to resolve the harmonics of a low voice
+const FRAME_TIME: f64 = 0.021;
+const OVERLAP: usize = 4;
+// quefrency below which the cepstrum is taken for the envelope; the pitch harmonics lie above,
+// for voices up to 1 kHz
+const LIFTER_TIME: f64 = 0.001;
+// how far a bin may be boosted or cut, so that warping a deep notch onto a peak doesn't blow up
+// the noise in it
+const MAX_LOG_GAIN: f32 = 2.3; // 20 dB
+
+/// Moves the resonances of the voice without touching its pitch: the spectral envelope of each
+/// frame is found by cepstral smoothing and stretched along the frequency axis, the harmonics
+/// stay in place. Works on each model output by itself, the crossfade hides the edges.
+pub(crate) struct FormantShifter {
+    frame_size: usize,
+    hop_size: usize,
+    lifter: usize,
+    window: Vec<f32>,
+    forward: Arc<dyn Fft<f32>>,
+    inverse: Arc<dyn Fft<f32>>,
+    spectrum: Vec<Complex<f32>>,
+    cepstrum: Vec<Complex<f32>>,
+    envelope: Vec<f32>,
+    scratch: Vec<Complex<f32>>,
+    output: Vec<f32>,
+    weights: Vec<f32>,
+}
+
+impl FormantShifter {
+    pub fn new(sample_rate: usize) -> Self {
+        let frame_size = ((FRAME_TIME * sample_rate as f64) as usize).next_power_of_two();
+        let mut planner = FftPlanner::new();
+        let forward = planner.plan_fft_forward(frame_size);
+        let inverse = planner.plan_fft_inverse(frame_size);
+        let scratch_size = usize::max(forward.get_inplace_scratch_len(), inverse.get_inplace_scratch_len());
+        let window = (0..frame_size)
+            .map(|i| 0.5 - 0.5 * f32::cos(2.0 * std::f32::consts::PI * i as f32 / frame_size as f32))
+            .collect();
+
+        Self {
+            frame_size,
+            hop_size: frame_size / OVERLAP,
+            lifter: usize::max((LIFTER_TIME * sample_rate as f64) as usize, 1),
+            window,
+            forward,
+            inverse,
+            spectrum: vec![Complex::default(); frame_size],
+            cepstrum: vec![Complex::default(); frame_size],
+            envelope: vec![0.0; frame_size / 2 + 1],
+            scratch: vec![Complex::default(); scratch_size],
+            output: Vec::new(),
+            weights: Vec::new(),
+        }
+    }
+
+    /// Shifts the resonances of `samples` by `semitones`, up for positive values.
+    pub fn process(&mut self, mut samples: ArrayViewMut1<f32>, semitones: f64) {
+        if semitones.abs() < MIN_SHIFT || samples.is_empty() {
+            return;
+        }
+        let ratio = 2f64.powf(semitones / 12.0) as f32;
+        let (frame_size, hop_size) = (self.frame_size, self.hop_size);
+
+        // frames start a frame early, so that every sample is covered by as many of them
+        let padded_len = samples.len() + 2 * frame_size;
+        self.output.clear();
+        self.output.resize(padded_len, 0.0);
+        self.weights.clear();
+        self.weights.resize(padded_len, 0.0);
+
+        let mut start = 0;
+        while start + frame_size <= padded_len {
+            for (i, bin) in self.spectrum.iter_mut().enumerate() {
+                let sample = (start + i)
+                    .checked_sub(frame_size)
+                    .and_then(|index| samples.get(index))
+                    .copied()
+                    .unwrap_or(0.0);
+                *bin = Complex::new(sample * self.window[i], 0.0);
+            }
+            self.forward.process_with_scratch(&mut self.spectrum, &mut self.scratch);
+            self.warp_envelope(ratio);
+            self.inverse.process_with_scratch(&mut self.spectrum, &mut self.scratch);
+
+            for (i, (bin, window)) in self.spectrum.iter().zip(self.window.iter()).enumerate() {
+                self.output[start + i] += bin.re / frame_size as f32 * window;
+                self.weights[start + i] += window * window;
+            }
+            start += hop_size;
+        }
+
+        for (i, sample) in samples.iter_mut().enumerate() {
+            let weight = self.weights[frame_size + i];
+            if weight > 1e-3 {
+                *sample = self.output[frame_size + i] / weight;
+            }
+        }
+    }
+
+    /// Replaces the envelope of the frame in `spectrum` by the envelope stretched by `ratio`.
+    fn warp_envelope(&mut self, ratio: f32) {
+        let frame_size = self.frame_size;
+        for (cepstrum, bin) in self.cepstrum.iter_mut().zip(self.spectrum.iter()) {
+            *cepstrum = Complex::new(f32::max(bin.norm(), 1e-9).ln(), 0.0);
+        }
+        self.inverse.process_with_scratch(&mut self.cepstrum, &mut self.scratch);
+        for (i, cepstrum) in self.cepstrum.iter_mut().enumerate() {
+            let quefrency = usize::min(i, frame_size - i);
+            *cepstrum = if quefrency < self.lifter { *cepstrum / frame_size as f32 } else { Complex::default() };
+        }
+        self.forward.process_with_scratch(&mut self.cepstrum, &mut self.scratch);
+        for (envelope, cepstrum) in self.envelope.iter_mut().zip(self.cepstrum.iter()) {
+            *envelope = cepstrum.re;
+        }
+
+        let last_bin = frame_size / 2;
+        for bin in 0..=last_bin {
+            let source = bin as f32 / ratio;
+            let warped = if source >= last_bin as f32 {
+                self.envelope[last_bin]
+            } else {
+                let (index, fraction) = (source as usize, source.fract());
+                self.envelope[index] * (1.0 - fraction) + self.envelope[index + 1] * fraction
+            };
+            let gain = (warped - self.envelope[bin]).clamp(-MAX_LOG_GAIN, MAX_LOG_GAIN).exp();
+            self.spectrum[bin] *= gain;
+            if bin != 0 && bin != last_bin {
+                self.spectrum[frame_size - bin] *= gain;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formant_shift() {
+        let sample_rate = 16000;
+        let tone: Vec<f32> = (0..4000)
+            .map(|i| (2.0 * std::f32::consts::PI * 200.0 * i as f32 / sample_rate as f32).sin() * 0.5)
+            .collect();
+        let mut shifter = FormantShifter::new(sample_rate);
+
+        // no shift is no change
+        let mut samples = ndarray::Array1::from(tone.clone());
+        shifter.process(samples.view_mut(), 0.0);
+        assert_eq!(samples.to_vec(), tone);
+
+        // a shift keeps the length and the signal bounded, the analysis and resynthesis line up
+        let mut samples = ndarray::Array1::from(tone.clone());
+        shifter.process(samples.view_mut(), 3.0);
+        assert_eq!(samples.len(), tone.len());
+        assert!(samples.iter().all(|sample| sample.is_finite() && sample.abs() < 2.0));
+
+        // a pure sine has a flat envelope around its one peak, so a tiny shift barely changes it
+        let mut samples = ndarray::Array1::from(tone.clone());
+        shifter.process(samples.view_mut(), 0.02);
+        let error: f32 = samples.iter().zip(tone.iter()).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+        assert!(error < 0.05, "{}", error);
+    }
+}
@@ -0,0 +1,101 @@
+/// Sizes of the worker's frames and buffers, in samples at the device rate unless noted.
+///
+/// Every length is a whole number of units, the shortest multiple of 10 ms that is a whole
+/// number of samples both at the device rate and at 16 kHz: 10 ms for rates divisible by 100
+/// like 44.1 and 48 kHz, 20 ms for 22.05 kHz and so on. That keeps the 16 kHz buffer exactly in
+/// step with the device buffer, so nothing drifts from frame to frame.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FrameLayout {
+    pub unit: usize,
+    pub unit_16k: usize,
+    pub sample_frame_size: usize,
+    pub sample_frame_16k_size: usize,
+    pub crossfade_frame_size: usize,
+    pub sola_buffer_frame_size: usize,
+    pub sola_search_frame_size: usize,
+    pub extra_frame_size: usize,
+    /// What the model returns per frame, in its 10 ms frames.
+    pub model_return_length: usize,
+    pub input_buffer_size: usize,
+    pub input_buffer_16k_size: usize,
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+impl FrameLayout {
+    /// Lengths in seconds, each rounded to whole units.
+    pub fn new(
+        sample_rate: usize,
+        sample_length: f64,
+        crossfade_length: f64,
+        sola_search_length: f64,
+        extra_inference_time: f64,
+    ) -> Self {
+        let units_per_10ms = 100 / gcd(sample_rate, 100);
+        let unit = sample_rate * units_per_10ms / 100;
+        let unit_16k = 160 * units_per_10ms;
+        let units = |seconds: f64| (seconds * sample_rate as f64 / unit as f64).round() as usize;
+
+        let sample_frame_units = usize::max(units(sample_length), 1);
+        let sample_frame_size = sample_frame_units * unit;
+        let crossfade_frame_size = units(crossfade_length) * unit;
+        // at most 40 ms of the crossfade is blended, the rest only feeds the search
+        let sola_buffer_frame_size = usize::min(crossfade_frame_size, units(0.04).max(1) * unit);
+        let sola_search_frame_size = usize::max(units(sola_search_length), 1) * unit;
+        let extra_frame_size = units(extra_inference_time) * unit;
+
+        let input_buffer_size = extra_frame_size + crossfade_frame_size + sola_search_frame_size + sample_frame_size;
+        let model_return_length =
+            (sample_frame_size + sola_buffer_frame_size + sola_search_frame_size) / unit * units_per_10ms;
+
+        FrameLayout {
+            unit,
+            unit_16k,
+            sample_frame_size,
+            sample_frame_16k_size: sample_frame_units * unit_16k,
+            crossfade_frame_size,
+            sola_buffer_frame_size,
+            sola_search_frame_size,
+            extra_frame_size,
+            model_return_length,
+            input_buffer_size,
+            input_buffer_16k_size: input_buffer_size / unit * unit_16k,
+        }
+    }
+}
+
+/// Converts a length at `sample_rate` that is a whole number of units into 10 ms frames.
+pub(crate) fn ten_ms_frames(samples: usize, sample_rate: usize) -> usize {
+    samples * 100 / sample_rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_layout() {
+        for sample_rate in [48000, 44100, 32000, 22050] {
+            let layout = FrameLayout::new(sample_rate, 0.3, 0.07, 0.012, 2.0);
+            // the 16 kHz buffer covers exactly the time of the device buffer
+            assert_eq!(layout.input_buffer_16k_size * sample_rate, layout.input_buffer_size * 16000);
+            assert_eq!(layout.sample_frame_16k_size * sample_rate, layout.sample_frame_size * 16000);
+            assert_eq!(layout.sample_frame_size % layout.unit, 0);
+            assert_eq!(
+                layout.model_return_length * sample_rate,
+                (layout.sample_frame_size + layout.sola_buffer_frame_size + layout.sola_search_frame_size) * 100
+            );
+        }
+
+        let layout = FrameLayout::new(44100, 0.3, 0.07, 0.012, 2.0);
+        assert_eq!((layout.unit, layout.unit_16k), (441, 160));
+        assert_eq!(layout.sample_frame_size, 13230);
+        assert_eq!(layout.sola_buffer_frame_size, 4 * 441);
+
+        let layout = FrameLayout::new(22050, 0.3, 0.07, 0.012, 2.0);
+        assert_eq!((layout.unit, layout.unit_16k), (441, 320));
+        assert_eq!(ten_ms_frames(layout.extra_frame_size, 22050), 200);
+    }
+}
@@ -0,0 +1,111 @@
+// how long the gate stays open after the level drops, so that it doesn't chatter between words
+const HOLD_TIME: f64 = 0.05;
+// time constant of the level the threshold is compared with
+const ENVELOPE_RELEASE_TIME: f64 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct GateParams {
+    pub sample_rate: usize,
+    pub threshold_db: f64,
+    pub attack_ms: f64,
+    pub release_ms: f64,
+}
+
+/// Mutes the input below a threshold, so that keyboard clicks and room noise between phrases
+/// aren't converted into vocalizations. Opens over the attack time and closes over the release
+/// time once the level has stayed below the threshold for the hold time.
+pub(crate) struct NoiseGate {
+    params: GateParams,
+    threshold: f32,
+    attack_step: f32,
+    release_step: f32,
+    envelope_decay: f32,
+    hold_samples: usize,
+    envelope: f32,
+    held: usize,
+    gain: f32,
+}
+
+impl NoiseGate {
+    pub fn new(params: GateParams) -> Self {
+        let sample_rate = params.sample_rate as f64;
+        let step = |ms: f64| (1.0 / (ms.max(0.1) / 1000.0 * sample_rate)).min(1.0) as f32;
+        Self {
+            params,
+            threshold: 10f64.powf(params.threshold_db / 20.0) as f32,
+            attack_step: step(params.attack_ms),
+            release_step: step(params.release_ms),
+            envelope_decay: (-1.0 / (ENVELOPE_RELEASE_TIME * sample_rate)).exp() as f32,
+            hold_samples: (HOLD_TIME * sample_rate) as usize,
+            envelope: 0.0,
+            held: 0,
+            gain: 0.0,
+        }
+    }
+
+    pub fn params(&self) -> GateParams {
+        self.params
+    }
+
+    /// Gates `samples` in place. Returns whether the gate stayed shut for the whole block, in
+    /// which case there is nothing left to convert.
+    pub fn process(&mut self, samples: &mut [f32]) -> bool {
+        let mut shut = true;
+        for sample in samples.iter_mut() {
+            self.envelope = f32::max(sample.abs(), self.envelope * self.envelope_decay);
+            if self.envelope >= self.threshold {
+                self.held = 0;
+            } else {
+                self.held = self.held.saturating_add(1);
+            }
+
+            self.gain = if self.held <= self.hold_samples {
+                f32::min(self.gain + self.attack_step, 1.0)
+            } else {
+                f32::max(self.gain - self.release_step, 0.0)
+            };
+            shut &= self.gain == 0.0;
+            *sample *= self.gain;
+        }
+        shut
+    }
+
+    pub fn reset(&mut self) {
+        self.envelope = 0.0;
+        self.held = self.hold_samples + 1;
+        self.gain = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noise_gate() {
+        let mut gate = NoiseGate::new(GateParams {
+            sample_rate: 1000,
+            threshold_db: -20.0,
+            attack_ms: 2.0,
+            release_ms: 10.0,
+        });
+        gate.reset();
+
+        // noise below the threshold stays out
+        let mut noise = vec![0.05f32; 100];
+        assert!(gate.process(&mut noise));
+        assert!(noise.iter().all(|sample| *sample == 0.0));
+
+        // a loud block opens the gate within the attack time
+        let mut voice = vec![0.5f32; 100];
+        assert!(!gate.process(&mut voice));
+        assert_eq!(voice[0], 0.25);
+        assert_eq!(voice[99], 0.5);
+
+        // and it closes again after the hold and release time
+        let mut silence = vec![0.0f32; 100];
+        assert!(!gate.process(&mut silence));
+        let mut silence = vec![0.01f32; 100];
+        assert!(gate.process(&mut silence));
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+
+/// Holds processed samples by their position in the input stream so that the audio callback
+/// can always emit the samples for exactly `latency` samples ago. Samples that arrive early
+/// wait here; samples that are not there in time are concealed with silence and dropped
+/// when they show up.
+pub(crate) struct FixedLatencyBuffer {
+    // processed samples for stream positions starting at `start`
+    pending: VecDeque<f32>,
+    start: u64,
+    // nothing counts as concealed before the first real sample went out
+    primed: bool,
+}
+
+impl FixedLatencyBuffer {
+    pub fn new() -> Self {
+        Self {
+            pending: VecDeque::new(),
+            start: 0,
+            primed: false,
+        }
+    }
+
+    pub fn push(&mut self, position: u64, data: &[f32]) {
+        // everything before `start` has already been emitted or concealed
+        let skip = usize::min(self.start.saturating_sub(position) as usize, data.len());
+        let position = position + skip as u64;
+        let data = &data[skip..];
+        if data.is_empty() {
+            return;
+        }
+
+        if self.pending.is_empty() {
+            self.start = position;
+        }
+
+        let end = self.start + self.pending.len() as u64;
+        if position > end {
+            // a frame went missing in between
+            self.pending
+                .extend(std::iter::repeat(0.0).take((position - end) as usize));
+            self.pending.extend(data);
+        } else {
+            let overlap = usize::min((end - position) as usize, data.len());
+            self.pending.extend(&data[overlap..]);
+        }
+    }
+
+    /// Fills `output` with the samples for the positions starting at `position` and returns how
+    /// many of them had to be concealed.
+    pub fn pop_into(&mut self, position: u64, output: &mut [f32]) -> usize {
+        self.discard_until(position);
+
+        let mut concealed = 0;
+        for (i, sample) in output.iter_mut().enumerate() {
+            let wanted = position + i as u64;
+            let available = wanted >= self.start && ((wanted - self.start) as usize) < self.pending.len();
+            if available {
+                *sample = self.pending[(wanted - self.start) as usize];
+                self.primed = true;
+            } else {
+                *sample = 0.0;
+                if self.primed {
+                    concealed += 1;
+                }
+            }
+        }
+
+        self.discard_until(position + output.len() as u64);
+        concealed
+    }
+
+    pub fn discard_until(&mut self, position: u64) {
+        if position > self.start {
+            let drop_count = usize::min((position - self.start) as usize, self.pending.len());
+            self.pending.drain(..drop_count);
+            self.start = position;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.start = 0;
+        self.primed = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_latency_buffer_waits_for_position() {
+        let mut buffer = FixedLatencyBuffer::new();
+        buffer.push(0, &[1.0, 2.0, 3.0, 4.0]);
+
+        let mut output = [0.0; 2];
+        assert_eq!(buffer.pop_into(1, &mut output), 0);
+        assert_eq!(output, [2.0, 3.0]);
+        assert_eq!(buffer.pop_into(3, &mut output), 1);
+        assert_eq!(output, [4.0, 0.0]);
+    }
+
+    #[test]
+    fn test_fixed_latency_buffer_drops_late_samples() {
+        let mut buffer = FixedLatencyBuffer::new();
+        let mut output = [0.0; 2];
+        buffer.push(0, &[1.0, 2.0]);
+        assert_eq!(buffer.pop_into(0, &mut output), 0);
+        assert_eq!(buffer.pop_into(2, &mut output), 2);
+
+        // positions 2 and 3 were already concealed
+        buffer.push(2, &[3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(buffer.pop_into(4, &mut output), 0);
+        assert_eq!(output, [5.0, 6.0]);
+    }
+
+    #[test]
+    fn test_fixed_latency_buffer_fills_gaps() {
+        let mut buffer = FixedLatencyBuffer::new();
+        let mut output = [0.0; 4];
+        buffer.push(0, &[1.0]);
+        buffer.push(2, &[3.0, 4.0]);
+        assert_eq!(buffer.pop_into(0, &mut output), 0);
+        assert_eq!(output, [1.0, 0.0, 3.0, 4.0]);
+    }
+}
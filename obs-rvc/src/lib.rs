@@ -1,34 +1,82 @@
+mod advanced;
+mod benchmark;
+mod chunk_sizing;
+mod denoise;
+mod formant;
+mod frame_layout;
+mod gate;
+mod latency;
+mod limiter;
+mod locale;
+mod logging;
+mod loudness;
+mod metrics;
+mod model_download;
+mod model_library;
+mod monitor;
 mod ndarray_ext;
+mod presets;
+mod resample;
 mod rt_utils;
+mod runtime_probe;
 mod rvcadapter;
+mod vad;
+mod voice_bundle;
+mod websocket;
+mod worker_priority;
 
 #[cfg(test)]
 mod tests;
 
-use crossbeam::{queue::ArrayQueue, sync::{Parker, Unparker}};
-use ndarray::{s, ArrayView1, Zip};
+use crossbeam::{atomic::AtomicCell, channel::{Receiver, Sender}, queue::ArrayQueue, sync::{Parker, Unparker}};
+use log::{debug, error, info, warn};
+use ndarray::{s, ArrayView1, ArrayViewMut1, Zip};
 use parking_lot::{Condvar, FairMutex, Mutex};
-use rt_utils::{envelop_mixing, get_sola_offset, upmix_audio_data_context};
-use rubato::{FftFixedInOut, Resampler};
-use rvc_common::enums::{PitchAlgorithm, RvcModelVersion};
-use rvcadapter::RvcInfer;
+use advanced::AdvancedConfig;
+use benchmark::{run_benchmark, test_signal, BenchmarkStatus, BENCHMARK_FRAMES};
+use chunk_sizing::ChunkSizer;
+use denoise::Denoiser;
+use formant::FormantShifter;
+use frame_layout::{ten_ms_frames, FrameLayout};
+use gate::{GateParams, NoiseGate};
+use vad::VoiceActivityDetector;
+use latency::FixedLatencyBuffer;
+use limiter::OutputLimiter;
+use locale::{obs_text, text, text_with};
+use loudness::LoudnessMatcher;
+use metrics::{start_metrics_server, FilterMetrics};
+use monitor::InputLevelMonitor;
+use presets::{apply_preset, load_presets, step_preset, PresetProp, SourceHandle, SETTING_PRESET};
+use resample::FrameResampler;
+use rt_utils::{apply_front_side_signal, db_to_gain, envelop_mixing, fade, front_side_signal, resize_keeping_tail, fit_tail_to_length, get_sola_offset, level_db, peak, placement_gains, ramp_wet_dry, rms_level, upmix_audio_data_context, crossfade_windows, BandBlender, Biquad};
+use rvc_common::obs_props_ext::{ButtonProp, TextInfoProp, TextInfoType};
+use rvc_common::errors::RvcInferError;
+use rvc_common::enums::{AutotuneScale, BandSplitMode, CrossfadeWindow, DownmixMode, FeatureEncoder, InferenceDevice, PitchAlgorithm, ResamplerType, RetrievalMetric, RvcModelVersion, UnderrunFallback, UpmixMode, DEFAULT_DEVICE_PRIORITY};
+use model_download::DownloadStatus;
+use model_library::ModelLibraryProp;
+use rvcadapter::{EngineConfig, EngineReplies, FrameRequest, FrameShape, RvcInfer, StageTimes};
+use voice_bundle::{extract_bundle, is_voice_bundle, read_voice_defaults, VoiceDefaults};
+use websocket::RemoteControl;
 
 use obs_wrapper::{
     media::{audio, AudioData},
     obs_register_module, obs_string,
+    obs_sys::{bfree, obs_module_get_config_path, obs_properties_t, obs_property_t, obs_source_t},
     prelude::*,
     properties::{BoolProp, NumberProp, PathProp, PathType, Properties},
     source::*,
 };
 
 use std::{
-    borrow::Cow, cell::RefCell, collections::VecDeque, f32::consts::PI, panic, path::PathBuf, sync::{atomic::{AtomicBool, AtomicUsize}, Arc}, thread::{yield_now, JoinHandle}, time::{self, Duration, Instant}
+    borrow::Cow, cell::RefCell, collections::VecDeque, ffi::CStr, os::raw::c_void, panic, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize}, Arc}, thread::{yield_now, JoinHandle}, time::{self, Duration, Instant, SystemTime}
 };
 
-use crate::{rt_utils::downmix_to_mono, rvcadapter::RvcAdapterError};
+use crate::{rt_utils::{downmix_to_mono, downmix_weights}, rvcadapter::RvcAdapterError};
 
 static mut BINARY_PATH: Option<PathBuf> = None;
 static mut DATA_PATH: Option<PathBuf> = None;
+static mut CONFIG_PATH: Option<PathBuf> = None;
+static mut ADVANCED_CONFIG: Option<AdvancedConfig> = None;
 
 macro_rules! get_path_from_settings {
     ($settings:ident, $setting:ident) => {
@@ -75,40 +123,227 @@ macro_rules! get_path_from_settings {
 }
 
 const SETTING_MODEL_PATH: ObsString = obs_string!("model_path");
+const SETTING_MODEL_SELECT: ObsString = obs_string!("model_select");
+const SETTING_PRESET_SELECT: ObsString = obs_string!("preset_select");
+const SETTING_RELOAD_MODEL: ObsString = obs_string!("reload_model");
+const SETTING_RUN_BENCHMARK: ObsString = obs_string!("run_benchmark");
+const SETTING_BENCHMARK_STATUS: ObsString = obs_string!("benchmark_status");
+const SETTING_DOWNLOAD_STATUS: ObsString = obs_string!("download_status");
+// keeps its old key so that saved ContentVec paths carry over
+const SETTING_ENCODER_PATH: ObsString = obs_string!("contentvec_path");
+const SETTING_FEATURE_ENCODER: ObsString = obs_string!("feature_encoder");
+const SETTING_INFERENCE_DEVICE: ObsString = obs_string!("inference_device");
+const SETTING_DEVICE_ID: ObsString = obs_string!("device_id");
+const SETTING_WORKER_HIGH_PRIORITY: ObsString = obs_string!("worker_high_priority");
+const SETTING_WORKER_CORE: ObsString = obs_string!("worker_core");
+const MAX_DEVICE_PRIORITY: usize = DEFAULT_DEVICE_PRIORITY.len();
+const SETTING_USE_TENSORRT: ObsString = obs_string!("use_tensorrt");
+const SETTING_USE_CUDA_GRAPH: ObsString = obs_string!("use_cuda_graph");
+const SETTING_USE_FP16: ObsString = obs_string!("use_fp16");
+const SETTING_USE_INT8: ObsString = obs_string!("use_int8");
+const SETTING_CONTENTVEC_LAYERS: ObsString = obs_string!("contentvec_layers");
+const SETTING_FEATURE_CACHE: ObsString = obs_string!("feature_cache");
+const SETTING_SPEAKER_ID: ObsString = obs_string!("speaker_id");
+const SETTING_MORPH_SPEAKER_ID: ObsString = obs_string!("morph_speaker_id");
+const SETTING_SPEAKER_MORPH: ObsString = obs_string!("speaker_morph");
 const SETTING_INDEX_PATH: ObsString = obs_string!("index_path");
+const MAX_INDEX_COUNT: usize = 3;
+const SETTING_INDEX_METRIC: ObsString = obs_string!("index_metric");
 const SETTING_PITCH_SHIFT: ObsString = obs_string!("pitch_shift");
+const SETTING_F0_FILTER_RADIUS: ObsString = obs_string!("f0_filter_radius");
+const SETTING_F0_MIN: ObsString = obs_string!("f0_min");
+const SETTING_F0_MAX: ObsString = obs_string!("f0_max");
+const SETTING_VOICING_SENSITIVITY: ObsString = obs_string!("voicing_sensitivity");
+const SETTING_AUTOTUNE_SCALE: ObsString = obs_string!("autotune_scale");
+const SETTING_AUTOTUNE_KEY: ObsString = obs_string!("autotune_key");
+const SETTING_AUTOTUNE_STRENGTH: ObsString = obs_string!("autotune_strength");
 const SETTING_RESONANCE_SHIFT: ObsString = obs_string!("resonance_shift");
 const SETTING_INDEX_RATE: ObsString = obs_string!("index_rate");
 const SETTING_LOUDNESS_FACTOR: ObsString = obs_string!("loudness_factor");
 const SETTING_PITCH_ALGORITHM: ObsString = obs_string!("pitch_algorithm");
 const SETTING_SAMPLE_LENGTH: ObsString = obs_string!("sample_length");
+const SETTING_ADAPTIVE_SAMPLE_LENGTH: ObsString = obs_string!("adaptive_sample_length");
 const SETTING_FADE_LENGTH: ObsString = obs_string!("fade_length");
+const SETTING_CROSSFADE_WINDOW: ObsString = obs_string!("crossfade_window");
 const SETTING_EXTRA_INFERENCE_TIME: ObsString = obs_string!("extra_inference_time");
+const SETTING_SOLA_SEARCH_LENGTH: ObsString = obs_string!("sola_search_length");
+const SETTING_RESAMPLER: ObsString = obs_string!("resampler");
 const SETTING_DEST_SAMPLE_RATE: ObsString = obs_string!("dest_sample_rate");
 const SETTING_MODEL_VERSION: ObsString = obs_string!("model_version");
+const SETTING_MODEL_VERSION_INFO: ObsString = obs_string!("model_version_info");
+const SETTING_SAMPLE_RATE_INFO: ObsString = obs_string!("sample_rate_info");
 const SETTING_SKIP_INFERENCE: ObsString = obs_string!("skip_inference");
+const SETTING_IDLE_TIMEOUT: ObsString = obs_string!("idle_timeout");
+const SETTING_DENOISE: ObsString = obs_string!("denoise");
+const SETTING_HIGHPASS_FREQUENCY: ObsString = obs_string!("highpass_frequency");
+const SETTING_NOISE_GATE: ObsString = obs_string!("noise_gate");
+const SETTING_GATE_THRESHOLD: ObsString = obs_string!("noise_gate_threshold");
+const SETTING_GATE_ATTACK: ObsString = obs_string!("noise_gate_attack");
+const SETTING_GATE_RELEASE: ObsString = obs_string!("noise_gate_release");
+const SETTING_VAD: ObsString = obs_string!("vad");
+const SETTING_VAD_MARGIN: ObsString = obs_string!("vad_margin");
+const SETTING_STATUS: ObsString = obs_string!("status");
+const SETTING_MODEL_INFO: ObsString = obs_string!("model_info");
+const SETTING_ENGINE_STATUS: ObsString = obs_string!("engine_status");
+const SETTING_LATENCY_STATUS: ObsString = obs_string!("latency_status");
+const SETTING_PERFORMANCE_STATUS: ObsString = obs_string!("performance_status");
+const SETTING_SIBILANCE_BLEND: ObsString = obs_string!("sibilance_blend");
+const SETTING_SIBILANCE_CROSSOVER: ObsString = obs_string!("sibilance_crossover");
+const SETTING_FIXED_LATENCY: ObsString = obs_string!("fixed_latency");
+const SETTING_COMPENSATE_LATENCY: ObsString = obs_string!("compensate_latency");
+const SETTING_WATCH_MODEL: ObsString = obs_string!("watch_model");
+const SETTING_PUSH_TO_CONVERT: ObsString = obs_string!("push_to_convert");
+const SETTING_WET_MIX: ObsString = obs_string!("wet_mix");
+const SETTING_BAND_SPLIT_MODE: ObsString = obs_string!("band_split_mode");
+const SETTING_BAND_SPLIT_FREQUENCY: ObsString = obs_string!("band_split_frequency");
+const SETTING_UPMIX_MODE: ObsString = obs_string!("upmix_mode");
+const SETTING_UNDERRUN_FALLBACK: ObsString = obs_string!("underrun_fallback");
+const SETTING_DOWNMIX_MODE: ObsString = obs_string!("downmix_mode");
+const SETTING_DOWNMIX_LEFT_WEIGHT: ObsString = obs_string!("downmix_left_weight");
+const SETTING_DOWNMIX_RIGHT_WEIGHT: ObsString = obs_string!("downmix_right_weight");
+const SETTING_INPUT_GAIN: ObsString = obs_string!("input_gain_db");
+const SETTING_OUTPUT_GAIN: ObsString = obs_string!("output_gain_db");
+const SETTING_AUTO_LOUDNESS: ObsString = obs_string!("auto_loudness");
+const SETTING_OUTPUT_LIMITER: ObsString = obs_string!("output_limiter");
+const SETTING_LIMITER_CEILING: ObsString = obs_string!("output_limiter_ceiling");
+const SETTING_BYPASS_RETRIEVAL: ObsString = obs_string!("bypass_retrieval");
+const SETTING_BYPASS_ENVELOPE: ObsString = obs_string!("bypass_envelope");
+const SETTING_BYPASS_POST_FX: ObsString = obs_string!("bypass_post_fx");
+const SETTING_BYPASS_SOLA: ObsString = obs_string!("bypass_sola");
+
+// frames quieter than this count towards the idle timeout
+const IDLE_SILENCE_THRESHOLD_DB: f32 = -60.0;
+
+const MODEL_WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+// length of the fades across a change of the frame sizes, in seconds
+const DECLICK_TIME: f64 = 0.005;
+
+// frames waiting between the two stages of a pipelined worker, besides the one in each stage
+const PIPELINE_DEPTH: usize = 1;
+
+// how much quieter each repetition of the last converted block gets
+const REPEAT_DECAY: f32 = 0.5;
+
+// how much of the newly measured channel placement is taken over per block
+const PLACEMENT_SMOOTHING: f32 = 0.1;
+
+// longest the side signal is kept for in mid/side mode, in seconds, well past any worker lag
+const SIDE_HISTORY_TIME: f64 = 10.0;
+
+// most frames obs hands a filter at once (AUDIO_OUTPUT_FRAMES), what the per block scratch
+// buffers are sized for so that the audio callback doesn't allocate
+const MAX_BLOCK_FRAMES: usize = 1024;
+
+// output that is off by more than this is dropped rather than stretched
+const MAX_OUTPUT_LENGTH_CORRECTION: f64 = 0.01;
+
+// how long the push-to-convert and bypass crossfades take, in seconds
+const PUSH_TO_CONVERT_ATTACK: f64 = 0.02;
+const PUSH_TO_CONVERT_RELEASE: f64 = 0.08;
+
+// the first slot keeps the original setting names
+fn setting_index_path(slot: usize) -> ObsString {
+    match slot {
+        0 => SETTING_INDEX_PATH,
+        _ => ObsString::from(format!("index_path_{}", slot + 1)),
+    }
+}
+
+fn setting_index_weight(slot: usize) -> ObsString {
+    ObsString::from(format!("index_weight_{}", slot + 1))
+}
+
+fn setting_device_priority(slot: usize) -> ObsString {
+    ObsString::from(format!("device_priority_{}", slot + 1))
+}
+
+fn pitch_algorithm_label(algorithm: PitchAlgorithm) -> ObsString {
+    match algorithm {
+        PitchAlgorithm::Rmvpe => obs_string!("RMVPE"),
+        PitchAlgorithm::CrepeTiny => obs_string!("CREPE (tiny)"),
+        PitchAlgorithm::CrepeFull => obs_string!("CREPE (full)"),
+        PitchAlgorithm::Fcpe => obs_string!("FCPE"),
+        PitchAlgorithm::Dio => obs_string!("DIO (CPU)"),
+        PitchAlgorithm::Harvest => obs_string!("Harvest (CPU)"),
+        PitchAlgorithm::Hybrid => obs_text("PitchAlgorithm.Hybrid"),
+    }
+}
 
 struct Frame {
     data: Vec<f32>,
     timestamp: u64,
+    // sample index of the first sample in the input stream
+    position: u64,
 }
 
 struct RvcInferenceState {
     model_path: Option<PathBuf>,
-    index_path: Option<PathBuf>,
+    watch_model: bool,
+    // modification time of the model file the engine was started with
+    model_modified: Option<SystemTime>,
+    model_pending_modified: Option<SystemTime>,
+    model_last_checked: Instant,
+    index_paths: [Option<PathBuf>; MAX_INDEX_COUNT],
+    index_weights: [f64; MAX_INDEX_COUNT],
+    // the index of a voice bundle, used when the first slot is left empty
+    bundle_index: Option<PathBuf>,
+    bundle_error: Option<String>,
+    index_metric: RetrievalMetric,
+    inference_device: InferenceDevice,
+    // execution providers the automatic device chains, `Auto` marks an empty slot
+    device_priority: [InferenceDevice; MAX_DEVICE_PRIORITY],
+    // which GPU, for machines with more than one
+    device_id: i64,
+    use_tensorrt: bool,
+    use_cuda_graph: bool,
+    // load the FP16 exports of the synthesizer and the encoder where there are some
+    use_fp16: bool,
+    // prefer the INT8 exports over those, for CPU-only machines
+    use_int8: bool,
+    feature_encoder: FeatureEncoder,
+    // custom encoder export, `None` uses the bundled one
+    encoder_path: Option<PathBuf>,
+    // feature layer of the bundled ContentVec, 0 follows the model version
+    contentvec_layers: i64,
+    // reuse encoder features of the overlapping window region
+    feature_cache: bool,
+    // ignored by single-speaker models
+    speaker_id: i64,
+    // blends the speaker embedding towards `morph_speaker_id`, 0 keeps `speaker_id`
+    morph_speaker_id: i64,
+    speaker_morph: f64,
     model_version: RvcModelVersion,
     pitch_algorithm: PitchAlgorithm,
+    // the rate the model is resampled from: the one it states, or else `dest_sample_rate`
     model_output_sample_rate: usize,
+    dest_sample_rate: usize,
     pitch_shift: i32,
+    f0_filter_radius: i32,
+    f0_min: f64,
+    f0_max: f64,
+    voicing_sensitivity: f64,
+    autotune_scale: AutotuneScale,
+    autotune_key: i64,
+    autotune_strength: f64,
     resonance_shift: f64,
+    formant_shifter: FormantShifter,
     index_rate: f64,
     rms_mix_rate: f64,
     sample_length: f64,
+    // what the user set, `sample_length` goes past it while the worker can't keep up
+    configured_sample_length: f64,
+    adaptive_sample_length: bool,
+    chunk_sizer: ChunkSizer,
     crossfade_length: f64,
     extra_inference_time: f64,
+    sola_search_length: f64,
 
     sample_rate: usize,
 
+    // samples per frame unit, see `FrameLayout`
+    frame_unit: usize,
+    frame_unit_16k: usize,
     sample_frame_size: usize,
     sample_frame_16k_size: usize,
     crossfade_frame_size: usize,
@@ -118,20 +353,85 @@ struct RvcInferenceState {
     model_return_length: usize,
     model_return_size: usize,
 
+    advanced: AdvancedConfig,
+
     input_buffer: Vec<f32>,
     input_buffer_16k: Vec<f32>,
+    // what the model gets of `input_buffer_16k` while the high-pass is on
+    highpass_buffer_16k: Vec<f32>,
+    highpass_frequency: f64,
     sola_buffer: ndarray::Array1<f32>,
     output_buffer: Vec<f32>,
-
+    // scratch for the stages of a frame, sized once so that the frames themselves don't allocate
+    cleaned_sample: Vec<f32>,
+    downsample_buffer: Vec<f32>,
+    model_output: Vec<f32>,
+    envelopes: (Vec<f32>, Vec<f32>),
+    // what `process_one_frame` made of the last frame
+    frame_output: Vec<f32>,
+    // what its stages took, zero for the ones it skipped
+    stage_times: StageTimes,
+    resample_time: Duration,
+    // counts the times the frame buffers were sized anew
+    layout_generation: u64,
+
+    crossfade_window: CrossfadeWindow,
     fade_in_window: ndarray::Array1<f32>,
     fade_out_window: ndarray::Array1<f32>,
 
     skip_inference: bool,
 
-    upsampler: FftFixedInOut<f32>,
-    downsampler: FftFixedInOut<f32>,
+    idle_timeout: f64,
+    silent_samples: usize,
+    idle_parked: bool,
+
+    denoise_enabled: bool,
+    // `None` while disabled, or when the sample rate isn't supported
+    denoiser: Option<Denoiser>,
+
+    noise_gate_enabled: bool,
+    noise_gate: NoiseGate,
+
+    vad_enabled: bool,
+    vad_margin: f64,
+    vad: VoiceActivityDetector,
+
+    auto_loudness: bool,
+    loudness_matcher: LoudnessMatcher,
+
+    sibilance_blend: f64,
+    sibilance_crossover: f64,
+    sibilance_blender: BandBlender,
+
+    band_split_mode: BandSplitMode,
+    band_split_frequency: f64,
+    band_split_blender: BandBlender,
+
+    // debugging switches that take a single stage out of the chain
+    bypass_retrieval: bool,
+    bypass_envelope: bool,
+    bypass_post_fx: bool,
+    bypass_sola: bool,
+
+    push_to_convert: bool,
+    // copied from the shared state before every frame
+    convert_held: bool,
+    conversion_bypassed: bool,
+    // share of the converted voice in the output, the rest is the delay-matched dry input
+    wet_mix: f64,
+    // current wet gain, ramped towards the wet mix, or to 0 while push-to-convert is released or
+    // the conversion is bypassed
+    convert_gain: f32,
+
+    resampler_type: ResamplerType,
+    upsampler: FrameResampler,
+    downsampler: FrameResampler,
 
     engine: Option<RvcInfer>,
+    // takes over from `engine` between two frames once it has loaded
+    pending_engine: Option<RvcInfer>,
+    // why `rvc-rpc` could not be started, the audio passes through meanwhile
+    engine_error: Option<String>,
 }
 
 struct RvcInferenceSharedState {
@@ -140,15 +440,106 @@ struct RvcInferenceSharedState {
     channels: usize,
     input: ArrayQueue<Frame>,
     output: ArrayQueue<Frame>,
+    // emptied output frames on their way back to the audio callback, so that it can fill them
+    // with the next input instead of allocating
+    spare_buffers: ArrayQueue<Vec<f32>>,
+    // the frame sizes were changed, the worker fades across the switch
     buffer_changed: AtomicBool,
     sample_frame_size: AtomicUsize,
+    wait_timeout: Duration,
+    input_monitor: InputLevelMonitor,
+    metrics: Arc<FilterMetrics>,
+    // set from the hotkey callbacks
+    convert_held: AtomicBool,
+    conversion_bypassed: AtomicBool,
+    // pitch settings have no effect on the running model
+    model_without_f0: AtomicBool,
+    // reported by the running engine
+    model_version_info: Mutex<Option<(RvcModelVersion, bool)>>,
+    // output sample rate stated by the model, 0 when it doesn't
+    detected_sample_rate: AtomicUsize,
+    // speaker names from the model metadata, if it has any
+    speaker_names: Mutex<Option<Vec<String>>>,
+    speaker_morph_available: AtomicBool,
+    // an INT8 synthesizer or encoder was loaded, which costs some quality
+    quantized_model: AtomicBool,
+    // how long the engine has been loading, whether it builds TensorRT engines meanwhile and
+    // whether the previous one keeps converting until then
+    engine_loading: Mutex<Option<(Duration, bool, bool)>>,
+    // the last benchmark of the current settings, run next to the worker
+    benchmark: Mutex<Option<BenchmarkStatus>>,
+    // the encoder path setting points at something unusable
+    encoder_path_rejected: AtomicBool,
+    // asked for by the settings and picked up by the worker; the core is offset by one, 0 for none
+    worker_high_priority: AtomicBool,
+    worker_core: AtomicUsize,
+    // the system refused to raise or pin the worker
+    worker_priority_rejected: AtomicBool,
+    // 0 when the latency is left to float with the worker
+    fixed_latency_samples: AtomicUsize,
+    // taken off the timestamps of fixed latency output, in nanoseconds
+    timestamp_correction: AtomicU64,
+    concealed_samples: AtomicUsize,
+    // how far the content of an output frame lags behind the input frame it replaces
+    dry_delay_samples: AtomicUsize,
+    // linear gains of the input trim and the output gain settings, as f32 bits
+    input_gain: AtomicU32,
+    output_gain: AtomicU32,
+    // the limiter settings, applied by the audio thread to its own limiter; the ceiling in dB
+    limiter_enabled: AtomicBool,
+    limiter_ceiling: AtomicU32,
+    // set with a new upmix mode, the audio thread then switches over and starts its placement
+    // gains and side history afresh
+    upmix_mode: AtomicCell<UpmixMode>,
+    upmix_reset: AtomicBool,
+    // new downmix weights from the settings, swapped in by the audio thread
+    downmix_update: ArrayQueue<Vec<f32>>,
+    underrun_fallback: AtomicCell<UnderrunFallback>,
+}
+
+impl RemoteControl for RvcInferenceSharedState {
+    fn bypassed(&self) -> bool {
+        self.conversion_bypassed.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn set_bypassed(&self, bypassed: bool) {
+        self.conversion_bypassed.store(bypassed, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    fn metrics(&self) -> &FilterMetrics {
+        &self.metrics
+    }
 }
 
 struct RvcInferenceFilter {
     thread_handle: Option<JoinHandle<()>>,
     shared_state: Arc<RvcInferenceSharedState>,
     has_input: Option<Unparker>,
-    filter_audio_lock: Mutex<()>,
+    input_position: u64,
+    fixed_latency: FixedLatencyBuffer,
+    // the upmix mode in use, taken over from the shared state on a reset
+    upmix_mode: UpmixMode,
+    // share of each input channel in what the model gets
+    downmix_weights: Vec<f32>,
+    // per channel gain of the converted signal in placement mode
+    placement_gains: Vec<f32>,
+    // scratch for the per channel levels and the side signal of the current block
+    channel_levels: Vec<f32>,
+    side_scratch: Vec<f32>,
+    // side of the input's front pair by stream position in mid/side mode, until the converted
+    // mid it belongs to comes back
+    side_history: FixedLatencyBuffer,
+    side_history_limit: u64,
+    // the limiter settings the limiter was last brought in line with
+    limiter_enabled: bool,
+    limiter_ceiling: f32,
+    limiter: OutputLimiter,
+    // the last block the worker delivered and the gain it is repeated at next
+    last_output: Vec<f32>,
+    repeat_gain: f32,
+    source: SourceHandle,
+    // the preset the settings last came from, where the preset hotkeys step from
+    current_preset: Option<String>,
 }
 
 struct RvcInferenceModule {
@@ -162,32 +553,113 @@ impl Sourceable for RvcInferenceFilter {
     fn get_type() -> SourceType {
         SourceType::Filter
     }
-    fn create(create: &mut CreatableSourceContext<Self>, _source: SourceRef) -> Self {
+    fn create(create: &mut CreatableSourceContext<Self>, source: SourceRef) -> Self {
         let (sample_rate, channels) =
             create.with_audio(|audio| (audio.sample_rate(), audio.channels()));
 
         let settings = &mut create.settings;
 
-        let model_path = get_path_from_settings!(settings, SETTING_MODEL_PATH);
-        let index_path = get_path_from_settings!(settings, SETTING_INDEX_PATH);
+        // re-read on every create so edits apply without restarting OBS
+        let advanced = match AdvancedConfig::load(unsafe { DATA_PATH.as_ref().unwrap() }) {
+            Ok(advanced) => advanced,
+            Err(e) => {
+                error!("Error loading advanced config: {}", e);
+                unsafe { ADVANCED_CONFIG.clone() }.unwrap_or_default()
+            }
+        };
 
-        settings.set_default::<i32>(SETTING_DEST_SAMPLE_RATE, 40000);
-        settings.set_default::<i32>(SETTING_PITCH_SHIFT, 12);
-        settings.set_default::<f32>(SETTING_RESONANCE_SHIFT, 0.07);
-        settings.set_default::<f32>(SETTING_INDEX_RATE, 0.0);
+        let model_path = get_path_from_settings!(settings, SETTING_MODEL_PATH);
+        let mut index_paths: [Option<PathBuf>; MAX_INDEX_COUNT] = Default::default();
+        let mut index_weights = [1.0f64; MAX_INDEX_COUNT];
+        for slot in 0..MAX_INDEX_COUNT {
+            let index_path_setting = setting_index_path(slot);
+            index_paths[slot] = get_path_from_settings!(settings, index_path_setting);
+            settings.set_default::<f32>(setting_index_weight(slot), 1.0);
+            index_weights[slot] = settings.get(setting_index_weight(slot)).unwrap_or(1.0);
+        }
+        let (encoder_path, encoder_path_rejected) = encoder_path_from_settings(settings);
+
+        set_voice_defaults(settings, &voice_defaults(model_path.as_deref()));
+        settings.set_default::<i32>(SETTING_F0_FILTER_RADIUS, 0);
+        settings.set_default::<f32>(SETTING_F0_MIN, 50.0);
+        settings.set_default::<f32>(SETTING_F0_MAX, 1100.0);
+        settings.set_default::<f32>(SETTING_VOICING_SENSITIVITY, 0.5);
+        settings.set_default::<AutotuneScale>(SETTING_AUTOTUNE_SCALE, AutotuneScale::Off);
+        settings.set_default::<i64>(SETTING_AUTOTUNE_KEY, 0);
+        settings.set_default::<i64>(SETTING_CONTENTVEC_LAYERS, 0);
+        settings.set_default::<FeatureEncoder>(SETTING_FEATURE_ENCODER, FeatureEncoder::ContentVec);
+        settings.set_default::<RetrievalMetric>(SETTING_INDEX_METRIC, RetrievalMetric::Auto);
+        settings.set_default::<InferenceDevice>(SETTING_INFERENCE_DEVICE, InferenceDevice::Auto);
+        settings.set_default::<i64>(SETTING_DEVICE_ID, 0);
+        settings.set_default::<bool>(SETTING_WORKER_HIGH_PRIORITY, false);
+        settings.set_default::<i64>(SETTING_WORKER_CORE, -1);
+        settings.set_default::<bool>(SETTING_USE_TENSORRT, false);
+        settings.set_default::<bool>(SETTING_USE_CUDA_GRAPH, false);
+        settings.set_default::<bool>(SETTING_USE_FP16, false);
+        settings.set_default::<bool>(SETTING_USE_INT8, false);
+        let mut device_priority = DEFAULT_DEVICE_PRIORITY;
+        for (slot, device) in device_priority.iter_mut().enumerate() {
+            settings.set_default::<InferenceDevice>(setting_device_priority(slot), *device);
+            *device = settings.get(setting_device_priority(slot)).unwrap_or(*device);
+        }
+        settings.set_default::<f32>(SETTING_AUTOTUNE_STRENGTH, 1.0);
+        settings.set_default::<f32>(SETTING_RESONANCE_SHIFT, 0.0);
         settings.set_default::<f32>(SETTING_LOUDNESS_FACTOR, 0.5);
         settings.set_default::<f32>(SETTING_SAMPLE_LENGTH, 0.30);
+        settings.set_default::<bool>(SETTING_ADAPTIVE_SAMPLE_LENGTH, false);
         settings.set_default::<f32>(SETTING_FADE_LENGTH, 0.07);
+        settings.set_default::<CrossfadeWindow>(SETTING_CROSSFADE_WINDOW, CrossfadeWindow::SinSquared);
         settings.set_default::<f32>(SETTING_EXTRA_INFERENCE_TIME, 2.00);
-        settings.set_default::<RvcModelVersion>(SETTING_MODEL_VERSION, RvcModelVersion::V2);
-        settings
-            .set_default::<PitchAlgorithm>(SETTING_PITCH_ALGORITHM, PitchAlgorithm::Rmvpe);
+        // advanced.toml only decides the default, the property has the last word
+        settings.set_default::<f32>(SETTING_SOLA_SEARCH_LENGTH, (advanced.sola_search_ms / 1000.0) as f32);
+        settings.set_default::<ResamplerType>(SETTING_RESAMPLER, ResamplerType::Fft);
         settings.set_default::<bool>(SETTING_SKIP_INFERENCE, false);
-
-        let mut model_output_sample_rate = settings.get(SETTING_DEST_SAMPLE_RATE).unwrap_or(40000);
+        settings.set_default::<f32>(SETTING_IDLE_TIMEOUT, 0.0);
+        settings.set_default::<bool>(SETTING_DENOISE, false);
+        settings.set_default::<f32>(SETTING_HIGHPASS_FREQUENCY, 0.0);
+        settings.set_default::<bool>(SETTING_NOISE_GATE, false);
+        settings.set_default::<f32>(SETTING_GATE_THRESHOLD, -50.0);
+        settings.set_default::<f32>(SETTING_GATE_ATTACK, 5.0);
+        settings.set_default::<f32>(SETTING_GATE_RELEASE, 150.0);
+        settings.set_default::<bool>(SETTING_VAD, false);
+        settings.set_default::<f32>(SETTING_VAD_MARGIN, 10.0);
+        settings.set_default::<f32>(SETTING_SIBILANCE_BLEND, 0.0);
+        settings.set_default::<f32>(SETTING_SIBILANCE_CROSSOVER, 6000.0);
+        settings.set_default::<f32>(SETTING_FIXED_LATENCY, 0.0);
+        settings.set_default::<bool>(SETTING_COMPENSATE_LATENCY, false);
+        settings.set_default::<bool>(SETTING_WATCH_MODEL, true);
+        settings.set_default::<bool>(SETTING_FEATURE_CACHE, false);
+        settings.set_default::<i64>(SETTING_SPEAKER_ID, 0);
+        settings.set_default::<i64>(SETTING_MORPH_SPEAKER_ID, 0);
+        settings.set_default::<f32>(SETTING_SPEAKER_MORPH, 0.0);
+        settings.set_default::<bool>(SETTING_PUSH_TO_CONVERT, false);
+        settings.set_default::<f32>(SETTING_WET_MIX, 1.0);
+        settings.set_default::<BandSplitMode>(SETTING_BAND_SPLIT_MODE, BandSplitMode::Off);
+        settings.set_default::<f32>(SETTING_BAND_SPLIT_FREQUENCY, 300.0);
+        settings.set_default::<UpmixMode>(SETTING_UPMIX_MODE, UpmixMode::Duplicate);
+        settings.set_default::<UnderrunFallback>(SETTING_UNDERRUN_FALLBACK, UnderrunFallback::Discard);
+        settings.set_default::<DownmixMode>(SETTING_DOWNMIX_MODE, DownmixMode::Average);
+        settings.set_default::<f32>(SETTING_DOWNMIX_LEFT_WEIGHT, 1.0);
+        settings.set_default::<f32>(SETTING_DOWNMIX_RIGHT_WEIGHT, 1.0);
+        settings.set_default::<f32>(SETTING_INPUT_GAIN, 0.0);
+        settings.set_default::<f32>(SETTING_OUTPUT_GAIN, 0.0);
+        settings.set_default::<bool>(SETTING_AUTO_LOUDNESS, false);
+        settings.set_default::<bool>(SETTING_OUTPUT_LIMITER, true);
+        settings.set_default::<f32>(SETTING_LIMITER_CEILING, -1.0);
+        settings.set_default::<bool>(SETTING_BYPASS_RETRIEVAL, false);
+        settings.set_default::<bool>(SETTING_BYPASS_ENVELOPE, false);
+        settings.set_default::<bool>(SETTING_BYPASS_POST_FX, false);
+        settings.set_default::<bool>(SETTING_BYPASS_SOLA, false);
+
+        let dest_sample_rate = settings.get(SETTING_DEST_SAMPLE_RATE).unwrap_or(40000);
+        let mut model_output_sample_rate = dest_sample_rate;
         let sample_length = settings.get(SETTING_SAMPLE_LENGTH).unwrap_or(0.30);
         let crossfade_length = settings.get(SETTING_FADE_LENGTH).unwrap_or(0.07);
         let extra_inference_time = settings.get(SETTING_EXTRA_INFERENCE_TIME).unwrap_or(2.00);
+        let sola_search_length = settings
+            .get(SETTING_SOLA_SEARCH_LENGTH)
+            .unwrap_or(advanced.sola_search_ms / 1000.0);
+        let resampler_type = settings.get(SETTING_RESAMPLER).unwrap_or(ResamplerType::Fft);
         let model_version = settings
             .get(SETTING_MODEL_VERSION)
             .unwrap_or(RvcModelVersion::V2);
@@ -196,30 +668,33 @@ impl Sourceable for RvcInferenceFilter {
             .unwrap_or(PitchAlgorithm::Rmvpe);
 
         let skip_inference = settings.get(SETTING_SKIP_INFERENCE).unwrap_or(false);
+        let sibilance_crossover = settings.get(SETTING_SIBILANCE_CROSSOVER).unwrap_or(6000.0);
+        let fixed_latency: f64 = settings.get(SETTING_FIXED_LATENCY).unwrap_or(0.0);
+        let push_to_convert = settings.get(SETTING_PUSH_TO_CONVERT).unwrap_or(false);
+        let wet_mix: f64 = settings.get(SETTING_WET_MIX).unwrap_or(1.0);
+        let band_split_frequency = settings.get(SETTING_BAND_SPLIT_FREQUENCY).unwrap_or(300.0);
+        let upmix_mode = settings.get(SETTING_UPMIX_MODE).unwrap_or(UpmixMode::Duplicate);
+        let limiter_enabled = settings.get(SETTING_OUTPUT_LIMITER).unwrap_or(true);
+        let limiter_ceiling: f32 = settings.get(SETTING_LIMITER_CEILING).unwrap_or(-1.0);
+        let denoise_enabled = settings.get(SETTING_DENOISE).unwrap_or(false);
+        let vad_margin = settings.get(SETTING_VAD_MARGIN).unwrap_or(10.0);
+
+        let layout = FrameLayout::new(
+            sample_rate,
+            sample_length,
+            crossfade_length,
+            sola_search_length,
+            extra_inference_time,
+        );
+        let zc = layout.unit;
+        let sample_frame_size = layout.sample_frame_size;
+        let sola_buffer_frame_size = layout.sola_buffer_frame_size;
+        let model_return_length = layout.model_return_length;
 
-        let zc = sample_rate / 100;
-
-        let sample_frame_time = (sample_length * sample_rate as f64 / zc as f64).round() as usize;
-        let sample_frame_size = sample_frame_time * zc;
-
-        let sample_frame_16k = sample_frame_time * 160;
-        let crossfade_frame_size =
-            (crossfade_length * sample_rate as f64 / zc as f64).round() as usize * zc;
-        let sola_buffer_frame_size = usize::min(crossfade_frame_size, 4 * zc);
-        let sola_search_frame_size = zc;
-        let extra_frame_size =
-            (extra_inference_time * sample_rate as f64 / zc as f64).round() as usize * zc;
-
-        let input_buffer_size =
-            extra_frame_size + crossfade_frame_size + sola_search_frame_size + sample_frame_size;
-        let input_buffer = vec![0_f32; input_buffer_size];
-
-        let input_buffer_16k_size = 160 * input_buffer_size / zc;
-        let input_buffer_16k = vec![0_f32; input_buffer_16k_size];
+        let input_buffer = vec![0_f32; layout.input_buffer_size];
+        let input_buffer_16k = vec![0_f32; layout.input_buffer_16k_size];
 
-        let model_return_length =
-            (sample_frame_size + sola_buffer_frame_size + sola_search_frame_size) / zc;
-        let mut model_return_size = model_return_length * (model_output_sample_rate / 100);
+        let mut model_return_size = model_return_length * model_output_sample_rate / 100;
 
         if skip_inference {
             model_output_sample_rate = 16000;
@@ -228,89 +703,280 @@ impl Sourceable for RvcInferenceFilter {
 
         let sola_buffer = ndarray::Array1::zeros(sola_buffer_frame_size);
 
-        let mut fade_in_window = ndarray::Array1::linspace(0.0, 1.0, sola_buffer_frame_size);
-        fade_in_window.mapv_inplace(|x| f32::sin(x * 0.5 * PI).powi(2));
-        let fade_out_window = fade_in_window.mapv(|x| 1.0 - x);
+        let crossfade_window = settings.get(SETTING_CROSSFADE_WINDOW).unwrap_or(CrossfadeWindow::SinSquared);
+        let (fade_in_window, fade_out_window) = crossfade_windows(crossfade_window, sola_buffer_frame_size);
 
         // 48k => 16k sample frame size
-        let downsampler = FftFixedInOut::new(
-            sample_rate, 16000, sample_frame_size + 2 * zc, 1).unwrap();
+        let downsampler = FrameResampler::new(
+            resampler_type, sample_rate, 16000, sample_frame_size + 2 * zc).unwrap();
 
         // model_sample_size => 48k
         let upsampler =
-            FftFixedInOut::new(model_output_sample_rate, sample_rate, model_return_size, 1)
+            FrameResampler::new(resampler_type, model_output_sample_rate, sample_rate, model_return_size)
                 .unwrap();
 
         let output_buffer = vec![0_f32; upsampler.output_frames_max()];
 
-        let binary_path = unsafe { BINARY_PATH.as_ref().unwrap().parent().unwrap().join("rvc-rpc.exe") };
-        let infer_data_path = unsafe { DATA_PATH.as_ref().unwrap() }.join("rvcinfer");
-
-        let rvc = match model_path.clone() {
-            Some(path) => Some(RvcInfer::new(binary_path, model_version, pitch_algorithm, path, infer_data_path)),
-            None => None,
-        };
-
-        let state = RvcInferenceState {
+        let mut state = RvcInferenceState {
             sample_rate,
 
             model_path,
-            index_path,
+            watch_model: settings.get(SETTING_WATCH_MODEL).unwrap_or(true),
+            model_modified: None,
+            model_pending_modified: None,
+            model_last_checked: Instant::now(),
+            index_paths,
+            index_weights,
+            bundle_index: None,
+            bundle_error: None,
+            index_metric: settings.get(SETTING_INDEX_METRIC).unwrap_or(RetrievalMetric::Auto),
+            inference_device: settings.get(SETTING_INFERENCE_DEVICE).unwrap_or(InferenceDevice::Auto),
+            device_priority,
+            device_id: settings.get(SETTING_DEVICE_ID).unwrap_or(0),
+            use_tensorrt: settings.get(SETTING_USE_TENSORRT).unwrap_or(false),
+            use_cuda_graph: settings.get(SETTING_USE_CUDA_GRAPH).unwrap_or(false),
+            use_fp16: settings.get(SETTING_USE_FP16).unwrap_or(false),
+            use_int8: settings.get(SETTING_USE_INT8).unwrap_or(false),
+            feature_encoder: settings.get(SETTING_FEATURE_ENCODER).unwrap_or(FeatureEncoder::ContentVec),
+            encoder_path,
+            contentvec_layers: settings.get(SETTING_CONTENTVEC_LAYERS).unwrap_or(0),
+            feature_cache: settings.get(SETTING_FEATURE_CACHE).unwrap_or(false),
+            speaker_id: settings.get(SETTING_SPEAKER_ID).unwrap_or(0),
+            morph_speaker_id: settings.get(SETTING_MORPH_SPEAKER_ID).unwrap_or(0),
+            speaker_morph: settings.get(SETTING_SPEAKER_MORPH).unwrap_or(0.0),
             model_version,
             pitch_algorithm,
-            model_output_sample_rate,
+            model_output_sample_rate: dest_sample_rate,
+            dest_sample_rate,
             pitch_shift: settings.get(SETTING_PITCH_SHIFT).unwrap_or(12),
+            f0_filter_radius: settings.get(SETTING_F0_FILTER_RADIUS).unwrap_or(0),
+            f0_min: settings.get(SETTING_F0_MIN).unwrap_or(50.0),
+            f0_max: settings.get(SETTING_F0_MAX).unwrap_or(1100.0),
+            voicing_sensitivity: settings.get(SETTING_VOICING_SENSITIVITY).unwrap_or(0.5),
+            autotune_scale: settings
+                .get(SETTING_AUTOTUNE_SCALE)
+                .unwrap_or(AutotuneScale::Off),
+            autotune_key: settings.get(SETTING_AUTOTUNE_KEY).unwrap_or(0),
+            autotune_strength: settings.get(SETTING_AUTOTUNE_STRENGTH).unwrap_or(1.0),
             resonance_shift: settings.get(SETTING_RESONANCE_SHIFT).unwrap_or(0.00),
+            formant_shifter: FormantShifter::new(sample_rate),
             index_rate: settings.get(SETTING_INDEX_RATE).unwrap_or(0.00),
             rms_mix_rate: settings.get(SETTING_LOUDNESS_FACTOR).unwrap_or(0.00),
             sample_length,
+            configured_sample_length: sample_length,
+            adaptive_sample_length: settings.get(SETTING_ADAPTIVE_SAMPLE_LENGTH).unwrap_or(false),
+            chunk_sizer: ChunkSizer::new(),
             crossfade_length,
             extra_inference_time,
+            sola_search_length,
 
+            frame_unit: layout.unit,
+            frame_unit_16k: layout.unit_16k,
             sample_frame_size,
-            sample_frame_16k_size: sample_frame_16k,
-            crossfade_frame_size,
+            sample_frame_16k_size: layout.sample_frame_16k_size,
+            crossfade_frame_size: layout.crossfade_frame_size,
             sola_buffer_frame_size,
-            sola_search_frame_size,
-            extra_frame_size,
+            sola_search_frame_size: layout.sola_search_frame_size,
+            extra_frame_size: layout.extra_frame_size,
             model_return_length,
             model_return_size,
 
+            advanced: advanced.clone(),
+
             input_buffer,
             input_buffer_16k,
+            highpass_buffer_16k: Vec::new(),
+            highpass_frequency: settings.get(SETTING_HIGHPASS_FREQUENCY).unwrap_or(0.0),
             sola_buffer,
             output_buffer,
-
+            cleaned_sample: Vec::with_capacity(sample_frame_size),
+            downsample_buffer: vec![0_f32; downsampler.output_frames_max()],
+            model_output: Vec::with_capacity(model_return_size),
+            envelopes: (Vec::new(), Vec::new()),
+            frame_output: Vec::with_capacity(sample_frame_size),
+            stage_times: StageTimes::default(),
+            resample_time: Duration::ZERO,
+            layout_generation: 0,
+
+            crossfade_window,
             fade_in_window,
             fade_out_window,
 
             skip_inference,
 
+            idle_timeout: settings.get(SETTING_IDLE_TIMEOUT).unwrap_or(0.0),
+            silent_samples: 0,
+            idle_parked: false,
+
+            denoise_enabled,
+            denoiser: denoise_enabled.then(|| Denoiser::new(sample_rate)).flatten(),
+
+            noise_gate_enabled: settings.get(SETTING_NOISE_GATE).unwrap_or(false),
+            noise_gate: NoiseGate::new(gate_params_from_settings(settings, sample_rate)),
+
+            vad_enabled: settings.get(SETTING_VAD).unwrap_or(false),
+            vad_margin,
+            vad: VoiceActivityDetector::new(sample_rate, vad_margin),
+
+            auto_loudness: settings.get(SETTING_AUTO_LOUDNESS).unwrap_or(false),
+            loudness_matcher: LoudnessMatcher::new(sample_rate),
+
+            sibilance_blend: settings.get(SETTING_SIBILANCE_BLEND).unwrap_or(0.0),
+            sibilance_crossover,
+            sibilance_blender: BandBlender::new(sample_rate, sibilance_crossover),
+
+            band_split_mode: settings
+                .get(SETTING_BAND_SPLIT_MODE)
+                .unwrap_or(BandSplitMode::Off),
+            band_split_frequency,
+            band_split_blender: BandBlender::new(sample_rate, band_split_frequency),
+
+            bypass_retrieval: settings.get(SETTING_BYPASS_RETRIEVAL).unwrap_or(false),
+            bypass_envelope: settings.get(SETTING_BYPASS_ENVELOPE).unwrap_or(false),
+            bypass_post_fx: settings.get(SETTING_BYPASS_POST_FX).unwrap_or(false),
+            bypass_sola: settings.get(SETTING_BYPASS_SOLA).unwrap_or(false),
+
+            push_to_convert,
+            convert_held: false,
+            conversion_bypassed: false,
+            wet_mix,
+            convert_gain: if push_to_convert { 0.0 } else { wet_mix as f32 },
+
+            resampler_type,
             upsampler,
             downsampler,
 
-            engine: rvc,
+            engine: None,
+            pending_engine: None,
+            engine_error: None,
         };
 
+        RvcInferenceFilter::restart_rvc_engine_inner(&mut state);
+
         let state = FairMutex::new(state);
 
         let shared_state = RvcInferenceSharedState {
             state,
             running: AtomicBool::new(true),
             channels,
-            input: ArrayQueue::new(120),
-            output: ArrayQueue::new(200),
+            input: ArrayQueue::new(advanced.input_queue_capacity),
+            output: ArrayQueue::new(advanced.output_queue_capacity),
+            spare_buffers: ArrayQueue::new(advanced.output_queue_capacity),
             buffer_changed: AtomicBool::new(false),
             sample_frame_size: AtomicUsize::new(sample_frame_size),
+            wait_timeout: Duration::from_millis(advanced.worker_wait_timeout_ms),
+            input_monitor: InputLevelMonitor::new(),
+            metrics: FilterMetrics::register(),
+            convert_held: AtomicBool::new(false),
+            conversion_bypassed: AtomicBool::new(false),
+            model_without_f0: AtomicBool::new(false),
+            model_version_info: Mutex::new(None),
+            detected_sample_rate: AtomicUsize::new(0),
+            speaker_names: Mutex::new(None),
+            speaker_morph_available: AtomicBool::new(false),
+            quantized_model: AtomicBool::new(false),
+            engine_loading: Mutex::new(None),
+            benchmark: Mutex::new(None),
+            encoder_path_rejected: AtomicBool::new(encoder_path_rejected),
+            worker_high_priority: AtomicBool::new(settings.get(SETTING_WORKER_HIGH_PRIORITY).unwrap_or(false)),
+            worker_core: AtomicUsize::new(worker_core_from_settings(settings)),
+            worker_priority_rejected: AtomicBool::new(false),
+            fixed_latency_samples: AtomicUsize::new((fixed_latency * sample_rate as f64).round() as usize),
+            timestamp_correction: AtomicU64::new(timestamp_correction(
+                fixed_latency,
+                settings.get(SETTING_COMPENSATE_LATENCY).unwrap_or(false),
+            )),
+            concealed_samples: AtomicUsize::new(0),
+            dry_delay_samples: AtomicUsize::new(0),
+            input_gain: AtomicU32::new(db_to_gain(settings.get(SETTING_INPUT_GAIN).unwrap_or(0.0)).to_bits()),
+            output_gain: AtomicU32::new(db_to_gain(settings.get(SETTING_OUTPUT_GAIN).unwrap_or(0.0)).to_bits()),
+            limiter_enabled: AtomicBool::new(limiter_enabled),
+            limiter_ceiling: AtomicU32::new(limiter_ceiling.to_bits()),
+            upmix_mode: AtomicCell::new(upmix_mode),
+            upmix_reset: AtomicBool::new(false),
+            downmix_update: ArrayQueue::new(1),
+            underrun_fallback: AtomicCell::new(
+                settings.get(SETTING_UNDERRUN_FALLBACK).unwrap_or(UnderrunFallback::Discard),
+            ),
         };
 
         let shared_state = Arc::new(shared_state);
+        let source_handle = SourceHandle(source.as_ptr() as *mut obs_source_t);
+        websocket::register_filter(source_handle, shared_state.clone());
+
+        create.register_hotkey(
+            obs_string!("rvc_push_to_convert"),
+            obs_text("HotkeyConvert"),
+            |hotkey, filter: &mut RvcInferenceFilter| {
+                filter
+                    .shared_state
+                    .convert_held
+                    .store(hotkey.pressed, std::sync::atomic::Ordering::Relaxed);
+            },
+        );
+
+        // a pair rather than a toggle, so that a missed key press can't leave it the wrong way
+        // round on stream
+        create.register_hotkey(
+            obs_string!("rvc_bypass_conversion"),
+            obs_text("HotkeyBypass"),
+            |hotkey, filter: &mut RvcInferenceFilter| {
+                if hotkey.pressed {
+                    filter
+                        .shared_state
+                        .conversion_bypassed
+                        .store(true, std::sync::atomic::Ordering::Relaxed);
+                }
+            },
+        );
+        for (name, description, step) in [
+            (obs_string!("rvc_next_preset"), obs_text("HotkeyNextPreset"), 1),
+            (obs_string!("rvc_previous_preset"), obs_text("HotkeyPreviousPreset"), -1),
+        ] {
+            create.register_hotkey(name, description, move |hotkey, filter: &mut RvcInferenceFilter| {
+                if hotkey.pressed {
+                    filter.switch_preset(step);
+                }
+            });
+        }
+        create.register_hotkey(
+            obs_string!("rvc_enable_conversion"),
+            obs_text("HotkeyEnable"),
+            |hotkey, filter: &mut RvcInferenceFilter| {
+                if hotkey.pressed {
+                    filter
+                        .shared_state
+                        .conversion_bypassed
+                        .store(false, std::sync::atomic::Ordering::Relaxed);
+                }
+            },
+        );
 
         Self {
             thread_handle: None,
             shared_state,
             has_input: None,
-            filter_audio_lock: Mutex::new(()),
+            input_position: 0,
+            fixed_latency: FixedLatencyBuffer::new(),
+            upmix_mode,
+            downmix_weights: downmix_weights(
+                settings.get(SETTING_DOWNMIX_MODE).unwrap_or(DownmixMode::Average),
+                channels,
+                settings.get(SETTING_DOWNMIX_LEFT_WEIGHT).unwrap_or(1.0),
+                settings.get(SETTING_DOWNMIX_RIGHT_WEIGHT).unwrap_or(1.0),
+            ),
+            placement_gains: vec![1.0; channels],
+            channel_levels: vec![0.0; channels],
+            side_scratch: Vec::with_capacity(MAX_BLOCK_FRAMES),
+            side_history: FixedLatencyBuffer::new(),
+            side_history_limit: (SIDE_HISTORY_TIME * sample_rate as f64) as u64,
+            limiter_enabled,
+            limiter_ceiling,
+            limiter: OutputLimiter::new(sample_rate, limiter_ceiling as f64),
+            last_output: Vec::new(),
+            repeat_gain: 1.0,
+            source: source_handle,
+            current_preset: preset_from_settings(settings),
         }
     }
 }
@@ -325,58 +991,377 @@ impl GetPropertiesSource for RvcInferenceFilter {
     fn get_properties(&mut self) -> Properties {
         let mut p = Properties::new();
 
+        let warnings = self.get_status_warnings();
+        if !warnings.is_empty() {
+            p.add(
+                SETTING_STATUS,
+                ObsString::from(warnings.join("\n")),
+                TextInfoProp::new(TextInfoType::Warning),
+            );
+        }
+
+        let engine_loading = *self.shared_state.engine_loading.lock();
+        if let Some((loading_time, tensorrt, swapping)) = engine_loading {
+            let meanwhile = text(if swapping { "EngineLoading.KeepModel" } else { "EngineLoading.Dry" });
+            let status = if tensorrt {
+                text_with("EngineLoading.TensorRt", &[&loading_time.as_secs(), &meanwhile])
+            } else {
+                text_with("EngineLoading.Model", &[&loading_time.as_secs(), &meanwhile])
+            };
+            p.add(
+                SETTING_ENGINE_STATUS,
+                ObsString::from(status),
+                TextInfoProp::new(TextInfoType::Normal),
+            );
+        }
+
+        let latency = self.shared_state.metrics.latency();
+        if latency > Duration::ZERO {
+            p.add(
+                SETTING_LATENCY_STATUS,
+                ObsString::from(text_with("LatencyStatus", &[&latency.as_millis()])),
+                TextInfoProp::new(TextInfoType::Normal),
+            );
+        }
+
+        let metrics = &self.shared_state.metrics;
+        if metrics.frames_processed.load(std::sync::atomic::Ordering::Relaxed) > 0 {
+            let milliseconds = |gauge: &std::sync::atomic::AtomicU64| {
+                format!("{:.1}", gauge.load(std::sync::atomic::Ordering::Relaxed) as f64 / 1000.0)
+            };
+            let status = text_with(
+                "PerformanceStatus",
+                &[
+                    &format!("{:.2}", metrics.realtime_factor()),
+                    &milliseconds(&metrics.encoder_time_us),
+                    &milliseconds(&metrics.pitch_time_us),
+                    &milliseconds(&metrics.synthesis_time_us),
+                    &milliseconds(&metrics.resample_time_us),
+                    &metrics.input_queue_depth.load(std::sync::atomic::Ordering::Relaxed),
+                    &metrics.output_queue_depth.load(std::sync::atomic::Ordering::Relaxed),
+                    &metrics.discarded_blocks.load(std::sync::atomic::Ordering::Relaxed),
+                    &metrics.dropped_blocks.load(std::sync::atomic::Ordering::Relaxed),
+                ],
+            );
+            p.add(
+                SETTING_PERFORMANCE_STATUS,
+                ObsString::from(status),
+                TextInfoProp::new(TextInfoType::Normal),
+            );
+        }
+
+        if self.shared_state.model_without_f0.load(std::sync::atomic::Ordering::Relaxed) {
+            p.add(
+                SETTING_MODEL_INFO,
+                obs_text("ModelWithoutF0"),
+                TextInfoProp::new(TextInfoType::Normal),
+            );
+        }
+
         p.add(
-            SETTING_MODEL_PATH,
-            obs_string!("模型路径"),
-            PathProp::new(PathType::File).with_filter(obs_string!("ONNX 模型文件 (*.onnx)")),
+            SETTING_PRESET_SELECT,
+            obs_text("Presets"),
+            PresetProp::new(self.source, unsafe { CONFIG_PATH.clone() }),
         );
 
         p.add(
-            SETTING_INDEX_PATH,
-            obs_string!("RVC 音高索引文件路径"),
-            PathProp::new(PathType::File).with_filter(obs_string!("Index 文件 (*.index)")),
+            SETTING_MODEL_PATH,
+            obs_text("ModelPath"),
+            PathProp::new(PathType::File)
+                .with_filter(obs_text("ModelFileFilter")),
+        );
+        p.add(
+            SETTING_MODEL_SELECT,
+            obs_text("ModelFromFolder"),
+            ModelLibraryProp::new(SETTING_MODEL_PATH, rpc_binary_path(unsafe { BINARY_PATH.as_ref().unwrap() })),
+        );
+        if let Some(status) = model_download::download_status() {
+            let info_type = match status {
+                DownloadStatus::Failed(_) => TextInfoType::Error,
+                _ => TextInfoType::Normal,
+            };
+            p.add(SETTING_DOWNLOAD_STATUS, ObsString::from(status.describe()), TextInfoProp::new(info_type));
+        }
+        p.add(
+            SETTING_RELOAD_MODEL,
+            obs_text("ReloadModel"),
+            ButtonProp::new(reload_clicked, Arc::as_ptr(&self.shared_state) as *mut c_void),
         );
 
+        for slot in 0..MAX_INDEX_COUNT {
+            let description = match slot {
+                0 => obs_text("IndexPath"),
+                _ => ObsString::from(text_with("IndexPath.Extra", &[&(slot + 1)])),
+            };
+            p.add(
+                setting_index_path(slot),
+                description,
+                PathProp::new(PathType::File).with_filter(obs_text("IndexFileFilter")),
+            );
+
+            p.add(
+                setting_index_weight(slot),
+                ObsString::from(text_with("IndexWeight", &[&(slot + 1)])),
+                NumberProp::new_float(0.01)
+                    .with_range(0.00..=1.00)
+                    .with_slider(),
+            );
+        }
+
+        // faiss indices record their metric, a third-party one may have been built by inner product
+        let mut index_metric_list =
+            p.add_list::<RetrievalMetric>(SETTING_INDEX_METRIC, obs_text("IndexMetric"), false);
+        index_metric_list.push(obs_text("IndexMetric.Auto"), RetrievalMetric::Auto);
+        index_metric_list.push(obs_text("IndexMetric.L2"), RetrievalMetric::L2);
+        index_metric_list.push(obs_text("IndexMetric.Cosine"), RetrievalMetric::Cosine);
+
+        let model_version_info = match *self.shared_state.model_version_info.lock() {
+            Some((version, true)) => Some(text_with("ModelVersion.Detected", &[&version.to_string()])),
+            Some((version, false)) => Some(text_with("ModelVersion.Fallback", &[&version.to_string()])),
+            None => None,
+        };
+        if let Some(model_version_info) = model_version_info {
+            p.add(
+                SETTING_MODEL_VERSION_INFO,
+                ObsString::from(model_version_info),
+                TextInfoProp::new(TextInfoType::Normal),
+            );
+        }
+
         let mut version_list =
-            p.add_list::<RvcModelVersion>(SETTING_MODEL_VERSION, obs_string!("模型版本"), false);
+            p.add_list::<RvcModelVersion>(SETTING_MODEL_VERSION, obs_text("ModelVersion"), false);
 
         version_list.push(obs_string!("v1"), RvcModelVersion::V1);
         version_list.push(obs_string!("v2"), RvcModelVersion::V2);
 
+        let speaker_names = self.shared_state.speaker_names.lock().clone();
+        add_speaker_property(&mut p, SETTING_SPEAKER_ID, obs_text("Speaker"), speaker_names.as_deref());
+
+        if self.shared_state.speaker_morph_available.load(std::sync::atomic::Ordering::Relaxed) {
+            add_speaker_property(&mut p, SETTING_MORPH_SPEAKER_ID, obs_text("MorphSpeaker"), speaker_names.as_deref());
+            p.add(
+                SETTING_SPEAKER_MORPH,
+                obs_text("SpeakerMorph"),
+                NumberProp::new_float(0.01)
+                    .with_range(0.00..=1.00)
+                    .with_slider(),
+            );
+        }
+
+        let mut device_list =
+            p.add_list::<InferenceDevice>(SETTING_INFERENCE_DEVICE, obs_text("InferenceDevice"), false);
+        device_list.push(obs_text("InferenceDevice.Auto"), InferenceDevice::Auto);
+        if cfg!(target_os = "macos") {
+            device_list.push(obs_text("InferenceDevice.CoreMl"), InferenceDevice::CoreMl);
+        } else if cfg!(target_os = "linux") {
+            device_list.push(obs_text("InferenceDevice.Cuda"), InferenceDevice::Cuda);
+            device_list.push(obs_text("InferenceDevice.Rocm"), InferenceDevice::Rocm);
+            device_list.push(obs_text("InferenceDevice.OpenVino"), InferenceDevice::OpenVino);
+        } else {
+            device_list.push(obs_text("InferenceDevice.Cuda"), InferenceDevice::Cuda);
+            device_list.push(obs_text("InferenceDevice.OpenVino"), InferenceDevice::OpenVino);
+            device_list.push(obs_text("InferenceDevice.DirectMl"), InferenceDevice::DirectMl);
+        }
+        device_list.push(obs_string!("CPU"), InferenceDevice::Cpu);
+
+        for slot in 0..MAX_DEVICE_PRIORITY {
+            let mut priority_list = p.add_list::<InferenceDevice>(
+                setting_device_priority(slot),
+                ObsString::from(text_with("DevicePriority", &[&(slot + 1)])),
+                false,
+            );
+            priority_list.push(obs_text("DevicePriority.None"), InferenceDevice::Auto);
+            priority_list.push(obs_string!("TensorRT"), InferenceDevice::TensorRt);
+            priority_list.push(obs_string!("CUDA"), InferenceDevice::Cuda);
+            priority_list.push(obs_string!("ROCm"), InferenceDevice::Rocm);
+            priority_list.push(obs_string!("OpenVINO"), InferenceDevice::OpenVino);
+            priority_list.push(obs_string!("DirectML"), InferenceDevice::DirectMl);
+            priority_list.push(obs_string!("CoreML"), InferenceDevice::CoreMl);
+        }
+
+        p.add(
+            SETTING_DEVICE_ID,
+            obs_text("GpuId"),
+            NumberProp::new_int().with_range(0..=15),
+        );
+
+        p.add(
+            SETTING_WORKER_HIGH_PRIORITY,
+            obs_text("WorkerHighPriority"),
+            BoolProp
+        );
+
+        p.add(
+            SETTING_WORKER_CORE,
+            obs_text("WorkerCore"),
+            NumberProp::new_int().with_range(-1..=255),
+        );
+
+        p.add(
+            SETTING_USE_TENSORRT,
+            obs_text("UseTensorRt"),
+            BoolProp
+        );
+
+        p.add(
+            SETTING_USE_CUDA_GRAPH,
+            obs_text("UseCudaGraph"),
+            BoolProp
+        );
+
+        p.add(
+            SETTING_USE_FP16,
+            obs_text("Fp16"),
+            BoolProp
+        );
+
+        p.add(
+            SETTING_USE_INT8,
+            obs_text("Int8"),
+            BoolProp
+        );
+
+        let mut encoder_list =
+            p.add_list::<FeatureEncoder>(SETTING_FEATURE_ENCODER, obs_text("FeatureEncoder"), false);
+        encoder_list.push(obs_string!("ContentVec"), FeatureEncoder::ContentVec);
+        encoder_list.push(obs_string!("HuBERT-soft"), FeatureEncoder::HubertSoft);
+        encoder_list.push(obs_string!("Whisper"), FeatureEncoder::Whisper);
+
+        p.add(
+            SETTING_ENCODER_PATH,
+            obs_text("EncoderPath"),
+            PathProp::new(PathType::File).with_filter(obs_text("OnnxFileFilter")),
+        );
+
+        let mut contentvec_layers_list =
+            p.add_list::<i64>(SETTING_CONTENTVEC_LAYERS, obs_text("ContentVecLayers"), false);
+        contentvec_layers_list.push(obs_text("ContentVecLayers.Auto"), 0);
+        contentvec_layers_list.push(obs_text("ContentVecLayers.9"), 9);
+        contentvec_layers_list.push(obs_text("ContentVecLayers.12"), 12);
+
+        p.add(
+            SETTING_FEATURE_CACHE,
+            obs_text("FeatureCache"),
+            BoolProp
+        );
+
+        let detected_sample_rate = self
+            .shared_state
+            .detected_sample_rate
+            .load(std::sync::atomic::Ordering::Relaxed);
+        if detected_sample_rate > 0 {
+            p.add(
+                SETTING_SAMPLE_RATE_INFO,
+                ObsString::from(text_with("ModelSampleRate.Detected", &[&detected_sample_rate])),
+                TextInfoProp::new(TextInfoType::Normal),
+            );
+        }
+
         p.add(
             SETTING_DEST_SAMPLE_RATE,
-            obs_string!("模型目标采样率"),
+            obs_text("ModelSampleRate"),
             NumberProp::new_int()
                 .with_range(16000..=48000)
                 .with_step(4000)
                 .with_slider(),
         );
 
-        let mut pitch_algorithm_list =
-            p.add_list::<PitchAlgorithm>(SETTING_PITCH_ALGORITHM, obs_string!("音高算法"), false);
+        // the model has no pitch input, so none of the pitch settings would change anything
+        let model_without_f0 = self.shared_state.model_without_f0.load(std::sync::atomic::Ordering::Relaxed);
+        if !model_without_f0 {
+            let mut pitch_algorithm_list =
+                p.add_list::<PitchAlgorithm>(SETTING_PITCH_ALGORITHM, obs_text("PitchAlgorithm"), false);
+
+            // only offer what can actually be loaded, plus the current choice so it stays visible
+            let current_pitch_algorithm = self.shared_state.state.lock().pitch_algorithm;
+            let f0_path = unsafe { DATA_PATH.as_ref().unwrap() }.join("rvcinfer").join("f0");
+            for algorithm in PitchAlgorithm::ALL {
+                let available = algorithm
+                    .model_file_names()
+                    .iter()
+                    .all(|file_name| f0_path.join(file_name).exists());
+                if available || algorithm == current_pitch_algorithm {
+                    pitch_algorithm_list.push(pitch_algorithm_label(algorithm), algorithm);
+                }
+            }
 
-        pitch_algorithm_list.push(obs_string!("RMVPE"), PitchAlgorithm::Rmvpe);
+            p.add(
+                SETTING_PITCH_SHIFT,
+                obs_text("PitchShift"),
+                NumberProp::new_int()
+                    .with_range(-24..=24)
+                    .with_step(1)
+                    .with_slider(),
+            );
+
+            p.add(
+                SETTING_F0_FILTER_RADIUS,
+                obs_text("F0FilterRadius"),
+                NumberProp::new_int()
+                    .with_range(0..=7)
+                    .with_step(1)
+                    .with_slider(),
+            );
+
+            p.add(
+                SETTING_F0_MIN,
+                obs_text("F0Min"),
+                NumberProp::new_float(1.0)
+                    .with_range(20.0..=500.0)
+                    .with_slider(),
+            );
+
+            p.add(
+                SETTING_F0_MAX,
+                obs_text("F0Max"),
+                NumberProp::new_float(10.0)
+                    .with_range(200.0..=2000.0)
+                    .with_slider(),
+            );
+
+            p.add(
+                SETTING_VOICING_SENSITIVITY,
+                obs_text("VoicingSensitivity"),
+                NumberProp::new_float(0.01)
+                    .with_range(0.00..=1.00)
+                    .with_slider(),
+            );
+
+            let mut autotune_scale_list =
+                p.add_list::<AutotuneScale>(SETTING_AUTOTUNE_SCALE, obs_text("AutotuneScale"), false);
+
+            autotune_scale_list.push(obs_text("Disabled"), AutotuneScale::Off);
+            autotune_scale_list.push(obs_text("AutotuneScale.Chromatic"), AutotuneScale::Chromatic);
+            autotune_scale_list.push(obs_text("AutotuneScale.Major"), AutotuneScale::Major);
+            autotune_scale_list.push(obs_text("AutotuneScale.Minor"), AutotuneScale::Minor);
+
+            let mut autotune_key_list =
+                p.add_list::<i64>(SETTING_AUTOTUNE_KEY, obs_text("AutotuneKey"), false);
+
+            for (key, name) in ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"].iter().enumerate() {
+                autotune_key_list.push(ObsString::from(name.to_string()), key as i64);
+            }
 
-        p.add(
-            SETTING_PITCH_SHIFT,
-            obs_string!("音调设置"),
-            NumberProp::new_int()
-                .with_range(-24..=24)
-                .with_step(1)
-                .with_slider(),
-        );
+            p.add(
+                SETTING_AUTOTUNE_STRENGTH,
+                obs_text("AutotuneStrength"),
+                NumberProp::new_float(0.01)
+                    .with_range(0.00..=1.00)
+                    .with_slider(),
+            );
+        }
 
         p.add(
             SETTING_RESONANCE_SHIFT,
-            obs_string!("共振偏移"),
-            NumberProp::new_float(0.07)
+            obs_text("ResonanceShift"),
+            NumberProp::new_float(0.1)
                 .with_range(-5.0..=5.0)
                 .with_slider(),
         );
 
         p.add(
             SETTING_INDEX_RATE,
-            obs_string!("索引率"),
+            obs_text("IndexRate"),
             NumberProp::new_float(0.01)
                 .with_range(0.00..=1.00)
                 .with_slider(),
@@ -384,7 +1369,7 @@ impl GetPropertiesSource for RvcInferenceFilter {
 
         p.add(
             SETTING_LOUDNESS_FACTOR,
-            obs_string!("响度因子"),
+            obs_text("RmsMixRate"),
             NumberProp::new_float(0.01)
                 .with_range(0.00..=1.00)
                 .with_slider(),
@@ -392,101 +1377,555 @@ impl GetPropertiesSource for RvcInferenceFilter {
 
         p.add(
             SETTING_SAMPLE_LENGTH,
-            obs_string!("采样长度"),
+            obs_text("SampleLength"),
             NumberProp::new_float(0.01)
                 .with_range(0.01..=1.50)
                 .with_slider(),
         );
 
+        p.add(
+            SETTING_ADAPTIVE_SAMPLE_LENGTH,
+            obs_text("AdaptiveSampleLength"),
+            BoolProp
+        );
+
         p.add(
             SETTING_FADE_LENGTH,
-            obs_string!("淡入淡出长度"),
+            obs_text("CrossfadeLength"),
             NumberProp::new_float(0.01)
                 .with_range(0.01..=0.15)
                 .with_slider(),
         );
 
+        let mut crossfade_window_list =
+            p.add_list::<CrossfadeWindow>(SETTING_CROSSFADE_WINDOW, obs_text("CrossfadeWindow"), false);
+        crossfade_window_list.push(obs_string!("sin² (Hann)"), CrossfadeWindow::SinSquared);
+        crossfade_window_list.push(obs_text("CrossfadeWindow.EqualPower"), CrossfadeWindow::EqualPower);
+        crossfade_window_list.push(obs_text("CrossfadeWindow.Linear"), CrossfadeWindow::Linear);
+        crossfade_window_list.push(obs_text("CrossfadeWindow.Tukey"), CrossfadeWindow::Tukey);
+
         p.add(
             SETTING_EXTRA_INFERENCE_TIME,
-            obs_string!("额外推理时长"),
+            obs_text("ExtraInferenceTime"),
             NumberProp::new_float(0.01)
                 .with_range(0.00..=5.00)
                 .with_slider(),
         );
 
+        p.add(
+            SETTING_SOLA_SEARCH_LENGTH,
+            obs_text("SolaSearchLength"),
+            NumberProp::new_float(0.01)
+                .with_range(0.01..=0.10)
+                .with_slider(),
+        );
+
+        p.add(
+            SETTING_RUN_BENCHMARK,
+            ObsString::from(text_with("RunBenchmark", &[&BENCHMARK_FRAMES])),
+            ButtonProp::new(benchmark_clicked, Arc::as_ptr(&self.shared_state) as *mut c_void),
+        );
+        if let Some(status) = self.shared_state.benchmark.lock().clone() {
+            let info_type = match status {
+                BenchmarkStatus::Failed(_) => TextInfoType::Error,
+                _ => TextInfoType::Normal,
+            };
+            p.add(SETTING_BENCHMARK_STATUS, ObsString::from(status.describe()), TextInfoProp::new(info_type));
+        }
+
+        let mut resampler_list =
+            p.add_list::<ResamplerType>(SETTING_RESAMPLER, obs_text("Resampler"), false);
+        resampler_list.push(obs_text("Resampler.Fft"), ResamplerType::Fft);
+        resampler_list.push(obs_text("Resampler.SincFast"), ResamplerType::SincFast);
+        resampler_list.push(obs_text("Resampler.SincBalanced"), ResamplerType::SincBalanced);
+        resampler_list.push(obs_text("Resampler.SincBest"), ResamplerType::SincBest);
+
         p.add(
             SETTING_SKIP_INFERENCE,
-            obs_string!("跳过推理"),
+            obs_text("SkipInference"),
             BoolProp
         );
 
-        p
-    }
-}
+        p.add(
+            SETTING_WATCH_MODEL,
+            obs_text("WatchModel"),
+            BoolProp
+        );
 
-impl UpdateSource for RvcInferenceFilter {
-    fn update(&mut self, settings: &mut DataObj, context: &mut GlobalContext) {
-        let mut state = self.shared_state.state.lock();
+        p.add(
+            SETTING_PUSH_TO_CONVERT,
+            obs_text("PushToConvert"),
+            BoolProp
+        );
 
-        let sample_rate = context.with_audio(|audio| audio.sample_rate());
-        state.sample_rate = sample_rate;
+        p.add(
+            SETTING_WET_MIX,
+            obs_text("WetMix"),
+            NumberProp::new_float(0.01)
+                .with_range(0.00..=1.00)
+                .with_slider(),
+        );
 
-        let model_changed = get_path_from_settings!(state.model_path, settings, SETTING_MODEL_PATH);
-        let index_changed = get_path_from_settings!(state.index_path, settings, SETTING_INDEX_PATH);
+        p.add(
+            SETTING_IDLE_TIMEOUT,
+            obs_text("IdleTimeout"),
+            NumberProp::new_float(1.0)
+                .with_range(0.0..=600.0)
+                .with_slider(),
+        );
 
-        let mut recalculate_input_buffer = false;
-        let mut reload_rvc = model_changed || index_changed;
+        p.add(
+            SETTING_DENOISE,
+            obs_text("Denoise"),
+            BoolProp
+        );
 
-        if let Some(new_pitch_shift) = settings.get(SETTING_PITCH_SHIFT) {
-            if state.pitch_shift != new_pitch_shift {
-                state.pitch_shift = new_pitch_shift;
-            }
-        }
+        p.add(
+            SETTING_HIGHPASS_FREQUENCY,
+            obs_text("HighpassFrequency"),
+            NumberProp::new_float(1.0)
+                .with_range(0.0..=200.0)
+                .with_slider(),
+        );
 
-        if let Some(new_resonance_shift) = settings.get(SETTING_RESONANCE_SHIFT) {
-            if state.resonance_shift != new_resonance_shift {
-                state.resonance_shift = new_resonance_shift;
-            }
-        }
+        p.add(
+            SETTING_NOISE_GATE,
+            obs_text("NoiseGate"),
+            BoolProp
+        );
 
-        if let Some(new_index_rate) = settings.get(SETTING_INDEX_RATE) {
-            if state.index_rate != new_index_rate {
-                state.index_rate = new_index_rate;
-            }
-        }
+        p.add(
+            SETTING_GATE_THRESHOLD,
+            obs_text("NoiseGateThreshold"),
+            NumberProp::new_float(1.0)
+                .with_range(-80.0..=0.0)
+                .with_slider(),
+        );
 
-        if let Some(new_rms_mix_rate) = settings.get(SETTING_LOUDNESS_FACTOR) {
-            if state.rms_mix_rate != new_rms_mix_rate {
-                state.rms_mix_rate = new_rms_mix_rate;
-            }
-        }
+        p.add(
+            SETTING_GATE_ATTACK,
+            obs_text("NoiseGateAttack"),
+            NumberProp::new_float(0.5)
+                .with_range(0.5..=100.0)
+                .with_slider(),
+        );
 
-        if let Some(new_sample_length) = settings.get(SETTING_SAMPLE_LENGTH) {
-            if state.sample_length != new_sample_length {
-                state.sample_length = new_sample_length;
-                recalculate_input_buffer = true;
-            }
-        }
+        p.add(
+            SETTING_GATE_RELEASE,
+            obs_text("NoiseGateRelease"),
+            NumberProp::new_float(5.0)
+                .with_range(10.0..=1000.0)
+                .with_slider(),
+        );
 
-        if let Some(new_fade_length) = settings.get(SETTING_FADE_LENGTH) {
-            if state.crossfade_length != new_fade_length {
-                state.crossfade_length = new_fade_length;
-                recalculate_input_buffer = true;
-            }
-        }
+        p.add(
+            SETTING_VAD,
+            obs_text("Vad"),
+            BoolProp
+        );
 
-        if let Some(new_extra_inference_time) = settings.get(SETTING_EXTRA_INFERENCE_TIME) {
-            if state.extra_inference_time != new_extra_inference_time {
-                state.extra_inference_time = new_extra_inference_time;
-                recalculate_input_buffer = true;
-            }
-        }
+        p.add(
+            SETTING_VAD_MARGIN,
+            obs_text("VadMargin"),
+            NumberProp::new_float(1.0)
+                .with_range(3.0..=30.0)
+                .with_slider(),
+        );
 
-        if let Some(new_dest_sample_rate) = settings.get(SETTING_DEST_SAMPLE_RATE) {
-            if state.model_output_sample_rate != new_dest_sample_rate {
-                state.model_output_sample_rate = new_dest_sample_rate;
-                recalculate_input_buffer = true;
-            }
+        p.add(
+            SETTING_FIXED_LATENCY,
+            obs_text("FixedLatency"),
+            NumberProp::new_float(0.01)
+                .with_range(0.00..=5.00)
+                .with_slider(),
+        );
+
+        p.add(
+            SETTING_COMPENSATE_LATENCY,
+            obs_text("CompensateLatency"),
+            BoolProp
+        );
+
+        p.add(
+            SETTING_SIBILANCE_BLEND,
+            obs_text("SibilanceBlend"),
+            NumberProp::new_float(0.01)
+                .with_range(0.00..=1.00)
+                .with_slider(),
+        );
+
+        p.add(
+            SETTING_SIBILANCE_CROSSOVER,
+            obs_text("SibilanceCrossover"),
+            NumberProp::new_float(100.0)
+                .with_range(2000.0..=12000.0)
+                .with_slider(),
+        );
+
+        p.add(
+            SETTING_INPUT_GAIN,
+            obs_text("InputGain"),
+            NumberProp::new_float(0.5)
+                .with_range(-30.0..=30.0)
+                .with_slider(),
+        );
+
+        p.add(
+            SETTING_OUTPUT_GAIN,
+            obs_text("OutputGain"),
+            NumberProp::new_float(0.5)
+                .with_range(-30.0..=30.0)
+                .with_slider(),
+        );
+
+        p.add(
+            SETTING_AUTO_LOUDNESS,
+            obs_text("AutoLoudness"),
+            BoolProp
+        );
+
+        p.add(
+            SETTING_OUTPUT_LIMITER,
+            obs_text("OutputLimiter"),
+            BoolProp
+        );
+
+        p.add(
+            SETTING_LIMITER_CEILING,
+            obs_text("LimiterCeiling"),
+            NumberProp::new_float(0.1)
+                .with_range(-12.0..=0.0)
+                .with_slider(),
+        );
+
+        let mut downmix_list =
+            p.add_list::<DownmixMode>(SETTING_DOWNMIX_MODE, obs_text("DownmixMode"), false);
+
+        downmix_list.push(obs_text("DownmixMode.Average"), DownmixMode::Average);
+        downmix_list.push(obs_text("DownmixMode.Left"), DownmixMode::Left);
+        downmix_list.push(obs_text("DownmixMode.Right"), DownmixMode::Right);
+        downmix_list.push(obs_text("DownmixMode.Custom"), DownmixMode::Custom);
+
+        p.add(
+            SETTING_DOWNMIX_LEFT_WEIGHT,
+            obs_text("DownmixLeftWeight"),
+            NumberProp::new_float(0.01)
+                .with_range(0.00..=1.00)
+                .with_slider(),
+        );
+
+        p.add(
+            SETTING_DOWNMIX_RIGHT_WEIGHT,
+            obs_text("DownmixRightWeight"),
+            NumberProp::new_float(0.01)
+                .with_range(0.00..=1.00)
+                .with_slider(),
+        );
+
+        let mut upmix_list =
+            p.add_list::<UpmixMode>(SETTING_UPMIX_MODE, obs_text("UpmixMode"), false);
+
+        upmix_list.push(obs_text("UpmixMode.Duplicate"), UpmixMode::Duplicate);
+        upmix_list.push(obs_text("UpmixMode.CenterOnly"), UpmixMode::CenterOnly);
+        upmix_list.push(obs_text("UpmixMode.Placement"), UpmixMode::Placement);
+        upmix_list.push(obs_text("UpmixMode.MidSide"), UpmixMode::MidSide);
+
+        let mut underrun_list = p.add_list::<UnderrunFallback>(
+            SETTING_UNDERRUN_FALLBACK,
+            obs_text("UnderrunFallback"),
+            false,
+        );
+
+        underrun_list.push(obs_text("UnderrunFallback.Discard"), UnderrunFallback::Discard);
+        underrun_list.push(obs_text("UnderrunFallback.Dry"), UnderrunFallback::Dry);
+        underrun_list.push(obs_text("UnderrunFallback.RepeatLast"), UnderrunFallback::RepeatLast);
+
+        let mut band_split_list =
+            p.add_list::<BandSplitMode>(SETTING_BAND_SPLIT_MODE, obs_text("BandSplitMode"), false);
+
+        band_split_list.push(obs_text("Disabled"), BandSplitMode::Off);
+        band_split_list.push(obs_text("BandSplitMode.DryLow"), BandSplitMode::DryLow);
+        band_split_list.push(obs_text("BandSplitMode.DryHigh"), BandSplitMode::DryHigh);
+
+        p.add(
+            SETTING_BAND_SPLIT_FREQUENCY,
+            obs_text("BandSplitFrequency"),
+            NumberProp::new_float(10.0)
+                .with_range(80.0..=4000.0)
+                .with_slider(),
+        );
+
+        p.add(
+            SETTING_BYPASS_RETRIEVAL,
+            obs_text("BypassRetrieval"),
+            BoolProp
+        );
+
+        p.add(
+            SETTING_BYPASS_ENVELOPE,
+            obs_text("BypassEnvelope"),
+            BoolProp
+        );
+
+        p.add(
+            SETTING_BYPASS_POST_FX,
+            obs_text("BypassPostFx"),
+            BoolProp
+        );
+
+        p.add(
+            SETTING_BYPASS_SOLA,
+            obs_text("BypassSola"),
+            BoolProp
+        );
+
+        p
+    }
+}
+
+impl UpdateSource for RvcInferenceFilter {
+    fn update(&mut self, settings: &mut DataObj, context: &mut GlobalContext) {
+        let mut state = self.shared_state.state.lock();
+
+        let sample_rate = context.with_audio(|audio| audio.sample_rate());
+        state.sample_rate = sample_rate;
+
+        let model_changed = get_path_from_settings!(state.model_path, settings, SETTING_MODEL_PATH);
+        if model_changed {
+            // read below like any other default, so that what the user set still wins
+            set_voice_defaults(settings, &voice_defaults(state.model_path.as_deref()));
+        }
+        self.current_preset = preset_from_settings(settings);
+        let mut index_changed = false;
+        for slot in 0..MAX_INDEX_COUNT {
+            let index_path_setting = setting_index_path(slot);
+            index_changed |= get_path_from_settings!(state.index_paths[slot], settings, index_path_setting);
+
+            if let Some(new_index_weight) = settings.get(setting_index_weight(slot)) {
+                if state.index_weights[slot] != new_index_weight {
+                    state.index_weights[slot] = new_index_weight;
+                }
+            }
+        }
+
+        let (encoder_path, encoder_path_rejected) = encoder_path_from_settings(settings);
+        self.shared_state
+            .encoder_path_rejected
+            .store(encoder_path_rejected, std::sync::atomic::Ordering::Relaxed);
+        let encoder_changed = state.encoder_path != encoder_path;
+        state.encoder_path = encoder_path;
+
+        let mut recalculate_input_buffer = false;
+        let mut reload_rvc = model_changed || index_changed || encoder_changed;
+
+        if let Some(new_pitch_shift) = settings.get(SETTING_PITCH_SHIFT) {
+            if state.pitch_shift != new_pitch_shift {
+                state.pitch_shift = new_pitch_shift;
+            }
+        }
+
+        if let Some(new_f0_filter_radius) = settings.get(SETTING_F0_FILTER_RADIUS) {
+            if state.f0_filter_radius != new_f0_filter_radius {
+                state.f0_filter_radius = new_f0_filter_radius;
+            }
+        }
+
+        if let Some(new_f0_min) = settings.get(SETTING_F0_MIN) {
+            if state.f0_min != new_f0_min {
+                state.f0_min = new_f0_min;
+            }
+        }
+
+        if let Some(new_f0_max) = settings.get(SETTING_F0_MAX) {
+            if state.f0_max != new_f0_max {
+                state.f0_max = new_f0_max;
+            }
+        }
+
+        if let Some(new_voicing_sensitivity) = settings.get(SETTING_VOICING_SENSITIVITY) {
+            if state.voicing_sensitivity != new_voicing_sensitivity {
+                state.voicing_sensitivity = new_voicing_sensitivity;
+            }
+        }
+
+        if let Some(new_autotune_scale) = settings.get(SETTING_AUTOTUNE_SCALE) {
+            if state.autotune_scale != new_autotune_scale {
+                state.autotune_scale = new_autotune_scale;
+            }
+        }
+
+        if let Some(new_autotune_key) = settings.get(SETTING_AUTOTUNE_KEY) {
+            if state.autotune_key != new_autotune_key {
+                state.autotune_key = new_autotune_key;
+            }
+        }
+
+        if let Some(new_autotune_strength) = settings.get(SETTING_AUTOTUNE_STRENGTH) {
+            if state.autotune_strength != new_autotune_strength {
+                state.autotune_strength = new_autotune_strength;
+            }
+        }
+
+        if let Some(new_resonance_shift) = settings.get(SETTING_RESONANCE_SHIFT) {
+            if state.resonance_shift != new_resonance_shift {
+                state.resonance_shift = new_resonance_shift;
+            }
+        }
+
+        if let Some(new_index_rate) = settings.get(SETTING_INDEX_RATE) {
+            if state.index_rate != new_index_rate {
+                state.index_rate = new_index_rate;
+            }
+        }
+
+        if let Some(new_rms_mix_rate) = settings.get(SETTING_LOUDNESS_FACTOR) {
+            if state.rms_mix_rate != new_rms_mix_rate {
+                state.rms_mix_rate = new_rms_mix_rate;
+            }
+        }
+
+        if let Some(new_sample_length) = settings.get(SETTING_SAMPLE_LENGTH) {
+            if state.configured_sample_length != new_sample_length {
+                state.configured_sample_length = new_sample_length;
+                state.sample_length = new_sample_length;
+                state.chunk_sizer.reset();
+                recalculate_input_buffer = true;
+            }
+        }
+
+        if let Some(new_adaptive_sample_length) = settings.get(SETTING_ADAPTIVE_SAMPLE_LENGTH) {
+            if state.adaptive_sample_length != new_adaptive_sample_length {
+                state.adaptive_sample_length = new_adaptive_sample_length;
+                state.chunk_sizer.reset();
+                if state.sample_length != state.configured_sample_length {
+                    state.sample_length = state.configured_sample_length;
+                    recalculate_input_buffer = true;
+                }
+            }
+        }
+
+        if let Some(new_fade_length) = settings.get(SETTING_FADE_LENGTH) {
+            if state.crossfade_length != new_fade_length {
+                state.crossfade_length = new_fade_length;
+                recalculate_input_buffer = true;
+            }
+        }
+
+        if let Some(new_crossfade_window) = settings.get(SETTING_CROSSFADE_WINDOW) {
+            if state.crossfade_window != new_crossfade_window {
+                state.crossfade_window = new_crossfade_window;
+                // the crossfade length stays, only the windows change
+                let (fade_in_window, fade_out_window) =
+                    crossfade_windows(new_crossfade_window, state.sola_buffer_frame_size);
+                state.fade_in_window = fade_in_window;
+                state.fade_out_window = fade_out_window;
+            }
+        }
+
+        if let Some(new_extra_inference_time) = settings.get(SETTING_EXTRA_INFERENCE_TIME) {
+            if state.extra_inference_time != new_extra_inference_time {
+                state.extra_inference_time = new_extra_inference_time;
+                recalculate_input_buffer = true;
+            }
+        }
+
+        if let Some(new_sola_search_length) = settings.get(SETTING_SOLA_SEARCH_LENGTH) {
+            if state.sola_search_length != new_sola_search_length {
+                state.sola_search_length = new_sola_search_length;
+                recalculate_input_buffer = true;
+            }
+        }
+
+        if let Some(new_resampler_type) = settings.get(SETTING_RESAMPLER) {
+            if state.resampler_type != new_resampler_type {
+                state.resampler_type = new_resampler_type;
+                // rebuilds both resamplers with the buffers they feed
+                recalculate_input_buffer = true;
+            }
+        }
+
+        if let Some(new_dest_sample_rate) = settings.get(SETTING_DEST_SAMPLE_RATE) {
+            if state.dest_sample_rate != new_dest_sample_rate {
+                state.dest_sample_rate = new_dest_sample_rate;
+                // a rate stated by the model wins over the setting
+                let detected_sample_rate = state.engine.as_ref().and_then(RvcInfer::output_sample_rate).flatten();
+                if detected_sample_rate.is_none() {
+                    state.model_output_sample_rate = new_dest_sample_rate;
+                    recalculate_input_buffer = true;
+                }
+            }
+        }
+
+        if let Some(new_inference_device) = settings.get(SETTING_INFERENCE_DEVICE) {
+            if state.inference_device != new_inference_device {
+                state.inference_device = new_inference_device;
+                reload_rvc = true;
+            }
+        }
+
+        for slot in 0..MAX_DEVICE_PRIORITY {
+            if let Some(new_device) = settings.get(setting_device_priority(slot)) {
+                if state.device_priority[slot] != new_device {
+                    state.device_priority[slot] = new_device;
+                    reload_rvc = true;
+                }
+            }
+        }
+
+        if let Some(new_use_tensorrt) = settings.get(SETTING_USE_TENSORRT) {
+            if state.use_tensorrt != new_use_tensorrt {
+                state.use_tensorrt = new_use_tensorrt;
+                reload_rvc = true;
+            }
+        }
+
+        if let Some(new_use_cuda_graph) = settings.get(SETTING_USE_CUDA_GRAPH) {
+            if state.use_cuda_graph != new_use_cuda_graph {
+                state.use_cuda_graph = new_use_cuda_graph;
+                reload_rvc = true;
+            }
+        }
+
+        if let Some(new_use_fp16) = settings.get(SETTING_USE_FP16) {
+            if state.use_fp16 != new_use_fp16 {
+                state.use_fp16 = new_use_fp16;
+                reload_rvc = true;
+            }
+        }
+
+        if let Some(new_use_int8) = settings.get(SETTING_USE_INT8) {
+            if state.use_int8 != new_use_int8 {
+                state.use_int8 = new_use_int8;
+                reload_rvc = true;
+            }
+        }
+
+        if let Some(new_device_id) = settings.get(SETTING_DEVICE_ID) {
+            if state.device_id != new_device_id {
+                state.device_id = new_device_id;
+                reload_rvc = true;
+            }
+        }
+
+        if let Some(new_index_metric) = settings.get(SETTING_INDEX_METRIC) {
+            if state.index_metric != new_index_metric {
+                state.index_metric = new_index_metric;
+                reload_rvc = true;
+            }
+        }
+
+        if let Some(new_feature_encoder) = settings.get(SETTING_FEATURE_ENCODER) {
+            if state.feature_encoder != new_feature_encoder {
+                state.feature_encoder = new_feature_encoder;
+                reload_rvc = true;
+            }
+        }
+
+        if let Some(new_contentvec_layers) = settings.get(SETTING_CONTENTVEC_LAYERS) {
+            if state.contentvec_layers != new_contentvec_layers {
+                state.contentvec_layers = new_contentvec_layers;
+                reload_rvc = true;
+            }
         }
 
         if let Some(new_model_version) = settings.get(SETTING_MODEL_VERSION) {
@@ -510,77 +1949,238 @@ impl UpdateSource for RvcInferenceFilter {
             }
         }
 
-        if recalculate_input_buffer {
+        if let Some(new_speaker_id) = settings.get(SETTING_SPEAKER_ID) {
+            if state.speaker_id != new_speaker_id {
+                state.speaker_id = new_speaker_id;
+            }
+        }
+
+        if let Some(new_morph_speaker_id) = settings.get(SETTING_MORPH_SPEAKER_ID) {
+            if state.morph_speaker_id != new_morph_speaker_id {
+                state.morph_speaker_id = new_morph_speaker_id;
+            }
+        }
+
+        if let Some(new_speaker_morph) = settings.get(SETTING_SPEAKER_MORPH) {
+            if state.speaker_morph != new_speaker_morph {
+                state.speaker_morph = new_speaker_morph;
+            }
+        }
+
+        if let Some(new_feature_cache) = settings.get(SETTING_FEATURE_CACHE) {
+            if state.feature_cache != new_feature_cache {
+                state.feature_cache = new_feature_cache;
+            }
+        }
+
+        if let Some(new_watch_model) = settings.get(SETTING_WATCH_MODEL) {
+            if state.watch_model != new_watch_model {
+                state.watch_model = new_watch_model;
+            }
+        }
+
+        if let Some(new_bypass_retrieval) = settings.get(SETTING_BYPASS_RETRIEVAL) {
+            if state.bypass_retrieval != new_bypass_retrieval {
+                state.bypass_retrieval = new_bypass_retrieval;
+            }
+        }
+
+        if let Some(new_bypass_envelope) = settings.get(SETTING_BYPASS_ENVELOPE) {
+            if state.bypass_envelope != new_bypass_envelope {
+                state.bypass_envelope = new_bypass_envelope;
+            }
+        }
+
+        if let Some(new_bypass_post_fx) = settings.get(SETTING_BYPASS_POST_FX) {
+            if state.bypass_post_fx != new_bypass_post_fx {
+                state.bypass_post_fx = new_bypass_post_fx;
+            }
+        }
+
+        if let Some(new_bypass_sola) = settings.get(SETTING_BYPASS_SOLA) {
+            if state.bypass_sola != new_bypass_sola {
+                state.bypass_sola = new_bypass_sola;
+            }
+        }
+
+        if let Some(new_push_to_convert) = settings.get(SETTING_PUSH_TO_CONVERT) {
+            if state.push_to_convert != new_push_to_convert {
+                state.push_to_convert = new_push_to_convert;
+            }
+        }
+
+        if let Some(new_wet_mix) = settings.get(SETTING_WET_MIX) {
+            if state.wet_mix != new_wet_mix {
+                state.wet_mix = new_wet_mix;
+            }
+        }
+
+        if let Some(new_idle_timeout) = settings.get(SETTING_IDLE_TIMEOUT) {
+            if state.idle_timeout != new_idle_timeout {
+                state.idle_timeout = new_idle_timeout;
+            }
+        }
+
+        if let Some(new_denoise_enabled) = settings.get(SETTING_DENOISE) {
+            if state.denoise_enabled != new_denoise_enabled {
+                state.denoise_enabled = new_denoise_enabled;
+                state.denoiser = new_denoise_enabled.then(|| Denoiser::new(sample_rate)).flatten();
+            }
+        }
+
+        if let Some(new_highpass_frequency) = settings.get(SETTING_HIGHPASS_FREQUENCY) {
+            if state.highpass_frequency != new_highpass_frequency {
+                state.highpass_frequency = new_highpass_frequency;
+            }
+        }
+
+        if let Some(new_noise_gate_enabled) = settings.get(SETTING_NOISE_GATE) {
+            if state.noise_gate_enabled != new_noise_gate_enabled {
+                state.noise_gate_enabled = new_noise_gate_enabled;
+                state.noise_gate.reset();
+            }
+        }
+
+        let gate_params = gate_params_from_settings(settings, sample_rate);
+        if state.noise_gate.params() != gate_params {
+            state.noise_gate = NoiseGate::new(gate_params);
+        }
+
+        if let Some(new_auto_loudness) = settings.get(SETTING_AUTO_LOUDNESS) {
+            if state.auto_loudness != new_auto_loudness {
+                state.auto_loudness = new_auto_loudness;
+                state.loudness_matcher.reset();
+            }
+        }
+
+        if let Some(new_vad_enabled) = settings.get(SETTING_VAD) {
+            if state.vad_enabled != new_vad_enabled {
+                state.vad_enabled = new_vad_enabled;
+                state.vad.reset();
+            }
+        }
+
+        if let Some(new_vad_margin) = settings.get(SETTING_VAD_MARGIN) {
+            if state.vad_margin != new_vad_margin {
+                state.vad_margin = new_vad_margin;
+                state.vad = VoiceActivityDetector::new(sample_rate, new_vad_margin);
+            }
+        }
+
+        if let Some(new_sibilance_blend) = settings.get(SETTING_SIBILANCE_BLEND) {
+            if state.sibilance_blend != new_sibilance_blend {
+                state.sibilance_blend = new_sibilance_blend;
+            }
+        }
+
+        if let Some(new_input_gain) = settings.get(SETTING_INPUT_GAIN) {
             self.shared_state
-                .buffer_changed
-                .store(true, std::sync::atomic::Ordering::Relaxed);
-            let sample_length = state.sample_length;
-            let crossfade_length = state.crossfade_length;
-            let extra_inference_time = state.extra_inference_time;
-            let mut model_output_sample_rate = state.model_output_sample_rate;
-
-            // zc is sample per 0.1 sec
-            let zc = sample_rate / 100;
-
-            let sample_frame_time =
-                (sample_length * sample_rate as f64 / zc as f64).round() as usize;
-            let sample_frame_size = sample_frame_time * zc;
-            let sample_frame_16k = sample_frame_time * 160;
-            let crossfade_frame_size =
-                (crossfade_length * sample_rate as f64 / zc as f64).round() as usize * zc;
-            let sola_buffer_frame_size = usize::min(crossfade_frame_size, 4 * zc);
-            let sola_search_frame_size = zc;
-            let extra_frame_size =
-                (extra_inference_time * sample_rate as f64 / zc as f64).round() as usize * zc;
-            let model_return_length =
-                (sample_frame_size + sola_buffer_frame_size + sola_search_frame_size) / zc;
-            let mut model_return_size = model_return_length * (model_output_sample_rate / 100);
-
-            if state.skip_inference {
-                model_output_sample_rate = 16000;
-                model_return_size = model_return_length * 160;
-            }
-
-            state.sample_frame_size = sample_frame_size;
-            state.sample_frame_16k_size = sample_frame_16k;
-            state.crossfade_frame_size = crossfade_frame_size;
-            state.sola_buffer_frame_size = sola_buffer_frame_size;
-            state.sola_search_frame_size = sola_search_frame_size;
-            state.extra_frame_size = extra_frame_size;
-            state.model_return_length = model_return_length;
-            state.model_return_size = model_return_size;
-            self.shared_state.sample_frame_size.store(sample_frame_size, std::sync::atomic::Ordering::Relaxed);
-
-            let input_buffer_size = extra_frame_size
-                + crossfade_frame_size
-                + sola_search_frame_size
-                + sample_frame_size;
-            state.input_buffer.resize(input_buffer_size, 0_f32);
-
-            let input_buffer_16k_size = 160 * input_buffer_size / zc;
-            state.input_buffer_16k.resize(input_buffer_16k_size, 0_f32);
-
-            let mut fade_in_window = ndarray::Array1::linspace(0.0, 1.0, sola_buffer_frame_size);
-            fade_in_window.mapv_inplace(|x| f32::sin(x * 0.5 * PI).powi(2));
-            let fade_out_window = fade_in_window.mapv(|x| 1.0 - x);
-
-            state.fade_in_window = fade_in_window;
-            state.fade_out_window = fade_out_window;
-
-            // model_sample_size => 48k
-            state.upsampler =
-                FftFixedInOut::new(model_output_sample_rate, sample_rate, model_return_size, 1)
-                    .unwrap();
-            let output_buffer_size = state.upsampler.output_frames_max();
-            state.output_buffer.resize(output_buffer_size, 0_f32);
-            // 48k => 16k sample frame size
-            state.downsampler =
-                FftFixedInOut::new(sample_rate, 16000, sample_frame_size + 2 * zc, 1).unwrap();
-
-            state.input_buffer.fill(0_f32);
-            state.input_buffer_16k.fill(0_f32);
+                .input_gain
+                .store(db_to_gain(new_input_gain).to_bits(), std::sync::atomic::Ordering::Relaxed);
+        }
+
+        if let Some(new_output_gain) = settings.get(SETTING_OUTPUT_GAIN) {
+            self.shared_state
+                .output_gain
+                .store(db_to_gain(new_output_gain).to_bits(), std::sync::atomic::Ordering::Relaxed);
+        }
 
+        if let Some(new_limiter_enabled) = settings.get(SETTING_OUTPUT_LIMITER) {
+            self.shared_state
+                .limiter_enabled
+                .store(new_limiter_enabled, std::sync::atomic::Ordering::Relaxed);
+        }
 
+        if let Some(new_limiter_ceiling) = settings.get::<f32>(SETTING_LIMITER_CEILING) {
+            self.shared_state
+                .limiter_ceiling
+                .store(new_limiter_ceiling.to_bits(), std::sync::atomic::Ordering::Relaxed);
+        }
+
+        if let (Some(downmix_mode), Some(left_weight), Some(right_weight)) = (
+            settings.get(SETTING_DOWNMIX_MODE),
+            settings.get(SETTING_DOWNMIX_LEFT_WEIGHT),
+            settings.get(SETTING_DOWNMIX_RIGHT_WEIGHT),
+        ) {
+            // only the latest is of interest if the audio thread hasn't taken the previous ones yet
+            self.shared_state.downmix_update.force_push(downmix_weights(
+                downmix_mode,
+                self.shared_state.channels,
+                left_weight,
+                right_weight,
+            ));
+        }
+
+        if let Some(new_underrun_fallback) = settings.get(SETTING_UNDERRUN_FALLBACK) {
+            self.shared_state.underrun_fallback.store(new_underrun_fallback);
+        }
+
+        if let Some(new_upmix_mode) = settings.get(SETTING_UPMIX_MODE) {
+            if self.shared_state.upmix_mode.swap(new_upmix_mode) != new_upmix_mode {
+                self.shared_state
+                    .upmix_reset
+                    .store(true, std::sync::atomic::Ordering::Release);
+            }
+        }
+
+        if let Some(new_high_priority) = settings.get(SETTING_WORKER_HIGH_PRIORITY) {
+            self.shared_state
+                .worker_high_priority
+                .store(new_high_priority, std::sync::atomic::Ordering::Relaxed);
+        }
+        self.shared_state
+            .worker_core
+            .store(worker_core_from_settings(settings), std::sync::atomic::Ordering::Relaxed);
+
+        if let Some(new_fixed_latency) = settings.get::<f64>(SETTING_FIXED_LATENCY) {
+            let fixed_latency_samples = (new_fixed_latency * sample_rate as f64).round() as usize;
+            self.shared_state
+                .fixed_latency_samples
+                .store(fixed_latency_samples, std::sync::atomic::Ordering::Relaxed);
+            let compensate = settings.get(SETTING_COMPENSATE_LATENCY).unwrap_or(false);
+            self.shared_state
+                .timestamp_correction
+                .store(timestamp_correction(new_fixed_latency, compensate), std::sync::atomic::Ordering::Relaxed);
+        }
+
+        let mut rebuild_sibilance_blender = recalculate_input_buffer;
+        if let Some(new_sibilance_crossover) = settings.get(SETTING_SIBILANCE_CROSSOVER) {
+            if state.sibilance_crossover != new_sibilance_crossover {
+                state.sibilance_crossover = new_sibilance_crossover;
+                rebuild_sibilance_blender = true;
+            }
+        }
+
+        if rebuild_sibilance_blender {
+            state.sibilance_blender = BandBlender::new(sample_rate, state.sibilance_crossover);
+        }
+
+        if let Some(new_band_split_mode) = settings.get(SETTING_BAND_SPLIT_MODE) {
+            if state.band_split_mode != new_band_split_mode {
+                state.band_split_mode = new_band_split_mode;
+                state.band_split_blender.reset();
+            }
+        }
+
+        let mut rebuild_band_split_blender = recalculate_input_buffer;
+        if let Some(new_band_split_frequency) = settings.get(SETTING_BAND_SPLIT_FREQUENCY) {
+            if state.band_split_frequency != new_band_split_frequency {
+                state.band_split_frequency = new_band_split_frequency;
+                rebuild_band_split_blender = true;
+            }
+        }
+
+        if rebuild_band_split_blender {
+            state.band_split_blender = BandBlender::new(sample_rate, state.band_split_frequency);
+        }
+
+        if recalculate_input_buffer {
+            apply_frame_layout(&mut state, &self.shared_state);
+
+            // a smaller frame may already be complete
+            if let Some(has_input) = self.has_input.as_ref() {
+                has_input.unpark();
+            }
         }
     
         if reload_rvc {
@@ -593,27 +2193,150 @@ impl FilterAudioSource for RvcInferenceFilter {
     fn filter_audio(&mut self, audio: &mut audio::AudioDataContext) -> FilterAudioResult {
         // self.start_thread()
         let timestamp = audio.timestamp();
-        let main_channel = downmix_to_mono(audio, self.shared_state.channels).unwrap();
+
+        let input_peak = (0..self.shared_state.channels)
+            .filter_map(|channel| audio.get_channel_as_mut_slice(channel).map(|data| peak(data)))
+            .fold(0.0f32, f32::max);
+        if self.shared_state.input_monitor.record(input_peak) {
+            warn!("Input clipping detected");
+        }
+
+        if self
+            .shared_state
+            .upmix_reset
+            .swap(false, std::sync::atomic::Ordering::Acquire)
+        {
+            self.upmix_mode = self.shared_state.upmix_mode.load();
+            self.placement_gains.fill(1.0);
+            self.side_history.clear();
+        }
+
+        let placement = self.upmix_mode == UpmixMode::Placement;
+        if placement {
+            for (channel, level) in self.channel_levels.iter_mut().enumerate() {
+                *level = audio.get_channel_as_mut_slice(channel).map(|data| rms_level(data)).unwrap_or(0.0);
+            }
+        }
+
+        // taken before the downmix overwrites the first channel
+        let side = self.upmix_mode == UpmixMode::MidSide && front_side_signal(audio, &mut self.side_scratch);
+
+        if let Some(weights) = self.shared_state.downmix_update.pop() {
+            self.downmix_weights = weights;
+        }
+        // let through unconverted if OBS hands over fewer channels than it announced
+        let Ok(main_channel) = downmix_to_mono(audio, &self.downmix_weights) else {
+            return FilterAudioResult::Modified;
+        };
+
+        if placement && placement_gains(&mut self.channel_levels, rms_level(main_channel)) {
+            self.placement_gains
+                .iter_mut()
+                .zip(self.channel_levels.iter())
+                .for_each(|(gain, new_gain)| *gain += PLACEMENT_SMOOTHING * (new_gain - *gain));
+        }
         
+        let block_position = self.input_position;
+        let block_len = main_channel.len();
+        self.input_position += block_len as u64;
+
+        if side {
+            self.side_history.push(block_position, &self.side_scratch);
+            // nothing is popped while the worker has no output yet
+            self.side_history
+                .discard_until(block_position.saturating_sub(self.side_history_limit));
+        }
+
+        // trimmed before anything else sees it, so that the dry signal keeps matching the model's input
+        let mut data = self.shared_state.spare_buffers.pop().unwrap_or_default();
+        data.clear();
+        data.extend_from_slice(main_channel);
+        let input_gain = f32::from_bits(self.shared_state.input_gain.load(std::sync::atomic::Ordering::Relaxed));
+        if input_gain != 1.0 {
+            data.iter_mut().for_each(|sample| *sample *= input_gain);
+        }
+
         let frame = Frame {
-            data: main_channel.to_vec(),
+            data,
             timestamp,
+            position: block_position,
         };
 
-        {
-            let _lock = self.filter_audio_lock.lock();
-            self.shared_state.input.force_push(frame);
+        let fixed_latency = self
+            .shared_state
+            .fixed_latency_samples
+            .load(std::sync::atomic::Ordering::Relaxed) as u64;
 
-            if let Some(has_input) = self.has_input.as_ref() {
-                has_input.unpark();
+        // stream position of the input frame the output replaces
+        let mut output_position = None;
+        if let Some(dropped) = self.shared_state.input.force_push(frame) {
+            self.shared_state
+                .metrics
+                .dropped_blocks
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            let _ = self.shared_state.spare_buffers.push(dropped.data);
+        }
+
+        if let Some(has_input) = self.has_input.as_ref() {
+            has_input.unpark();
+        }
+
+        if fixed_latency > 0 {
+            while let Some(output) = self.shared_state.output.pop() {
+                self.fixed_latency.push(output.position, &output.data);
+                let _ = self.shared_state.spare_buffers.push(output.data);
             }
 
+            // the content is always exactly `fixed_latency` behind; the timestamp is left alone
+            // unless asked to move it back by as much, which lets OBS line it up with the video
+            let timestamp_correction = self
+                .shared_state
+                .timestamp_correction
+                .load(std::sync::atomic::Ordering::Relaxed);
+            if timestamp_correction > 0 {
+                audio.set_timestamp(timestamp.saturating_sub(timestamp_correction));
+            }
+            output_position = block_position.checked_sub(fixed_latency);
+            let concealed = match block_position.checked_sub(fixed_latency) {
+                Some(position) => self.fixed_latency.pop_into(position, main_channel),
+                None => {
+                    main_channel.fill(0_f32);
+                    0
+                }
+            };
+
+            if concealed > 0 {
+                self.shared_state
+                    .concealed_samples
+                    .fetch_add(concealed, std::sync::atomic::Ordering::Relaxed);
+            }
+        } else {
+            let underrun_fallback = self.shared_state.underrun_fallback.load();
             let output = match self.shared_state.output.pop() {
                 Some(frame) => frame,
-                None => return FilterAudioResult::Discarded,
+                None => {
+                    self.shared_state
+                        .metrics
+                        .discarded_blocks
+                        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    match underrun_fallback {
+                        UnderrunFallback::Discard => return FilterAudioResult::Discarded,
+                        // the downmix is still in place
+                        UnderrunFallback::Dry => (),
+                        UnderrunFallback::RepeatLast => {
+                            main_channel.fill(0_f32);
+                            for (sample, last) in main_channel.iter_mut().zip(self.last_output.iter()) {
+                                *sample = last * self.repeat_gain;
+                            }
+                            self.repeat_gain *= REPEAT_DECAY;
+                        }
+                    }
+                    return self.finish_block(audio, None, block_len, side);
+                }
             };
 
             let timestamp = output.timestamp;
+            output_position = Some(output.position);
             // assuming same length
             if output.data.len() < main_channel.len() {
                 let mut output_head = 0;
@@ -628,16 +2351,81 @@ impl FilterAudioSource for RvcInferenceFilter {
 
                     main_channel[output_head..(output_head + output.data.len())].copy_from_slice(&output.data);
                     output_head += output.data.len();
+                    let _ = self.shared_state.spare_buffers.push(output.data);
                 }
                 
             } else {
                 main_channel.copy_from_slice(&output.data);
             }
-            
+            let _ = self.shared_state.spare_buffers.push(output.data);
+
+            if underrun_fallback == UnderrunFallback::RepeatLast {
+                self.last_output.clear();
+                self.last_output.extend_from_slice(main_channel);
+                self.repeat_gain = 1.0;
+            }
+
             audio.set_timestamp(timestamp);
         }
 
-        upmix_audio_data_context(audio, self.shared_state.channels).unwrap();
+        self.finish_block(audio, output_position, block_len, side)
+    }
+}
+
+impl RvcInferenceFilter {
+    /// Applies the output stages to the converted block in the first channel and spreads it over
+    /// the others. `output_position` is where the input it replaces starts in the stream.
+    fn finish_block(
+        &mut self,
+        audio: &mut audio::AudioDataContext,
+        output_position: Option<u64>,
+        block_len: usize,
+        restore_side: bool,
+    ) -> FilterAudioResult {
+        let main_channel = audio.get_channel_as_mut_slice(0).unwrap();
+
+        let output_gain = f32::from_bits(self.shared_state.output_gain.load(std::sync::atomic::Ordering::Relaxed));
+        if output_gain != 1.0 {
+            main_channel.iter_mut().for_each(|sample| *sample *= output_gain);
+        }
+
+        let limiter_enabled = self
+            .shared_state
+            .limiter_enabled
+            .load(std::sync::atomic::Ordering::Relaxed);
+        if self.limiter_enabled != limiter_enabled {
+            self.limiter_enabled = limiter_enabled;
+            self.limiter.reset();
+        }
+        let limiter_ceiling = f32::from_bits(self.shared_state.limiter_ceiling.load(std::sync::atomic::Ordering::Relaxed));
+        if self.limiter_ceiling != limiter_ceiling {
+            self.limiter_ceiling = limiter_ceiling;
+            self.limiter.set_ceiling(limiter_ceiling as f64);
+        }
+
+        // last, so that neither the model nor the output gain can push the mix over the ceiling
+        if self.limiter_enabled {
+            self.limiter.process(main_channel);
+        }
+
+        upmix_audio_data_context(audio, self.shared_state.channels, self.upmix_mode, &self.placement_gains).unwrap();
+
+        if restore_side {
+            let dry_delay = self
+                .shared_state
+                .dry_delay_samples
+                .load(std::sync::atomic::Ordering::Relaxed) as u64;
+            // the side of the input block isn't needed anymore, it's already in the history
+            self.side_scratch.clear();
+            self.side_scratch.resize(block_len, 0.0);
+            if let Some(position) = output_position.and_then(|position| position.checked_sub(dry_delay)) {
+                self.side_history.pop_into(position, &mut self.side_scratch);
+            }
+            if output_gain != 1.0 {
+                self.side_scratch.iter_mut().for_each(|sample| *sample *= output_gain);
+            }
+            apply_front_side_signal(audio, &self.side_scratch);
+        }
         FilterAudioResult::Modified
     }
 }
@@ -656,13 +2444,329 @@ impl DeactivateSource for RvcInferenceFilter {
     }
 }
 
-fn process_one_frame(input_sample: &[f32], state: &mut RvcInferenceState) -> ndarray::Array1<f32> {
+/// Parks the engine after `idle_timeout` seconds of silence, which has `rvc-rpc` release its
+/// sessions, and wakes it on the first non-silent frame. The voice goes through unconverted
+/// until the sessions are warm again. Returns whether the engine is parked for this frame.
+fn update_idle_state(input_sample: &[f32], state: &mut RvcInferenceState) -> bool {
+    if level_db(input_sample) > IDLE_SILENCE_THRESHOLD_DB {
+        if state.idle_parked {
+            info!("Voice activity detected, waking engine...");
+            if let Some(Err(e)) = state.engine.as_mut().map(RvcInfer::wake) {
+                error!("Error waking engine: {:?}", e);
+            }
+            state.idle_parked = false;
+        }
+        state.silent_samples = 0;
+        return false;
+    }
+
+    state.silent_samples = state.silent_samples.saturating_add(input_sample.len());
+
+    if state.idle_timeout > 0.
+        && !state.idle_parked
+        && state.engine.as_ref().is_some_and(RvcInfer::is_ready)
+        // a new engine waiting to take over is swapped in first
+        && state.pending_engine.is_none()
+        && state.silent_samples as f64 >= state.idle_timeout * state.sample_rate as f64
+    {
+        info!("Idle for {} seconds, parking engine...", state.idle_timeout);
+        if let Some(Err(e)) = state.engine.as_mut().map(RvcInfer::park) {
+            error!("Error parking engine: {:?}", e);
+        }
+        state.idle_parked = true;
+        state.sola_buffer.fill(0_f32);
+    }
+
+    state.idle_parked
+}
+
+fn gate_params_from_settings(settings: &DataObj, sample_rate: usize) -> GateParams {
+    GateParams {
+        sample_rate,
+        threshold_db: settings.get(SETTING_GATE_THRESHOLD).unwrap_or(-50.0),
+        attack_ms: settings.get(SETTING_GATE_ATTACK).unwrap_or(5.0),
+        release_ms: settings.get(SETTING_GATE_RELEASE).unwrap_or(150.0),
+    }
+}
+
+/// The custom encoder export and whether the setting had to be rejected. Anything but an
+/// existing ONNX file falls back to the bundled model instead of failing the engine.
+fn encoder_path_from_settings(settings: &DataObj) -> (Option<PathBuf>, bool) {
+    match settings.get::<Cow<str>>(SETTING_ENCODER_PATH) {
+        Some(path) if !path.is_empty() => {
+            let path = PathBuf::from(path.to_string());
+            let is_onnx = path
+                .extension()
+                .map_or(false, |extension| extension.eq_ignore_ascii_case("onnx"));
+            if path.is_file() && is_onnx {
+                (Some(path), false)
+            } else {
+                (None, true)
+            }
+        }
+        _ => (None, false),
+    }
+}
+
+/// A dropdown of the model's speaker names, or a plain id when it doesn't name them.
+fn add_speaker_property(p: &mut Properties, setting: ObsString, description: ObsString, speaker_names: Option<&[String]>) {
+    match speaker_names {
+        Some(speaker_names) if !speaker_names.is_empty() => {
+            let mut speaker_list = p.add_list::<i64>(setting, description, false);
+            for (speaker_id, name) in speaker_names.iter().enumerate() {
+                speaker_list.push(ObsString::from(name.clone()), speaker_id as i64);
+            }
+        }
+        _ => {
+            p.add(
+                setting,
+                description,
+                NumberProp::new_int()
+                    .with_range(0..=255)
+                    .with_step(1),
+            );
+        }
+    }
+}
+
+/// Rebuilds what depends on the rate the model output comes at, skipping inference passes the
+/// 16k input through instead.
+fn set_model_output_sample_rate(state: &mut RvcInferenceState, model_output_sample_rate: usize) {
+    state.model_output_sample_rate = model_output_sample_rate;
+    state.layout_generation += 1;
+
+    let (upsampler_input_rate, model_return_size) = if state.skip_inference {
+        (16000, state.model_return_length * 160)
+    } else {
+        (model_output_sample_rate, state.model_return_length * model_output_sample_rate / 100)
+    };
+    state.model_return_size = model_return_size;
+
+    // model_sample_size => 48k
+    state.upsampler =
+        FrameResampler::new(state.resampler_type, upsampler_input_rate, state.sample_rate, model_return_size)
+            .unwrap();
+    let output_buffer_size = state.upsampler.output_frames_max();
+    state.output_buffer.resize(output_buffer_size, 0_f32);
+}
+
+/// Sizes the frames and buffers after the lengths in `state`, keeping what they hold where it
+/// still fits, and tells the worker to fade across the change.
+fn apply_frame_layout(state: &mut RvcInferenceState, shared_state: &RvcInferenceSharedState) {
+    shared_state
+        .buffer_changed
+        .store(true, std::sync::atomic::Ordering::Relaxed);
+    let sample_rate = state.sample_rate;
+    let layout = FrameLayout::new(
+        sample_rate,
+        state.sample_length,
+        state.crossfade_length,
+        state.sola_search_length,
+        state.extra_inference_time,
+    );
+    let zc = layout.unit;
+    let sample_frame_size = layout.sample_frame_size;
+    let sola_buffer_frame_size = layout.sola_buffer_frame_size;
+
+    state.frame_unit = layout.unit;
+    state.frame_unit_16k = layout.unit_16k;
+    state.sample_frame_size = sample_frame_size;
+    state.sample_frame_16k_size = layout.sample_frame_16k_size;
+    state.crossfade_frame_size = layout.crossfade_frame_size;
+    state.sola_buffer_frame_size = sola_buffer_frame_size;
+    state.sola_search_frame_size = layout.sola_search_frame_size;
+    state.extra_frame_size = layout.extra_frame_size;
+    state.model_return_length = layout.model_return_length;
+    shared_state.sample_frame_size.store(sample_frame_size, std::sync::atomic::Ordering::Relaxed);
+
+    // the latest input stays, so the next frame still has context to convert from
+    resize_keeping_tail(&mut state.input_buffer, layout.input_buffer_size);
+    resize_keeping_tail(&mut state.input_buffer_16k, layout.input_buffer_16k_size);
+
+    // and the tail of the last output stays for it to be crossfaded into
+    let mut sola_buffer = ndarray::Array1::zeros(sola_buffer_frame_size);
+    let kept = usize::min(sola_buffer_frame_size, state.sola_buffer.len());
+    sola_buffer.slice_mut(s![..kept]).assign(&state.sola_buffer.slice(s![..kept]));
+    state.sola_buffer = sola_buffer;
+
+    let (fade_in_window, fade_out_window) = crossfade_windows(state.crossfade_window, sola_buffer_frame_size);
+    state.fade_in_window = fade_in_window;
+    state.fade_out_window = fade_out_window;
+
+    let model_output_sample_rate = state.model_output_sample_rate;
+    set_model_output_sample_rate(state, model_output_sample_rate);
+    // 48k => 16k sample frame size
+    state.downsampler =
+        FrameResampler::new(state.resampler_type, sample_rate, 16000, sample_frame_size + 2 * zc).unwrap();
+    state.downsample_buffer.resize(state.downsampler.output_frames_max(), 0_f32);
+}
+
+/// The defaults of the settings a voice bundle can decide, the usual ones for plain models.
+fn voice_defaults(model_path: Option<&Path>) -> VoiceDefaults {
+    match model_path.filter(|path| is_voice_bundle(path)) {
+        Some(path) => read_voice_defaults(path).unwrap_or_else(|e| {
+            error!("Error reading voice bundle: {}", e);
+            VoiceDefaults::default()
+        }),
+        None => VoiceDefaults::default(),
+    }
+}
+
+fn preset_from_settings(settings: &DataObj) -> Option<String> {
+    settings
+        .get::<Cow<str>>(SETTING_PRESET)
+        .filter(|name| !name.is_empty())
+        .map(Cow::into_owned)
+}
+
+fn set_voice_defaults(settings: &mut DataObj, defaults: &VoiceDefaults) {
+    settings.set_default::<i32>(SETTING_PITCH_SHIFT, defaults.pitch_shift);
+    settings.set_default::<f32>(SETTING_INDEX_RATE, defaults.index_rate);
+    settings.set_default::<i32>(SETTING_DEST_SAMPLE_RATE, defaults.dest_sample_rate);
+    settings.set_default::<PitchAlgorithm>(SETTING_PITCH_ALGORITHM, defaults.pitch_algorithm);
+    settings.set_default::<RvcModelVersion>(SETTING_MODEL_VERSION, defaults.model_version);
+}
+
+/// One weight per index that was handed over when the engine started, and how many there are.
+fn engine_index_weights(state: &RvcInferenceState) -> ([f32; MAX_INDEX_COUNT], usize) {
+    let mut index_weights = [0_f32; MAX_INDEX_COUNT];
+    let mut index_count = 0;
+    for slot in 0..MAX_INDEX_COUNT {
+        if index_slot_path(state, slot).is_some() {
+            index_weights[index_count] = state.index_weights[slot] as f32;
+            index_count += 1;
+        }
+    }
+    (index_weights, index_count)
+}
+
+fn index_slot_path(state: &RvcInferenceState, slot: usize) -> Option<&PathBuf> {
+    match slot {
+        0 => state.index_paths[0].as_ref().or(state.bundle_index.as_ref()),
+        _ => state.index_paths[slot].as_ref(),
+    }
+}
+
+fn file_modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Restarts the engine once the model file has been replaced on disk. The new modification
+/// time has to hold for one more check so that a file still being written isn't picked up.
+fn watch_model_file(state: &mut RvcInferenceState) {
+    if !state.watch_model || state.idle_parked || state.model_last_checked.elapsed() < MODEL_WATCH_INTERVAL {
+        return;
+    }
+    state.model_last_checked = Instant::now();
+
+    let modified = state.model_path.as_deref().and_then(file_modified);
+    if modified.is_none() || modified == state.model_modified {
+        state.model_pending_modified = None;
+        return;
+    }
+
+    if state.model_pending_modified != modified {
+        state.model_pending_modified = modified;
+        return;
+    }
+
+    info!("Model file changed on disk, reloading engine...");
+    RvcInferenceFilter::restart_rvc_engine_inner(state);
+}
+
+/// Leaves the frame in `state.frame_output`.
+fn process_one_frame(input_sample: &[f32], state: &mut RvcInferenceState) {
+    convert_one_frame(input_sample, state);
+    mix_frame(state);
+}
+
+/// Matches the loudness of the converted frame to the input and blends the dry signal back in.
+fn mix_frame(state: &mut RvcInferenceState) {
+    if state.auto_loudness {
+        let dry_start = delay_matched_dry_start(state);
+        let dry = ArrayView1::from(&state.input_buffer[dry_start..dry_start + state.sample_frame_size]);
+        state.loudness_matcher.process(ArrayViewMut1::from(&mut state.frame_output[..]), dry);
+    }
+
+    // ramped like the push-to-convert crossfade, so that moving the mix slider doesn't click
+    let target_gain = target_convert_gain(state);
+    if state.convert_gain != target_gain || target_gain < 1.0 {
+        let ramp_time = if target_gain > state.convert_gain {
+            PUSH_TO_CONVERT_ATTACK
+        } else {
+            PUSH_TO_CONVERT_RELEASE
+        };
+        let step = (1.0 / (ramp_time * state.sample_rate as f64)) as f32;
+        let dry_start = delay_matched_dry_start(state);
+        let dry = ArrayView1::from(&state.input_buffer[dry_start..dry_start + state.sample_frame_size]);
+        state.convert_gain =
+            ramp_wet_dry(ArrayViewMut1::from(&mut state.frame_output[..]), dry, state.convert_gain, target_gain, step);
+    }
+}
+
+/// The wet gain `convert_gain` ramps towards: the wet mix, unless push-to-convert or the bypass
+/// hotkey holds the conversion back.
+fn target_convert_gain(state: &RvcInferenceState) -> f32 {
+    if state.conversion_bypassed || (state.push_to_convert && !state.convert_held) {
+        0.0
+    } else {
+        state.wet_mix as f32
+    }
+}
+
+/// How a frame goes on once `prepare_frame` has taken its input in.
+enum FramePlan {
+    /// silence, after which the next converted frame fades in from silence too if `reset_sola`
+    Silent { reset_sola: bool },
+    /// the unconverted input
+    Dry,
+    /// up to the engine
+    Convert,
+    /// `model_output` holds what the frame is made from
+    Converted,
+}
+
+fn convert_one_frame(input_sample: &[f32], state: &mut RvcInferenceState) {
+    let plan = match prepare_frame(input_sample, state) {
+        FramePlan::Convert => run_engine(state),
+        plan => plan,
+    };
+    finish_frame(plan, state);
+}
+
+/// Takes a frame of input into the buffers and decides what becomes of it.
+fn prepare_frame(input_sample: &[f32], state: &mut RvcInferenceState) -> FramePlan {
+    state.stage_times = StageTimes::default();
+    state.resample_time = Duration::ZERO;
+
+    // cleaned up before it enters the buffers, so that the dry signal is treated the same way
+    let mut cleaned_sample = std::mem::take(&mut state.cleaned_sample);
+    let mut gate_shut = false;
+    let input_sample = if state.denoiser.is_some() || state.noise_gate_enabled {
+        cleaned_sample.clear();
+        cleaned_sample.extend_from_slice(input_sample);
+        if let Some(denoiser) = state.denoiser.as_mut() {
+            denoiser.process(&mut cleaned_sample);
+        }
+        if state.noise_gate_enabled {
+            gate_shut = state.noise_gate.process(&mut cleaned_sample);
+        }
+        &cleaned_sample[..]
+    } else {
+        input_sample
+    };
+    let speech = !state.vad_enabled || state.vad.process(input_sample);
+
+    let parked = update_idle_state(input_sample, state);
+
     // move and append the last n samples
+    // (this keeps running while parked so the context is already warm on resume)
     {
         let input_buffer_retaining = state.input_buffer.len() - state.sample_frame_size;
         state.input_buffer.copy_within(state.sample_frame_size.., 0);
         state.input_buffer[input_buffer_retaining..].copy_from_slice(input_sample);
     }
+    state.cleaned_sample = cleaned_sample;
 
     // resample and set to 16k
 
@@ -670,119 +2774,229 @@ fn process_one_frame(input_sample: &[f32], state: &mut RvcInferenceState) -> nda
         .input_buffer_16k
         .copy_within(state.sample_frame_16k_size.., 0);
 
-    let downsample_start = state.input_buffer.len() - state.sample_frame_size - 2 * state.sample_rate / 100;
-    let input_sample = &[&state.input_buffer[downsample_start..]];
-    match state.downsampler.process(input_sample, None) {
-        Ok(result) => {
-            let copy_begin = state.input_buffer_16k.len() - (state.sample_frame_size / (state.sample_rate / 100) + 1) * 160;
-            state.input_buffer_16k[copy_begin..].copy_from_slice(&result[0][160..]);
+    let downsample_start = state.input_buffer.len() - state.sample_frame_size - 2 * state.frame_unit;
+    let input_sample = &state.input_buffer[downsample_start..];
+    let resample_start = Instant::now();
+    match state.downsampler.process_into_buffer(input_sample, &mut state.downsample_buffer) {
+        Ok(resampled_size) => {
+            // the first unit only primes the resampler
+            let copy_begin = state.input_buffer_16k.len()
+                - (state.sample_frame_size / state.frame_unit + 1) * state.frame_unit_16k;
+            state.input_buffer_16k[copy_begin..]
+                .copy_from_slice(&state.downsample_buffer[state.frame_unit_16k..resampled_size]);
         },
         Err(e) => {
             panic!("Error: {:?}", e);
         }
     };
+    state.resample_time = resample_start.elapsed();
 
-    let input_buffer_view =
-        ndarray::ArrayView1::from_shape((state.input_buffer.len(),), &state.input_buffer).unwrap();
+    if state.highpass_frequency > 0.0 {
+        highpass_model_input(&state.input_buffer_16k, &mut state.highpass_buffer_16k, state.highpass_frequency);
+    }
 
-    let input_buffer_16k_view =
-        ndarray::ArrayView1::from_shape((state.input_buffer_16k.len(),), &state.input_buffer_16k)
-            .unwrap();
+    if parked {
+        return FramePlan::Silent { reset_sola: false };
+    }
 
-    // println!("input: {:?}", input_buffer_16k_view);
-
-    let skip_head = (state.extra_frame_size / (state.sample_rate / 100)) as u32;
-
-    // inference
-    let output = if state.skip_inference {
-        let output_start = input_buffer_16k_view.len() - state.model_return_size;
-        input_buffer_16k_view.slice(s![output_start..]).to_owned()
-    } else if let Some(engine) = state.engine.as_mut() {
-        match engine.infer(
-            input_buffer_16k_view,
-            state.sample_frame_16k_size,
-            state.pitch_shift,
-            skip_head,
-            state.model_return_length as u32
-        ) {
-            Ok(output) => {
-                output
-                // let skip_head = state.extra_frame_size / (state.sample_rate / 100);
-                // let flow_head = if skip_head > 24 { skip_head - 24 } else { 0 };
-                // let dec_head = skip_head - flow_head;
-                // let end = state.model_return_size + dec_head;
-                // output.slice(s![dec_head..end]).to_owned()
-            },
-            Err(e) => {
-                eprintln!("Error: {:?}", e);
+    if gate_shut || !speech {
+        // nothing to convert; the next frame fades in from silence like after parking
+        return FramePlan::Silent { reset_sola: true };
+    }
 
-                match e {
-                    RvcAdapterError::IoError(e) => {
-                        RvcInferenceFilter::restart_rvc_engine_inner(state);
-                    },
-                    _ => (),
-                }
+    // the SOLA crossfade blends this frame of the new engine into the tail of the old one. One
+    // that failed to load takes over as well, so that the properties show why
+    if state
+        .pending_engine
+        .as_ref()
+        .is_some_and(|engine| engine.is_ready() || engine.load_error().is_some())
+    {
+        info!("New engine is ready, switching over");
+        state.engine = state.pending_engine.take();
+    }
 
-                return ndarray::Array1::zeros(state.sample_frame_size);
-            }
-        }
+    if state.skip_inference {
+        let model_input_16k = if state.highpass_frequency > 0.0 {
+            &state.highpass_buffer_16k[..]
+        } else {
+            &state.input_buffer_16k[..]
+        };
+        let output_start = model_input_16k.len() - state.model_return_size;
+        state.model_output.clear();
+        state.model_output.extend_from_slice(&model_input_16k[output_start..]);
+        return FramePlan::Converted;
+    }
+
+    match state.engine.as_ref() {
+        // still loading, which takes minutes while TensorRT builds its engines; don't stall
+        // the worker on it and let the voice through unconverted meanwhile
+        Some(engine) if !engine.is_ready() => FramePlan::Dry,
+        Some(_) => FramePlan::Convert,
+        // no model selected, or its engine could not be started
+        None => FramePlan::Dry,
+    }
+}
+
+/// Converts the prepared frame into `model_output` and waits for it.
+fn run_engine(state: &mut RvcInferenceState) -> FramePlan {
+    let (index_weights, index_count) = engine_index_weights(state);
+    let request = frame_request(state, &index_weights[..index_count]);
+    let model_input_16k = if state.highpass_frequency > 0.0 {
+        &state.highpass_buffer_16k[..]
     } else {
-        return ndarray::Array1::zeros(state.sample_frame_size);
+        &state.input_buffer_16k[..]
+    };
+    let Some(engine) = state.engine.as_mut() else {
+        return FramePlan::Dry;
     };
 
-    if output.len() != state.model_return_size {
-        eprintln!(
-            "Model output size mismatch: {} != {}",
-            output.len(),
-            state.model_return_size
-        );
-        // return ndarray::Array1::zeros(state.sample_frame_size);
+    match engine.infer(ArrayView1::from(model_input_16k), &request, &mut state.model_output) {
+        Ok(stage_times) => {
+            state.stage_times = stage_times;
+            // let skip_head = state.extra_frame_size / (state.sample_rate / 100);
+            // let flow_head = if skip_head > 24 { skip_head - 24 } else { 0 };
+            // let dec_head = skip_head - flow_head;
+            // let end = state.model_return_size + dec_head;
+            // output.slice(s![dec_head..end]).to_owned()
+            FramePlan::Converted
+        },
+        Err(e) => engine_failed(e, state),
+    }
+}
+
+/// Sends the prepared frame to the engine without waiting for it, for a pipelined worker.
+fn submit_to_engine(state: &mut RvcInferenceState) -> Result<EngineReplies, RvcAdapterError> {
+    let (index_weights, index_count) = engine_index_weights(state);
+    let request = frame_request(state, &index_weights[..index_count]);
+    let model_input_16k = if state.highpass_frequency > 0.0 {
+        &state.highpass_buffer_16k[..]
+    } else {
+        &state.input_buffer_16k[..]
+    };
+    let engine = state.engine.as_mut().ok_or(RvcInferError::ModelNotLoaded)?;
+
+    engine.submit(ArrayView1::from(model_input_16k), &request)?;
+    Ok(engine.replies())
+}
+
+/// The current settings as the engine takes them for a frame.
+fn frame_request<'a>(state: &RvcInferenceState, index_weights: &'a [f32]) -> FrameRequest<'a> {
+    FrameRequest {
+        sample_frame_16k_size: state.sample_frame_16k_size,
+        pitch_shift: state.pitch_shift,
+        skip_head: ten_ms_frames(state.extra_frame_size, state.sample_rate) as u32,
+        return_length: state.model_return_length as u32,
+        index_rate: if state.bypass_retrieval { 0.0 } else { state.index_rate as f32 },
+        index_weights,
+        f0_filter_radius: state.f0_filter_radius.max(0) as u32,
+        autotune: (state.autotune_scale, state.autotune_key as i32, state.autotune_strength as f32),
+        f0_range: (state.f0_min as f32, state.f0_max as f32),
+        voicing_sensitivity: state.voicing_sensitivity as f32,
+        feature_cache: state.feature_cache,
+        speaker_id: state.speaker_id.max(0) as u32,
+        speaker_morph: (state.morph_speaker_id.max(0) as u32, state.speaker_morph as f32),
+    }
+}
+
+/// Gives up on a frame the engine failed to convert, restarting the engine when its pipes broke.
+fn engine_failed(e: RvcAdapterError, state: &mut RvcInferenceState) -> FramePlan {
+    error!("Inference failed: {:?}", e);
+
+    if let RvcAdapterError::IoError(_) = e {
+        RvcInferenceFilter::restart_rvc_engine_inner(state);
     }
 
-    let mut output = {
-        let output = output.into_raw_vec();
-        let output_sample = &[&output];
-        let output_buffer = &mut [&mut state.output_buffer[..]];
+    // an unconverted frame beats a dropout
+    FramePlan::Dry
+}
+
+/// Makes `frame_output` out of a frame `prepare_frame` took in, the way `plan` says.
+fn finish_frame(plan: FramePlan, state: &mut RvcInferenceState) {
+    match plan {
+        FramePlan::Silent { reset_sola } => {
+            if reset_sola {
+                state.sola_buffer.fill(0_f32);
+            }
+            return silent_frame(state);
+        }
+        // a frame still up to the engine here wasn't sent to it
+        FramePlan::Dry | FramePlan::Convert => return dry_frame(state),
+        FramePlan::Converted => (),
+    }
+
+    let input_buffer_view =
+        ndarray::ArrayView1::from_shape((state.input_buffer.len(),), &state.input_buffer).unwrap();
+
+    if state.model_output.len() != state.model_return_size {
+        let mismatch = state.model_output.len().abs_diff(state.model_return_size);
+        if mismatch as f64 > state.model_return_size as f64 * MAX_OUTPUT_LENGTH_CORRECTION {
+            warn!(
+                "Model output size mismatch: {} != {}",
+                state.model_output.len(),
+                state.model_return_size
+            );
+            return dry_frame(state);
+        }
+
+        // absorb the difference in the part that only feeds the next crossfade, so this frame's
+        // timing is kept and nothing accumulates from frame to frame
+        let tail = state.model_return_size * (state.sola_buffer_frame_size + state.sola_search_frame_size)
+            / (state.sample_frame_size + state.sola_buffer_frame_size + state.sola_search_frame_size);
+        let fitted = fit_tail_to_length(ArrayView1::from(&state.model_output[..]), state.model_return_size, tail);
+        state.model_output.clear();
+        state.model_output.extend(fitted.iter());
+    }
 
+    let mut output = {
+        let resample_start = Instant::now();
         let result = state
             .upsampler
-            .process_into_buffer(output_sample, output_buffer, None);
+            .process_into_buffer(&state.model_output, &mut state.output_buffer);
+        state.resample_time += resample_start.elapsed();
         if let Err(e) = result {
             panic!("Error: {:?}", e);
         }
-        let (csi, cso) = result.unwrap();
+        let cso = result.unwrap();
         ndarray::ArrayViewMut1::from_shape((cso,), &mut state.output_buffer)
             .unwrap()
     };
 
-    if state.rms_mix_rate < 1. {
+    state.formant_shifter.process(output.view_mut(), state.resonance_shift);
+
+    if state.rms_mix_rate < 1. && !state.bypass_envelope {
         envelop_mixing(
             input_buffer_view.slice(s![state.extra_frame_size..]),
             output.view_mut(),
             state.sample_rate,
             state.rms_mix_rate,
+            &mut state.envelopes,
         )
     }
 
     // sola
-    let sola_offset = get_sola_offset(
-        output.view(),
-        state.sola_buffer.view(),
-        state.sola_buffer_frame_size,
-        state.sola_search_frame_size,
-    )
-    .unwrap();
+    let sola_offset = if state.bypass_sola {
+        0
+    } else {
+        get_sola_offset(
+            output.as_slice().unwrap(),
+            state.sola_buffer.as_slice().unwrap(),
+            state.sola_buffer_frame_size,
+            state.sola_search_frame_size,
+        )
+    };
 
     let mut output = output.slice_mut(s![sola_offset..]);
 
     // TODO: phase vocoder
-    Zip::from(output.slice_mut(s![..state.sola_buffer_frame_size]))
-        .and(state.fade_in_window.view())
-        .and(state.sola_buffer.view())
-        .and(state.fade_out_window.view())
-        .for_each(|output_sola_buffer_view, fade_in, sola, fade_out| {
-            *output_sola_buffer_view = *output_sola_buffer_view * fade_in + sola * fade_out;
-        });
+    if !state.bypass_sola {
+        Zip::from(output.slice_mut(s![..state.sola_buffer_frame_size]))
+            .and(state.fade_in_window.view())
+            .and(state.sola_buffer.view())
+            .and(state.fade_out_window.view())
+            .for_each(|output_sola_buffer_view, fade_in, sola, fade_out| {
+                *output_sola_buffer_view = *output_sola_buffer_view * fade_in + sola * fade_out;
+            });
+    }
 
 
     // self.sola_buffer.assign(&output[self.sample_frame_size..(self.sample_frame_size + self.sola_buffer_frame_size)]);
@@ -791,7 +3005,147 @@ fn process_one_frame(input_sample: &[f32], state: &mut RvcInferenceState) -> nda
     ]));
 
     // output.iter().for_each(|sample| self.output.push_back(*sample));
-    output.slice(s![..state.sample_frame_size]).into_owned()
+    state.frame_output.clear();
+    state.frame_output.extend(output.slice(s![..state.sample_frame_size]).iter());
+
+    if state.sibilance_blend > 0. && !state.bypass_post_fx {
+        let dry_start = delay_matched_dry_start(state);
+        let dry = ArrayView1::from(&state.input_buffer[dry_start..dry_start + state.sample_frame_size]);
+        state
+            .sibilance_blender
+            .process(ArrayViewMut1::from(&mut state.frame_output[..]), dry, state.sibilance_blend as f32);
+    }
+
+    if state.band_split_mode != BandSplitMode::Off && !state.bypass_post_fx {
+        let dry_start = delay_matched_dry_start(state);
+        let dry = ArrayView1::from(&state.input_buffer[dry_start..dry_start + state.sample_frame_size]);
+        let output = ArrayViewMut1::from(&mut state.frame_output[..]);
+        match state.band_split_mode {
+            BandSplitMode::DryLow => state.band_split_blender.process_low(output, dry, 1.0),
+            BandSplitMode::DryHigh => state.band_split_blender.process(output, dry, 1.0),
+            BandSplitMode::Off => (),
+        }
+    }
+}
+
+/// The 16 kHz input with the rumble below `cutoff` taken out, for the model only so that the dry
+/// signal keeps it. The buffer slides under the filter, so it is filtered from its start every
+/// frame; the settling at the start falls into the oldest context.
+fn highpass_model_input<'a>(input_16k: &[f32], buffer: &'a mut Vec<f32>, cutoff: f64) -> &'a [f32] {
+    buffer.clear();
+    buffer.extend_from_slice(input_16k);
+    // two butterworth sections, 24 dB/oct, to also take out plosive thumps
+    let filter = Biquad::highpass(16000, cutoff, std::f64::consts::FRAC_1_SQRT_2);
+    for mut filter in [filter.clone(), filter] {
+        filter.process(ndarray::ArrayViewMut1::from(&mut buffer[..]));
+    }
+    buffer
+}
+
+/// Where the input that lines up with the frame returned by `process_one_frame` starts in the
+/// input buffer. The sola offset moves within the search window every frame, so the middle of
+/// it is used to keep the dry stream continuous.
+fn delay_matched_dry_start(state: &RvcInferenceState) -> usize {
+    state.extra_frame_size + state.sola_search_frame_size / 2
+}
+
+/// Makes this frame the unconverted input, delayed like the converted output would be.
+fn dry_frame(state: &mut RvcInferenceState) {
+    let dry_start = delay_matched_dry_start(state);
+    state.frame_output.clear();
+    state.frame_output.extend_from_slice(&state.input_buffer[dry_start..dry_start + state.sample_frame_size]);
+}
+
+/// Makes this frame silence.
+fn silent_frame(state: &mut RvcInferenceState) {
+    state.frame_output.clear();
+    state.frame_output.resize(state.sample_frame_size, 0_f32);
+}
+
+/// What is taken off the timestamps of fixed latency output, in nanoseconds.
+fn timestamp_correction(fixed_latency: f64, compensate: bool) -> u64 {
+    if compensate {
+        (fixed_latency * 1e9).round() as u64
+    } else {
+        0
+    }
+}
+
+/// The core the worker is pinned to, offset by one with 0 for none, as kept in the shared state.
+fn worker_core_from_settings(settings: &DataObj) -> usize {
+    match settings.get::<i64>(SETTING_WORKER_CORE) {
+        Some(core) if core >= 0 => core as usize + 1,
+        _ => 0,
+    }
+}
+
+fn rpc_binary_path(binary_path: &Path) -> PathBuf {
+    binary_path.parent().unwrap().join(format!("rvc-rpc{}", std::env::consts::EXE_SUFFIX))
+}
+
+/// A frame between the two stages of a pipelined worker.
+struct InFlightFrame {
+    plan: FramePlan,
+    // the reply to wait for while the plan is `FramePlan::Convert`
+    replies: Option<EngineReplies>,
+    // the input buffer as the frame left it, for the dry signal and the envelope
+    input_buffer: Vec<f32>,
+    model_output: Vec<f32>,
+    stage_times: StageTimes,
+    resample_time: Duration,
+    // a frame from before the buffers were resized can't be finished with them
+    layout_generation: u64,
+    sample_frame_size: usize,
+    dry_delay: usize,
+    // the input blocks taken in with this frame, which its output goes out in
+    blocks: Vec<Frame>,
+    start_time: Instant,
+    prepare_time: Duration,
+}
+
+impl InFlightFrame {
+    fn new() -> Self {
+        InFlightFrame {
+            plan: FramePlan::Dry,
+            replies: None,
+            input_buffer: Vec::new(),
+            model_output: Vec::new(),
+            stage_times: StageTimes::default(),
+            resample_time: Duration::ZERO,
+            layout_generation: 0,
+            sample_frame_size: 0,
+            dry_delay: 0,
+            blocks: Vec::new(),
+            start_time: Instant::now(),
+            prepare_time: Duration::ZERO,
+        }
+    }
+}
+
+/// The first half of `process_one_frame` for a pipelined worker, which sends the frame to the
+/// engine if it needs converting and leaves the rest of it to `finish_loop` in `frame`.
+fn prepare_in_flight_frame(input_sample: &[f32], state: &mut RvcInferenceState, frame: &mut InFlightFrame) {
+    frame.replies = None;
+    frame.plan = match prepare_frame(input_sample, state) {
+        FramePlan::Convert => match submit_to_engine(state) {
+            Ok(replies) => {
+                frame.replies = Some(replies);
+                FramePlan::Convert
+            }
+            Err(e) => engine_failed(e, state),
+        },
+        plan => plan,
+    };
+    if let FramePlan::Converted = frame.plan {
+        // inference is skipped, the output is already there
+        std::mem::swap(&mut frame.model_output, &mut state.model_output);
+    }
+    frame.input_buffer.clear();
+    frame.input_buffer.extend_from_slice(&state.input_buffer);
+    frame.stage_times = StageTimes::default();
+    frame.resample_time = state.resample_time;
+    frame.layout_generation = state.layout_generation;
+    frame.sample_frame_size = state.sample_frame_size;
 }
 
 fn thread_loop(shared_state: Arc<RvcInferenceSharedState>, has_input: Parker) {
@@ -804,62 +3158,443 @@ fn thread_loop(shared_state: Arc<RvcInferenceSharedState>, has_input: Parker) {
 
     let mut frame_buffer: VecDeque<Frame> = VecDeque::with_capacity(300);
 
-    'frame_loop: while shared_state
+    // a raised priority or a pinned core stays with the thread until it is restarted
+    let mut priority_raised = false;
+    let mut pinned_core = 0;
+
+    // when pipelined, this thread only prepares and sends the frames; a second one waits for the
+    // engine and finishes them, and hands the emptied ones back for their buffers
+    let pipelined = shared_state.state.lock().advanced.pipelined_worker;
+    let pipeline = pipelined.then(|| {
+        let (frame_sender, frames) = crossbeam::channel::bounded(PIPELINE_DEPTH);
+        let (spent_sender, spent) = crossbeam::channel::bounded(PIPELINE_DEPTH + 2);
+        let finish_state = shared_state.clone();
+        let handle = std::thread::spawn(move || finish_loop(finish_state, frames, spent_sender));
+        (frame_sender, spent, handle)
+    });
+
+    while shared_state
         .running
         .load(std::sync::atomic::Ordering::Relaxed)
     {
-        let mut state = match shared_state.state.try_lock() {
-            Some(state) => state,
+        if !priority_raised
+            && shared_state
+                .worker_high_priority
+                .load(std::sync::atomic::Ordering::Relaxed)
+        {
+            priority_raised = true;
+            if !worker_priority::raise_current_thread_priority() {
+                shared_state
+                    .worker_priority_rejected
+                    .store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+        let core = shared_state.worker_core.load(std::sync::atomic::Ordering::Relaxed);
+        if core != 0 && core != pinned_core {
+            pinned_core = core;
+            if !worker_priority::pin_current_thread(core - 1) {
+                shared_state
+                    .worker_priority_rejected
+                    .store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+
+        // gathered without the state, so that settings can change while the worker sleeps
+        let sample_frame_size = shared_state
+            .sample_frame_size
+            .load(std::sync::atomic::Ordering::Relaxed);
+        if input_sample.len() < sample_frame_size {
+            match shared_state.input.pop() {
+                Some(frame) => {
+                    input_sample.extend_from_slice(&frame.data);
+                    frame_buffer.push_back(frame);
+                }
+                // woken by the next input block, a reconfiguration or stopping
+                None => has_input.park_timeout(shared_state.wait_timeout),
+            }
+            continue;
+        }
+
+        let mut state = shared_state.state.lock();
+        // the frame may have grown while waiting for the lock
+        let sample_frame_size = state.sample_frame_size;
+        if input_sample.len() < sample_frame_size {
+            continue;
+        }
+
+        let start_time = Instant::now();
+
+        watch_model_file(&mut state);
+        state.convert_held = shared_state
+            .convert_held
+            .load(std::sync::atomic::Ordering::Relaxed);
+        state.conversion_bypassed = shared_state
+            .conversion_bypassed
+            .load(std::sync::atomic::Ordering::Relaxed);
+
+        let in_flight = match &pipeline {
+            Some((_, spent, _)) => {
+                let mut frame = spent.try_recv().unwrap_or_else(|_| InFlightFrame::new());
+                prepare_in_flight_frame(&input_sample[..sample_frame_size], &mut state, &mut frame);
+                frame.blocks.extend(frame_buffer.drain(..));
+                frame.start_time = start_time;
+                Some(frame)
+            }
             None => {
-                continue;
+                process_one_frame(&input_sample[..sample_frame_size], &mut state);
+                None
             }
         };
+        let dry_delay = state.input_buffer.len() - sample_frame_size - delay_matched_dry_start(&state);
+        shared_state
+            .dry_delay_samples
+            .store(dry_delay, std::sync::atomic::Ordering::Relaxed);
+        report_engine_state(&shared_state, &mut state);
 
-        let sample_frame_size = state.sample_frame_size;
-        while input_sample.len() < sample_frame_size {
-            let frame = shared_state.input.pop();
-            if let Some(frame) = frame {
-                input_sample.extend_from_slice(&frame.data);
-                frame_buffer.push_back(frame);
-            } else {
-                has_input.park_timeout(Duration::from_secs(1));
-                continue 'frame_loop;
+        input_sample.copy_within(sample_frame_size.., 0);
+        input_sample.truncate(input_sample.len() - sample_frame_size);
+
+        match (in_flight, &pipeline) {
+            (Some(mut frame), Some((frame_sender, _, _))) => {
+                frame.dry_delay = dry_delay;
+                frame.prepare_time = start_time.elapsed();
+                drop(state);
+                // waits while the second stage is a frame behind
+                if frame_sender.send(frame).is_err() {
+                    error!("Pipelined worker stage stopped");
+                    break;
+                }
+            }
+            _ => {
+                let elapsed = start_time.elapsed();
+                deliver_frame(
+                    &shared_state,
+                    &mut state,
+                    &mut output_sample,
+                    &mut frame_buffer,
+                    sample_frame_size,
+                    dry_delay,
+                    elapsed,
+                    elapsed,
+                );
             }
         }
-         
-        let start_time = Instant::now();
+    }
+
+    if let Some((frame_sender, _, handle)) = pipeline {
+        // the second stage finishes what was sent and stops with the channel
+        drop(frame_sender);
+        if handle.join().is_err() {
+            error!("Error joining pipelined worker stage");
+        }
+    }
+}
+
+/// The second stage of a pipelined worker: waits for the engine to convert each frame the
+/// first one sent and finishes it where `thread_loop` would have.
+fn finish_loop(
+    shared_state: Arc<RvcInferenceSharedState>,
+    frames: Receiver<InFlightFrame>,
+    spent: Sender<InFlightFrame>,
+) {
+    let mut output_sample: Vec<f32> = Vec::new();
+    let mut frame_buffer: VecDeque<Frame> = VecDeque::with_capacity(300);
 
-        let output_frame = process_one_frame(&input_sample[..sample_frame_size], &mut state);
-        output_sample.extend_from_slice(&output_frame.as_slice().unwrap());
+    // the core pin stays with the first stage, the two are meant to run side by side
+    if shared_state
+        .worker_high_priority
+        .load(std::sync::atomic::Ordering::Relaxed)
+        && !worker_priority::raise_current_thread_priority()
+    {
+        shared_state
+            .worker_priority_rejected
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+    }
 
-        let mut output_head = 0;
-        while let Some(mut frame) = frame_buffer.pop_front() {
-            let frame_len = frame.data.len();
-            if output_head + frame_len >= output_sample.len() {
-                frame_buffer.push_front(frame);
-                break;
+    for mut frame in frames {
+        let picked_up = Instant::now();
+        // the engine stays unlocked meanwhile, so the first stage can already send the next frame
+        let reply = frame
+            .replies
+            .take()
+            .map(|replies| (replies.receive(&mut frame.model_output), replies));
+
+        let mut state = shared_state.state.lock();
+        let plan = match reply {
+            Some((Ok(stage_times), _)) => {
+                frame.stage_times = stage_times;
+                FramePlan::Converted
+            }
+            // a restart for an engine that was already replaced would throw away the new one
+            Some((Err(e), replies)) if state.engine.as_ref().is_some_and(|engine| replies.come_from(engine)) => {
+                engine_failed(e, &mut state)
             }
-            let output = &output_sample[output_head..output_head + frame_len];
-            frame.data.copy_from_slice(output);
-            shared_state.output.force_push(frame);
-            output_head += frame_len;
+            Some((Err(e), _)) => {
+                error!("Inference failed: {:?}", e);
+                FramePlan::Dry
+            }
+            None => std::mem::replace(&mut frame.plan, FramePlan::Dry),
+        };
+
+        if frame.layout_generation == state.layout_generation {
+            // the second half of the frame sees the buffers as the first half left them
+            std::mem::swap(&mut state.input_buffer, &mut frame.input_buffer);
+            std::mem::swap(&mut state.model_output, &mut frame.model_output);
+            state.stage_times = frame.stage_times;
+            state.resample_time = frame.resample_time;
+            finish_frame(plan, &mut state);
+            mix_frame(&mut state);
+            std::mem::swap(&mut state.input_buffer, &mut frame.input_buffer);
+            std::mem::swap(&mut state.model_output, &mut frame.model_output);
+        } else {
+            // sized for buffers that are gone, the fade across the change covers the gap
+            state.frame_output.clear();
+            state.frame_output.resize(frame.sample_frame_size, 0_f32);
         }
-        
-        input_sample.copy_within(sample_frame_size.., 0);
-        input_sample.truncate(input_sample.len() - sample_frame_size);
-        output_sample.copy_within(output_head.., 0);
-        output_sample.truncate(output_sample.len() - output_head);
 
-        let elapsed = start_time.elapsed();
-        eprintln!("Thread Loop Elapsed: {:?}", elapsed);
+        frame_buffer.extend(frame.blocks.drain(..));
+        let elapsed = Duration::max(frame.prepare_time, picked_up.elapsed());
+        let in_flight = frame.start_time.elapsed();
+        deliver_frame(
+            &shared_state,
+            &mut state,
+            &mut output_sample,
+            &mut frame_buffer,
+            frame.sample_frame_size,
+            frame.dry_delay,
+            elapsed,
+            in_flight,
+        );
+        drop(state);
+
+        let _ = spent.try_send(frame);
+    }
+}
+
+/// Passes what the running engine reported about its model on to the properties and the
+/// metrics.
+fn report_engine_state(shared_state: &RvcInferenceSharedState, state: &mut RvcInferenceState) {
+    let model_without_f0 = state.engine.as_ref().and_then(RvcInfer::uses_f0) == Some(false);
+    shared_state
+        .model_without_f0
+        .store(model_without_f0, std::sync::atomic::Ordering::Relaxed);
+    *shared_state.model_version_info.lock() = state.engine.as_ref().and_then(RvcInfer::model_version);
+    if let Some(detected_sample_rate) = state.engine.as_ref().and_then(RvcInfer::output_sample_rate) {
+        let model_output_sample_rate = detected_sample_rate.unwrap_or(state.dest_sample_rate);
+        if state.model_output_sample_rate != model_output_sample_rate {
+            info!("Model output sample rate changed to {}", model_output_sample_rate);
+            set_model_output_sample_rate(state, model_output_sample_rate);
+        }
+        shared_state
+            .detected_sample_rate
+            .store(detected_sample_rate.unwrap_or(0), std::sync::atomic::Ordering::Relaxed);
+    }
+    let mut shared_speaker_names = shared_state.speaker_names.lock();
+    // compared in place, the names are only copied when they change
+    let speakers_changed = match state.engine.as_ref() {
+        Some(engine) => !engine.has_speakers(shared_speaker_names.as_deref()),
+        None => shared_speaker_names.is_some(),
+    };
+    if speakers_changed {
+        *shared_speaker_names = state.engine.as_ref().and_then(RvcInfer::speakers).flatten();
+    }
+    drop(shared_speaker_names);
+    let speaker_morph_available = state.engine.as_ref().and_then(RvcInfer::can_morph_speakers) == Some(true);
+    shared_state
+        .speaker_morph_available
+        .store(speaker_morph_available, std::sync::atomic::Ordering::Relaxed);
+    let quantized_model = state.engine.as_ref().and_then(RvcInfer::is_quantized) == Some(true);
+    shared_state
+        .quantized_model
+        .store(quantized_model, std::sync::atomic::Ordering::Relaxed);
+    let tensorrt = state.use_tensorrt || state.inference_device == InferenceDevice::TensorRt;
+    let swapping = state.pending_engine.is_some();
+    *shared_state.engine_loading.lock() = state
+        .pending_engine
+        .as_ref()
+        .or(state.engine.as_ref())
+        .and_then(RvcInfer::loading_time)
+        .map(|loading_time| (loading_time, tensorrt, swapping));
+}
+
+/// Hands `frame_output` to the audio callback in the blocks the input came in, and records how
+/// the frame did. `elapsed` is what the frame kept the worker busy for, `in_flight` how long it
+/// took from being taken in to coming out.
+fn deliver_frame(
+    shared_state: &RvcInferenceSharedState,
+    state: &mut RvcInferenceState,
+    output_sample: &mut Vec<f32>,
+    frame_buffer: &mut VecDeque<Frame>,
+    sample_frame_size: usize,
+    dry_delay: usize,
+    elapsed: Duration,
+    in_flight: Duration,
+) {
+    if shared_state.buffer_changed.swap(false, std::sync::atomic::Ordering::Relaxed) {
+        // what is still to go out came from the old buffers and this frame from the new ones,
+        // a short dip between them hides any step the switch left
+        let declick_size =
+            usize::min((DECLICK_TIME * state.sample_rate as f64) as usize, state.frame_output.len());
+        let pending_start = output_sample.len().saturating_sub(declick_size);
+        fade(&mut output_sample[pending_start..], false);
+        fade(&mut state.frame_output[..declick_size], true);
+    }
+    output_sample.extend_from_slice(&state.frame_output);
+
+    let mut output_head = 0;
+    while let Some(mut frame) = frame_buffer.pop_front() {
+        let frame_len = frame.data.len();
+        if output_head + frame_len >= output_sample.len() {
+            frame_buffer.push_front(frame);
+            break;
+        }
+        let output = &output_sample[output_head..output_head + frame_len];
+        frame.data.copy_from_slice(output);
+        if let Some(dropped) = shared_state.output.force_push(frame) {
+            shared_state
+                .metrics
+                .dropped_blocks
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            let _ = shared_state.spare_buffers.push(dropped.data);
+        }
+        output_head += frame_len;
+    }
+
+    output_sample.copy_within(output_head.., 0);
+    output_sample.truncate(output_sample.len() - output_head);
 
+    debug!("Frame converted in {:?}", elapsed);
+
+    let frame_duration = Duration::from_secs_f64(sample_frame_size as f64 / state.sample_rate as f64);
+    if elapsed > frame_duration {
+        warn!("Frame took {:?} to convert, longer than the {:?} it lasts", elapsed, frame_duration);
+    }
+    shared_state.metrics.record_frame(elapsed, frame_duration);
+    let stages = state.stage_times;
+    shared_state
+        .metrics
+        .record_stage_times(stages.encoder, stages.pitch, stages.synthesis, state.resample_time);
+    // a sample waits for the rest of its frame, then for the worker, and comes out delayed by
+    // as much as the dry signal is
+    let fixed_latency = shared_state.fixed_latency_samples.load(std::sync::atomic::Ordering::Relaxed);
+    let latency = if fixed_latency > 0 {
+        Duration::from_secs_f64(fixed_latency as f64 / state.sample_rate as f64)
+    } else {
+        Duration::from_secs_f64((sample_frame_size + dry_delay) as f64 / state.sample_rate as f64) + in_flight
+    };
+    shared_state.metrics.record_latency(latency);
+    if state.adaptive_sample_length {
+        let (current, configured) = (state.sample_length, state.configured_sample_length);
+        if let Some(sample_length) = state.chunk_sizer.record(elapsed, frame_duration, current, configured) {
+            info!("Sample length adapted to {:.2}s", sample_length);
+            state.sample_length = sample_length;
+            apply_frame_layout(state, shared_state);
+        }
+    }
+    shared_state
+        .metrics
+        .input_queue_depth
+        .store(shared_state.input.len(), std::sync::atomic::Ordering::Relaxed);
+    shared_state
+        .metrics
+        .output_queue_depth
+        .store(shared_state.output.len(), std::sync::atomic::Ordering::Relaxed);
+}
+
+// gets the shared state of the filter, which lives as long as the filter's properties are shown
+unsafe extern "C" fn reload_clicked(
+    _props: *mut obs_properties_t,
+    _property: *mut obs_property_t,
+    data: *mut c_void,
+) -> bool {
+    let Some(shared_state) = (data as *const RvcInferenceSharedState).as_ref() else {
+        return false;
+    };
+    info!("Reloading engine on request...");
+    let mut state = shared_state.state.lock();
+    // a fresh start, rather than converting with the old engine while the new one loads
+    state.engine = None;
+    state.pending_engine = None;
+    RvcInferenceFilter::restart_rvc_engine_inner(&mut state);
+    true
+}
+
+// gets the shared state of the filter like the reload button
+unsafe extern "C" fn benchmark_clicked(
+    _props: *mut obs_properties_t,
+    _property: *mut obs_property_t,
+    data: *mut c_void,
+) -> bool {
+    let data = data as *const RvcInferenceSharedState;
+    let Some(shared_state) = data.as_ref() else {
+        return false;
+    };
+    {
+        let mut benchmark = shared_state.benchmark.lock();
+        if matches!(*benchmark, Some(BenchmarkStatus::Loading | BenchmarkStatus::Running { .. })) {
+            return false;
+        }
+        *benchmark = Some(BenchmarkStatus::Loading);
     }
+    // the thread holds on to the state on its own, the pointer came from the filter's Arc
+    Arc::increment_strong_count(data);
+    let shared_state = Arc::from_raw(data);
+    std::thread::spawn(move || benchmark_thread(shared_state));
+    true
+}
+
+/// Converts a test signal at the current settings through an engine of its own.
+fn benchmark_thread(shared_state: Arc<RvcInferenceSharedState>) {
+    let (mut engine, input, frame_duration, convert) = {
+        let state = shared_state.state.lock();
+        let model_path = match state.model_path.clone() {
+            Some(path) if is_voice_bundle(&path) => {
+                let cache_dir = unsafe { DATA_PATH.as_ref().unwrap() }.join("bundles");
+                extract_bundle(&path, &cache_dir).ok().map(|bundle| bundle.model_path)
+            }
+            path => path,
+        };
+        let Some(model_path) = model_path.filter(|_| !runtime_probe::runtime_unavailable()) else {
+            *shared_state.benchmark.lock() = Some(BenchmarkStatus::Failed(text("Benchmark.NoModel")));
+            return;
+        };
+        let engine = match RvcInferenceFilter::start_engine(&state, model_path, false) {
+            Ok(engine) => engine,
+            Err(e) => {
+                *shared_state.benchmark.lock() = Some(BenchmarkStatus::Failed(e.to_string()));
+                return;
+            }
+        };
+
+        let (index_weights, index_count) = engine_index_weights(&state);
+        // the weights are put in by the closure, which owns them
+        let settings = frame_request(&state, &[]);
+        let convert = move |engine: &mut RvcInfer, input: &ndarray::Array1<f32>, output: &mut Vec<f32>| {
+            let request = FrameRequest { index_weights: &index_weights[..index_count], ..settings };
+            engine.infer(input.view(), &request, output)
+        };
+        let frame_duration = Duration::from_secs_f64(state.sample_frame_size as f64 / state.sample_rate as f64);
+        (engine, test_signal(state.input_buffer_16k.len()), frame_duration, convert)
+    };
+    run_benchmark(&shared_state.benchmark, &mut engine, &input, frame_duration, convert);
 }
 
 impl RvcInferenceFilter {
+    /// Applies the preset `step` places away from the current one.
+    fn switch_preset(&self, step: isize) {
+        let Some(config_path) = (unsafe { CONFIG_PATH.as_ref() }) else {
+            return;
+        };
+        let presets = load_presets(config_path);
+        if let Some(preset) = step_preset(&presets, self.current_preset.as_deref(), step) {
+            info!("Switching to preset {}", preset.name);
+            apply_preset(self.source, preset);
+        }
+    }
+
     fn start_thread(&mut self) {
         if self.thread_handle.is_none() {
-            eprintln!("Starting thread...");
+            info!("Starting thread...");
             self.shared_state.running.store(true, std::sync::atomic::Ordering::Relaxed);
             let shared_state = self.shared_state.clone();
             let parker = Parker::new();
@@ -872,15 +3607,17 @@ impl RvcInferenceFilter {
 
     fn stop_thread(&mut self) {
         if let Some(handle) = self.thread_handle.take() {
-            eprintln!("Stopping thread...");
+            info!("Stopping thread...");
             self.shared_state
                 .running
                 .store(false, std::sync::atomic::Ordering::Relaxed);
-            self.has_input.take();
+            if let Some(has_input) = self.has_input.take() {
+                has_input.unpark();
+            }
             match handle.join() {
                 Ok(_) => (),
                 Err(e) => {
-                    println!("Error joining thread: {:?}", e);
+                    error!("Error joining thread: {:?}", e);
                 }
             }
         }
@@ -892,42 +3629,260 @@ impl RvcInferenceFilter {
     }
 
     fn restart_rvc_engine_inner(state: &mut RvcInferenceState) {
-        let binary_path = unsafe { BINARY_PATH.as_ref().unwrap().parent().unwrap().join("rvc-rpc.exe") };
+        if runtime_probe::runtime_unavailable() {
+            // nothing would start, so the filter passes audio through
+            state.engine = None;
+            state.pending_engine = None;
+            return;
+        }
+
+        // bundles are unpacked again when replaced on disk, so this goes before the index list
+        let model_path = match state.model_path.clone() {
+            Some(path) if is_voice_bundle(&path) => {
+                let cache_dir = unsafe { DATA_PATH.as_ref().unwrap() }.join("bundles");
+                match extract_bundle(&path, &cache_dir) {
+                    Ok(bundle) => {
+                        state.bundle_index = bundle.index_path;
+                        state.bundle_error = None;
+                        Some(bundle.model_path)
+                    }
+                    Err(e) => {
+                        error!("Error unpacking voice bundle: {}", e);
+                        state.bundle_index = None;
+                        state.bundle_error = Some(e);
+                        None
+                    }
+                }
+            }
+            path => {
+                state.bundle_index = None;
+                state.bundle_error = None;
+                path
+            }
+        };
+
+        let rvc = match model_path.map(|path| Self::start_engine(state, path, true)).transpose() {
+            Ok(rvc) => {
+                state.engine_error = None;
+                rvc
+            }
+            Err(e) => {
+                error!("Error starting rvc-rpc: {}", e);
+                state.engine_error = Some(e.to_string());
+                None
+            }
+        };
+
+        match (&state.engine, rvc) {
+            // keep converting with the running engine while the new one loads, unless it is
+            // parked and has nothing loaded to convert with
+            (Some(engine), Some(rvc)) if engine.is_ready() && !engine.has_failed() && !state.idle_parked => {
+                state.pending_engine = Some(rvc);
+            }
+            (_, rvc) => {
+                state.engine = rvc;
+                state.pending_engine = None;
+            }
+        }
+        state.model_modified = state.model_path.as_deref().and_then(file_modified);
+        state.model_pending_modified = None;
+        state.idle_parked = false;
+        state.silent_samples = 0;
+    }
+
+    /// Starts loading an engine for `model_path` with the current settings.
+    /// Starts an engine for the current settings, or joins one other filters started with them
+    /// when `shared`.
+    fn start_engine(state: &RvcInferenceState, model_path: PathBuf, shared: bool) -> std::io::Result<RvcInfer> {
+        let binary_path = rpc_binary_path(unsafe { BINARY_PATH.as_ref().unwrap() });
         let infer_data_path = unsafe { DATA_PATH.as_ref().unwrap() }.join("rvcinfer");
 
-        let rvc = match state.model_path.clone() {
-            Some(path) => Some(RvcInfer::new(binary_path, state.model_version, state.pitch_algorithm, path, infer_data_path)),
-            None => None,
+        let index_paths = (0..MAX_INDEX_COUNT)
+            .filter_map(|slot| index_slot_path(state, slot).cloned())
+            .collect();
+        let contentvec_layers = match state.contentvec_layers {
+            layers if layers > 0 => Some(layers as usize),
+            _ => None,
+        };
+
+        // the same shape `convert_one_frame` sends, so that the warm-up runs compile for it
+        let frame_shape = FrameShape {
+            input_len: state.input_buffer_16k.len(),
+            sample_frame_16k_size: state.sample_frame_16k_size,
+            skip_head: ten_ms_frames(state.extra_frame_size, state.sample_rate) as u32,
+            return_length: state.model_return_length as u32,
+        };
+        let config = EngineConfig {
+            binary_path,
+            model_version: state.model_version,
+            pitch_algorithm: state.pitch_algorithm,
+            model_path,
+            data_path: infer_data_path,
+            index_paths,
+            index_metric: state.index_metric,
+            device: state.inference_device,
+            device_priority: state.device_priority.to_vec(),
+            device_id: state.device_id,
+            tensorrt: state.use_tensorrt,
+            cuda_graph: state.use_cuda_graph,
+            fp16: state.use_fp16,
+            int8: state.use_int8,
+            encoder: state.feature_encoder,
+            encoder_path: state.encoder_path.clone(),
+            contentvec_layers,
+            shared,
         };
+        RvcInfer::new(config, &frame_shape, &state.advanced)
+    }
+
+    fn get_status_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        match runtime_probe::probe_result() {
+            Some(Err(e)) => warnings.push(text_with("Warning.OnnxRuntime", &[&e])),
+            Some(Ok(devices)) => {
+                let device = self.shared_state.state.lock().inference_device;
+                if !matches!(device, InferenceDevice::Auto | InferenceDevice::Cpu) && !devices.contains(&device) {
+                    warnings.push(text_with("Warning.DeviceUnavailable", &[&device.to_string()]));
+                }
+            }
+            None => (),
+        }
+
+        {
+            let state = self.shared_state.state.lock();
+            if let Some(e) = &state.bundle_error {
+                warnings.push(text_with("Warning.Bundle", &[e]));
+            }
+            if let Some(e) = state.engine_error.clone().or_else(|| state.engine.as_ref().and_then(RvcInfer::load_error)) {
+                warnings.push(text_with("Warning.Engine", &[&e]));
+            }
+            if state.sample_length > state.configured_sample_length {
+                warnings.push(text_with(
+                    "Warning.SampleLengthAdapted",
+                    &[&format!("{:.2}", state.sample_length)],
+                ));
+            }
+            if state.denoise_enabled && state.denoiser.is_none() {
+                warnings.push(text_with("Warning.DenoiseUnsupported", &[&state.sample_rate]));
+            }
+        }
+
+        if let Some(warning) = self.shared_state.input_monitor.warning() {
+            warnings.push(warning);
+        }
+
+        let concealed_samples = self
+            .shared_state
+            .concealed_samples
+            .load(std::sync::atomic::Ordering::Relaxed);
+        if concealed_samples > 0 {
+            warnings.push(text_with("Warning.FixedLatencyMissed", &[&concealed_samples]));
+        }
+
+        if self
+            .shared_state
+            .worker_priority_rejected
+            .load(std::sync::atomic::Ordering::Relaxed)
+        {
+            warnings.push(text("Warning.WorkerPriority"));
+        }
+
+        if self
+            .shared_state
+            .encoder_path_rejected
+            .load(std::sync::atomic::Ordering::Relaxed)
+        {
+            warnings.push(text("Warning.EncoderPath"));
+        }
 
-        state.engine = rvc;
+        if self
+            .shared_state
+            .quantized_model
+            .load(std::sync::atomic::Ordering::Relaxed)
+        {
+            warnings.push(text("Warning.Quantized"));
+        }
+
+        warnings
     }
 
     fn clear_state(&mut self) {
+        self.shared_state.input_monitor.reset();
+        self.shared_state
+            .concealed_samples
+            .store(0, std::sync::atomic::Ordering::Relaxed);
+        self.input_position = 0;
+        self.fixed_latency.clear();
+        self.side_history.clear();
+        self.limiter.reset();
+
         let mut state = self.shared_state.state.lock();
         state.input_buffer.fill(0_f32);
         state.input_buffer_16k.fill(0_f32);
         state.sola_buffer.fill(0_f32);
         state.output_buffer.fill(0_f32);
+        state.silent_samples = 0;
+        state.noise_gate.reset();
+        state.vad.reset();
+        state.loudness_matcher.reset();
+        if let Some(denoiser) = state.denoiser.as_mut() {
+            denoiser.reset();
+        }
+        state.sibilance_blender.reset();
+        state.band_split_blender.reset();
+        state.convert_gain = target_convert_gain(&state);
     }
 }
 
 impl Drop for RvcInferenceFilter {
     fn drop(&mut self) {
+        websocket::unregister_filter(self.source);
         self.stop_thread();
     }
 }
 
+/// The plugin's directory in the OBS configuration, where the presets are kept.
+fn module_config_path(context: &ModuleRef) -> Option<PathBuf> {
+    unsafe {
+        let path = obs_module_get_config_path(context.get_raw(), obs_string!("").as_ptr());
+        if path.is_null() {
+            return None;
+        }
+        let config_path = PathBuf::from(CStr::from_ptr(path).to_string_lossy().into_owned());
+        bfree(path as *mut c_void);
+        Some(config_path)
+    }
+}
+
 impl Module for RvcInferenceModule {
     fn new(context: ModuleRef) -> Self {
+        logging::init();
 
         let binary_path = PathBuf::from(context.binary_path().unwrap().as_str());
         let data_path = PathBuf::from(context.data_path().unwrap().as_str());
+        let config_path = module_config_path(&context);
+
+        let advanced = AdvancedConfig::load(&data_path).unwrap_or_else(|e| {
+            error!("Error loading advanced config: {}", e);
+            AdvancedConfig::default()
+        });
+        log::set_max_level(logging::parse_level(&advanced.log_level));
+
+        if advanced.metrics_port > 0 {
+            if let Err(e) = start_metrics_server(advanced.metrics_port) {
+                error!("Error starting metrics endpoint on port {}: {:?}", advanced.metrics_port, e);
+            }
+        }
 
+        runtime_probe::start_probe(rpc_binary_path(&binary_path));
+        locale::load(&data_path, &locale::obs_locale());
 
         unsafe {
             BINARY_PATH = Some(binary_path);
             DATA_PATH = Some(data_path);
+            CONFIG_PATH = config_path;
+            ADVANCED_CONFIG = Some(advanced);
         };
 
         Self { context }
@@ -937,6 +3892,10 @@ impl Module for RvcInferenceModule {
         &self.context
     }
 
+    fn post_load(&mut self) {
+        websocket::register_vendor(unsafe { CONFIG_PATH.clone() });
+    }
+
     fn load(&mut self, load_context: &mut LoadContext) -> bool {
         let source = load_context
             .create_source_builder::<RvcInferenceFilter>()
@@ -0,0 +1,64 @@
+// how long the gain takes to recover after a peak, long enough not to distort the low end
+const RELEASE_TIME: f64 = 0.1;
+
+/// Keeps the output under a ceiling. The gain drops at once to whatever the sample needs and
+/// recovers over the release time, so nothing gets past the ceiling without adding lookahead
+/// latency; the model's rare spikes on plosives and sibilants are short enough for that.
+pub(crate) struct OutputLimiter {
+    ceiling: f32,
+    release: f32,
+    gain: f32,
+}
+
+impl OutputLimiter {
+    pub fn new(sample_rate: usize, ceiling_db: f64) -> Self {
+        Self {
+            ceiling: crate::rt_utils::db_to_gain(ceiling_db),
+            release: (-1.0 / (RELEASE_TIME * sample_rate as f64)).exp() as f32,
+            gain: 1.0,
+        }
+    }
+
+    pub fn set_ceiling(&mut self, ceiling_db: f64) {
+        self.ceiling = crate::rt_utils::db_to_gain(ceiling_db);
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            // back towards unity, then down again if this sample is over the ceiling
+            self.gain = 1.0 - (1.0 - self.gain) * self.release;
+            let level = sample.abs();
+            if level * self.gain > self.ceiling {
+                self.gain = self.ceiling / level;
+            }
+            *sample *= self.gain;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.gain = 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_limiter() {
+        let mut limiter = OutputLimiter::new(1000, -6.0);
+        let ceiling = crate::rt_utils::db_to_gain(-6.0);
+
+        let mut samples = vec![0.1, 0.9, -2.0, 0.4, 0.4];
+        limiter.process(&mut samples);
+        assert_eq!(samples[0], 0.1);
+        assert!(samples.iter().all(|sample| sample.abs() <= ceiling + 1e-6));
+        // still held down right after the peak
+        assert!(samples[3] < 0.4 * 0.5);
+
+        // and back to unity once it is released
+        let mut quiet = vec![0.1; 1000];
+        limiter.process(&mut quiet);
+        assert!((quiet[999] - 0.1).abs() < 1e-4);
+    }
+}
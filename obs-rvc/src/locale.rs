@@ -0,0 +1,92 @@
+use std::{collections::HashMap, ffi::CStr, fmt::Display, fs, path::Path, sync::OnceLock};
+
+use log::warn;
+use obs_wrapper::{obs_sys::obs_get_locale, string::ObsString};
+
+pub(crate) const DEFAULT_LOCALE: &str = "en-US";
+
+// the English strings are built in, so that a missing data directory (or the tests) still
+// shows text rather than keys
+const BUILTIN: &str = include_str!("../data/locale/en-US.ini");
+
+static TEXT: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Reads the strings of `locale` from `locale/<locale>.ini` in the plugin data directory, over
+/// the English ones so that a translation that lags behind still fills every label.
+pub(crate) fn load(data_path: &Path, locale: &str) {
+    let mut text = parse(BUILTIN);
+    if locale != DEFAULT_LOCALE {
+        let path = data_path.join("locale").join(format!("{}.ini", locale));
+        match fs::read_to_string(&path) {
+            Ok(source) => text.extend(parse(&source)),
+            Err(e) => warn!("No {} strings at {}: {}", locale, path.display(), e),
+        }
+    }
+    let _ = TEXT.set(text);
+}
+
+/// The locale OBS runs in, like "zh-CN".
+pub(crate) fn obs_locale() -> String {
+    let locale = unsafe { obs_get_locale() };
+    if locale.is_null() {
+        return DEFAULT_LOCALE.to_string();
+    }
+    unsafe { CStr::from_ptr(locale) }.to_string_lossy().into_owned()
+}
+
+/// Parses the `Key="Value"` lines OBS locale files are made of.
+fn parse(source: &str) -> HashMap<String, String> {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with(';'))
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+            Some((key.trim().to_string(), value.replace("\\n", "\n").replace("\\\"", "\"")))
+        })
+        .collect()
+}
+
+/// The string for `key`, or the key itself if no locale has it.
+pub(crate) fn text(key: &str) -> String {
+    let text = TEXT.get_or_init(|| parse(BUILTIN));
+    text.get(key).cloned().unwrap_or_else(|| key.to_string())
+}
+
+/// Like `text`, with `%1`, `%2` and so on replaced by `args` in order.
+pub(crate) fn text_with(key: &str, args: &[&dyn Display]) -> String {
+    let mut text = text(key);
+    // from the last, so that %1 doesn't take the start of %10
+    for (index, arg) in args.iter().enumerate().rev() {
+        text = text.replace(&format!("%{}", index + 1), &arg.to_string());
+    }
+    text
+}
+
+pub(crate) fn obs_text(key: &str) -> ObsString {
+    ObsString::from(text(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_strings() {
+        let parsed = parse("# comment\nGreeting=\"Hello \\\"%1\\\"\"\n\nTwoLines=\"a\\nb\"\nbroken line\n");
+        assert_eq!(parsed["Greeting"], "Hello \"%1\"");
+        assert_eq!(parsed["TwoLines"], "a\nb");
+        assert_eq!(parsed.len(), 2);
+
+        assert_eq!(text("Download.Progress"), "Downloading %1 (%2%)");
+        assert_eq!(text_with("Download.Progress", &[&"model.onnx", &25]), "Downloading model.onnx (25%)");
+        assert_eq!(text("No.Such.Key"), "No.Such.Key");
+
+        // a translation has no keys English doesn't
+        let english = parse(BUILTIN);
+        let chinese = parse(include_str!("../data/locale/zh-CN.ini"));
+        assert!(chinese.keys().all(|key| english.contains_key(key)));
+    }
+}
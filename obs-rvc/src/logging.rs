@@ -0,0 +1,65 @@
+use std::ffi::CString;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use obs_wrapper::{
+    obs_string,
+    obs_sys::{blog, LOG_DEBUG, LOG_ERROR, LOG_INFO, LOG_WARNING},
+};
+
+/// Sends the `log` records into the OBS log file, which is where users look and what they send
+/// along with bug reports. Debug records are written at the info level, since OBS only keeps its
+/// own debug level in debug builds; they only go through when the level asks for them anyway.
+struct ObsLogger;
+
+impl Log for ObsLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let level = match record.level() {
+            Level::Error => LOG_ERROR,
+            Level::Warn => LOG_WARNING,
+            Level::Info | Level::Debug => LOG_INFO,
+            Level::Trace => LOG_DEBUG,
+        };
+        let Ok(message) = CString::new(format!("[obs-rvc] {}", record.args())) else {
+            return;
+        };
+        unsafe {
+            blog(level as i32, obs_string!("%s").as_ptr(), message.as_ptr());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: ObsLogger = ObsLogger;
+
+/// Installs the OBS logger, at the info level until `set_level` says otherwise.
+pub(crate) fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
+
+/// Takes a level name like "warn" or "debug", anything else keeps the info level.
+pub(crate) fn parse_level(name: &str) -> LevelFilter {
+    name.trim().parse().unwrap_or(LevelFilter::Info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level("debug"), LevelFilter::Debug);
+        assert_eq!(parse_level(" WARN "), LevelFilter::Warn);
+        assert_eq!(parse_level("off"), LevelFilter::Off);
+        assert_eq!(parse_level("chatty"), LevelFilter::Info);
+    }
+}
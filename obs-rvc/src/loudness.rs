@@ -0,0 +1,101 @@
+use ndarray::{ArrayView1, ArrayViewMut1};
+
+// how long the levels are averaged over, slow enough to leave the dynamics of speech alone
+const TIME_CONSTANT: f64 = 3.0;
+// frames with a quieter input don't count, so that pauses don't drag the makeup gain around
+const MIN_INPUT_LEVEL_DB: f32 = -50.0;
+// and neither do frames the model returned (near) silence for
+const MIN_OUTPUT_LEVEL_DB: f32 = -70.0;
+const MAX_GAIN_DB: f32 = 12.0;
+
+/// Brings the converted voice to the loudness of the input it came from, so that switching the
+/// model or the index rate doesn't call for re-leveling. The gain follows slowly and is ramped
+/// over each frame.
+pub(crate) struct LoudnessMatcher {
+    sample_rate: usize,
+    input_power: f32,
+    output_power: f32,
+    gain: f32,
+}
+
+impl LoudnessMatcher {
+    pub fn new(sample_rate: usize) -> Self {
+        Self {
+            sample_rate,
+            input_power: 0.0,
+            output_power: 0.0,
+            gain: 1.0,
+        }
+    }
+
+    /// Measures `output` against the `dry` input it lines up with and applies the makeup gain.
+    pub fn process(&mut self, mut output: ArrayViewMut1<f32>, dry: ArrayView1<f32>) {
+        if output.is_empty() {
+            return;
+        }
+        let power = |samples: ArrayView1<f32>| samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32;
+        let (input_power, output_power) = (power(dry), power(output.view()));
+
+        let to_db = |power: f32| 10.0 * (power + 1e-12).log10();
+        if to_db(input_power) > MIN_INPUT_LEVEL_DB && to_db(output_power) > MIN_OUTPUT_LEVEL_DB {
+            let weight = 1.0 - (-(output.len() as f64) / (TIME_CONSTANT * self.sample_rate as f64)).exp() as f32;
+            if self.output_power == 0.0 {
+                // the first frame with speech starts the averages, rather than fading in from silence
+                self.input_power = input_power;
+                self.output_power = output_power;
+            } else {
+                self.input_power += weight * (input_power - self.input_power);
+                self.output_power += weight * (output_power - self.output_power);
+            }
+        }
+
+        let target = if self.output_power > 0.0 {
+            let max_gain = 10f32.powf(MAX_GAIN_DB / 20.0);
+            (self.input_power / self.output_power).sqrt().clamp(1.0 / max_gain, max_gain)
+        } else {
+            1.0
+        };
+
+        let step = (target - self.gain) / output.len() as f32;
+        for sample in output.iter_mut() {
+            self.gain += step;
+            *sample *= self.gain;
+        }
+        self.gain = target;
+    }
+
+    pub fn reset(&mut self) {
+        self.input_power = 0.0;
+        self.output_power = 0.0;
+        self.gain = 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loudness_matcher() {
+        let mut matcher = LoudnessMatcher::new(1000);
+        let dry = ndarray::Array1::from_elem(100, 0.2f32);
+
+        // an output at half the level is brought up to the input
+        let mut output = ndarray::Array1::from_elem(100, 0.1f32);
+        matcher.process(output.view_mut(), dry.view());
+        let mut output = ndarray::Array1::from_elem(100, 0.1f32);
+        matcher.process(output.view_mut(), dry.view());
+        assert!((output[99] - 0.2).abs() < 1e-4);
+
+        // silence in the input leaves the gain where it was
+        let mut output = ndarray::Array1::from_elem(100, 0.1f32);
+        matcher.process(output.view_mut(), ndarray::Array1::zeros(100).view());
+        assert!((output[0] - 0.2).abs() < 1e-4);
+
+        // and it never goes past the limit
+        let mut output = ndarray::Array1::from_elem(100, 0.001f32);
+        matcher.reset();
+        matcher.process(output.view_mut(), dry.view());
+        assert!((output[99] - 0.001 * 10f32.powf(MAX_GAIN_DB / 20.0)).abs() < 1e-4);
+    }
+}
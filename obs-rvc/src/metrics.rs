@@ -0,0 +1,239 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    time::Duration,
+};
+
+use log::error;
+use parking_lot::Mutex;
+
+static REGISTRY: Mutex<Vec<Weak<FilterMetrics>>> = Mutex::new(Vec::new());
+static NEXT_FILTER_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Counters and gauges of one filter instance. Written lock-free from the worker and the
+/// audio callback, read by whoever wants to report them.
+pub(crate) struct FilterMetrics {
+    pub id: usize,
+    pub frames_processed: AtomicU64,
+    pub inference_time_us_total: AtomicU64,
+    pub last_inference_time_us: AtomicU64,
+    pub frame_duration_us: AtomicU64,
+    // how far the converted voice is behind the input, buffering and inference time together
+    pub latency_us: AtomicU64,
+    // what the stages of the last frame took, the first three in `rvc-rpc`
+    pub encoder_time_us: AtomicU64,
+    pub pitch_time_us: AtomicU64,
+    pub synthesis_time_us: AtomicU64,
+    pub resample_time_us: AtomicU64,
+    pub discarded_blocks: AtomicU64,
+    // blocks pushed out of a full queue, because the worker or OBS fell behind
+    pub dropped_blocks: AtomicU64,
+    pub input_queue_depth: AtomicUsize,
+    pub output_queue_depth: AtomicUsize,
+}
+
+impl FilterMetrics {
+    /// Creates the metrics of a new filter and makes them visible to the endpoint.
+    pub fn register() -> Arc<Self> {
+        let metrics = Arc::new(FilterMetrics {
+            id: NEXT_FILTER_ID.fetch_add(1, Ordering::Relaxed),
+            frames_processed: AtomicU64::new(0),
+            inference_time_us_total: AtomicU64::new(0),
+            last_inference_time_us: AtomicU64::new(0),
+            frame_duration_us: AtomicU64::new(0),
+            latency_us: AtomicU64::new(0),
+            encoder_time_us: AtomicU64::new(0),
+            pitch_time_us: AtomicU64::new(0),
+            synthesis_time_us: AtomicU64::new(0),
+            resample_time_us: AtomicU64::new(0),
+            discarded_blocks: AtomicU64::new(0),
+            dropped_blocks: AtomicU64::new(0),
+            input_queue_depth: AtomicUsize::new(0),
+            output_queue_depth: AtomicUsize::new(0),
+        });
+
+        let mut registry = REGISTRY.lock();
+        registry.retain(|metrics| metrics.strong_count() > 0);
+        registry.push(Arc::downgrade(&metrics));
+
+        metrics
+    }
+
+    pub fn record_frame(&self, inference_time: Duration, frame_duration: Duration) {
+        let inference_time_us = inference_time.as_micros() as u64;
+        self.frames_processed.fetch_add(1, Ordering::Relaxed);
+        self.inference_time_us_total
+            .fetch_add(inference_time_us, Ordering::Relaxed);
+        self.last_inference_time_us
+            .store(inference_time_us, Ordering::Relaxed);
+        self.frame_duration_us
+            .store(frame_duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_latency(&self, latency: Duration) {
+        self.latency_us.store(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn latency(&self) -> Duration {
+        Duration::from_micros(self.latency_us.load(Ordering::Relaxed))
+    }
+
+    pub fn record_stage_times(&self, encoder: Duration, pitch: Duration, synthesis: Duration, resample: Duration) {
+        for (gauge, time) in [
+            (&self.encoder_time_us, encoder),
+            (&self.pitch_time_us, pitch),
+            (&self.synthesis_time_us, synthesis),
+            (&self.resample_time_us, resample),
+        ] {
+            gauge.store(time.as_micros() as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Time spent processing the last frame relative to the audio it produced.
+    pub fn realtime_factor(&self) -> f64 {
+        let frame_duration_us = self.frame_duration_us.load(Ordering::Relaxed);
+        if frame_duration_us == 0 {
+            return 0.0;
+        }
+        self.last_inference_time_us.load(Ordering::Relaxed) as f64 / frame_duration_us as f64
+    }
+}
+
+fn registered_metrics() -> Vec<Arc<FilterMetrics>> {
+    REGISTRY
+        .lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect()
+}
+
+pub(crate) fn render_prometheus(metrics: &[Arc<FilterMetrics>]) -> String {
+    let mut output = String::new();
+
+    let mut family = |name: &str, kind: &str, help: &str, value: &dyn Fn(&FilterMetrics) -> String| {
+        output.push_str(&format!("# HELP obsrvc_{} {}\n# TYPE obsrvc_{} {}\n", name, help, name, kind));
+        for filter in metrics {
+            output.push_str(&format!("obsrvc_{}{{filter=\"{}\"}} {}\n", name, filter.id, value(filter.as_ref())));
+        }
+    };
+
+    family("frames_processed_total", "counter", "Inference frames processed.",
+        &|m: &FilterMetrics| m.frames_processed.load(Ordering::Relaxed).to_string());
+    family("inference_seconds_total", "counter", "Time spent processing frames.",
+        &|m: &FilterMetrics| (m.inference_time_us_total.load(Ordering::Relaxed) as f64 / 1e6).to_string());
+    family("last_inference_seconds", "gauge", "Time spent processing the last frame.",
+        &|m: &FilterMetrics| (m.last_inference_time_us.load(Ordering::Relaxed) as f64 / 1e6).to_string());
+    family("realtime_factor", "gauge", "Processing time of the last frame divided by its duration.",
+        &|m: &FilterMetrics| m.realtime_factor().to_string());
+    family("latency_seconds", "gauge", "Delay of the converted voice behind the input.",
+        &|m: &FilterMetrics| m.latency().as_secs_f64().to_string());
+    family("last_encoder_seconds", "gauge", "Time the feature encoder took in the last frame.",
+        &|m: &FilterMetrics| (m.encoder_time_us.load(Ordering::Relaxed) as f64 / 1e6).to_string());
+    family("last_pitch_seconds", "gauge", "Time pitch extraction and retrieval took in the last frame.",
+        &|m: &FilterMetrics| (m.pitch_time_us.load(Ordering::Relaxed) as f64 / 1e6).to_string());
+    family("last_synthesis_seconds", "gauge", "Time the synthesizer took in the last frame.",
+        &|m: &FilterMetrics| (m.synthesis_time_us.load(Ordering::Relaxed) as f64 / 1e6).to_string());
+    family("last_resample_seconds", "gauge", "Time resampling took in the last frame.",
+        &|m: &FilterMetrics| (m.resample_time_us.load(Ordering::Relaxed) as f64 / 1e6).to_string());
+    family("discarded_blocks_total", "counter", "Audio blocks discarded because no output was ready.",
+        &|m: &FilterMetrics| m.discarded_blocks.load(Ordering::Relaxed).to_string());
+    family("dropped_blocks_total", "counter", "Audio blocks dropped from a full queue.",
+        &|m: &FilterMetrics| m.dropped_blocks.load(Ordering::Relaxed).to_string());
+    family("input_queue_depth", "gauge", "Audio blocks waiting for the worker.",
+        &|m: &FilterMetrics| m.input_queue_depth.load(Ordering::Relaxed).to_string());
+    family("output_queue_depth", "gauge", "Processed audio blocks waiting for OBS.",
+        &|m: &FilterMetrics| m.output_queue_depth.load(Ordering::Relaxed).to_string());
+
+    output
+}
+
+pub(crate) fn render_json(metrics: &[Arc<FilterMetrics>]) -> String {
+    let filters: Vec<String> = metrics
+        .iter()
+        .map(|m| {
+            format!(
+                "{{\"filter\":{},\"frames_processed\":{},\"inference_seconds_total\":{},\"last_inference_seconds\":{},\"realtime_factor\":{},\"latency_seconds\":{},\"discarded_blocks\":{},\"dropped_blocks\":{},\"input_queue_depth\":{},\"output_queue_depth\":{}}}",
+                m.id,
+                m.frames_processed.load(Ordering::Relaxed),
+                m.inference_time_us_total.load(Ordering::Relaxed) as f64 / 1e6,
+                m.last_inference_time_us.load(Ordering::Relaxed) as f64 / 1e6,
+                m.realtime_factor(),
+                m.latency().as_secs_f64(),
+                m.discarded_blocks.load(Ordering::Relaxed),
+                m.dropped_blocks.load(Ordering::Relaxed),
+                m.input_queue_depth.load(Ordering::Relaxed),
+                m.output_queue_depth.load(Ordering::Relaxed),
+            )
+        })
+        .collect();
+    format!("{{\"filters\":[{}]}}", filters.join(","))
+}
+
+fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+
+    let metrics = registered_metrics();
+    let (status, content_type, body) = match path {
+        "/metrics" => ("200 OK", "text/plain; version=0.0.4", render_prometheus(&metrics)),
+        "/metrics.json" => ("200 OK", "application/json", render_json(&metrics)),
+        _ => ("404 Not Found", "text/plain", "Not Found\n".to_string()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Serves `/metrics` (Prometheus text format) and `/metrics.json` on localhost.
+pub(crate) fn start_metrics_server(port: u16) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = handle_connection(stream) {
+                        error!("Error serving metrics: {:?}", e);
+                    }
+                }
+                Err(e) => error!("Error accepting metrics connection: {:?}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_metrics() {
+        let metrics = FilterMetrics::register();
+        metrics.record_frame(Duration::from_millis(150), Duration::from_millis(300));
+        metrics.record_latency(Duration::from_millis(250));
+
+        let prometheus = render_prometheus(&[metrics.clone()]);
+        assert!(prometheus.contains(&format!("obsrvc_frames_processed_total{{filter=\"{}\"}} 1\n", metrics.id)));
+        assert!(prometheus.contains(&format!("obsrvc_realtime_factor{{filter=\"{}\"}} 0.5\n", metrics.id)));
+
+        let json = render_json(&[metrics.clone()]);
+        assert!(json.contains("\"realtime_factor\":0.5"));
+        assert!(json.contains("\"latency_seconds\":0.25"));
+    }
+}
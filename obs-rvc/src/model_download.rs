@@ -0,0 +1,159 @@
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+
+use log::error;
+use parking_lot::Mutex;
+
+use crate::locale::{text, text_with};
+
+// keeps the subprocess from opening a console window
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum DownloadStatus {
+    Running { file: String, received: u64, size: u64 },
+    Finished(Vec<String>),
+    Failed(String),
+}
+
+impl DownloadStatus {
+    pub fn describe(&self) -> String {
+        match self {
+            DownloadStatus::Running { file, .. } if file.is_empty() => text("Download.Connecting"),
+            DownloadStatus::Running { file, received, size: 0 } => {
+                text_with("Download.Receiving", &[file, &format!("{:.1}", *received as f64 / 1e6)])
+            }
+            DownloadStatus::Running { file, received, size } => {
+                text_with("Download.Progress", &[file, &format!("{:.0}", *received as f64 * 100.0 / *size as f64)])
+            }
+            DownloadStatus::Finished(files) => {
+                text_with("Download.Finished", &[&files.join(", ")])
+            }
+            DownloadStatus::Failed(reason) => text_with("Download.Failed", &[reason]),
+        }
+    }
+}
+
+// one download at a time, shared by every filter since they share the models folder
+static DOWNLOAD: Mutex<Option<DownloadStatus>> = Mutex::new(None);
+
+pub(crate) fn download_status() -> Option<DownloadStatus> {
+    DOWNLOAD.lock().clone()
+}
+
+/// Runs `rvc-rpc download` in the background. Returns false while another download is running.
+pub(crate) fn start_download(rpc_path: PathBuf, source: &str, models_folder: Option<&Path>, sha256: &str) -> bool {
+    let mut download = DOWNLOAD.lock();
+    if matches!(*download, Some(DownloadStatus::Running { .. })) {
+        return false;
+    }
+    let Some(models_folder) = models_folder.filter(|_| !source.trim().is_empty()) else {
+        *download = Some(DownloadStatus::Failed(text("Download.MissingFields")));
+        return true;
+    };
+    *download = Some(DownloadStatus::Running { file: String::new(), received: 0, size: 0 });
+    drop(download);
+
+    let mut command = Command::new(&rpc_path);
+    command.arg("download").arg(source.trim()).arg(models_folder);
+    if !sha256.trim().is_empty() {
+        command.arg(sha256.trim());
+    }
+    if let Some(working_dir) = rpc_path.parent() {
+        command.current_dir(working_dir);
+    }
+    #[cfg(windows)]
+    command.creation_flags(CREATE_NO_WINDOW);
+
+    std::thread::spawn(move || {
+        let status = run_download(command).unwrap_or_else(DownloadStatus::Failed);
+        if let DownloadStatus::Failed(reason) = &status {
+            error!("Error downloading model: {}", reason);
+        }
+        *DOWNLOAD.lock() = Some(status);
+    });
+    true
+}
+
+fn run_download(mut command: Command) -> Result<DownloadStatus, String> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    let mut finished = Vec::new();
+    for line in BufReader::new(child.stdout.take().unwrap()).lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let mut download = DOWNLOAD.lock();
+        if let Some(DownloadStatus::Running { file, received, size }) = download.as_mut() {
+            match parse_progress_line(&line) {
+                Some(Progress::File(name, file_size)) => {
+                    *file = name;
+                    *received = 0;
+                    *size = file_size;
+                }
+                Some(Progress::Received(bytes)) => *received = bytes,
+                Some(Progress::Done(name)) => finished.push(name),
+                None => (),
+            }
+        }
+    }
+
+    let mut stderr = String::new();
+    let _ = child.stderr.take().unwrap().read_to_string(&mut stderr);
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if !status.success() {
+        let reason = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("rvc-rpc exited early");
+        return Err(reason.trim().to_string());
+    }
+    Ok(DownloadStatus::Finished(finished))
+}
+
+#[derive(Debug, PartialEq)]
+enum Progress {
+    File(String, u64),
+    Received(u64),
+    Done(String),
+}
+
+fn parse_progress_line(line: &str) -> Option<Progress> {
+    let (kind, rest) = line.trim_end().split_once(' ')?;
+    match kind {
+        // the size comes last, file names may contain spaces
+        "file" => {
+            let (name, size) = rest.rsplit_once(' ')?;
+            Some(Progress::File(name.to_string(), size.parse().ok()?))
+        }
+        "progress" => Some(Progress::Received(rest.parse().ok()?)),
+        "done" => Some(Progress::Done(rest.to_string())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_progress_line() {
+        assert_eq!(
+            parse_progress_line("file my voice/model.onnx 57000000\r"),
+            Some(Progress::File("my voice/model.onnx".to_string(), 57000000))
+        );
+        assert_eq!(parse_progress_line("progress 1048576"), Some(Progress::Received(1048576)));
+        assert_eq!(parse_progress_line("done model.onnx"), Some(Progress::Done("model.onnx".to_string())));
+        assert_eq!(parse_progress_line("progress lots"), None);
+        assert_eq!(parse_progress_line("garbage"), None);
+
+        let status = DownloadStatus::Running { file: "model.onnx".to_string(), received: 50, size: 200 };
+        assert_eq!(status.describe(), "Downloading model.onnx (25%)");
+    }
+}
@@ -0,0 +1,315 @@
+use std::{
+    ffi::{c_void, CStr, CString},
+    os::raw::c_char,
+    path::{Path, PathBuf},
+};
+
+use obs_wrapper::{
+    obs_sys::{
+        obs_combo_format_OBS_COMBO_FORMAT_STRING, obs_combo_type_OBS_COMBO_TYPE_LIST, obs_data_get_string,
+        obs_data_set_string, obs_data_t, obs_path_type_OBS_PATH_DIRECTORY, obs_properties_add_button,
+        obs_properties_add_list, obs_properties_add_path, obs_properties_add_text, obs_properties_get,
+        obs_properties_get_param, obs_properties_set_param, obs_properties_t, obs_property_list_add_string,
+        obs_property_list_clear, obs_property_set_modified_callback, obs_property_t, obs_text_type_OBS_TEXT_DEFAULT,
+    },
+    obs_string,
+    properties::ObsProp,
+    string::ObsString,
+};
+
+use crate::{locale::obs_text, model_download::start_download, voice_bundle::VOICE_BUNDLE_EXTENSION};
+
+const SETTING_MODELS_FOLDER: ObsString = obs_string!("models_folder");
+const SETTING_REFRESH_MODELS: ObsString = obs_string!("refresh_models");
+const SETTING_DOWNLOAD_SOURCE: ObsString = obs_string!("download_source");
+const SETTING_DOWNLOAD_SHA256: ObsString = obs_string!("download_sha256");
+const SETTING_DOWNLOAD: ObsString = obs_string!("download");
+
+// subfolders looked into below the models folder, enough for one folder per voice
+const MAX_SCAN_DEPTH: usize = 2;
+
+/// The voice models and bundles below `folder`, sorted by path. INT8 variants are left out, they
+/// are picked up next to their float model when INT8 inference is on.
+pub(crate) fn scan_models(folder: &Path) -> Vec<PathBuf> {
+    let mut models = Vec::new();
+    scan_folder(folder, MAX_SCAN_DEPTH, &mut models);
+    models.sort();
+    models
+}
+
+fn scan_folder(folder: &Path, depth: usize, models: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if depth > 0 {
+                scan_folder(&path, depth - 1, models);
+            }
+            continue;
+        }
+
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("").to_lowercase();
+        let bundle = name.ends_with(&format!(".{}", VOICE_BUNDLE_EXTENSION));
+        if bundle || (name.ends_with(".onnx") && !name.ends_with(".int8.onnx")) {
+            models.push(path);
+        }
+    }
+}
+
+/// The models folder, a dropdown of the models found in it and a button to scan it again,
+/// followed by the fields to download a voice into it. Picking a model writes it into the model
+/// path setting, so that the rest of the filter only ever looks at that one.
+pub(crate) struct ModelLibraryProp {
+    model_path_setting: ObsString,
+    rpc_path: PathBuf,
+}
+
+impl ModelLibraryProp {
+    pub fn new(model_path_setting: ObsString, rpc_path: PathBuf) -> Self {
+        Self { model_path_setting, rpc_path }
+    }
+}
+
+// what the callbacks need to find their way back, owned by the properties. Button callbacks
+// don't get the settings, so the modified callbacks keep copies of what they need.
+struct LibraryParam {
+    model_path_setting: ObsString,
+    list_setting: CString,
+    rpc_path: PathBuf,
+    folder: Option<PathBuf>,
+    download_source: String,
+    download_sha256: String,
+}
+
+unsafe extern "C" fn destroy_param(param: *mut c_void) {
+    drop(Box::from_raw(param as *mut LibraryParam));
+}
+
+unsafe fn library_param<'a>(props: *mut obs_properties_t) -> Option<&'a mut LibraryParam> {
+    (obs_properties_get_param(props) as *mut LibraryParam).as_mut()
+}
+
+pub(crate) unsafe fn get_string(settings: *mut obs_data_t, name: *const c_char) -> String {
+    let value = obs_data_get_string(settings, name);
+    if value.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(value).to_string_lossy().into_owned()
+}
+
+unsafe fn fill_model_list(props: *mut obs_properties_t, param: &LibraryParam) {
+    let list = obs_properties_get(props, param.list_setting.as_ptr());
+    if list.is_null() {
+        return;
+    }
+    obs_property_list_clear(list);
+
+    obs_property_list_add_string(list, obs_text("ModelLibrary.Choose").as_ptr(), obs_string!("").as_ptr());
+
+    let Some(folder) = &param.folder else {
+        return;
+    };
+    for model in scan_models(folder) {
+        let name = model.strip_prefix(folder).unwrap_or(&model).with_extension("");
+        let (Ok(name), Ok(value)) = (
+            CString::new(name.to_string_lossy().as_bytes()),
+            CString::new(model.to_string_lossy().as_bytes()),
+        ) else {
+            continue;
+        };
+        obs_property_list_add_string(list, name.as_ptr(), value.as_ptr());
+    }
+}
+
+unsafe extern "C" fn folder_modified(
+    props: *mut obs_properties_t,
+    _property: *mut obs_property_t,
+    settings: *mut obs_data_t,
+) -> bool {
+    let Some(param) = library_param(props) else {
+        return false;
+    };
+    let folder = get_string(settings, SETTING_MODELS_FOLDER.as_ptr());
+    param.folder = (!folder.is_empty()).then(|| PathBuf::from(folder));
+    fill_model_list(props, param);
+    true
+}
+
+unsafe extern "C" fn refresh_clicked(
+    props: *mut obs_properties_t,
+    _property: *mut obs_property_t,
+    _data: *mut c_void,
+) -> bool {
+    let Some(param) = library_param(props) else {
+        return false;
+    };
+    fill_model_list(props, param);
+    true
+}
+
+unsafe extern "C" fn download_fields_modified(
+    props: *mut obs_properties_t,
+    _property: *mut obs_property_t,
+    settings: *mut obs_data_t,
+) -> bool {
+    let Some(param) = library_param(props) else {
+        return false;
+    };
+    param.download_source = get_string(settings, SETTING_DOWNLOAD_SOURCE.as_ptr());
+    param.download_sha256 = get_string(settings, SETTING_DOWNLOAD_SHA256.as_ptr());
+    false
+}
+
+unsafe extern "C" fn download_clicked(
+    props: *mut obs_properties_t,
+    _property: *mut obs_property_t,
+    _data: *mut c_void,
+) -> bool {
+    let Some(param) = library_param(props) else {
+        return false;
+    };
+    start_download(
+        param.rpc_path.clone(),
+        &param.download_source,
+        param.folder.as_deref(),
+        &param.download_sha256,
+    )
+}
+
+unsafe extern "C" fn model_selected(
+    props: *mut obs_properties_t,
+    _property: *mut obs_property_t,
+    settings: *mut obs_data_t,
+) -> bool {
+    let Some(param) = library_param(props) else {
+        return false;
+    };
+    let selected = get_string(settings, param.list_setting.as_ptr());
+    if selected.is_empty() || selected == get_string(settings, param.model_path_setting.as_ptr()) {
+        return false;
+    }
+    let Ok(selected) = CString::new(selected) else {
+        return false;
+    };
+    obs_data_set_string(settings, param.model_path_setting.as_ptr(), selected.as_ptr());
+    true
+}
+
+// keeps the dropdown on the model in use after it was browsed for instead, otherwise the stale
+// selection would be written back over it the next time the properties open
+unsafe extern "C" fn model_path_modified(
+    props: *mut obs_properties_t,
+    _property: *mut obs_property_t,
+    settings: *mut obs_data_t,
+) -> bool {
+    let Some(param) = library_param(props) else {
+        return false;
+    };
+    let model_path = get_string(settings, param.model_path_setting.as_ptr());
+    if model_path == get_string(settings, param.list_setting.as_ptr()) {
+        return false;
+    }
+    let Ok(model_path) = CString::new(model_path) else {
+        return false;
+    };
+    obs_data_set_string(settings, param.list_setting.as_ptr(), model_path.as_ptr());
+    true
+}
+
+impl ObsProp for ModelLibraryProp {
+    /// Goes right after the model path property, whose modified callback it takes over.
+    unsafe fn add_to_props(self, p: *mut obs_properties_t, name: ObsString, description: ObsString) {
+        let model_path = obs_properties_get(p, self.model_path_setting.as_ptr());
+        if !model_path.is_null() {
+            obs_property_set_modified_callback(model_path, Some(model_path_modified));
+        }
+
+        let param = Box::new(LibraryParam {
+            model_path_setting: self.model_path_setting,
+            list_setting: CStr::from_ptr(name.as_ptr()).to_owned(),
+            rpc_path: self.rpc_path,
+            folder: None,
+            download_source: String::new(),
+            download_sha256: String::new(),
+        });
+        obs_properties_set_param(p, Box::into_raw(param) as *mut c_void, Some(destroy_param));
+
+        let folder = obs_properties_add_path(
+            p,
+            SETTING_MODELS_FOLDER.as_ptr(),
+            obs_text("ModelsFolder").as_ptr(),
+            obs_path_type_OBS_PATH_DIRECTORY,
+            std::ptr::null(),
+            std::ptr::null(),
+        );
+        obs_property_set_modified_callback(folder, Some(folder_modified));
+
+        // filled by `folder_modified`, which OBS calls as soon as the properties are shown
+        let list = obs_properties_add_list(
+            p,
+            name.as_ptr(),
+            description.as_ptr(),
+            obs_combo_type_OBS_COMBO_TYPE_LIST,
+            obs_combo_format_OBS_COMBO_FORMAT_STRING,
+        );
+        obs_property_set_modified_callback(list, Some(model_selected));
+
+        obs_properties_add_button(
+            p,
+            SETTING_REFRESH_MODELS.as_ptr(),
+            obs_text("RefreshModels").as_ptr(),
+            Some(refresh_clicked),
+        );
+
+        for (setting, description) in [
+            (SETTING_DOWNLOAD_SOURCE, obs_text("DownloadSource")),
+            (SETTING_DOWNLOAD_SHA256, obs_text("DownloadSha256")),
+        ] {
+            let field = obs_properties_add_text(p, setting.as_ptr(), description.as_ptr(), obs_text_type_OBS_TEXT_DEFAULT);
+            obs_property_set_modified_callback(field, Some(download_fields_modified));
+        }
+        obs_properties_add_button(
+            p,
+            SETTING_DOWNLOAD.as_ptr(),
+            obs_text("Download").as_ptr(),
+            Some(download_clicked),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_models() {
+        let folder = std::env::temp_dir().join(format!("obs-rvc-scan-{}", std::process::id()));
+        std::fs::create_dir_all(folder.join("voice/nested/too_deep")).unwrap();
+        for file in [
+            "b.onnx",
+            "c.rvcvoice",
+            "a.ONNX",
+            "a.int8.onnx",
+            "notes.txt",
+            "voice/voice.onnx",
+            "voice/nested/too_deep/hidden.onnx",
+        ] {
+            std::fs::write(folder.join(file), b"").unwrap();
+        }
+
+        let models = scan_models(&folder);
+        let _ = std::fs::remove_dir_all(&folder);
+
+        assert_eq!(
+            models,
+            vec![
+                folder.join("a.ONNX"),
+                folder.join("b.onnx"),
+                folder.join("c.rvcvoice"),
+                folder.join("voice/voice.onnx"),
+            ]
+        );
+    }
+}
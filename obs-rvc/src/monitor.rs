@@ -0,0 +1,87 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use crate::locale::text_with;
+
+// samples at or above this magnitude are treated as clipped
+const CLIP_THRESHOLD: f32 = 0.999;
+// sustained peaks above this level leave no headroom for the model
+const HOT_LEVEL_DB: f32 = -3.0;
+
+/// Tracks the input peak level from the audio thread so that the properties view can warn
+/// about clipped or overly hot input, which is otherwise invisible to the user.
+pub(crate) struct InputLevelMonitor {
+    clipped_blocks: AtomicUsize,
+    hot_blocks: AtomicUsize,
+    // f32 bits of the highest peak seen, in dBFS
+    max_peak_db: AtomicU32,
+}
+
+impl InputLevelMonitor {
+    pub fn new() -> Self {
+        Self {
+            clipped_blocks: AtomicUsize::new(0),
+            hot_blocks: AtomicUsize::new(0),
+            max_peak_db: AtomicU32::new(f32::NEG_INFINITY.to_bits()),
+        }
+    }
+
+    /// Records the peak of one audio block. Returns true the first time clipping is seen.
+    pub fn record(&self, peak: f32) -> bool {
+        let peak_db = 20.0 * (peak + 1e-12).log10();
+        if peak_db > f32::from_bits(self.max_peak_db.load(Ordering::Relaxed)) {
+            self.max_peak_db.store(peak_db.to_bits(), Ordering::Relaxed);
+        }
+
+        if peak >= CLIP_THRESHOLD {
+            return self.clipped_blocks.fetch_add(1, Ordering::Relaxed) == 0;
+        }
+
+        if peak_db >= HOT_LEVEL_DB {
+            self.hot_blocks.fetch_add(1, Ordering::Relaxed);
+        }
+
+        false
+    }
+
+    pub fn reset(&self) {
+        self.clipped_blocks.store(0, Ordering::Relaxed);
+        self.hot_blocks.store(0, Ordering::Relaxed);
+        self.max_peak_db
+            .store(f32::NEG_INFINITY.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn warning(&self) -> Option<String> {
+        let clipped_blocks = self.clipped_blocks.load(Ordering::Relaxed);
+        let max_peak_db = f32::from_bits(self.max_peak_db.load(Ordering::Relaxed));
+
+        if clipped_blocks > 0 {
+            Some(text_with("Warning.InputClipping", &[&clipped_blocks]))
+        } else if self.hot_blocks.load(Ordering::Relaxed) > 0 {
+            Some(text_with("Warning.InputHot", &[&format!("{:.1}", max_peak_db)]))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_level_monitor() {
+        let monitor = InputLevelMonitor::new();
+        assert!(!monitor.record(0.1));
+        assert!(monitor.warning().is_none());
+
+        assert!(!monitor.record(0.9));
+        assert!(monitor.warning().unwrap().starts_with("The input level is very high"));
+
+        assert!(monitor.record(1.0));
+        assert!(!monitor.record(1.0));
+        assert!(monitor.warning().unwrap().starts_with("The input is clipping (2 times)"));
+
+        monitor.reset();
+        assert!(monitor.warning().is_none());
+    }
+}
@@ -0,0 +1,357 @@
+use std::{
+    ffi::{c_void, CStr, CString},
+    fs, io,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use log::{error, warn};
+use obs_wrapper::{
+    obs_string,
+    obs_sys::{
+        obs_combo_format_OBS_COMBO_FORMAT_STRING, obs_combo_type_OBS_COMBO_TYPE_LIST, obs_data_create_from_json,
+        obs_data_get_json, obs_data_release, obs_data_t, obs_group_type_OBS_GROUP_NORMAL, obs_properties_add_button,
+        obs_properties_add_group, obs_properties_add_list, obs_properties_add_text, obs_properties_create,
+        obs_properties_get, obs_properties_get_param, obs_properties_set_param, obs_properties_t,
+        obs_property_group_content, obs_property_list_add_string, obs_property_list_clear,
+        obs_property_set_modified_callback, obs_property_t, obs_source_get_settings, obs_source_t, obs_source_update,
+        obs_text_type_OBS_TEXT_DEFAULT,
+    },
+    properties::ObsProp,
+    string::ObsString,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{locale::obs_text, model_library::get_string};
+
+pub(crate) const PRESETS_FILE: &str = "presets.json";
+// the name of the preset last applied, set along with its settings
+pub(crate) const SETTING_PRESET: ObsString = obs_string!("preset");
+// the group the preset properties are in, which also holds what their callbacks need
+const SETTING_PRESETS: ObsString = obs_string!("presets");
+const SETTING_PRESET_NAME: ObsString = obs_string!("preset_name");
+const SETTING_LOAD_PRESET: ObsString = obs_string!("load_preset");
+const SETTING_SAVE_PRESET: ObsString = obs_string!("save_preset");
+const SETTING_DELETE_PRESET: ObsString = obs_string!("delete_preset");
+
+/// A saved voice: the filter settings it sets, usually the model with its pitch and index
+/// settings. Settings it leaves out keep their value when it is applied.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub(crate) struct Preset {
+    pub name: String,
+    #[serde(default)]
+    pub settings: Map<String, Value>,
+}
+
+/// Reads the presets from `presets.json` in the plugin config directory, shared by every filter.
+pub(crate) fn load_presets(config_path: &Path) -> Vec<Preset> {
+    let path = config_path.join(PRESETS_FILE);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            error!("Error reading {}: {}", path.display(), e);
+            return Vec::new();
+        }
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        error!("Error reading {}: {}", path.display(), e);
+        Vec::new()
+    })
+}
+
+pub(crate) fn save_presets(config_path: &Path, presets: &[Preset]) -> io::Result<()> {
+    fs::create_dir_all(config_path)?;
+    let content = serde_json::to_string_pretty(presets).map_err(io::Error::other)?;
+    fs::write(config_path.join(PRESETS_FILE), content)
+}
+
+/// Adds `preset`, or replaces the one with its name in place.
+pub(crate) fn store_preset(presets: &mut Vec<Preset>, preset: Preset) {
+    match presets.iter_mut().find(|stored| stored.name == preset.name) {
+        Some(stored) => *stored = preset,
+        None => presets.push(preset),
+    }
+}
+
+/// The preset `step` places after the one named `current`, before it for a negative step,
+/// wrapping around. Without a current preset, stepping forward starts at the first one and
+/// stepping back at the last.
+pub(crate) fn step_preset<'a>(presets: &'a [Preset], current: Option<&str>, step: isize) -> Option<&'a Preset> {
+    if presets.is_empty() {
+        return None;
+    }
+    let count = presets.len() as isize;
+    let position = match current.and_then(|name| presets.iter().position(|preset| preset.name == name)) {
+        Some(position) => position as isize + step,
+        None if step > 0 => step - 1,
+        None => count + step,
+    };
+    presets.get(position.rem_euclid(count) as usize)
+}
+
+/// The source of a filter, which lives exactly as long as the filter does. Held without taking
+/// a reference, which would keep the source from ever being destroyed.
+#[derive(Clone, Copy)]
+pub(crate) struct SourceHandle(pub *mut obs_source_t);
+
+// OBS takes settings updates from any thread
+unsafe impl Send for SourceHandle {}
+
+/// Updates the settings of `source` with the preset, the way the properties view would.
+pub(crate) fn apply_preset(source: SourceHandle, preset: &Preset) {
+    let mut settings = preset.settings.clone();
+    settings.insert(SETTING_PRESET.as_str().to_string(), Value::String(preset.name.clone()));
+    if !update_settings(source, settings) {
+        warn!("Preset {} could not be applied", preset.name);
+    }
+}
+
+/// Updates the given settings of `source` and leaves the others, false if OBS took none.
+pub(crate) fn update_settings(source: SourceHandle, settings: Map<String, Value>) -> bool {
+    let Ok(json) = CString::new(Value::Object(settings).to_string()) else {
+        return false;
+    };
+    unsafe {
+        let data = obs_data_create_from_json(json.as_ptr());
+        if data.is_null() {
+            return false;
+        }
+        obs_source_update(source.0, data);
+        obs_data_release(data);
+    }
+    true
+}
+
+/// The settings of `source` worth keeping in a preset, the ones that differ from the defaults
+/// apart from those of the presets themselves.
+unsafe fn source_settings(source: SourceHandle, list_setting: &CStr) -> Map<String, Value> {
+    let data = obs_source_get_settings(source.0);
+    if data.is_null() {
+        return Map::new();
+    }
+    let json = obs_data_get_json(data);
+    let mut settings = if json.is_null() {
+        Map::new()
+    } else {
+        serde_json::from_slice(CStr::from_ptr(json).to_bytes()).unwrap_or_default()
+    };
+    obs_data_release(data);
+    for setting in [SETTING_PRESET.as_str(), SETTING_PRESET_NAME.as_str(), &list_setting.to_string_lossy()] {
+        settings.remove(setting);
+    }
+    settings
+}
+
+/// A group with a dropdown of the saved presets, buttons to load and delete the one picked, and
+/// a name field with a button to save the current settings under it. The dropdown is stored
+/// under the name the property is added with.
+pub(crate) struct PresetProp {
+    source: SourceHandle,
+    config_path: Option<PathBuf>,
+}
+
+impl PresetProp {
+    pub fn new(source: SourceHandle, config_path: Option<PathBuf>) -> Self {
+        Self { source, config_path }
+    }
+}
+
+// owned by the group, since the model library already takes the parameter of the properties the
+// callbacks get. Button callbacks don't get the settings, so the modified callbacks keep copies
+// of the fields.
+struct PresetParam {
+    source: SourceHandle,
+    config_path: Option<PathBuf>,
+    list_setting: CString,
+    selected: String,
+    name: String,
+}
+
+unsafe extern "C" fn destroy_param(param: *mut c_void) {
+    drop(Box::from_raw(param as *mut PresetParam));
+}
+
+unsafe fn preset_param<'a>(props: *mut obs_properties_t) -> Option<&'a mut PresetParam> {
+    let group = obs_properties_get(props, SETTING_PRESETS.as_ptr());
+    if group.is_null() {
+        return None;
+    }
+    (obs_properties_get_param(obs_property_group_content(group)) as *mut PresetParam).as_mut()
+}
+
+unsafe fn fill_preset_list(props: *mut obs_properties_t, param: &PresetParam) {
+    let list = obs_properties_get(props, param.list_setting.as_ptr());
+    if list.is_null() {
+        return;
+    }
+    obs_property_list_clear(list);
+    let Some(config_path) = &param.config_path else {
+        return;
+    };
+    for preset in load_presets(config_path) {
+        let Ok(name) = CString::new(preset.name) else {
+            continue;
+        };
+        obs_property_list_add_string(list, name.as_ptr(), name.as_ptr());
+    }
+}
+
+unsafe extern "C" fn preset_selected(
+    props: *mut obs_properties_t,
+    _property: *mut obs_property_t,
+    settings: *mut obs_data_t,
+) -> bool {
+    let Some(param) = preset_param(props) else {
+        return false;
+    };
+    param.selected = get_string(settings, param.list_setting.as_ptr());
+    false
+}
+
+unsafe extern "C" fn name_modified(
+    props: *mut obs_properties_t,
+    _property: *mut obs_property_t,
+    settings: *mut obs_data_t,
+) -> bool {
+    let Some(param) = preset_param(props) else {
+        return false;
+    };
+    param.name = get_string(settings, SETTING_PRESET_NAME.as_ptr());
+    false
+}
+
+unsafe extern "C" fn load_clicked(props: *mut obs_properties_t, _property: *mut obs_property_t, _data: *mut c_void) -> bool {
+    let Some(param) = preset_param(props) else {
+        return false;
+    };
+    let Some(config_path) = &param.config_path else {
+        return false;
+    };
+    let presets = load_presets(config_path);
+    let Some(preset) = presets.iter().find(|preset| preset.name == param.selected) else {
+        return false;
+    };
+    apply_preset(param.source, preset);
+    true
+}
+
+unsafe extern "C" fn save_clicked(props: *mut obs_properties_t, _property: *mut obs_property_t, _data: *mut c_void) -> bool {
+    let Some(param) = preset_param(props) else {
+        return false;
+    };
+    let name = param.name.trim();
+    let Some(config_path) = param.config_path.as_deref().filter(|_| !name.is_empty()) else {
+        return false;
+    };
+    let mut presets = load_presets(config_path);
+    let preset = Preset {
+        name: name.to_string(),
+        settings: source_settings(param.source, &param.list_setting),
+    };
+    store_preset(&mut presets, preset);
+    if let Err(e) = save_presets(config_path, &presets) {
+        error!("Error saving presets: {}", e);
+    }
+    fill_preset_list(props, param);
+    true
+}
+
+unsafe extern "C" fn delete_clicked(props: *mut obs_properties_t, _property: *mut obs_property_t, _data: *mut c_void) -> bool {
+    let Some(param) = preset_param(props) else {
+        return false;
+    };
+    let Some(config_path) = &param.config_path else {
+        return false;
+    };
+    let mut presets = load_presets(config_path);
+    presets.retain(|preset| preset.name != param.selected);
+    if let Err(e) = save_presets(config_path, &presets) {
+        error!("Error saving presets: {}", e);
+    }
+    fill_preset_list(props, param);
+    true
+}
+
+impl ObsProp for PresetProp {
+    unsafe fn add_to_props(self, p: *mut obs_properties_t, name: ObsString, description: ObsString) {
+        let param = Box::new(PresetParam {
+            source: self.source,
+            config_path: self.config_path,
+            list_setting: CStr::from_ptr(name.as_ptr()).to_owned(),
+            selected: String::new(),
+            name: String::new(),
+        });
+        let group = obs_properties_create();
+        obs_properties_set_param(group, Box::into_raw(param) as *mut c_void, Some(destroy_param));
+        obs_properties_add_group(p, SETTING_PRESETS.as_ptr(), description.as_ptr(), obs_group_type_OBS_GROUP_NORMAL, group);
+
+        let list = obs_properties_add_list(
+            group,
+            name.as_ptr(),
+            obs_text("PresetList").as_ptr(),
+            obs_combo_type_OBS_COMBO_TYPE_LIST,
+            obs_combo_format_OBS_COMBO_FORMAT_STRING,
+        );
+        obs_property_set_modified_callback(list, Some(preset_selected));
+        if let Some(param) = preset_param(p) {
+            fill_preset_list(p, param);
+        }
+        obs_properties_add_button(
+            group,
+            SETTING_LOAD_PRESET.as_ptr(),
+            obs_text("LoadPreset").as_ptr(),
+            Some(load_clicked),
+        );
+        obs_properties_add_button(
+            group,
+            SETTING_DELETE_PRESET.as_ptr(),
+            obs_text("DeletePreset").as_ptr(),
+            Some(delete_clicked),
+        );
+
+        let name_field = obs_properties_add_text(
+            group,
+            SETTING_PRESET_NAME.as_ptr(),
+            obs_text("PresetName").as_ptr(),
+            obs_text_type_OBS_TEXT_DEFAULT,
+        );
+        obs_property_set_modified_callback(name_field, Some(name_modified));
+        obs_properties_add_button(
+            group,
+            SETTING_SAVE_PRESET.as_ptr(),
+            obs_text("SavePreset").as_ptr(),
+            Some(save_clicked),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_preset() {
+        let presets: Vec<Preset> =
+            serde_json::from_str(r#"[{"name": "a", "settings": {"pitch_shift": 5}}, {"name": "b"}, {"name": "c"}]"#)
+                .unwrap();
+        assert_eq!(presets[0].settings["pitch_shift"], 5);
+        assert!(presets[1].settings.is_empty());
+
+        let step = |current, step| step_preset(&presets, current, step).map(|preset| preset.name.as_str());
+        assert_eq!(step(Some("a"), 1), Some("b"));
+        assert_eq!(step(Some("c"), 1), Some("a"));
+        assert_eq!(step(Some("a"), -1), Some("c"));
+        assert_eq!(step(None, 1), Some("a"));
+        assert_eq!(step(None, -1), Some("c"));
+        assert_eq!(step(Some("removed"), 1), Some("a"));
+        assert_eq!(step_preset(&[], Some("a"), 1), None);
+
+        let mut presets = presets;
+        store_preset(&mut presets, Preset { name: "b".to_string(), settings: Map::new() });
+        store_preset(&mut presets, Preset { name: "d".to_string(), settings: Map::new() });
+        let names = presets.iter().map(|preset| preset.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["a", "b", "c", "d"]);
+    }
+}
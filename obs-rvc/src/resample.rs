@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+
+use rubato::{
+    calculate_cutoff, FftFixedInOut, ResampleResult, Resampler, ResamplerConstructionError, SincFixedIn,
+    SincInterpolationParameters, SincInterpolationType, WindowFunction,
+};
+use rvc_common::enums::ResamplerType;
+
+// output held back by the sinc resamplers, so that the count they return can vary by a sample
+// between frames without running short
+const SINC_SLACK: usize = 2;
+
+/// A resampler that takes and returns the same number of samples every frame, whichever the
+/// algorithm. The FFT resampler works on whole blocks and adds their latency and a little
+/// pre-echo; the sinc resamplers interpolate sample by sample and only delay by half their
+/// filter length.
+pub(crate) enum FrameResampler {
+    Fft(FftFixedInOut<f32>),
+    Sinc {
+        resampler: SincFixedIn<f32>,
+        output_size: usize,
+        pending: VecDeque<f32>,
+        // what the resampler returned this frame, before it joins `pending`
+        scratch: Vec<f32>,
+    },
+}
+
+impl FrameResampler {
+    pub fn new(
+        kind: ResamplerType,
+        input_rate: usize,
+        output_rate: usize,
+        input_size: usize,
+    ) -> Result<Self, ResamplerConstructionError> {
+        let (sinc_len, oversampling_factor, interpolation) = match kind {
+            ResamplerType::Fft => {
+                return Ok(FrameResampler::Fft(FftFixedInOut::new(input_rate, output_rate, input_size, 1)?));
+            }
+            ResamplerType::SincFast => (64, 128, SincInterpolationType::Linear),
+            ResamplerType::SincBalanced => (128, 256, SincInterpolationType::Linear),
+            ResamplerType::SincBest => (256, 256, SincInterpolationType::Cubic),
+        };
+        let window = WindowFunction::BlackmanHarris2;
+        let parameters = SincInterpolationParameters {
+            sinc_len,
+            f_cutoff: calculate_cutoff(sinc_len, window),
+            oversampling_factor,
+            interpolation,
+            window,
+        };
+        let resampler = SincFixedIn::new(output_rate as f64 / input_rate as f64, 1.0, parameters, input_size, 1)?;
+
+        let output_size = input_size * output_rate / input_rate;
+        let mut pending = VecDeque::with_capacity(output_size + resampler.output_frames_max() + SINC_SLACK);
+        pending.extend(std::iter::repeat(0.0).take(SINC_SLACK));
+        Ok(FrameResampler::Sinc {
+            scratch: vec![0.0; resampler.output_frames_max()],
+            resampler,
+            output_size,
+            pending,
+        })
+    }
+
+    pub fn output_frames_max(&self) -> usize {
+        match self {
+            FrameResampler::Fft(resampler) => resampler.output_frames_max(),
+            FrameResampler::Sinc { output_size, .. } => *output_size,
+        }
+    }
+
+    pub fn process(&mut self, input: &[f32]) -> ResampleResult<Vec<f32>> {
+        let mut output = vec![0.0; self.output_frames_max()];
+        let output_size = self.process_into_buffer(input, &mut output)?;
+        output.truncate(output_size);
+        Ok(output)
+    }
+
+    /// Returns how many samples were written to `output`.
+    pub fn process_into_buffer(&mut self, input: &[f32], output: &mut [f32]) -> ResampleResult<usize> {
+        match self {
+            FrameResampler::Fft(resampler) => {
+                let (_, output_size) = resampler.process_into_buffer(&[input], &mut [output], None)?;
+                Ok(output_size)
+            }
+            FrameResampler::Sinc { resampler, output_size, pending, scratch } => {
+                let (_, resampled_size) = resampler.process_into_buffer(&[input], &mut [&mut scratch[..]], None)?;
+                pending.extend(scratch[..resampled_size].iter());
+                for sample in output[..*output_size].iter_mut() {
+                    *sample = pending.pop_front().unwrap_or(0.0);
+                }
+                Ok(*output_size)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sinc_frame_sizes() {
+        // 40 kHz model output to a 48 kHz device, 0.3 s frames
+        let mut resampler = FrameResampler::new(ResamplerType::SincFast, 40000, 48000, 12000).unwrap();
+        assert_eq!(resampler.output_frames_max(), 14400);
+        let input = vec![0.5f32; 12000];
+        let mut output = Vec::new();
+        for _ in 0..5 {
+            output = resampler.process(&input).unwrap();
+            assert_eq!(output.len(), 14400);
+        }
+        // settled on the level of the input once the filter is filled
+        assert!(output.iter().all(|sample| (sample - 0.5).abs() < 1e-2));
+    }
+}
@@ -1,16 +1,45 @@
-use ndarray::{s, Array1, ArrayView1, ArrayViewMut1, Axis, Zip};
-use ndarray_conv::ConvFFTExt as _;
+use ndarray::{s, Array1, ArrayView1, ArrayViewMut1, Zip};
 use obs_wrapper::media::{AudioData, AudioDataContext};
+use rvc_common::enums::{CrossfadeWindow, DownmixMode, UpmixMode};
 
-pub fn downmix_to_mono(audio: &mut AudioDataContext, channels: usize) -> std::io::Result<&mut [f32]> {
+/// Weight of each input channel in the downmix. They add up to one, so that the level of a
+/// channel that is picked alone stays as it is.
+pub(crate) fn downmix_weights(mode: DownmixMode, channels: usize, left_weight: f32, right_weight: f32) -> Vec<f32> {
+    let mut weights = vec![0.0; channels];
+    if channels < 2 {
+        weights.fill(1.0);
+        return weights;
+    }
+    match mode {
+        DownmixMode::Average => weights.fill(1.0 / channels as f32),
+        DownmixMode::Left => weights[0] = 1.0,
+        DownmixMode::Right => weights[1] = 1.0,
+        DownmixMode::Custom => {
+            let total = left_weight + right_weight;
+            if total > 1e-6 {
+                weights[0] = left_weight / total;
+                weights[1] = right_weight / total;
+            } else {
+                // both turned all the way down, nothing sensible to pick
+                weights.fill(1.0 / channels as f32);
+            }
+        }
+    }
+    weights
+}
+
+/// Mixes the channels into the first one by `weights`, one per channel.
+pub fn downmix_to_mono<'a>(audio: &'a mut AudioDataContext, weights: &[f32]) -> std::io::Result<&'a mut [f32]> {
     let main_channel = audio.get_channel_as_mut_slice(0).ok_or_else(|| std::io::Error::new(
         std::io::ErrorKind::InvalidData,
         "No main channel found.",
     ))?;
 
-    let interpolation_factor = 1.0 / channels as f32;
-    main_channel.iter_mut().for_each(|sample| *sample *= interpolation_factor);
-    for channel in 1..channels {
+    main_channel.iter_mut().for_each(|sample| *sample *= weights[0]);
+    for (channel, &weight) in weights.iter().enumerate().skip(1) {
+        if weight == 0.0 {
+            continue;
+        }
         let buffer = audio
             .get_channel_as_mut_slice(channel)
             .ok_or_else(|| std::io::Error::new(
@@ -19,14 +48,74 @@ pub fn downmix_to_mono(audio: &mut AudioDataContext, channels: usize) -> std::io
             ))?;
 
         for (base_stream, additional_stream) in main_channel.iter_mut().zip(buffer.iter()) {
-            *base_stream = *base_stream + *additional_stream * interpolation_factor;
+            *base_stream = *base_stream + *additional_stream * weight;
         }
     }
 
     Ok(main_channel)
 }
 
-pub fn upmix_audio_data_context(audio: &mut AudioDataContext, channels: usize) -> std::io::Result<()> {
+/// Front center in the OBS speaker layouts that have one.
+fn center_channel(channels: usize) -> Option<usize> {
+    match channels {
+        4 | 5 | 6 | 8 => Some(2),
+        _ => None,
+    }
+}
+
+/// Gain of the mono signal on `channel`. `placement_gains` are only used for `UpmixMode::Placement`.
+pub(crate) fn upmix_gain(mode: UpmixMode, channels: usize, channel: usize, placement_gains: &[f32]) -> f32 {
+    match mode {
+        UpmixMode::Duplicate | UpmixMode::MidSide => 1.0,
+        UpmixMode::CenterOnly => match center_channel(channels) {
+            Some(center) => if channel == center { 1.0 } else { 0.0 },
+            // no center speaker, the front pair makes a phantom center
+            None => if channel < 2 { 1.0 } else { 0.0 },
+        },
+        UpmixMode::Placement => placement_gains.get(channel).copied().unwrap_or(1.0),
+    }
+}
+
+/// Turns the level of each channel into its level relative to their downmix, in place, so that
+/// the converted signal can be put back where the input came from. Returns `false` and leaves
+/// the levels alone while the input is too quiet to tell.
+pub(crate) fn placement_gains(channel_levels: &mut [f32], mix_level: f32) -> bool {
+    if mix_level <= 1e-4 {
+        return false;
+    }
+    let max_gain = channel_levels.len() as f32;
+    channel_levels.iter_mut().for_each(|level| *level = (*level / mix_level).min(max_gain));
+    true
+}
+
+/// Writes half the difference of the front pair, what the downmix loses of a stereo input, into
+/// `side`. Returns `false` for mono, with `side` left empty.
+pub(crate) fn front_side_signal(audio: &mut AudioDataContext, side: &mut Vec<f32>) -> bool {
+    side.clear();
+    let Some(right) = audio.get_channel_as_mut_slice(1) else {
+        return false;
+    };
+    side.extend_from_slice(right);
+    let Some(left) = audio.get_channel_as_mut_slice(0) else {
+        side.clear();
+        return false;
+    };
+    side.iter_mut().zip(left.iter()).for_each(|(side, left)| *side = (left - *side) * 0.5);
+    true
+}
+
+/// Puts `side` back on the front pair after the upmix, left plus and right minus.
+pub(crate) fn apply_front_side_signal(audio: &mut AudioDataContext, side: &[f32]) {
+    for (channel, sign) in [(0, 1.0), (1, -1.0)] {
+        if let Some(buffer) = audio.get_channel_as_mut_slice(channel) {
+            for (output, side) in buffer.iter_mut().zip(side.iter()) {
+                *output += sign * side;
+            }
+        }
+    }
+}
+
+pub fn upmix_audio_data_context(audio: &mut AudioDataContext, channels: usize, mode: UpmixMode, placement_gains: &[f32]) -> std::io::Result<()> {
     let main_channel = audio.get_channel_as_mut_slice(0).ok_or_else(|| std::io::Error::new(
         std::io::ErrorKind::InvalidData,
         "No main channel found.",
@@ -40,7 +129,16 @@ pub fn upmix_audio_data_context(audio: &mut AudioDataContext, channels: usize) -
                 "Channel count said there was a buffer here.",
             ))?;
 
-        buffer.copy_from_slice(&main_channel)
+        let gain = upmix_gain(mode, channels, channel, placement_gains);
+        for (output, input) in buffer.iter_mut().zip(main_channel.iter()) {
+            *output = *input * gain;
+        }
+    }
+
+    // the main channel is the source of all the others, so it goes last
+    let gain = upmix_gain(mode, channels, 0, placement_gains);
+    if gain != 1.0 {
+        main_channel.iter_mut().for_each(|sample| *sample *= gain);
     }
 
     Ok(())
@@ -57,48 +155,100 @@ pub fn upmix_audio_data(audio: &mut AudioData) -> std::io::Result<()> {
     Ok(())
 }
 
-pub fn get_sola_offset(input_buffer: ndarray::ArrayView1<f32>, sola_buffer: ndarray::ArrayView1<f32>, 
-    buffer_frame_size: usize, search_frame_size: usize) -> Result<usize, Box<dyn std::error::Error>> {
-    let conv_input_size = buffer_frame_size + search_frame_size;
-    let conv_input = input_buffer
-        .slice(s![..conv_input_size]);
-
-    let cor_nom = conv_input.conv_fft(
-        &sola_buffer, ndarray_conv::ConvMode::Valid, 
-        ndarray_conv::PaddingMode::Zeros
-    )?;
-
-    let cor_den_filler = ndarray::Array1::<f32>::ones((buffer_frame_size));
-    let mut cor_den = conv_input.mapv(|x| x.powi(2)).conv_fft(
-        &cor_den_filler, ndarray_conv::ConvMode::Valid, 
-        ndarray_conv::PaddingMode::Zeros
-    )?;
-    cor_den.mapv_inplace(|x| (x + 1e-8).sqrt());
-
-    let cor = Zip::from(&cor_nom).and(&cor_den)
-        .map_collect(|&nom, &den| nom / den);
-    let (idx_max, _val_max) =
-        cor.indexed_iter()
-            .fold((0, cor[0]), |(idx_max, val_max), (idx, val)| {
-                if val_max > *val {
-                    (idx_max, val_max)
-                } else {
-                    (idx, *val)
-                }
-            });
-    Ok(idx_max)
+/// Offset into `input_buffer` where it lines up best with the tail of the previous frame in
+/// `sola_buffer`, by normalized cross-correlation so that loud stretches don't win just for
+/// being loud. The middle of the search window, where the dry signal is aligned, when there is
+/// nothing to line up with.
+pub fn get_sola_offset(input_buffer: &[f32], sola_buffer: &[f32], buffer_frame_size: usize, search_frame_size: usize) -> usize {
+    let sola_energy = dot(sola_buffer, sola_buffer);
+    if sola_energy < 1e-8 {
+        // the previous frame was silent, e.g. gated or parked
+        return search_frame_size / 2;
+    }
+
+    let conv_input = &input_buffer[..buffer_frame_size + search_frame_size];
+    // slides along with the offset, in f64 so that adding and taking off doesn't drift
+    let mut window_energy = conv_input[..buffer_frame_size].iter().map(|x| (x * x) as f64).sum::<f64>();
+    // the first of equally good offsets, rather than drifting to the end of the window
+    let mut best = (0, f32::NEG_INFINITY);
+    for offset in 0..=search_frame_size {
+        if offset > 0 {
+            let (leaving, entering) = (conv_input[offset - 1], conv_input[offset + buffer_frame_size - 1]);
+            window_energy += (entering * entering) as f64 - (leaving * leaving) as f64;
+        }
+        let nom = dot(&conv_input[offset..offset + buffer_frame_size], sola_buffer);
+        let den = (window_energy.max(0.0) as f32 * sola_energy + 1e-8).sqrt();
+        if nom / den > best.1 {
+            best = (offset, nom / den);
+        }
+    }
+    best.0
+}
+
+// partial sums kept apart in `dot`, as many as fit an AVX register
+const DOT_LANES: usize = 8;
+
+/// Dot product of two slices of the same length. The sum is split over `DOT_LANES` independent
+/// accumulators, which the compiler turns into SIMD instructions; a single running sum would be
+/// one long dependency chain it has to keep in order.
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let (a_chunks, b_chunks) = (a.chunks_exact(DOT_LANES), b.chunks_exact(DOT_LANES));
+    let remainder = a_chunks.remainder().iter().zip(b_chunks.remainder()).map(|(a, b)| a * b).sum::<f32>();
+    let mut lanes = [0.0f32; DOT_LANES];
+    for (a, b) in a_chunks.zip(b_chunks) {
+        for ((lane, a), b) in lanes.iter_mut().zip(a).zip(b) {
+            *lane += a * b;
+        }
+    }
+    lanes.iter().sum::<f32>() + remainder
 }
 
 
 pub(crate) fn rms(y: ArrayView1<f32>, frame_length: usize, hop_length: usize) -> Array1<f32> {
+    let mut output = Vec::new();
+    rms_into(y, frame_length, hop_length, &mut output);
+    Array1::from(output)
+}
+
+/// `rms` into a buffer that is reused between frames. The frames are centered, with zeros
+/// taken for the samples past either end.
+pub(crate) fn rms_into(y: ArrayView1<f32>, frame_length: usize, hop_length: usize, output: &mut Vec<f32>) {
     let padding = frame_length / 2;
-    let y_padded = ndarray::concatenate![Axis(0), Array1::zeros(padding), y, Array1::zeros(padding)].mapv(|x| x.powi(2));
-    let y_mean = y_padded
-        .windows((frame_length,))
-        .into_iter()
-        .step_by(hop_length)
-        .map(|f| f.mean().unwrap().sqrt());
-    y_mean.collect()
+    let padded_len = y.len() + 2 * padding;
+    output.clear();
+    if padded_len < frame_length {
+        return;
+    }
+    output.extend((0..=padded_len - frame_length).step_by(hop_length).map(|start| {
+        let begin = start.saturating_sub(padding);
+        let end = usize::min(start + frame_length, padding + y.len()).saturating_sub(padding);
+        let sum = y.slice(s![begin..end.max(begin)]).iter().map(|x| x.powi(2)).sum::<f32>();
+        (sum / frame_length as f32).sqrt()
+    }));
+}
+
+pub(crate) fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0f32, |peak, x| peak.max(x.abs()))
+}
+
+pub(crate) fn rms_level(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|x| x.powi(2)).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+/// RMS level of a block of samples in dBFS.
+pub(crate) fn level_db(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    let mean_square = samples.iter().map(|x| x.powi(2)).sum::<f32>() / samples.len() as f32;
+    10.0 * (mean_square + 1e-12).log10()
+}
+
+pub(crate) fn db_to_gain(db: f64) -> f32 {
+    10f64.powf(db / 20.0) as f32
 }
 
 pub(crate) fn linear_interpolate_align_corners(input: ArrayView1<f32>, size: usize) -> Array1<f32> {
@@ -116,19 +266,243 @@ pub(crate) fn linear_interpolate_align_corners(input: ArrayView1<f32>, size: usi
     output
 }
 
-pub fn envelop_mixing(input: ArrayView1<f32>, output: ArrayViewMut1<f32>, sample_rate: usize, mix_rate: f64) {
+/// Fits `input` to exactly `size` samples by stretching or squeezing only the part that ends up
+/// as the last `tail` samples, leaving everything before it untouched.
+pub(crate) fn fit_tail_to_length(input: ArrayView1<f32>, size: usize, tail: usize) -> Array1<f32> {
+    let tail = usize::min(tail, size);
+    let head = size - tail;
+    if input.len() <= head + 1 || tail < 2 {
+        // nothing left to stretch, fall back to the whole signal
+        return linear_interpolate_align_corners(input, size);
+    }
+
+    let mut output = Array1::zeros(size);
+    output.slice_mut(s![..head]).assign(&input.slice(s![..head]));
+    output
+        .slice_mut(s![head..])
+        .assign(&linear_interpolate_align_corners(input.slice(s![head..]), tail));
+    output
+}
+
+/// Sample `idx` of `input` stretched to `size` samples, as `linear_interpolate_align_corners`
+/// would return it.
+fn interpolate_align_corners(input: &[f32], size: usize, idx: usize) -> f32 {
+    let step = (input.len() - 1) as f32 / (size - 1) as f32;
+    let idx = idx as f32 * step;
+    let idx_floor = usize::clamp(idx.floor() as usize, 0, input.len() - 1);
+    let idx_ceil = usize::clamp(idx.ceil() as usize, 0, input.len() - 1);
+    let idx_frac = idx - idx_floor as f32;
+    input[idx_floor] * (1.0 - idx_frac) + input[idx_ceil] * idx_frac
+}
+
+/// `envelopes` holds the two RMS curves between frames, so that mixing doesn't allocate.
+pub fn envelop_mixing(
+    input: ArrayView1<f32>,
+    mut output: ArrayViewMut1<f32>,
+    sample_rate: usize,
+    mix_rate: f64,
+    envelopes: &mut (Vec<f32>, Vec<f32>),
+) {
     let zc = sample_rate / 100;
     let output_len = output.len();
-    let rms1 = rms(input.slice(s![..output_len]), 4*zc, zc);
-    let rms2 = rms(output.view(), 4*zc, zc);
-    let rms1 = linear_interpolate_align_corners(rms1.view(), output_len + 1);
-    let rms2 = linear_interpolate_align_corners(rms2.view(), output_len + 1)
-        .mapv(|x| f32::max(x, 1e-3));
+    let (rms1, rms2) = envelopes;
+    rms_into(input.slice(s![..output_len]), 4*zc, zc, rms1);
+    rms_into(output.view(), 4*zc, zc, rms2);
     let mix_power = 1.0f64 - mix_rate;
-    Zip::from(output).and(rms1.slice(s![..output_len])).and(rms2.slice(s![..output_len]))
-        .for_each(|out, rms1, rms2| {
-            *out = *out * (*rms1 / *rms2).powf(mix_power as f32);
+    for (idx, out) in output.iter_mut().enumerate() {
+        let rms1 = interpolate_align_corners(rms1, output_len + 1, idx);
+        let rms2 = f32::max(interpolate_align_corners(rms2, output_len + 1, idx), 1e-3);
+        *out = *out * (rms1 / rms2).powf(mix_power as f32);
+    }
+}
+
+/// Second-order IIR section (RBJ cookbook coefficients, transposed direct form II).
+/// The state is carried across calls so a stream can be filtered block by block.
+#[derive(Clone, Debug)]
+pub(crate) struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    fn from_coefficients(b0: f64, b1: f64, b2: f64, a0: f64, a1: f64, a2: f64) -> Self {
+        Biquad {
+            b0: (b0 / a0) as f32,
+            b1: (b1 / a0) as f32,
+            b2: (b2 / a0) as f32,
+            a1: (a1 / a0) as f32,
+            a2: (a2 / a0) as f32,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    pub fn highpass(sample_rate: usize, cutoff: f64, q: f64) -> Self {
+        let w0 = 2.0 * std::f64::consts::PI * cutoff / sample_rate as f64;
+        let cos_w0 = w0.cos();
+        let alpha = w0.sin() / (2.0 * q);
+        Self::from_coefficients(
+            (1.0 + cos_w0) / 2.0,
+            -(1.0 + cos_w0),
+            (1.0 + cos_w0) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos_w0,
+            1.0 - alpha,
+        )
+    }
+
+    pub fn lowpass(sample_rate: usize, cutoff: f64, q: f64) -> Self {
+        let w0 = 2.0 * std::f64::consts::PI * cutoff / sample_rate as f64;
+        let cos_w0 = w0.cos();
+        let alpha = w0.sin() / (2.0 * q);
+        Self::from_coefficients(
+            (1.0 - cos_w0) / 2.0,
+            1.0 - cos_w0,
+            (1.0 - cos_w0) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos_w0,
+            1.0 - alpha,
+        )
+    }
+
+    pub fn process(&mut self, samples: ArrayViewMut1<f32>) {
+        samples.into_iter().for_each(|x| {
+            let input = *x;
+            let output = self.b0 * input + self.z1;
+            self.z1 = self.b1 * input - self.a1 * output + self.z2;
+            self.z2 = self.b2 * input - self.a2 * output;
+            *x = output;
         });
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+/// Swaps part of the band above a crossover frequency between two aligned streams.
+/// Both streams run through identical high-pass filters, so the untouched remainder of
+/// the wet signal is the exact complement of the band being replaced.
+pub(crate) struct BandBlender {
+    wet_filters: [Biquad; 2],
+    dry_filters: [Biquad; 2],
+    wet_band: Vec<f32>,
+    dry_band: Vec<f32>,
+}
+
+impl BandBlender {
+    pub fn new(sample_rate: usize, crossover: f64) -> Self {
+        let crossover = crossover.min(sample_rate as f64 * 0.45);
+        // two cascaded butterworth sections give a 24 dB/oct slope
+        let filter = Biquad::highpass(sample_rate, crossover, std::f64::consts::FRAC_1_SQRT_2);
+        BandBlender {
+            wet_filters: [filter.clone(), filter.clone()],
+            dry_filters: [filter.clone(), filter],
+            wet_band: Vec::new(),
+            dry_band: Vec::new(),
+        }
+    }
+
+    /// Filters both streams into `wet_band` and `dry_band`, which keep their capacity between
+    /// frames.
+    fn high_bands(&mut self, wet: ArrayView1<f32>, dry: ArrayView1<f32>) -> (ArrayView1<f32>, ArrayView1<f32>) {
+        self.wet_band.clear();
+        self.wet_band.extend(wet.iter());
+        self.dry_band.clear();
+        self.dry_band.extend(dry.iter());
+        for filter in self.wet_filters.iter_mut() {
+            filter.process(ArrayViewMut1::from(&mut self.wet_band[..]));
+        }
+        for filter in self.dry_filters.iter_mut() {
+            filter.process(ArrayViewMut1::from(&mut self.dry_band[..]));
+        }
+        (ArrayView1::from(&self.wet_band[..]), ArrayView1::from(&self.dry_band[..]))
+    }
+
+    /// Replaces `amount` of the high band in `wet` with the high band of `dry`.
+    pub fn process(&mut self, mut wet: ArrayViewMut1<f32>, dry: ArrayView1<f32>, amount: f32) {
+        let (wet_band, dry_band) = self.high_bands(wet.view(), dry);
+
+        Zip::from(&mut wet).and(wet_band).and(dry_band)
+            .for_each(|out, wet_band, dry_band| {
+                *out += amount * (*dry_band - *wet_band);
+            });
+    }
+
+    /// Replaces `amount` of the low band in `wet` with the low band of `dry`. The low band is
+    /// whatever the high-pass leaves behind, so the two bands always sum back up exactly.
+    pub fn process_low(&mut self, mut wet: ArrayViewMut1<f32>, dry: ArrayView1<f32>, amount: f32) {
+        let (wet_band, dry_band) = self.high_bands(wet.view(), dry);
+
+        Zip::from(&mut wet).and(&dry).and(wet_band).and(dry_band)
+            .for_each(|out, dry, wet_band, dry_band| {
+                *out += amount * ((*dry - *dry_band) - (*out - *wet_band));
+            });
+    }
+
+    pub fn reset(&mut self) {
+        self.wet_filters.iter_mut().for_each(Biquad::reset);
+        self.dry_filters.iter_mut().for_each(Biquad::reset);
+    }
+}
+
+/// The fade in and fade out windows of the SOLA crossfade, `size` samples each.
+pub(crate) fn crossfade_windows(shape: CrossfadeWindow, size: usize) -> (Array1<f32>, Array1<f32>) {
+    let ramp = Array1::linspace(0.0, 1.0, size);
+    let fade_in = match shape {
+        CrossfadeWindow::SinSquared => ramp.mapv(|x: f32| f32::sin(x * 0.5 * std::f32::consts::PI).powi(2)),
+        CrossfadeWindow::EqualPower => ramp.mapv(|x: f32| f32::sin(x * 0.5 * std::f32::consts::PI)),
+        CrossfadeWindow::Linear => ramp,
+        CrossfadeWindow::Tukey => ramp.mapv(|x: f32| {
+            let x = ((x - 0.25) * 2.0).clamp(0.0, 1.0);
+            f32::sin(x * 0.5 * std::f32::consts::PI).powi(2)
+        }),
+    };
+    let fade_out = match shape {
+        // the powers rather than the amplitudes add up to one
+        CrossfadeWindow::EqualPower => fade_in.mapv(|x| (1.0 - x * x).max(0.0).sqrt()),
+        _ => fade_in.mapv(|x| 1.0 - x),
+    };
+    (fade_in, fade_out)
+}
+
+/// A linear fade over all of `samples`, in from silence or out to it.
+pub(crate) fn fade(samples: &mut [f32], fade_in: bool) {
+    let len = samples.len() as f32;
+    for (i, sample) in samples.iter_mut().enumerate() {
+        let gain = (i as f32 + 1.0) / (len + 1.0);
+        *sample *= if fade_in { gain } else { 1.0 - gain };
+    }
+}
+
+/// Resizes a buffer whose newest samples are at the end, keeping as many of them as fit.
+pub(crate) fn resize_keeping_tail(buffer: &mut Vec<f32>, size: usize) {
+    if size < buffer.len() {
+        buffer.drain(..buffer.len() - size);
+    } else {
+        buffer.splice(0..0, std::iter::repeat(0.0).take(size - buffer.len()));
+    }
+}
+
+/// Mixes `wet` towards `dry` in place, moving the wet gain from `gain` towards `target` by at
+/// most `step` per sample. Returns the gain reached at the end of the block.
+pub(crate) fn ramp_wet_dry(wet: ArrayViewMut1<f32>, dry: ArrayView1<f32>, gain: f32, target: f32, step: f32) -> f32 {
+    let mut gain = gain;
+    Zip::from(wet).and(&dry).for_each(|wet, dry| {
+        gain = if gain < target {
+            f32::min(gain + step, target)
+        } else {
+            f32::max(gain - step, target)
+        };
+        *wet = *dry + gain * (*wet - *dry);
+    });
+    gain
 }
 
 #[cfg(test)]
@@ -147,6 +521,157 @@ mod tests {
         assert_eq!(rms_values, expected_rms);
     }
 
+    #[test]
+    fn test_biquad_dc_response() {
+        let mut highpass = Biquad::highpass(48000, 1000.0, std::f64::consts::FRAC_1_SQRT_2);
+        let mut lowpass = Biquad::lowpass(48000, 1000.0, std::f64::consts::FRAC_1_SQRT_2);
+        let mut high = Array1::<f32>::ones(4800);
+        let mut low = Array1::<f32>::ones(4800);
+        highpass.process(high.view_mut());
+        lowpass.process(low.view_mut());
+        assert!(high[4799].abs() < 1e-4);
+        assert!((low[4799] - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_band_blender_full_swap_keeps_identical_streams() {
+        let signal = Array1::from_shape_fn(4800, |i| (i as f32 * 0.37).sin());
+        let mut blender = BandBlender::new(48000, 6000.0);
+        let mut wet = signal.clone();
+        blender.process(wet.view_mut(), signal.view(), 1.0);
+        assert!(wet.iter().zip(signal.iter()).all(|(a, b)| (a - b).abs() < 1e-5));
+    }
+
+    #[test]
+    fn test_band_blender_low_swap_takes_dry_bass() {
+        let wet = Array1::from_shape_fn(4800, |i| (i as f32 * 2.0).sin());
+        let dry = Array1::<f32>::ones(4800);
+        let mut blender = BandBlender::new(48000, 300.0);
+        let mut output = wet.clone();
+        blender.process_low(output.view_mut(), dry.view(), 1.0);
+        // the dc of the dry stream comes through, the high wet tone stays on top of it
+        // (up to the small phase shift the high-pass leaves on it)
+        assert!((output[4799] - (1.0 + wet[4799])).abs() < 5e-2);
+    }
+
+    #[test]
+    fn test_db_to_gain() {
+        assert_eq!(db_to_gain(0.0), 1.0);
+        assert!((db_to_gain(20.0) - 10.0).abs() < 1e-5);
+        assert!((db_to_gain(-6.0) - 0.501187).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_sola_offset_normalized() {
+        // a quiet copy of the tail outscores a loud block that only correlates by its level
+        let sola_buffer = Array1::from_shape_fn(32, |i| (std::f32::consts::PI * i as f32 / 31.0).sin());
+        let mut input = Array1::<f32>::zeros(96);
+        input.slice_mut(s![20..52]).assign(&(&sola_buffer * 0.1));
+        input.slice_mut(s![70..]).fill(1.0);
+        assert_eq!(get_sola_offset(input.as_slice().unwrap(), sola_buffer.as_slice().unwrap(), 32, 64), 20);
+
+        // and without a tail there is nothing to line up with
+        let silence = Array1::<f32>::zeros(32);
+        assert_eq!(get_sola_offset(input.as_slice().unwrap(), silence.as_slice().unwrap(), 32, 64), 32);
+    }
+
+    #[test]
+    fn test_crossfade_windows() {
+        for shape in [CrossfadeWindow::SinSquared, CrossfadeWindow::Linear, CrossfadeWindow::Tukey] {
+            let (fade_in, fade_out) = crossfade_windows(shape, 64);
+            assert!(fade_in[0].abs() < 1e-6 && (fade_in[63] - 1.0).abs() < 1e-6);
+            assert!(Zip::from(&fade_in).and(&fade_out).all(|a, b| (a + b - 1.0).abs() < 1e-6));
+        }
+
+        let (fade_in, fade_out) = crossfade_windows(CrossfadeWindow::EqualPower, 64);
+        assert!(Zip::from(&fade_in).and(&fade_out).all(|a, b| (a * a + b * b - 1.0).abs() < 1e-5));
+
+        let (fade_in, _) = crossfade_windows(CrossfadeWindow::Tukey, 65);
+        assert_eq!(fade_in[8], 0.0);
+        assert!((fade_in[32] - 0.5).abs() < 1e-6);
+        assert!((fade_in[56] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_ramp_wet_dry() {
+        let mut wet = Array1::<f32>::ones(8);
+        let dry = Array1::<f32>::zeros(8);
+        let gain = ramp_wet_dry(wet.view_mut(), dry.view(), 0.0, 1.0, 0.25);
+        assert_eq!(gain, 1.0);
+        assert_eq!(wet, Array1::from(vec![0.25, 0.5, 0.75, 1.0, 1.0, 1.0, 1.0, 1.0]));
+
+        let mut wet = Array1::<f32>::ones(2);
+        let gain = ramp_wet_dry(wet.view_mut(), dry.slice(s![..2]), 1.0, 0.0, 0.25);
+        assert_eq!(gain, 0.5);
+        assert_eq!(wet, Array1::from(vec![0.75, 0.5]));
+    }
+
+    #[test]
+    fn test_fade_and_resize() {
+        let mut samples = vec![1.0; 3];
+        fade(&mut samples, true);
+        assert_eq!(samples, vec![0.25, 0.5, 0.75]);
+        let mut samples = vec![1.0; 3];
+        fade(&mut samples, false);
+        assert_eq!(samples, vec![0.75, 0.5, 0.25]);
+
+        let mut buffer = vec![1.0, 2.0, 3.0];
+        resize_keeping_tail(&mut buffer, 5);
+        assert_eq!(buffer, vec![0.0, 0.0, 1.0, 2.0, 3.0]);
+        resize_keeping_tail(&mut buffer, 2);
+        assert_eq!(buffer, vec![2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_downmix_weights() {
+        assert_eq!(downmix_weights(DownmixMode::Average, 2, 1.0, 1.0), vec![0.5, 0.5]);
+        assert_eq!(downmix_weights(DownmixMode::Right, 4, 1.0, 1.0), vec![0.0, 1.0, 0.0, 0.0]);
+        assert_eq!(downmix_weights(DownmixMode::Custom, 2, 0.75, 0.25), vec![0.75, 0.25]);
+        assert_eq!(downmix_weights(DownmixMode::Custom, 2, 0.0, 0.0), vec![0.5, 0.5]);
+        // a mono input only has the one channel to take
+        assert_eq!(downmix_weights(DownmixMode::Right, 1, 1.0, 1.0), vec![1.0]);
+    }
+
+    #[test]
+    fn test_upmix_gain() {
+        assert_eq!(upmix_gain(UpmixMode::Duplicate, 6, 4, &[]), 1.0);
+        assert_eq!(upmix_gain(UpmixMode::CenterOnly, 6, 2, &[]), 1.0);
+        assert_eq!(upmix_gain(UpmixMode::CenterOnly, 6, 0, &[]), 0.0);
+        assert_eq!(upmix_gain(UpmixMode::CenterOnly, 2, 1, &[]), 1.0);
+        assert_eq!(upmix_gain(UpmixMode::Placement, 2, 1, &[2.0, 0.0]), 0.0);
+        assert_eq!(upmix_gain(UpmixMode::MidSide, 2, 1, &[]), 1.0);
+
+        // hard left input comes out hard left at its original level
+        let mut levels = [0.5, 0.0];
+        assert!(placement_gains(&mut levels, 0.25));
+        assert_eq!(levels, [2.0, 0.0]);
+        let mut levels = [0.0, 0.0];
+        assert!(!placement_gains(&mut levels, 0.0));
+        assert_eq!(levels, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_level_db() {
+        assert_eq!(level_db(&[]), f32::NEG_INFINITY);
+        assert!(level_db(&[0.0; 480]) < -100.0);
+        assert!((level_db(&[1.0, -1.0, 1.0, -1.0]) - 0.0).abs() < 1e-4);
+        assert!((level_db(&[0.5; 480]) - -6.0206).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_fit_tail_to_length() {
+        let input = Array1::from_shape_fn(10, |i| i as f32);
+        let longer = fit_tail_to_length(input.view(), 11, 6);
+        assert_eq!(longer.len(), 11);
+        assert_eq!(longer.slice(s![..5]), input.slice(s![..5]));
+        assert_eq!(longer[10], 9.0);
+
+        let shorter = fit_tail_to_length(input.view(), 9, 5);
+        assert_eq!(shorter.len(), 9);
+        assert_eq!(shorter.slice(s![..4]), input.slice(s![..4]));
+        assert_eq!(shorter[8], 9.0);
+    }
+
     #[test]
     fn test_linear_interpolate_align_corners() {
         let input = Array1::from(vec![0.2353, 0.9068, 0.7870, 0.5878, 0.0097, 0.7160, 0.5812, 0.8901, 0.8822, 0.8547]);
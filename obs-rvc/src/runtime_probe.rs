@@ -0,0 +1,98 @@
+use std::{path::PathBuf, process::Command, sync::OnceLock};
+
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+
+use log::warn;
+use rvc_common::enums::InferenceDevice;
+
+// keeps the subprocess from opening a console window
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// The execution providers that work, or why onnxruntime could not be loaded at all. Unset
+/// until the probe started at module load has finished.
+static PROBE_RESULT: OnceLock<Result<Vec<InferenceDevice>, String>> = OnceLock::new();
+
+/// Runs `rvc-rpc probe` in the background, so that filters can fall back to passing audio through
+/// instead of respawning an engine that can't start.
+pub(crate) fn start_probe(rpc_path: PathBuf) {
+    std::thread::spawn(move || {
+        let mut command = Command::new(&rpc_path);
+        command.arg("probe");
+        if let Some(working_dir) = rpc_path.parent() {
+            command.current_dir(working_dir);
+        }
+        #[cfg(windows)]
+        command.creation_flags(CREATE_NO_WINDOW);
+
+        let result = match command.output() {
+            Ok(output) => parse_probe_output(
+                output.status.success(),
+                &String::from_utf8_lossy(&output.stdout),
+                &String::from_utf8_lossy(&output.stderr),
+            ),
+            Err(e) => Err(format!("{}: {}", rpc_path.display(), e)),
+        };
+        if let Err(e) = &result {
+            warn!("onnxruntime is unavailable, filters will pass audio through: {}", e);
+        }
+        let _ = PROBE_RESULT.set(result);
+    });
+}
+
+pub(crate) fn probe_result() -> Option<&'static Result<Vec<InferenceDevice>, String>> {
+    PROBE_RESULT.get()
+}
+
+/// Whether the probe has found that onnxruntime can't be loaded.
+pub(crate) fn runtime_unavailable() -> bool {
+    matches!(probe_result(), Some(Err(_)))
+}
+
+fn parse_probe_output(success: bool, stdout: &str, stderr: &str) -> Result<Vec<InferenceDevice>, String> {
+    if !success {
+        // the error is the last line that says anything, apart from the backtrace note of a panic
+        let reason = stderr
+            .lines()
+            .rev()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with("note:"))
+            .unwrap_or("rvc-rpc exited early");
+        return Err(reason.trim().to_string());
+    }
+
+    Ok(stdout
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(InferenceDevice::from)
+        .filter(|device| *device != InferenceDevice::Auto)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_probe_output() {
+        assert_eq!(
+            parse_probe_output(true, "cuda\r\ndirectml\n\n", ""),
+            Ok(vec![InferenceDevice::Cuda, InferenceDevice::DirectMl])
+        );
+        assert_eq!(parse_probe_output(true, "", ""), Ok(vec![]));
+        assert_eq!(
+            parse_probe_output(false, "", "thread 'main' panicked at src/main.rs:14:13:\nError loading onnxruntime: missing dll\n"),
+            Err("Error loading onnxruntime: missing dll".to_string())
+        );
+        assert_eq!(
+            parse_probe_output(
+                false,
+                "",
+                "thread 'main' panicked at rvc-rpc\\src\\main.rs:20:13:\r\nError loading onnxruntime: LoadLibraryExW failed\r\nnote: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\r\n"
+            ),
+            Err("Error loading onnxruntime: LoadLibraryExW failed".to_string())
+        );
+    }
+}
@@ -1,20 +1,188 @@
-use std::{io::{BufReader, BufWriter}, os::windows::process::CommandExt, path::PathBuf, process::{Child, ChildStdin, ChildStdout}};
+use std::{collections::VecDeque, ffi::OsString, io::{BufRead, BufReader, BufWriter}, path::PathBuf, process::{Child, ChildStdin, ChildStdout}, sync::{atomic::{AtomicU32, Ordering}, Arc, Weak}, thread::JoinHandle, time::SystemTime};
 
-use rvc_common::{enums::{PitchAlgorithm, RvcModelVersion}, errors::RvcInferError};
+use rvc_common::{enums::{AutotuneScale, FeatureEncoder, InferenceDevice, PitchAlgorithm, RetrievalMetric, RvcModelVersion}, errors::RvcInferError, protocol::{FRAME_FAILED, LOAD_FAILED_MAGIC, MODEL_FLAG_F0, MODEL_FLAG_MULTI_SPEAKER, MODEL_FLAG_QUANTIZED, MODEL_FLAG_SPEAKER_MORPH, MODEL_FLAG_V2, MODEL_FLAG_VERSION_DETECTED, READY_MAGIC, STAGE_TIME_COUNT, STREAM_CLOSED, STREAM_PARKED, STREAM_WOKEN}};
 use std::process::{Command, Stdio};
 use std::io::{Read, Write};
-use ndarray::Array1;
+use log::{error, info};
+use parking_lot::{Condvar, Mutex, MutexGuard};
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
 
+use crate::advanced::AdvancedConfig;
+
+// keeps the subprocess from opening a console window
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+// running engines by the binary, arguments and model modification time they were launched with.
+// The model goes by its path, so a copy of it elsewhere starts an engine of its own.
+static ENGINES: Mutex<Vec<(EngineKey, Weak<Mutex<Engine>>)>> = Mutex::new(Vec::new());
+static NEXT_STREAM_ID: AtomicU32 = AtomicU32::new(0);
+
+type EngineKey = (Vec<OsString>, Option<SystemTime>);
+
+/// What the stages of a frame took in `rvc-rpc`.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct StageTimes {
+    pub encoder: std::time::Duration,
+    // along with the retrieval
+    pub pitch: std::time::Duration,
+    pub synthesis: std::time::Duration,
+}
+
+/// A filter's handle on an `rvc-rpc` subprocess. Filters launched with the same settings share
+/// one, so that a model used by several sources is only loaded once; the subprocess keeps the
+/// history of each stream apart. It converts one frame at a time, so the filters on it wait for
+/// each other's frames, and answers them in the order they were sent, which is how the replies
+/// find their stream.
 pub struct RvcInfer {
+    stream: Arc<Stream>,
+}
+
+/// A filter's stream on a subprocess, closed once neither the filter nor a reply it still waits
+/// for holds on to it.
+struct Stream {
+    engine: Arc<Mutex<Engine>>,
+    id: u32,
+}
+
+struct Engine {
     subprocess: Child,
     input: BufWriter<ChildStdin>,
-    output: BufReader<ChildStdout>,
+    // taken out while a reply is read without the lock, or while a handshake is awaited
+    output: Option<BufReader<ChildStdout>>,
+    // reported by the subprocess along with the handshake
+    model_info: Option<ModelInfo>,
+    // resolves to the stdout reader once the subprocess has finished loading its sessions
+    loading: Option<JoinHandle<Result<(BufReader<ChildStdout>, ModelInfo), RvcAdapterError>>>,
+    // why the subprocess could not load its sessions, after which it exits
+    load_error: Option<String>,
+    // what the subprocess has yet to answer, in the order it answers
+    outstanding: VecDeque<Outstanding>,
+    // replies read while waiting for another stream's, until their own stream picks them up
+    arrived: Vec<Reply>,
+    // signalled whenever a reply has been read, for the streams waiting on another's read
+    reply_read: Arc<Condvar>,
+    started: std::time::Instant,
+    // lost its pipes, so new filters start their own instead of sharing it
+    failed: bool,
+    // the samples of a request and of a reply as bytes, kept so that frames don't allocate; apart,
+    // as replies are read while the next request is written
+    bytes: Vec<u8>,
+    reply_bytes: Vec<u8>,
+    // sample buffers handed back by the streams, for the replies read on another's behalf
+    spare_samples: Vec<Vec<f32>>,
+}
+
+enum Outstanding {
+    Reply(u32),
+    // the reply to a stream that has closed since, read and thrown away
+    Dropped,
+    // sent after a wake-up; the replies behind it wait until the sessions are loaded again
+    Handshake,
+}
+
+struct Reply {
+    stream_id: u32,
+    samples: Vec<f32>,
+    result: Result<StageTimes, RvcAdapterError>,
+}
+
+/// Picks up the replies to the frames a filter sent with `RvcInfer::submit`, in the order they
+/// were sent. Keeps the subprocess and the stream alive like an `RvcInfer` does.
+pub struct EngineReplies {
+    stream: Arc<Stream>,
+}
+
+/// What an `rvc-rpc` is launched with.
+pub struct EngineConfig {
+    pub binary_path: PathBuf,
+    pub model_version: RvcModelVersion,
+    pub pitch_algorithm: PitchAlgorithm,
+    pub model_path: PathBuf,
+    pub data_path: PathBuf,
+    pub index_paths: Vec<PathBuf>,
+    pub index_metric: RetrievalMetric,
+    pub device: InferenceDevice,
+    pub device_priority: Vec<InferenceDevice>,
+    pub device_id: i64,
+    pub tensorrt: bool,
+    pub cuda_graph: bool,
+    pub fp16: bool,
+    pub int8: bool,
+    pub encoder: FeatureEncoder,
+    pub encoder_path: Option<PathBuf>,
+    pub contentvec_layers: Option<usize>,
+    // joins a running engine with the same settings rather than starting one; a benchmark runs
+    // on its own so that it neither slows the live filters down nor measures their frames
+    pub shared: bool,
+}
+
+/// The settings a frame is converted with, sent along with its samples.
+#[derive(Clone, Copy)]
+pub struct FrameRequest<'a> {
+    pub sample_frame_16k_size: usize,
+    pub pitch_shift: i32,
+    pub skip_head: u32,
+    pub return_length: u32,
+    pub index_rate: f32,
+    // one per index the engine was launched with
+    pub index_weights: &'a [f32],
+    pub f0_filter_radius: u32,
+    pub autotune: (AutotuneScale, i32, f32),
+    pub f0_range: (f32, f32),
+    pub voicing_sensitivity: f32,
+    pub feature_cache: bool,
+    pub speaker_id: u32,
+    pub speaker_morph: (u32, f32),
+}
+
+/// Shape of the frames the filter is going to send, which the subprocess warms its sessions up
+/// with before reporting ready.
+pub struct FrameShape {
+    pub input_len: usize,
+    pub sample_frame_16k_size: usize,
+    pub skip_head: u32,
+    pub return_length: u32,
+}
+
+struct ModelInfo {
+    flags: u32,
+    output_sample_rate: Option<usize>,
+    speaker_names: Vec<String>,
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_model_info(reader: &mut impl Read) -> std::io::Result<ModelInfo> {
+    let flags = read_u32(reader)?;
+    let output_sample_rate = match read_u32(reader)? {
+        0 => None,
+        sample_rate => Some(sample_rate as usize),
+    };
+    let mut speaker_names = Vec::new();
+    if flags & MODEL_FLAG_MULTI_SPEAKER != 0 {
+        for _ in 0..read_u32(reader)? {
+            let mut name = vec![0u8; read_u32(reader)? as usize];
+            reader.read_exact(&mut name)?;
+            speaker_names.push(String::from_utf8_lossy(&name).into_owned());
+        }
+    }
+    Ok(ModelInfo { flags, output_sample_rate, speaker_names })
 }
 
 #[derive(Debug)]
 pub enum RvcAdapterError {
     RvcInferError(RvcInferError),
     IoError(std::io::Error),
+    // the subprocess could not convert the frame, and says why
+    FrameFailed(String),
+    // the subprocess could not load its sessions
+    LoadFailed(String),
 }
 
 impl From<RvcInferError> for RvcAdapterError {
@@ -31,52 +199,428 @@ impl From<std::io::Error> for RvcAdapterError {
 
 
 impl RvcInfer {
-    pub fn new(binary_path: PathBuf, model_version: RvcModelVersion, pitch_algorithm: PitchAlgorithm, model_path: PathBuf, data_path: PathBuf) -> Self {
+    /// Fails only when the subprocess can't be started, its model failing to load is reported
+    /// by `load_error` later on.
+    pub fn new(config: EngineConfig, frame_shape: &FrameShape, advanced: &AdvancedConfig) -> std::io::Result<Self> {
+        let EngineConfig {
+            binary_path,
+            model_version,
+            pitch_algorithm,
+            model_path,
+            data_path,
+            index_paths,
+            index_metric,
+            device,
+            device_priority,
+            device_id,
+            tensorrt,
+            cuda_graph,
+            fp16,
+            int8,
+            encoder,
+            encoder_path,
+            contentvec_layers,
+            shared,
+        } = config;
+
+        let mut args: Vec<OsString> = Vec::new();
+        args.push(format!("--intra-threads={}", advanced.intra_threads).into());
+        args.push(format!("--execution-provider={}", device.to_string()).into());
+        args.push(format!("--device-id={}", device_id).into());
+        if tensorrt {
+            args.push("--tensorrt".into());
+        }
+        if cuda_graph {
+            args.push("--cuda-graph".into());
+        }
+        if fp16 {
+            args.push("--fp16".into());
+        }
+        if int8 {
+            args.push("--int8".into());
+        }
+        let device_priority: Vec<String> = device_priority.iter().map(InferenceDevice::to_string).collect();
+        args.push(format!("--execution-provider-priority={}", device_priority.join(",")).into());
+        args.push(format!("--index-top-k={}", advanced.index_top_k).into());
+        args.push(format!("--index-weight-exponent={}", advanced.index_weight_exponent).into());
+        args.push(format!("--index-metric={}", index_metric.to_string()).into());
+        args.push(format!("--encoder={}", encoder.to_string()).into());
+        if let Some(encoder_path) = encoder_path {
+            args.push(format!("--encoder-model={}", encoder_path.display()).into());
+        }
+        if let Some(contentvec_layers) = contentvec_layers {
+            args.push(format!("--contentvec-layers={}", contentvec_layers).into());
+        }
+        args.push(model_version.to_string().into());
+        args.push(pitch_algorithm.to_string().into());
+        // a model rewritten on disk has to be loaded again rather than shared
+        let model_modified = std::fs::metadata(&model_path).and_then(|metadata| metadata.modified()).ok();
+        args.push(model_path.into());
+        args.push(data_path.into());
+        args.extend(index_paths.into_iter().map(PathBuf::into_os_string));
+
+        let mut key_args = vec![binary_path.clone().into_os_string()];
+        key_args.extend(args.iter().cloned());
+        let key = (key_args, model_modified);
+
+        let engine = if shared {
+            let mut engines = ENGINES.lock();
+            engines.retain(|(_, engine)| engine.strong_count() > 0);
+            let running = engines
+                .iter()
+                .filter(|(engine_key, _)| *engine_key == key)
+                .filter_map(|(_, engine)| engine.upgrade())
+                .find(|engine| !engine.lock().failed);
+            match running {
+                Some(engine) => engine,
+                None => {
+                    let engine = Arc::new(Mutex::new(Engine::spawn(binary_path, args, frame_shape)?));
+                    engines.push((key, Arc::downgrade(&engine)));
+                    engine
+                }
+            }
+        } else {
+            Arc::new(Mutex::new(Engine::spawn(binary_path, args, frame_shape)?))
+        };
+
+        Ok(RvcInfer {
+            stream: Arc::new(Stream {
+                engine,
+                id: NEXT_STREAM_ID.fetch_add(1, Ordering::Relaxed),
+            }),
+        })
+    }
+
+    /// Whether the subprocess has finished loading, so that `infer` will not block on model startup.
+    pub fn is_ready(&self) -> bool {
+        self.stream.engine.lock().is_ready()
+    }
+
+    /// Why the subprocess could not load its model. It has exited then, and the same settings
+    /// would fail again.
+    pub fn load_error(&self) -> Option<String> {
+        let mut engine = self.stream.engine.lock();
+        engine.poll_loading();
+        engine.load_error.clone()
+    }
+
+    /// Whether the pipes to the subprocess broke, so that it has to be replaced.
+    pub fn has_failed(&self) -> bool {
+        self.stream.engine.lock().failed
+    }
+
+    /// How long the subprocess has been loading for, `None` once it is ready. TensorRT reports no
+    /// progress while it builds its engines, so this is all there is to show.
+    pub fn loading_time(&self) -> Option<std::time::Duration> {
+        let mut engine = self.stream.engine.lock();
+        (!engine.is_ready() && engine.load_error.is_none()).then(|| engine.started.elapsed())
+    }
+
+    /// Whether the model takes a pitch contour. Unknown until the first frame went through.
+    pub fn uses_f0(&self) -> Option<bool> {
+        self.stream.engine.lock().model_info.as_ref().map(|info| info.flags & MODEL_FLAG_F0 != 0)
+    }
+
+    /// The sample rate the model synthesizes at, `None` inside when the model doesn't state
+    /// it. Unknown until the first frame went through.
+    pub fn output_sample_rate(&self) -> Option<Option<usize>> {
+        self.stream.engine.lock().model_info.as_ref().map(|info| info.output_sample_rate)
+    }
+
+    /// The speaker names of a multi-speaker model, which may well be empty, and `None` for
+    /// single-speaker ones. Unknown until the first frame went through.
+    pub fn speakers(&self) -> Option<Option<Vec<String>>> {
+        self.stream.engine.lock().model_info.as_ref().map(|info| {
+            (info.flags & MODEL_FLAG_MULTI_SPEAKER != 0).then(|| info.speaker_names.clone())
+        })
+    }
+
+    /// Whether `speaker_names` are what `speakers` would return, without copying the names.
+    pub fn has_speakers(&self, speaker_names: Option<&[String]>) -> bool {
+        let engine = self.stream.engine.lock();
+        let speakers = engine
+            .model_info
+            .as_ref()
+            .filter(|info| info.flags & MODEL_FLAG_MULTI_SPEAKER != 0)
+            .map(|info| &info.speaker_names[..]);
+        speakers == speaker_names
+    }
+
+    /// Whether two speakers can be blended. Unknown until the first frame went through.
+    pub fn can_morph_speakers(&self) -> Option<bool> {
+        self.stream.engine.lock().model_info.as_ref().map(|info| info.flags & MODEL_FLAG_SPEAKER_MORPH != 0)
+    }
+
+    /// Whether an INT8 quantized model was loaded. Unknown until the first frame went through.
+    pub fn is_quantized(&self) -> Option<bool> {
+        self.stream.engine.lock().model_info.as_ref().map(|info| info.flags & MODEL_FLAG_QUANTIZED != 0)
+    }
+
+    /// The version the subprocess runs with and whether it was detected from the model rather
+    /// than taken from the settings. Unknown until the first frame went through.
+    pub fn model_version(&self) -> Option<(RvcModelVersion, bool)> {
+        self.stream.engine.lock().model_info.as_ref().map(|info| {
+            let version = if info.flags & MODEL_FLAG_V2 != 0 { RvcModelVersion::V2 } else { RvcModelVersion::V1 };
+            (version, info.flags & MODEL_FLAG_VERSION_DETECTED != 0)
+        })
+    }
+
+    pub fn infer(
+        &mut self,
+        input: ndarray::ArrayView1<f32>,
+        request: &FrameRequest,
+        output: &mut Vec<f32>,
+    ) -> Result<StageTimes, RvcAdapterError> {
+        self.submit(input, request)?;
+        receive(&self.stream.engine, self.stream.id, output)
+    }
+
+    /// Sends a frame like `infer` does, without waiting for the reply. Replies come back in
+    /// order through `replies`, so the next frame can already be on its way while the engine
+    /// converts this one.
+    pub fn submit(&mut self, input: ndarray::ArrayView1<f32>, request: &FrameRequest) -> Result<(), RvcAdapterError> {
+        let mut engine = self.stream.engine.lock();
+        let result = engine.write_request(self.stream.id, input, request);
+        if let Err(RvcAdapterError::IoError(_)) = &result {
+            engine.failed = true;
+        }
+        result
+    }
+
+    pub fn replies(&self) -> EngineReplies {
+        EngineReplies { stream: self.stream.clone() }
+    }
+
+    /// Lets the subprocess drop its sessions while the filter has nothing to convert. It keeps
+    /// running, and the sessions stay loaded for as long as another stream on it converts.
+    pub fn park(&mut self) -> Result<(), RvcAdapterError> {
+        let mut engine = self.stream.engine.lock();
+        let result = engine.write_control(self.stream.id, STREAM_PARKED);
+        if let Err(RvcAdapterError::IoError(_)) = &result {
+            engine.failed = true;
+        }
+        result
+    }
+
+    /// Has the subprocess load its sessions again after `park`. `is_ready` is false until they
+    /// are warm again.
+    pub fn wake(&mut self) -> Result<(), RvcAdapterError> {
+        let mut engine = self.stream.engine.lock();
+        let result = engine.write_control(self.stream.id, STREAM_WOKEN);
+        if result.is_ok() {
+            engine.outstanding.push_back(Outstanding::Handshake);
+            engine.start_handshake();
+        }
+        if let Err(RvcAdapterError::IoError(_)) = &result {
+            engine.failed = true;
+        }
+        result
+    }
+}
+
+impl EngineReplies {
+    /// Waits for the reply to the oldest frame of the stream still out. The engine is not
+    /// locked while waiting, so that the next frame can be submitted meanwhile.
+    pub fn receive(&self, output: &mut Vec<f32>) -> Result<StageTimes, RvcAdapterError> {
+        receive(&self.stream.engine, self.stream.id, output)
+    }
+
+    /// Whether these are the replies of the engine behind `infer`.
+    pub fn come_from(&self, infer: &RvcInfer) -> bool {
+        Arc::ptr_eq(&self.stream.engine, &infer.stream.engine)
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        // lets the subprocess forget this stream, unless it goes away with the last handle
+        if Arc::strong_count(&self.engine) > 1 {
+            let mut engine = self.engine.lock();
+            let stream_id = self.id;
+            for outstanding in engine.outstanding.iter_mut() {
+                if matches!(outstanding, Outstanding::Reply(id) if *id == stream_id) {
+                    *outstanding = Outstanding::Dropped;
+                }
+            }
+            while let Some(position) = engine.arrived.iter().position(|reply| reply.stream_id == stream_id) {
+                let reply = engine.arrived.swap_remove(position);
+                engine.spare_samples.push(reply.samples);
+            }
+            let input = &mut engine.input;
+            let _ = input
+                .write_all(&stream_id.to_le_bytes())
+                .and_then(|_| input.write_all(&STREAM_CLOSED.to_le_bytes()))
+                .and_then(|_| input.flush());
+        }
+    }
+}
+
+impl Engine {
+    fn spawn(binary_path: PathBuf, args: Vec<OsString>, frame_shape: &FrameShape) -> std::io::Result<Self> {
         let working_dir = binary_path.parent().unwrap().to_owned();
 
-        let mut subprocess = Command::new(binary_path)
-            .arg(model_version.to_string())
-            .arg(pitch_algorithm.to_string())
-            .arg(model_path)
-            .arg(data_path)
+        let mut command = Command::new(binary_path);
+        command.arg(format!(
+            "--warm-up={},{},{},{}",
+            frame_shape.input_len, frame_shape.sample_frame_16k_size, frame_shape.skip_head, frame_shape.return_length
+        ));
+
+        command
+            .args(args)
             .current_dir(working_dir)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
-            .creation_flags(0x08000000)
-            .spawn()
-            .expect("Failed to spawn child process");
+            .stderr(Stdio::piped());
+        #[cfg(windows)]
+        command.creation_flags(CREATE_NO_WINDOW);
+
+        let mut subprocess = command.spawn()?;
 
         let buffered_stdin = std::io::BufWriter::with_capacity(1024 * 1024, subprocess.stdin.take().unwrap());
         let buffered_stdout = std::io::BufReader::with_capacity(1024 * 1024, subprocess.stdout.take().unwrap());
 
-        RvcInfer {
+        // the subprocess reports on stderr, which OBS doesn't keep, so it goes into the log
+        let stderr = BufReader::new(subprocess.stderr.take().unwrap());
+        std::thread::spawn(move || {
+            for line in stderr.lines().map_while(Result::ok) {
+                if line.starts_with("Error") || line.contains("panicked") {
+                    error!("rvc-rpc: {}", line);
+                } else {
+                    info!("rvc-rpc: {}", line);
+                }
+            }
+        });
+
+        Ok(Engine {
             subprocess,
             input: buffered_stdin,
-            output: buffered_stdout,
+            output: None,
+            model_info: None,
+            loading: Some(read_handshake(buffered_stdout)),
+            load_error: None,
+            outstanding: VecDeque::new(),
+            arrived: Vec::new(),
+            reply_read: Arc::new(Condvar::new()),
+            started: std::time::Instant::now(),
+            failed: false,
+            bytes: Vec::new(),
+            reply_bytes: Vec::new(),
+            spare_samples: Vec::new(),
+        })
+    }
+
+    fn is_ready(&mut self) -> bool {
+        self.poll_loading();
+        self.loading.is_none()
+            && !self.outstanding.iter().any(|outstanding| matches!(outstanding, Outstanding::Handshake))
+            && self.load_error.is_none()
+    }
+
+    /// Picks up the outcome of the handshake once it is in, without waiting for it.
+    fn poll_loading(&mut self) {
+        if self.loading.as_ref().is_some_and(JoinHandle::is_finished) {
+            // a broken pipe shows again on the next frame, and a failed load is kept
+            if self.join_loading().is_ok() {
+                self.start_handshake();
+            }
         }
     }
 
-    pub fn infer(
+    /// Reads the handshake the subprocess sends once it has woken up in the background, like
+    /// the one at startup, as soon as the replies ahead of it are in and nobody is reading.
+    fn start_handshake(&mut self) {
+        if let Some(Outstanding::Handshake) = self.outstanding.front() {
+            if let Some(output) = self.output.take() {
+                self.outstanding.pop_front();
+                self.started = std::time::Instant::now();
+                self.loading = Some(read_handshake(output));
+            }
+        }
+    }
+
+    fn write_control(&mut self, stream_id: u32, request: u32) -> Result<(), RvcAdapterError> {
+        self.input.write_all(&stream_id.to_le_bytes())?;
+        self.input.write_all(&request.to_le_bytes())?;
+        self.input.flush()?;
+        Ok(())
+    }
+
+    /// Waits for the handshake being read, if there is one, and takes in what it says.
+    fn join_loading(&mut self) -> Result<(), RvcAdapterError> {
+        if let Some(loading) = self.loading.take() {
+            let loaded = loading
+                .join()
+                .map_err(|_| std::io::Error::other("Handshake thread panicked"))?;
+            match loaded {
+                Ok((output, model_info)) => {
+                    self.output = Some(output);
+                    self.model_info = Some(model_info);
+                }
+                Err(RvcAdapterError::LoadFailed(reason)) => {
+                    error!("rvc-rpc could not load the model: {}", reason);
+                    // not shared with filters started later, they launch one of their own
+                    self.failed = true;
+                    self.load_error = Some(reason);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        if let Some(reason) = &self.load_error {
+            return Err(RvcAdapterError::LoadFailed(reason.clone()));
+        }
+        Ok(())
+    }
+
+    /// Takes the output to read the next reply with, once the handshakes ahead of it are in.
+    /// `None` while another stream is reading.
+    fn take_output(&mut self) -> Result<Option<BufReader<ChildStdout>>, RvcAdapterError> {
+        loop {
+            self.join_loading()?;
+            self.start_handshake();
+            if self.loading.is_none() {
+                return Ok(self.output.take());
+            }
+        }
+    }
+
+    fn write_request(
         &mut self,
+        stream_id: u32,
         input: ndarray::ArrayView1<f32>,
-        sample_frame_16k_size: usize,
-        pitch_shift: i32,
-        skip_head: u32,
-        return_length: u32,
-    ) -> Result<ndarray::Array1<f32>, RvcAdapterError> {
+        request: &FrameRequest,
+    ) -> Result<(), RvcAdapterError> {
+        let FrameRequest {
+            sample_frame_16k_size,
+            pitch_shift,
+            skip_head,
+            return_length,
+            index_rate,
+            index_weights,
+            f0_filter_radius,
+            autotune,
+            f0_range,
+            voicing_sensitivity,
+            feature_cache,
+            speaker_id,
+            speaker_morph,
+        } = *request;
+
         // Convert input array to bytes
-        let input_bytes: Vec<u8> = input.iter().flat_map(|&x| x.to_le_bytes().to_vec()).collect();
+        self.bytes.clear();
+        for sample in input.iter() {
+            self.bytes.extend_from_slice(&sample.to_le_bytes());
+        }
 
         let sample_frame_16k_size = sample_frame_16k_size as u32;
-        let input_bytes_length = input_bytes.len() as u32;
+        let input_bytes_length = self.bytes.len() as u32;
 
        { 
             // Write input bytes to the subprocess stdin
             // let stdin = self.subprocess.stdin.as_mut().ok_or(std::io::Error::other("Failed to open stdin"))?;
             let stdin = &mut self.input;
+            stdin.write_all(&stream_id.to_le_bytes())?;
             stdin.write_all(&input_bytes_length.to_le_bytes())?;
-            stdin.write_all(&input_bytes)?;
+            stdin.write_all(&self.bytes)?;
 
             // Write sample_frame_16k_size to the subprocess stdin
             stdin.write_all(&sample_frame_16k_size.to_le_bytes())?;
@@ -90,37 +634,173 @@ impl RvcInfer {
             // Write return_length to the subprocess stdin
             stdin.write_all(&return_length.to_le_bytes())?;
 
+            // Write index_rate and one weight per index passed at startup
+            stdin.write_all(&index_rate.to_le_bytes())?;
+            stdin.write_all(&(index_weights.len() as u32).to_le_bytes())?;
+            for weight in index_weights {
+                stdin.write_all(&weight.to_le_bytes())?;
+            }
+
+            // Write the f0 median filter radius to the subprocess stdin
+            stdin.write_all(&f0_filter_radius.to_le_bytes())?;
+
+            // Write the autotune scale, key and strength to the subprocess stdin
+            let (autotune_scale, autotune_key, autotune_strength) = autotune;
+            stdin.write_all(&(i64::from(autotune_scale) as u32).to_le_bytes())?;
+            stdin.write_all(&autotune_key.to_le_bytes())?;
+            stdin.write_all(&autotune_strength.to_le_bytes())?;
+
+            // Write the f0 floor and ceiling to the subprocess stdin
+            stdin.write_all(&f0_range.0.to_le_bytes())?;
+            stdin.write_all(&f0_range.1.to_le_bytes())?;
+
+            // Write the voicing sensitivity to the subprocess stdin
+            stdin.write_all(&voicing_sensitivity.to_le_bytes())?;
+
+            // Write whether cached encoder features may be reused to the subprocess stdin
+            stdin.write_all(&(feature_cache as u32).to_le_bytes())?;
+
+            // Write the speaker id to the subprocess stdin
+            stdin.write_all(&speaker_id.to_le_bytes())?;
+
+            // Write the speaker to blend towards and by how much to the subprocess stdin
+            stdin.write_all(&speaker_morph.0.to_le_bytes())?;
+            stdin.write_all(&speaker_morph.1.to_le_bytes())?;
+
 
             // Flush the stdin buffer
             stdin.flush()?;
         }
+        self.outstanding.push_back(Outstanding::Reply(stream_id));
+        Ok(())
+    }
+}
 
-        // Read the result from the subprocess stdout
-        // let stdout = self.subprocess.stdout.as_mut().ok_or(std::io::Error::other("Failed to open stdout"))?;
-        let stdout = &mut self.output;
+/// Waits for the reply to the oldest frame `stream_id` has out. The replies of other streams
+/// ahead of it are read on the way and left for them, and the engine is only locked in between.
+fn receive(engine: &Mutex<Engine>, stream_id: u32, output: &mut Vec<f32>) -> Result<StageTimes, RvcAdapterError> {
+    let mut engine = engine.lock();
+    loop {
+        if let Some(position) = engine.arrived.iter().position(|reply| reply.stream_id == stream_id) {
+            let reply = engine.arrived.remove(position);
+            let samples = std::mem::replace(output, reply.samples);
+            engine.spare_samples.push(samples);
+            return reply.result;
+        }
+        if engine.failed {
+            return Err(match &engine.load_error {
+                Some(reason) => RvcAdapterError::LoadFailed(reason.clone()),
+                None => std::io::Error::other("Subprocess pipes are broken").into(),
+            });
+        }
+        if !engine.outstanding.iter().any(|outstanding| matches!(outstanding, Outstanding::Reply(id) if *id == stream_id)) {
+            return Err(std::io::Error::other("No frame of this stream is waiting for a reply").into());
+        }
 
-        let mut output_bytes_length = [0u8; 4];
-        stdout.read_exact(&mut output_bytes_length)?;
-        let output_bytes_length = u32::from_le_bytes(output_bytes_length) as usize;
+        let mut stdout = match engine.take_output() {
+            Ok(Some(stdout)) => stdout,
+            Ok(None) => {
+                // another stream is reading, possibly this one's reply
+                let reply_read = engine.reply_read.clone();
+                reply_read.wait(&mut engine);
+                continue;
+            }
+            Err(e) => {
+                engine.failed = true;
+                return Err(e);
+            }
+        };
+        let reply_to = match engine.outstanding.pop_front() {
+            Some(Outstanding::Reply(id)) => Some(id),
+            _ => None,
+        };
+        let mut samples = match reply_to {
+            Some(id) if id == stream_id => std::mem::take(output),
+            _ => engine.spare_samples.pop().unwrap_or_default(),
+        };
+        let mut bytes = std::mem::take(&mut engine.reply_bytes);
 
-        let mut output_bytes = vec![0u8; output_bytes_length];
-        stdout.read_exact(&mut output_bytes)?;
+        let result = MutexGuard::unlocked(&mut engine, || read_reply(&mut stdout, &mut bytes, &mut samples));
 
-        // Convert output bytes to array
-        let output_iter = output_bytes
-            .chunks_exact(std::mem::size_of::<f32>())
-            .map(|chunk| {
-                let mut bytes = [0u8; std::mem::size_of::<f32>()];
-                bytes.copy_from_slice(chunk);
-                f32::from_le_bytes(bytes)
-            });
-        let output_array = Array1::from_iter(output_iter);
-        Ok(output_array)
+        engine.output = Some(stdout);
+        engine.reply_bytes = bytes;
+        if let Err(RvcAdapterError::IoError(_)) = &result {
+            engine.failed = true;
+        }
+        engine.start_handshake();
+        engine.reply_read.notify_all();
+        match reply_to {
+            Some(id) if id == stream_id => {
+                *output = samples;
+                return result;
+            }
+            Some(id) => engine.arrived.push(Reply { stream_id: id, samples, result }),
+            None => engine.spare_samples.push(samples),
+        }
+    }
+}
+
+/// Waits for `READY_MAGIC` and the model description on a thread of its own, handing the reader
+/// back along with them.
+fn read_handshake(mut stdout: BufReader<ChildStdout>) -> JoinHandle<Result<(BufReader<ChildStdout>, ModelInfo), RvcAdapterError>> {
+    std::thread::spawn(move || {
+        match read_u32(&mut stdout)? {
+            READY_MAGIC => (),
+            LOAD_FAILED_MAGIC => {
+                let mut reason = vec![0u8; read_u32(&mut stdout)? as usize];
+                stdout.read_exact(&mut reason)?;
+                return Err(RvcAdapterError::LoadFailed(String::from_utf8_lossy(&reason).into_owned()));
+            }
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Unexpected handshake from subprocess",
+                )
+                .into());
+            }
+        }
+        let model_info = read_model_info(&mut stdout)?;
+        Ok((stdout, model_info))
+    })
+}
+
+/// Reads a reply of the subprocess from `stdout` into `output`, by way of `bytes`.
+fn read_reply(stdout: &mut impl Read, bytes: &mut Vec<u8>, output: &mut Vec<f32>) -> Result<StageTimes, RvcAdapterError> {
+    let mut output_bytes_length = [0u8; 4];
+    stdout.read_exact(&mut output_bytes_length)?;
+    let output_bytes_length = match u32::from_le_bytes(output_bytes_length) {
+        FRAME_FAILED => {
+            let mut reason = vec![0u8; read_u32(stdout)? as usize];
+            stdout.read_exact(&mut reason)?;
+            return Err(RvcAdapterError::FrameFailed(String::from_utf8_lossy(&reason).into_owned()));
+        }
+        length => length as usize,
+    };
+
+    bytes.resize(output_bytes_length, 0);
+    stdout.read_exact(bytes)?;
+
+    // Convert output bytes to samples
+    output.clear();
+    output.extend(bytes.chunks_exact(std::mem::size_of::<f32>()).map(|chunk| {
+        let mut bytes = [0u8; std::mem::size_of::<f32>()];
+        bytes.copy_from_slice(chunk);
+        f32::from_le_bytes(bytes)
+    }));
+
+    let mut stage_times = [std::time::Duration::ZERO; STAGE_TIME_COUNT];
+    for stage_time in stage_times.iter_mut() {
+        let mut micros = [0u8; 4];
+        stdout.read_exact(&mut micros)?;
+        *stage_time = std::time::Duration::from_micros(u32::from_le_bytes(micros) as u64);
     }
+    let [encoder, pitch, synthesis] = stage_times;
+    Ok(StageTimes { encoder, pitch, synthesis })
 }
 
-impl Drop for RvcInfer {
+impl Drop for Engine {
     fn drop(&mut self) {
-        self.subprocess.kill().expect("Failed to kill subprocess");
+        // it has exited on its own already when it failed to load
+        let _ = self.subprocess.kill();
     }
 }
@@ -11,7 +11,7 @@ mod tests {
     fn test_sola() {
         let input: Array1<f32> = read_npy("D:\\obs-rvc\\obs-rvc\\src\\tests\\infer_wav.npy").unwrap();
         let sola_buffer: Array1<f32> = read_npy("D:\\obs-rvc\\obs-rvc\\src\\tests\\sola_buffer.npy").unwrap();
-        let output = get_sola_offset(input.view(), sola_buffer.view(), 1920, 480).unwrap();
+        let output = get_sola_offset(input.as_slice().unwrap(), sola_buffer.as_slice().unwrap(), 1920, 480);
         assert_eq!(output, 321);
     }
 }
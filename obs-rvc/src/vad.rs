@@ -0,0 +1,79 @@
+// how long speech is assumed to go on after the level dropped, so that pauses between words and
+// quiet word endings are still converted
+const HANGOVER_TIME: f64 = 0.3;
+// never taken for speech below this, so that digital silence doesn't make every click speech
+const MIN_SPEECH_LEVEL: f32 = 0.000316; // -70 dBFS
+// how fast the noise floor follows a rising level, per second; falling levels are followed at once
+const NOISE_FLOOR_RISE: f32 = 1.9953; // 6 dB
+
+/// Tells speech from background by the level of each 10 ms against a running estimate of the
+/// noise floor. Cheap enough to run on every frame ahead of inference.
+pub(crate) struct VoiceActivityDetector {
+    chunk_size: usize,
+    margin: f32,
+    noise_floor_rise: f32,
+    hangover_samples: usize,
+    noise_floor: f32,
+    quiet_samples: usize,
+}
+
+impl VoiceActivityDetector {
+    /// `margin_db` is how far above the noise floor speech has to be.
+    pub fn new(sample_rate: usize, margin_db: f64) -> Self {
+        let chunk_size = usize::max(sample_rate / 100, 1);
+        let mut vad = Self {
+            chunk_size,
+            margin: 10f64.powf(margin_db / 20.0) as f32,
+            noise_floor_rise: NOISE_FLOOR_RISE.powf(chunk_size as f32 / sample_rate as f32),
+            hangover_samples: (HANGOVER_TIME * sample_rate as f64) as usize,
+            noise_floor: MIN_SPEECH_LEVEL,
+            quiet_samples: 0,
+        };
+        vad.reset();
+        vad
+    }
+
+    /// Whether the voice may be in these samples, counting the hangover.
+    pub fn process(&mut self, samples: &[f32]) -> bool {
+        for chunk in samples.chunks(self.chunk_size) {
+            let level = (chunk.iter().map(|sample| sample * sample).sum::<f32>() / chunk.len() as f32).sqrt();
+            if level > f32::max(self.noise_floor * self.margin, MIN_SPEECH_LEVEL) {
+                self.quiet_samples = 0;
+            } else {
+                self.quiet_samples = self.quiet_samples.saturating_add(chunk.len());
+            }
+            self.noise_floor = f32::min(level, self.noise_floor * self.noise_floor_rise).max(MIN_SPEECH_LEVEL / 10.0);
+        }
+        self.quiet_samples <= self.hangover_samples
+    }
+
+    pub fn reset(&mut self) {
+        self.noise_floor = MIN_SPEECH_LEVEL;
+        self.quiet_samples = self.hangover_samples + 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_voice_activity() {
+        let mut vad = VoiceActivityDetector::new(1000, 10.0);
+
+        // a steady hum becomes the noise floor within seconds
+        let hum: Vec<f32> = (0..1000).map(|i| if i % 2 == 0 { 0.01 } else { -0.01 }).collect();
+        for _ in 0..8 {
+            vad.process(&hum);
+        }
+        assert!(!vad.process(&hum));
+
+        // speech well above it is detected at once
+        let speech: Vec<f32> = hum.iter().map(|sample| sample * 20.0).take(100).collect();
+        assert!(vad.process(&speech));
+
+        // and held over the hangover
+        assert!(vad.process(&hum[..200]));
+        assert!(!vad.process(&hum[..200]));
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use rvc_common::enums::{PitchAlgorithm, RvcModelVersion};
+use serde::Deserialize;
+use zip::ZipArchive;
+
+pub(crate) const VOICE_BUNDLE_EXTENSION: &str = "rvcvoice";
+const METADATA_FILE: &str = "metadata.json";
+
+/// What `metadata.json` may say about the voice, every key is optional. `model` and `index`
+/// name the files to use when the bundle holds more than one of a kind.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct BundleMetadata {
+    model: Option<String>,
+    index: Option<String>,
+    pitch_shift: Option<i32>,
+    index_rate: Option<f32>,
+    sample_rate: Option<i32>,
+    pitch_algorithm: Option<String>,
+    model_version: Option<String>,
+}
+
+/// The defaults of the settings a voice decides. A bundle replaces them while it is loaded,
+/// settings the user changed keep their value.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct VoiceDefaults {
+    pub pitch_shift: i32,
+    pub index_rate: f32,
+    pub dest_sample_rate: i32,
+    pub pitch_algorithm: PitchAlgorithm,
+    pub model_version: RvcModelVersion,
+}
+
+impl Default for VoiceDefaults {
+    fn default() -> Self {
+        VoiceDefaults {
+            pitch_shift: 12,
+            index_rate: 0.0,
+            dest_sample_rate: 40000,
+            pitch_algorithm: PitchAlgorithm::Rmvpe,
+            model_version: RvcModelVersion::V2,
+        }
+    }
+}
+
+impl BundleMetadata {
+    fn parse(content: &str) -> Result<Self, String> {
+        serde_json::from_str(content).map_err(|e| format!("{}: {}", METADATA_FILE, e))
+    }
+
+    fn defaults(&self) -> VoiceDefaults {
+        let defaults = VoiceDefaults::default();
+        VoiceDefaults {
+            pitch_shift: self.pitch_shift.unwrap_or(defaults.pitch_shift),
+            index_rate: self.index_rate.map_or(defaults.index_rate, |rate| rate.clamp(0.0, 1.0)),
+            dest_sample_rate: self.sample_rate.unwrap_or(defaults.dest_sample_rate),
+            pitch_algorithm: self.pitch_algorithm.as_deref().map_or(defaults.pitch_algorithm, PitchAlgorithm::from),
+            model_version: self.model_version.as_deref().map_or(defaults.model_version, RvcModelVersion::from),
+        }
+    }
+}
+
+/// The model and index of a bundle, unpacked.
+pub(crate) struct ExtractedBundle {
+    pub model_path: PathBuf,
+    pub index_path: Option<PathBuf>,
+}
+
+pub(crate) fn is_voice_bundle(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(VOICE_BUNDLE_EXTENSION) || ext.eq_ignore_ascii_case("zip"))
+}
+
+fn open_archive(path: &Path) -> Result<ZipArchive<File>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    ZipArchive::new(file).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn read_metadata(archive: &mut ZipArchive<File>) -> Result<BundleMetadata, String> {
+    let mut content = String::new();
+    match archive.by_name(METADATA_FILE) {
+        Ok(mut file) => file.read_to_string(&mut content).map_err(|e| format!("{}: {}", METADATA_FILE, e))?,
+        // a bundle without metadata is just a zipped model
+        Err(zip::result::ZipError::FileNotFound) => return Ok(BundleMetadata::default()),
+        Err(e) => return Err(format!("{}: {}", METADATA_FILE, e)),
+    };
+    BundleMetadata::parse(&content)
+}
+
+/// Only reads `metadata.json`, cheap enough for every settings update.
+pub(crate) fn read_voice_defaults(path: &Path) -> Result<VoiceDefaults, String> {
+    Ok(read_metadata(&mut open_archive(path)?)?.defaults())
+}
+
+/// Names the cache folders of the bundle at `path`, `<stem>-<hash of the full path>`, so that
+/// bundles of the same name in different folders are kept apart.
+fn cache_key(path: &Path) -> String {
+    let full_path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    // FNV-1a, which unlike the std hashers is the same in every build
+    let hash = full_path
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("voice");
+    format!("{}-{:016x}", stem, hash)
+}
+
+/// Unpacks the bundle below `cache_dir`, once per version of the file: a bundle replaced on
+/// disk gets a new folder, and the folders of its older versions are removed.
+pub(crate) fn extract_bundle(path: &Path, cache_dir: &Path) -> Result<ExtractedBundle, String> {
+    let error = |e: std::io::Error| format!("{}: {}", path.display(), e);
+    let file_metadata = std::fs::metadata(path).map_err(error)?;
+    let modified = file_metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_secs());
+    let key = cache_key(path);
+    let folder_name = format!("{}-{:x}-{:x}", key, modified, file_metadata.len());
+    let folder = cache_dir.join(&folder_name);
+
+    let mut archive = open_archive(path)?;
+    let metadata = read_metadata(&mut archive)?;
+
+    if !folder.exists() {
+        remove_old_versions(cache_dir, &key);
+        // unpacked aside first, so that an interrupted extraction is never taken for a complete one
+        let part_folder = cache_dir.join(format!("{}.part", folder_name));
+        let _ = std::fs::remove_dir_all(&part_folder);
+        std::fs::create_dir_all(&part_folder).map_err(error)?;
+        archive.extract(&part_folder).map_err(|e| format!("{}: {}", path.display(), e))?;
+        std::fs::rename(&part_folder, &folder).map_err(error)?;
+    }
+
+    let files: Vec<String> = archive.file_names().map(str::to_string).collect();
+    let find = |named: &Option<String>, extension: &str| -> Option<PathBuf> {
+        let name = match named {
+            Some(name) => files.iter().find(|file| *file == name),
+            None => {
+                let mut candidates: Vec<&String> = files
+                    .iter()
+                    .filter(|file| {
+                        let file = file.to_lowercase();
+                        file.ends_with(extension) && !file.ends_with(".int8.onnx")
+                    })
+                    .collect();
+                candidates.sort();
+                candidates.first().copied()
+            }
+        }?;
+        Some(folder.join(name))
+    };
+
+    let model_path = find(&metadata.model, ".onnx")
+        .ok_or_else(|| format!("{}: no .onnx model in the bundle", path.display()))?;
+    let index_path = find(&metadata.index, ".index");
+    Ok(ExtractedBundle { model_path, index_path })
+}
+
+fn remove_old_versions(cache_dir: &Path, key: &str) {
+    let Ok(entries) = std::fs::read_dir(cache_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if is_version_of(&name.to_string_lossy(), key) {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+/// Whether the folder `name` is `<key>-<modified>-<length>`, matched from the right as the
+/// stem in the key may contain dashes.
+fn is_version_of(name: &str, key: &str) -> bool {
+    let mut parts = name.rsplitn(3, '-');
+    matches!((parts.next(), parts.next(), parts.next()), (Some(_), Some(_), Some(old_key)) if old_key == key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_defaults() {
+        let metadata = BundleMetadata::parse(
+            r#"{"name": "voice", "pitch_shift": -3, "index_rate": 1.5, "pitch_algorithm": "fcpe"}"#,
+        )
+        .unwrap();
+        let defaults = metadata.defaults();
+        assert_eq!(defaults.pitch_shift, -3);
+        assert_eq!(defaults.index_rate, 1.0);
+        assert_eq!(defaults.pitch_algorithm, PitchAlgorithm::Fcpe);
+        assert_eq!(defaults.dest_sample_rate, 40000);
+        assert_eq!(defaults.model_version, RvcModelVersion::V2);
+
+        assert_eq!(BundleMetadata::parse("{}").unwrap().defaults(), VoiceDefaults::default());
+        assert!(BundleMetadata::parse(r#"{"pitch_shift": "high"}"#).is_err());
+
+        // the same file name in two folders
+        let key = cache_key(Path::new("streams/alto/voice.zip"));
+        assert_ne!(key, cache_key(Path::new("streams/bass/voice.zip")));
+        assert!(key.starts_with("voice-"));
+        assert!(is_version_of(&format!("{}-6700a1b2-1f00", key), &key));
+        assert!(!is_version_of(&format!("{}-6700a1b2-1f00", cache_key(Path::new("streams/bass/voice.zip"))), &key));
+        assert!(!is_version_of("voice-6700a1b2-1f00", &key));
+    }
+}
@@ -0,0 +1,421 @@
+use std::{
+    ffi::{c_void, CStr, CString},
+    mem::size_of,
+    path::PathBuf,
+    ptr::null_mut,
+    sync::{atomic::Ordering, Arc, OnceLock, Weak},
+};
+
+use log::{info, warn};
+use obs_wrapper::{
+    obs_string,
+    obs_sys::{
+        bfree, calldata_get_data, calldata_set_data, calldata_t, obs_data_apply, obs_data_create_from_json,
+        obs_data_get_double, obs_data_get_int, obs_data_get_json, obs_data_get_string, obs_data_release, obs_data_t,
+        obs_filter_get_parent, obs_get_proc_handler, obs_source_get_name, obs_source_get_settings, proc_handler_call,
+        proc_handler_t,
+    },
+    string::ObsString,
+};
+use parking_lot::Mutex;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    metrics::FilterMetrics,
+    presets::{apply_preset, load_presets, update_settings, SourceHandle, SETTING_PRESET},
+};
+
+pub(crate) const VENDOR_NAME: &str = "obs-rvc";
+
+// the filters that can be controlled, added on create and taken out on destroy
+static FILTERS: Mutex<Vec<(SourceHandle, Weak<dyn RemoteControl>)>> = Mutex::new(Vec::new());
+static CONFIG_PATH: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// What a request may do to a filter besides changing its settings.
+pub(crate) trait RemoteControl: Send + Sync {
+    fn bypassed(&self) -> bool;
+    fn set_bypassed(&self, bypassed: bool);
+    fn metrics(&self) -> &FilterMetrics;
+}
+
+pub(crate) fn register_filter(source: SourceHandle, control: Arc<dyn RemoteControl>) {
+    let mut filters = FILTERS.lock();
+    filters.retain(|(_, control)| control.strong_count() > 0);
+    filters.push((source, Arc::downgrade(&control)));
+}
+
+/// Takes the filter out before its source goes away, waiting for a request that uses it.
+pub(crate) fn unregister_filter(source: SourceHandle) {
+    FILTERS.lock().retain(|(handle, _)| handle.0 != source.0);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Request {
+    GetFilters,
+    GetPitchShift,
+    SetPitchShift,
+    GetIndexRate,
+    SetIndexRate,
+    GetBypass,
+    SetBypass,
+    GetPreset,
+    SetPreset,
+    GetStats,
+}
+
+const REQUESTS: [(&str, Request); 10] = [
+    ("GetFilters", Request::GetFilters),
+    ("GetPitchShift", Request::GetPitchShift),
+    ("SetPitchShift", Request::SetPitchShift),
+    ("GetIndexRate", Request::GetIndexRate),
+    ("SetIndexRate", Request::SetIndexRate),
+    ("GetBypass", Request::GetBypass),
+    ("SetBypass", Request::SetBypass),
+    ("GetPreset", Request::GetPreset),
+    ("SetPreset", Request::SetPreset),
+    ("GetStats", Request::GetStats),
+];
+
+/// Parameters and results of a procedure call, freed along with it.
+struct CallData(calldata_t);
+
+impl CallData {
+    fn new() -> Self {
+        CallData(unsafe { std::mem::zeroed() })
+    }
+
+    fn set_string(&mut self, name: ObsString, value: &CStr) {
+        let value = value.to_bytes_with_nul();
+        unsafe { calldata_set_data(&mut self.0, name.as_ptr(), value.as_ptr() as *const c_void, value.len()) };
+    }
+
+    fn set_ptr(&mut self, name: ObsString, value: *mut c_void) {
+        unsafe {
+            calldata_set_data(
+                &mut self.0,
+                name.as_ptr(),
+                &value as *const *mut c_void as *const c_void,
+                size_of::<*mut c_void>(),
+            )
+        };
+    }
+
+    fn ptr(&self, name: ObsString) -> *mut c_void {
+        let mut value: *mut c_void = null_mut();
+        unsafe {
+            calldata_get_data(
+                &self.0,
+                name.as_ptr(),
+                &mut value as *mut *mut c_void as *mut c_void,
+                size_of::<*mut c_void>(),
+            )
+        };
+        value
+    }
+
+    fn bool(&self, name: ObsString) -> bool {
+        let mut value = false;
+        unsafe { calldata_get_data(&self.0, name.as_ptr(), &mut value as *mut bool as *mut c_void, size_of::<bool>()) };
+        value
+    }
+
+    fn call(&mut self, handler: *mut proc_handler_t, name: ObsString) -> bool {
+        unsafe { proc_handler_call(handler, name.as_ptr(), &mut self.0) }
+    }
+}
+
+impl Drop for CallData {
+    fn drop(&mut self) {
+        if !self.0.fixed {
+            unsafe { bfree(self.0.stack as *mut c_void) };
+        }
+    }
+}
+
+// laid out like obs_websocket_request_callback, which obs-websocket copies on registration
+#[repr(C)]
+struct RequestCallback {
+    callback: unsafe extern "C" fn(*mut obs_data_t, *mut obs_data_t, *mut c_void),
+    priv_data: *mut c_void,
+}
+
+/// Registers the vendor requests with obs-websocket, which has to be loaded by then, so this
+/// runs once every module is. Without obs-websocket this does nothing.
+pub(crate) fn register_vendor(config_path: Option<PathBuf>) {
+    let _ = CONFIG_PATH.set(config_path);
+
+    let mut call = CallData::new();
+    let global = unsafe { obs_get_proc_handler() };
+    if global.is_null() || !call.call(global, obs_string!("obs_websocket_api_get_ph")) {
+        info!("obs-websocket is not loaded, remote control is off");
+        return;
+    }
+    let handler = call.ptr(obs_string!("ph")) as *mut proc_handler_t;
+    if handler.is_null() {
+        info!("obs-websocket is not loaded, remote control is off");
+        return;
+    }
+
+    let mut call = CallData::new();
+    call.set_string(obs_string!("name"), &CString::new(VENDOR_NAME).unwrap());
+    call.call(handler, obs_string!("vendor_register"));
+    let vendor = call.ptr(obs_string!("vendor"));
+    if vendor.is_null() {
+        warn!("obs-websocket refused the {} vendor", VENDOR_NAME);
+        return;
+    }
+
+    for (index, (name, _)) in REQUESTS.iter().enumerate() {
+        let mut callback = RequestCallback { callback: request_callback, priv_data: index as *mut c_void };
+        let mut call = CallData::new();
+        call.set_ptr(obs_string!("vendor"), vendor);
+        call.set_string(obs_string!("type"), &CString::new(*name).unwrap());
+        call.set_ptr(obs_string!("callback"), &mut callback as *mut RequestCallback as *mut c_void);
+        call.call(handler, obs_string!("vendor_request_register"));
+        if !call.bool(obs_string!("success")) {
+            warn!("obs-websocket refused the {} request", name);
+        }
+    }
+    info!("Registered the {} obs-websocket vendor", VENDOR_NAME);
+}
+
+unsafe extern "C" fn request_callback(request_data: *mut obs_data_t, response_data: *mut obs_data_t, priv_data: *mut c_void) {
+    let request = REQUESTS[priv_data as usize].1;
+    let data = json_object(request_data);
+    let response = match handle(request, &data) {
+        Ok(mut response) => {
+            response.insert("success".to_string(), Value::Bool(true));
+            response
+        }
+        Err(error) => Map::from_iter([
+            ("success".to_string(), Value::Bool(false)),
+            ("error".to_string(), Value::String(error)),
+        ]),
+    };
+
+    let Ok(json) = CString::new(Value::Object(response).to_string()) else {
+        return;
+    };
+    let response = obs_data_create_from_json(json.as_ptr());
+    if !response.is_null() {
+        obs_data_apply(response_data, response);
+        obs_data_release(response);
+    }
+}
+
+unsafe fn json_object(data: *mut obs_data_t) -> Map<String, Value> {
+    if data.is_null() {
+        return Map::new();
+    }
+    let json = obs_data_get_json(data);
+    if json.is_null() {
+        return Map::new();
+    }
+    serde_json::from_slice(CStr::from_ptr(json).to_bytes()).unwrap_or_default()
+}
+
+/// A filter the request picked, with the names it goes by.
+struct Target {
+    source: SourceHandle,
+    control: Arc<dyn RemoteControl>,
+    source_name: String,
+    filter_name: String,
+}
+
+impl Target {
+    fn names(&self) -> Map<String, Value> {
+        Map::from_iter([
+            ("sourceName".to_string(), Value::String(self.source_name.clone())),
+            ("filterName".to_string(), Value::String(self.filter_name.clone())),
+        ])
+    }
+
+    unsafe fn settings<T>(&self, get: impl FnOnce(*mut obs_data_t) -> T) -> T {
+        let settings = obs_source_get_settings(self.source.0);
+        let value = get(settings);
+        obs_data_release(settings);
+        value
+    }
+
+    fn pitch_shift(&self) -> i64 {
+        unsafe { self.settings(|settings| obs_data_get_int(settings, obs_string!("pitch_shift").as_ptr())) }
+    }
+
+    fn index_rate(&self) -> f64 {
+        unsafe { self.settings(|settings| obs_data_get_double(settings, obs_string!("index_rate").as_ptr())) }
+    }
+
+    fn preset(&self) -> Option<String> {
+        let preset = unsafe {
+            self.settings(|settings| name(obs_data_get_string(settings, SETTING_PRESET.as_ptr())))
+        };
+        Some(preset).filter(|preset| !preset.is_empty())
+    }
+
+    fn stats(&self) -> Value {
+        let metrics = self.control.metrics();
+        json!({
+            "framesProcessed": metrics.frames_processed.load(Ordering::Relaxed),
+            "lastInferenceSeconds": metrics.last_inference_time_us.load(Ordering::Relaxed) as f64 / 1e6,
+            "realtimeFactor": metrics.realtime_factor(),
+            "latencySeconds": metrics.latency().as_secs_f64(),
+            "discardedBlocks": metrics.discarded_blocks.load(Ordering::Relaxed),
+            "droppedBlocks": metrics.dropped_blocks.load(Ordering::Relaxed),
+            "inputQueueDepth": metrics.input_queue_depth.load(Ordering::Relaxed),
+            "outputQueueDepth": metrics.output_queue_depth.load(Ordering::Relaxed),
+        })
+    }
+}
+
+unsafe fn name(name: *const std::ffi::c_char) -> String {
+    if name.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(name).to_string_lossy().into_owned()
+}
+
+/// Whether a filter goes by the `sourceName` and `filterName` of the request; either may be
+/// left out to pick filters by the other alone, or both to pick every filter.
+fn matches(data: &Map<String, Value>, source_name: &str, filter_name: &str) -> Result<bool, String> {
+    let mut matches = true;
+    for (key, name) in [("sourceName", source_name), ("filterName", filter_name)] {
+        match data.get(key) {
+            None | Some(Value::Null) => {}
+            Some(Value::String(wanted)) => matches &= wanted == name,
+            Some(_) => return Err(format!("{} is not a string", key)),
+        }
+    }
+    Ok(matches)
+}
+
+/// The number `key` holds, which has to be within `min` and `max`.
+fn number(data: &Map<String, Value>, key: &str, min: f64, max: f64) -> Result<f64, String> {
+    let value = data
+        .get(key)
+        .and_then(Value::as_f64)
+        .ok_or_else(|| format!("{} is missing or not a number", key))?;
+    if !(min..=max).contains(&value) {
+        return Err(format!("{} is outside {} to {}", key, min, max));
+    }
+    Ok(value)
+}
+
+fn targets(filters: &[(SourceHandle, Weak<dyn RemoteControl>)], data: &Map<String, Value>) -> Result<Vec<Target>, String> {
+    let mut targets = Vec::new();
+    for (source, control) in filters {
+        let Some(control) = control.upgrade() else {
+            continue;
+        };
+        let (source_name, filter_name) = unsafe {
+            let parent = obs_filter_get_parent(source.0);
+            let source_name = if parent.is_null() { String::new() } else { name(obs_source_get_name(parent)) };
+            (source_name, name(obs_source_get_name(source.0)))
+        };
+        if matches(data, &source_name, &filter_name)? {
+            targets.push(Target { source: *source, control, source_name, filter_name });
+        }
+    }
+    Ok(targets)
+}
+
+/// Answers a request for the filters it picks. Getters answer for the first of them, setters
+/// change them all.
+fn handle(request: Request, data: &Map<String, Value>) -> Result<Map<String, Value>, String> {
+    // held throughout, so that a filter can't be destroyed while the request uses it
+    let filters = FILTERS.lock();
+    let targets = targets(&filters, data)?;
+    if request == Request::GetFilters {
+        let filters = targets
+            .iter()
+            .map(|target| {
+                let mut filter = target.names();
+                filter.insert("pitchShift".to_string(), json!(target.pitch_shift()));
+                filter.insert("indexRate".to_string(), json!(target.index_rate()));
+                filter.insert("bypassed".to_string(), json!(target.control.bypassed()));
+                filter.insert("preset".to_string(), json!(target.preset()));
+                Value::Object(filter)
+            })
+            .collect();
+        return Ok(Map::from_iter([("filters".to_string(), Value::Array(filters))]));
+    }
+    let Some(first) = targets.first() else {
+        return Err("No voice conversion filter matches".to_string());
+    };
+
+    let mut response = first.names();
+    let mut respond = |key: &str, value: Value| {
+        response.insert(key.to_string(), value);
+    };
+    match request {
+        Request::GetFilters => unreachable!(),
+        Request::GetPitchShift => respond("pitchShift", json!(first.pitch_shift())),
+        Request::SetPitchShift => {
+            let pitch_shift = number(data, "pitchShift", -24.0, 24.0)?.round() as i64;
+            for target in &targets {
+                update_settings(target.source, Map::from_iter([("pitch_shift".to_string(), json!(pitch_shift))]));
+            }
+            respond("pitchShift", json!(pitch_shift));
+        }
+        Request::GetIndexRate => respond("indexRate", json!(first.index_rate())),
+        Request::SetIndexRate => {
+            let index_rate = number(data, "indexRate", 0.0, 1.0)?;
+            for target in &targets {
+                update_settings(target.source, Map::from_iter([("index_rate".to_string(), json!(index_rate))]));
+            }
+            respond("indexRate", json!(index_rate));
+        }
+        Request::GetBypass => respond("bypassed", json!(first.control.bypassed())),
+        Request::SetBypass => {
+            let bypassed = data.get("bypassed").and_then(Value::as_bool).ok_or("bypassed is missing or not a boolean")?;
+            for target in &targets {
+                target.control.set_bypassed(bypassed);
+            }
+            respond("bypassed", json!(bypassed));
+        }
+        Request::GetPreset => {
+            let presets = match CONFIG_PATH.get() {
+                Some(Some(config_path)) => load_presets(config_path),
+                _ => Vec::new(),
+            };
+            respond("preset", json!(first.preset()));
+            respond("presets", presets.into_iter().map(|preset| Value::String(preset.name)).collect());
+        }
+        Request::SetPreset => {
+            let name = data.get("preset").and_then(Value::as_str).ok_or("preset is missing or not a string")?;
+            let Some(Some(config_path)) = CONFIG_PATH.get() else {
+                return Err("Presets are unavailable".to_string());
+            };
+            let presets = load_presets(config_path);
+            let preset = presets
+                .iter()
+                .find(|preset| preset.name == name)
+                .ok_or_else(|| format!("No preset is named {}", name))?;
+            for target in &targets {
+                apply_preset(target.source, preset);
+            }
+            respond("preset", json!(name));
+        }
+        Request::GetStats => respond("stats", first.stats()),
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_parameters() {
+        let data: Map<String, Value> =
+            serde_json::from_str(r#"{"sourceName": "Mic", "pitchShift": 30, "indexRate": 0.5, "filterName": 3}"#).unwrap();
+        assert_eq!(number(&data, "indexRate", 0.0, 1.0), Ok(0.5));
+        assert!(number(&data, "pitchShift", -24.0, 24.0).is_err());
+        assert!(number(&data, "semitones", -24.0, 24.0).is_err());
+        assert!(matches(&data, "Mic", "RVC").is_err());
+
+        let data: Map<String, Value> = serde_json::from_str(r#"{"sourceName": "Mic"}"#).unwrap();
+        assert_eq!(matches(&data, "Mic", "RVC"), Ok(true));
+        assert_eq!(matches(&data, "Desktop", "RVC"), Ok(false));
+        assert_eq!(matches(&Map::new(), "Desktop", "RVC"), Ok(true));
+    }
+}
@@ -0,0 +1,49 @@
+use log::warn;
+use thread_priority::{set_current_thread_priority, ThreadPriority, ThreadPriorityValue};
+
+// tried when the highest priority is refused, still above everything at the normal level
+const FALLBACK_PRIORITY: u8 = 75;
+
+/// Raises the priority of the calling thread, so that the scheduler doesn't starve the worker
+/// while a game takes every core. Returns whether any raise got through; the thread keeps
+/// running at its normal priority otherwise.
+pub(crate) fn raise_current_thread_priority() -> bool {
+    let error = match set_current_thread_priority(ThreadPriority::Max) {
+        Ok(()) => return true,
+        Err(e) => e,
+    };
+    let fallback = ThreadPriorityValue::try_from(FALLBACK_PRIORITY).map(ThreadPriority::Crossplatform);
+    match fallback.map(set_current_thread_priority) {
+        Ok(Ok(())) => {
+            warn!("Highest worker priority refused ({:?}), raised it part of the way", error);
+            true
+        }
+        _ => {
+            warn!("Could not raise the worker priority: {:?}", error);
+            false
+        }
+    }
+}
+
+/// Pins the calling thread to the logical core with the index `core`. Returns false if there is
+/// no such core or the system refused.
+pub(crate) fn pin_current_thread(core: usize) -> bool {
+    let core_id = core_affinity::get_core_ids().and_then(|ids| ids.into_iter().find(|id| id.id == core));
+    match core_id {
+        Some(core_id) => core_affinity::set_for_current(core_id),
+        None => {
+            warn!("Cannot pin the worker to core {}, there is no such core", core);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin_to_missing_core() {
+        assert!(!pin_current_thread(usize::MAX));
+    }
+}
@@ -20,11 +20,152 @@ impl RvcModelVersion {
             RvcModelVersion::V2 => 12,
         }
     }
+
+    /// The version whose ContentVec features have `channels` dimensions.
+    pub fn from_text_encoder_in_channels(channels: usize) -> Option<Self> {
+        match channels {
+            256 => Some(RvcModelVersion::V1),
+            768 => Some(RvcModelVersion::V2),
+            _ => None,
+        }
+    }
 }
 
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub enum PitchAlgorithm {
     Rmvpe,
+    CrepeTiny,
+    CrepeFull,
+    Fcpe,
+    Dio,
+    Harvest,
+    /// RMVPE and FCPE fused frame by frame.
+    Hybrid,
+}
+
+/// Speech encoder whose features the voice model was trained on.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum FeatureEncoder {
+    ContentVec,
+    HubertSoft,
+    Whisper,
+}
+
+/// How retrieval compares features, `Auto` follows the metric the index was built with.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum RetrievalMetric {
+    Auto,
+    L2,
+    /// Inner product of normalized features, for indices built with `METRIC_INNER_PRODUCT`.
+    Cosine,
+}
+
+/// onnxruntime execution provider sessions run on, `Auto` takes the first available one in
+/// order of priority and falls back to the CPU.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum InferenceDevice {
+    Auto,
+    Cuda,
+    DirectMl,
+    Cpu,
+    CoreMl,
+    Rocm,
+    /// Intel iGPUs and NPUs.
+    OpenVino,
+    /// Falls back to CUDA for what TensorRT cannot run, only in builds with the `tensorrt` feature.
+    TensorRt,
+}
+
+/// The execution providers `InferenceDevice::Auto` chains unless told otherwise. Discrete GPUs
+/// come first; OpenVINO goes before DirectML as it runs Intel GPUs faster and can use their NPUs.
+/// TensorRT is left out, building its engines takes minutes on first load.
+pub const DEFAULT_DEVICE_PRIORITY: [InferenceDevice; 5] = [
+    InferenceDevice::Cuda,
+    InferenceDevice::Rocm,
+    InferenceDevice::OpenVino,
+    InferenceDevice::DirectMl,
+    InferenceDevice::CoreMl,
+];
+
+/// Scale the autotune stage snaps the pitch to, `Off` disables it.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum AutotuneScale {
+    Off,
+    Chromatic,
+    Major,
+    Minor,
+}
+
+impl AutotuneScale {
+    /// Semitones above the key that belong to the scale.
+    pub fn degrees(&self) -> &'static [i32] {
+        match self {
+            AutotuneScale::Off => &[],
+            AutotuneScale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            AutotuneScale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            AutotuneScale::Minor => &[0, 2, 3, 5, 7, 8, 10],
+        }
+    }
+}
+
+/// Which input channels are mixed into the mono signal the model converts.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum DownmixMode {
+    Average,
+    Left,
+    Right,
+    /// Left and right by the weights in the settings, the remaining channels left out.
+    Custom,
+}
+
+/// What the filter outputs when the worker has nothing ready for a block.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum UnderrunFallback {
+    /// The block is dropped, which mutes it.
+    Discard,
+    /// The unconverted input goes out instead.
+    Dry,
+    /// The last converted block again, quieter each time.
+    RepeatLast,
+}
+
+/// How the converted mono signal is written back to the filter's channels.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum UpmixMode {
+    Duplicate,
+    CenterOnly,
+    Placement,
+    /// The converted voice as the mid of the front pair, with the original side put back on top.
+    MidSide,
+}
+
+/// Which side of the band split keeps the unconverted voice.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum BandSplitMode {
+    Off,
+    DryLow,
+    DryHigh,
+}
+
+/// How the input is brought to 16 kHz and the model output to the device rate.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ResamplerType {
+    Fft,
+    SincFast,
+    SincBalanced,
+    SincBest,
+}
+
+/// Shape of the SOLA crossfade between consecutive model outputs.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum CrossfadeWindow {
+    /// sin² rise, the raised cosine half of a Hann window.
+    SinSquared,
+    /// sin rise against a cos fall, constant power for splices that don't correlate.
+    EqualPower,
+    Linear,
+    /// Raised cosine over the middle half of the fade only.
+    Tukey,
 }
 
 
@@ -92,10 +233,188 @@ impl RvcModelVersion {
 }
 
 
+impl From<FeatureEncoder> for i64 {
+    fn from(encoder: FeatureEncoder) -> Self {
+        match encoder {
+            FeatureEncoder::ContentVec => 1,
+            FeatureEncoder::HubertSoft => 2,
+            FeatureEncoder::Whisper => 3,
+        }
+    }
+}
+
+impl From<i64> for FeatureEncoder {
+    fn from(val: i64) -> Self {
+        match val {
+            2 => FeatureEncoder::HubertSoft,
+            3 => FeatureEncoder::Whisper,
+            _ => FeatureEncoder::ContentVec,
+        }
+    }
+}
+
+impl From<&str> for FeatureEncoder {
+    fn from(val: &str) -> Self {
+        match val {
+            "hubert-soft" => FeatureEncoder::HubertSoft,
+            "whisper" => FeatureEncoder::Whisper,
+            _ => FeatureEncoder::ContentVec,
+        }
+    }
+}
+
+impl ToString for FeatureEncoder {
+    fn to_string(&self) -> String {
+        match self {
+            FeatureEncoder::ContentVec => "contentvec".to_string(),
+            FeatureEncoder::HubertSoft => "hubert-soft".to_string(),
+            FeatureEncoder::Whisper => "whisper".to_string(),
+        }
+    }
+}
+
+impl FeatureEncoder {
+    pub(crate) fn is_valid(val: i64) -> bool {
+        match val {
+            1..=3 => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<RetrievalMetric> for i64 {
+    fn from(metric: RetrievalMetric) -> Self {
+        match metric {
+            RetrievalMetric::Auto => 0,
+            RetrievalMetric::L2 => 1,
+            RetrievalMetric::Cosine => 2,
+        }
+    }
+}
+
+impl From<i64> for RetrievalMetric {
+    fn from(val: i64) -> Self {
+        match val {
+            1 => RetrievalMetric::L2,
+            2 => RetrievalMetric::Cosine,
+            _ => RetrievalMetric::Auto,
+        }
+    }
+}
+
+impl From<&str> for RetrievalMetric {
+    fn from(val: &str) -> Self {
+        match val {
+            "l2" => RetrievalMetric::L2,
+            "cosine" => RetrievalMetric::Cosine,
+            _ => RetrievalMetric::Auto,
+        }
+    }
+}
+
+impl ToString for RetrievalMetric {
+    fn to_string(&self) -> String {
+        match self {
+            RetrievalMetric::Auto => "auto".to_string(),
+            RetrievalMetric::L2 => "l2".to_string(),
+            RetrievalMetric::Cosine => "cosine".to_string(),
+        }
+    }
+}
+
+impl RetrievalMetric {
+    pub(crate) fn is_valid(val: i64) -> bool {
+        match val {
+            0..=2 => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<InferenceDevice> for i64 {
+    fn from(device: InferenceDevice) -> Self {
+        match device {
+            InferenceDevice::Auto => 0,
+            InferenceDevice::Cuda => 1,
+            InferenceDevice::DirectMl => 2,
+            InferenceDevice::Cpu => 3,
+            InferenceDevice::CoreMl => 4,
+            InferenceDevice::Rocm => 5,
+            InferenceDevice::OpenVino => 6,
+            InferenceDevice::TensorRt => 7,
+        }
+    }
+}
+
+impl From<i64> for InferenceDevice {
+    fn from(val: i64) -> Self {
+        match val {
+            1 => InferenceDevice::Cuda,
+            2 => InferenceDevice::DirectMl,
+            3 => InferenceDevice::Cpu,
+            4 => InferenceDevice::CoreMl,
+            5 => InferenceDevice::Rocm,
+            6 => InferenceDevice::OpenVino,
+            7 => InferenceDevice::TensorRt,
+            _ => InferenceDevice::Auto,
+        }
+    }
+}
+
+impl From<&str> for InferenceDevice {
+    fn from(val: &str) -> Self {
+        match val {
+            "cuda" => InferenceDevice::Cuda,
+            "directml" => InferenceDevice::DirectMl,
+            "cpu" => InferenceDevice::Cpu,
+            "coreml" => InferenceDevice::CoreMl,
+            "rocm" => InferenceDevice::Rocm,
+            "openvino" => InferenceDevice::OpenVino,
+            "tensorrt" => InferenceDevice::TensorRt,
+            _ => InferenceDevice::Auto,
+        }
+    }
+}
+
+impl ToString for InferenceDevice {
+    fn to_string(&self) -> String {
+        match self {
+            InferenceDevice::Auto => "auto".to_string(),
+            InferenceDevice::Cuda => "cuda".to_string(),
+            InferenceDevice::DirectMl => "directml".to_string(),
+            InferenceDevice::Cpu => "cpu".to_string(),
+            InferenceDevice::CoreMl => "coreml".to_string(),
+            InferenceDevice::Rocm => "rocm".to_string(),
+            InferenceDevice::OpenVino => "openvino".to_string(),
+            InferenceDevice::TensorRt => "tensorrt".to_string(),
+        }
+    }
+}
+
+impl InferenceDevice {
+    pub(crate) fn is_valid(val: i64) -> bool {
+        match val {
+            0..=7 => true,
+            _ => false,
+        }
+    }
+
+    /// Whether sessions of this device may run on CUDA.
+    pub fn allows_cuda(&self) -> bool {
+        matches!(self, InferenceDevice::Auto | InferenceDevice::Cuda | InferenceDevice::TensorRt)
+    }
+}
+
 impl From<PitchAlgorithm> for i64 {
     fn from(algorithm: PitchAlgorithm) -> Self {
         match algorithm {
             PitchAlgorithm::Rmvpe => 1,
+            PitchAlgorithm::CrepeTiny => 2,
+            PitchAlgorithm::CrepeFull => 3,
+            PitchAlgorithm::Fcpe => 4,
+            PitchAlgorithm::Dio => 5,
+            PitchAlgorithm::Harvest => 6,
+            PitchAlgorithm::Hybrid => 7,
         }
     }
 }
@@ -104,6 +423,12 @@ impl From<i64> for PitchAlgorithm {
     fn from(val: i64) -> Self {
         match val {
             1 => PitchAlgorithm::Rmvpe,
+            2 => PitchAlgorithm::CrepeTiny,
+            3 => PitchAlgorithm::CrepeFull,
+            4 => PitchAlgorithm::Fcpe,
+            5 => PitchAlgorithm::Dio,
+            6 => PitchAlgorithm::Harvest,
+            7 => PitchAlgorithm::Hybrid,
             _ => PitchAlgorithm::Rmvpe,
         }
     }
@@ -113,6 +438,12 @@ impl From<PitchAlgorithm> for String {
     fn from(algorithm: PitchAlgorithm) -> Self {
         match algorithm {
             PitchAlgorithm::Rmvpe => "rmvpe".to_string(),
+            PitchAlgorithm::CrepeTiny => "crepe-tiny".to_string(),
+            PitchAlgorithm::CrepeFull => "crepe-full".to_string(),
+            PitchAlgorithm::Fcpe => "fcpe".to_string(),
+            PitchAlgorithm::Dio => "dio".to_string(),
+            PitchAlgorithm::Harvest => "harvest".to_string(),
+            PitchAlgorithm::Hybrid => "hybrid".to_string(),
         }
     }
 }
@@ -127,6 +458,12 @@ impl From<&str> for PitchAlgorithm {
     fn from(val: &str) -> Self {
         match val {
             "rmvpe" => PitchAlgorithm::Rmvpe,
+            "crepe-tiny" => PitchAlgorithm::CrepeTiny,
+            "crepe-full" => PitchAlgorithm::CrepeFull,
+            "fcpe" => PitchAlgorithm::Fcpe,
+            "dio" => PitchAlgorithm::Dio,
+            "harvest" => PitchAlgorithm::Harvest,
+            "hybrid" => PitchAlgorithm::Hybrid,
             _ => PitchAlgorithm::Rmvpe,
         }
     }
@@ -136,14 +473,260 @@ impl ToString for PitchAlgorithm {
     fn to_string(&self) -> String {
         match self {
             PitchAlgorithm::Rmvpe => "rmvpe".to_string(),
+            PitchAlgorithm::CrepeTiny => "crepe-tiny".to_string(),
+            PitchAlgorithm::CrepeFull => "crepe-full".to_string(),
+            PitchAlgorithm::Fcpe => "fcpe".to_string(),
+            PitchAlgorithm::Dio => "dio".to_string(),
+            PitchAlgorithm::Harvest => "harvest".to_string(),
+            PitchAlgorithm::Hybrid => "hybrid".to_string(),
         }
     }
 }
 
 impl PitchAlgorithm {
+    pub const ALL: [PitchAlgorithm; 7] = [
+        PitchAlgorithm::Rmvpe,
+        PitchAlgorithm::CrepeTiny,
+        PitchAlgorithm::CrepeFull,
+        PitchAlgorithm::Fcpe,
+        PitchAlgorithm::Dio,
+        PitchAlgorithm::Harvest,
+        PitchAlgorithm::Hybrid,
+    ];
+
+    /// The ONNX models in the f0 data folder, empty for the CPU estimators that need none.
+    pub fn model_file_names(&self) -> &'static [&'static str] {
+        match self {
+            PitchAlgorithm::Rmvpe => &["rmvpe.onnx"],
+            PitchAlgorithm::CrepeTiny => &["crepe-tiny.onnx"],
+            PitchAlgorithm::CrepeFull => &["crepe-full.onnx"],
+            PitchAlgorithm::Fcpe => &["fcpe.onnx"],
+            PitchAlgorithm::Dio | PitchAlgorithm::Harvest => &[],
+            PitchAlgorithm::Hybrid => &["rmvpe.onnx", "fcpe.onnx"],
+        }
+    }
+
+    pub(crate) fn is_valid(val: i64) -> bool {
+        match val {
+            1..=7 => true,
+            _ => false,
+        }
+    }
+}
+
+
+impl From<BandSplitMode> for i64 {
+    fn from(mode: BandSplitMode) -> Self {
+        match mode {
+            BandSplitMode::Off => 0,
+            BandSplitMode::DryLow => 1,
+            BandSplitMode::DryHigh => 2,
+        }
+    }
+}
+
+impl From<i64> for BandSplitMode {
+    fn from(val: i64) -> Self {
+        match val {
+            1 => BandSplitMode::DryLow,
+            2 => BandSplitMode::DryHigh,
+            _ => BandSplitMode::Off,
+        }
+    }
+}
+
+impl BandSplitMode {
+    pub(crate) fn is_valid(val: i64) -> bool {
+        match val {
+            0 | 1 | 2 => true,
+            _ => false,
+        }
+    }
+}
+
+
+impl From<ResamplerType> for i64 {
+    fn from(kind: ResamplerType) -> Self {
+        match kind {
+            ResamplerType::Fft => 0,
+            ResamplerType::SincFast => 1,
+            ResamplerType::SincBalanced => 2,
+            ResamplerType::SincBest => 3,
+        }
+    }
+}
+
+impl From<i64> for ResamplerType {
+    fn from(val: i64) -> Self {
+        match val {
+            1 => ResamplerType::SincFast,
+            2 => ResamplerType::SincBalanced,
+            3 => ResamplerType::SincBest,
+            _ => ResamplerType::Fft,
+        }
+    }
+}
+
+impl ResamplerType {
+    pub(crate) fn is_valid(val: i64) -> bool {
+        match val {
+            0 | 1 | 2 | 3 => true,
+            _ => false,
+        }
+    }
+}
+
+
+impl From<CrossfadeWindow> for i64 {
+    fn from(window: CrossfadeWindow) -> Self {
+        match window {
+            CrossfadeWindow::SinSquared => 0,
+            CrossfadeWindow::EqualPower => 1,
+            CrossfadeWindow::Linear => 2,
+            CrossfadeWindow::Tukey => 3,
+        }
+    }
+}
+
+impl From<i64> for CrossfadeWindow {
+    fn from(val: i64) -> Self {
+        match val {
+            1 => CrossfadeWindow::EqualPower,
+            2 => CrossfadeWindow::Linear,
+            3 => CrossfadeWindow::Tukey,
+            _ => CrossfadeWindow::SinSquared,
+        }
+    }
+}
+
+impl CrossfadeWindow {
+    pub(crate) fn is_valid(val: i64) -> bool {
+        match val {
+            0 | 1 | 2 | 3 => true,
+            _ => false,
+        }
+    }
+}
+
+
+impl From<DownmixMode> for i64 {
+    fn from(mode: DownmixMode) -> Self {
+        match mode {
+            DownmixMode::Average => 0,
+            DownmixMode::Left => 1,
+            DownmixMode::Right => 2,
+            DownmixMode::Custom => 3,
+        }
+    }
+}
+
+impl From<i64> for DownmixMode {
+    fn from(val: i64) -> Self {
+        match val {
+            1 => DownmixMode::Left,
+            2 => DownmixMode::Right,
+            3 => DownmixMode::Custom,
+            _ => DownmixMode::Average,
+        }
+    }
+}
+
+impl DownmixMode {
+    pub(crate) fn is_valid(val: i64) -> bool {
+        match val {
+            0..=3 => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<UnderrunFallback> for i64 {
+    fn from(fallback: UnderrunFallback) -> Self {
+        match fallback {
+            UnderrunFallback::Discard => 0,
+            UnderrunFallback::Dry => 1,
+            UnderrunFallback::RepeatLast => 2,
+        }
+    }
+}
+
+impl From<i64> for UnderrunFallback {
+    fn from(val: i64) -> Self {
+        match val {
+            1 => UnderrunFallback::Dry,
+            2 => UnderrunFallback::RepeatLast,
+            _ => UnderrunFallback::Discard,
+        }
+    }
+}
+
+impl UnderrunFallback {
+    pub(crate) fn is_valid(val: i64) -> bool {
+        match val {
+            0..=2 => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<UpmixMode> for i64 {
+    fn from(mode: UpmixMode) -> Self {
+        match mode {
+            UpmixMode::Duplicate => 0,
+            UpmixMode::CenterOnly => 1,
+            UpmixMode::Placement => 2,
+            UpmixMode::MidSide => 3,
+        }
+    }
+}
+
+impl From<i64> for UpmixMode {
+    fn from(val: i64) -> Self {
+        match val {
+            1 => UpmixMode::CenterOnly,
+            2 => UpmixMode::Placement,
+            3 => UpmixMode::MidSide,
+            _ => UpmixMode::Duplicate,
+        }
+    }
+}
+
+impl UpmixMode {
+    pub(crate) fn is_valid(val: i64) -> bool {
+        match val {
+            0 | 1 | 2 | 3 => true,
+            _ => false,
+        }
+    }
+}
+
+
+impl From<AutotuneScale> for i64 {
+    fn from(scale: AutotuneScale) -> Self {
+        match scale {
+            AutotuneScale::Off => 0,
+            AutotuneScale::Chromatic => 1,
+            AutotuneScale::Major => 2,
+            AutotuneScale::Minor => 3,
+        }
+    }
+}
+
+impl From<i64> for AutotuneScale {
+    fn from(val: i64) -> Self {
+        match val {
+            1 => AutotuneScale::Chromatic,
+            2 => AutotuneScale::Major,
+            3 => AutotuneScale::Minor,
+            _ => AutotuneScale::Off,
+        }
+    }
+}
+
+impl AutotuneScale {
     pub(crate) fn is_valid(val: i64) -> bool {
         match val {
-            1 => true,
+            0..=3 => true,
             _ => false,
         }
     }
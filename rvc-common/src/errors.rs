@@ -2,7 +2,13 @@
 pub enum RvcInferError {
     ModelNotLoaded,
     ContentvecNotLoaded,
+    Encoder(String),
     F0NotLoaded,
+    Index(String),
+    Speaker(String),
+    Model(String),
+    // the synthesizer put out NaN or infinite samples
+    NonFiniteOutput,
     Ort(ort::Error),
     NdarrayShapeError(ndarray::ShapeError),
 }
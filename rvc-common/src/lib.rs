@@ -1,5 +1,6 @@
 pub mod enums;
 pub mod errors;
+pub mod protocol;
 
 #[cfg(feature="obs_props")]
 pub mod obs_props_ext;
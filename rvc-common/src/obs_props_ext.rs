@@ -1,6 +1,8 @@
-use obs_wrapper::{data::FromDataItem, obs_sys::{obs_property_list_add_int, obs_property_list_insert_int, obs_property_t, size_t}, properties::{ComboFormat, ListType}, string::ObsString};
+use std::os::raw::c_void;
 
-use crate::enums::{PitchAlgorithm, RvcModelVersion};
+use obs_wrapper::{data::FromDataItem, obs_sys::{obs_properties_add_button2, obs_properties_add_text, obs_properties_t, obs_property_list_add_int, obs_property_list_insert_int, obs_property_t, obs_property_text_set_info_type, obs_text_info_type, obs_text_info_type_OBS_TEXT_INFO_ERROR, obs_text_info_type_OBS_TEXT_INFO_NORMAL, obs_text_info_type_OBS_TEXT_INFO_WARNING, obs_text_type_OBS_TEXT_INFO, size_t}, properties::{ComboFormat, ListType, ObsProp}, string::ObsString};
+
+use crate::enums::{AutotuneScale, BandSplitMode, CrossfadeWindow, DownmixMode, FeatureEncoder, InferenceDevice, PitchAlgorithm, ResamplerType, RetrievalMetric, RvcModelVersion, UnderrunFallback, UpmixMode};
 
 macro_rules! enum_to_int_list_type {
     ($t:ty) => {
@@ -48,3 +50,64 @@ macro_rules! enum_to_int_list_type {
 
 enum_to_int_list_type!(RvcModelVersion);
 enum_to_int_list_type!(PitchAlgorithm);
+enum_to_int_list_type!(FeatureEncoder);
+enum_to_int_list_type!(RetrievalMetric);
+enum_to_int_list_type!(InferenceDevice);
+enum_to_int_list_type!(BandSplitMode);
+enum_to_int_list_type!(DownmixMode);
+enum_to_int_list_type!(UpmixMode);
+enum_to_int_list_type!(UnderrunFallback);
+enum_to_int_list_type!(CrossfadeWindow);
+enum_to_int_list_type!(ResamplerType);
+enum_to_int_list_type!(AutotuneScale);
+
+#[derive(Clone, Copy, Debug)]
+pub enum TextInfoType {
+    Normal,
+    Warning,
+    Error,
+}
+
+/// A read-only text line in the properties view. The description is what gets displayed.
+pub struct TextInfoProp {
+    info_type: TextInfoType,
+}
+
+impl TextInfoProp {
+    pub fn new(info_type: TextInfoType) -> Self {
+        Self { info_type }
+    }
+}
+
+impl ObsProp for TextInfoProp {
+    unsafe fn add_to_props(self, p: *mut obs_properties_t, name: ObsString, description: ObsString) {
+        let info_type: obs_text_info_type = match self.info_type {
+            TextInfoType::Normal => obs_text_info_type_OBS_TEXT_INFO_NORMAL,
+            TextInfoType::Warning => obs_text_info_type_OBS_TEXT_INFO_WARNING,
+            TextInfoType::Error => obs_text_info_type_OBS_TEXT_INFO_ERROR,
+        };
+        let prop = obs_properties_add_text(p, name.as_ptr(), description.as_ptr(), obs_text_type_OBS_TEXT_INFO);
+        obs_property_text_set_info_type(prop, info_type);
+    }
+}
+
+pub type ButtonCallback = unsafe extern "C" fn(*mut obs_properties_t, *mut obs_property_t, *mut c_void) -> bool;
+
+/// A button that calls `callback` with `data` when clicked. `data` has to stay valid for as
+/// long as the properties are shown. The description is the text on the button.
+pub struct ButtonProp {
+    callback: ButtonCallback,
+    data: *mut c_void,
+}
+
+impl ButtonProp {
+    pub fn new(callback: ButtonCallback, data: *mut c_void) -> Self {
+        Self { callback, data }
+    }
+}
+
+impl ObsProp for ButtonProp {
+    unsafe fn add_to_props(self, p: *mut obs_properties_t, name: ObsString, description: ObsString) {
+        obs_properties_add_button2(p, name.as_ptr(), description.as_ptr(), Some(self.callback), self.data);
+    }
+}
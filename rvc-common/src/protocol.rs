@@ -0,0 +1,47 @@
+/// Written by `rvc-rpc` to its stdout once every session has been loaded,
+/// before the first inference response.
+pub const READY_MAGIC: u32 = 0x52564331;
+
+/// Written in place of `READY_MAGIC` when the sessions could not be loaded, followed by the u32
+/// byte length and the UTF-8 bytes of the reason. `rvc-rpc` exits after it.
+pub const LOAD_FAILED_MAGIC: u32 = 0x52564345;
+
+/// Every request starts with the u32 id of the stream it belongs to, as filters with the same
+/// settings share one `rvc-rpc`. Sent in place of the input length, this drops the stream and
+/// gets no response.
+pub const STREAM_CLOSED: u32 = u32::MAX;
+
+/// Sent in place of the input length by a filter that has had nothing to convert for a while.
+/// Once every stream is parked, `rvc-rpc` drops its sessions along with the device memory they
+/// hold, but keeps running. Gets no response.
+pub const STREAM_PARKED: u32 = u32::MAX - 1;
+
+/// Sent in place of the input length by a parked filter that has voice to convert again. The
+/// response is the one `rvc-rpc` gives at startup, `READY_MAGIC` and the model description, once
+/// the sessions are loaded and warm again.
+pub const STREAM_WOKEN: u32 = u32::MAX - 2;
+
+/// An inference response is the u32 byte length of the samples and the f32 samples, followed by
+/// this many u32 microseconds the stages of the frame took: encoding, pitch extraction along with
+/// retrieval, and synthesis. Encoding runs alongside the pitch extraction, so the first two
+/// can overlap.
+pub const STAGE_TIME_COUNT: usize = 3;
+
+/// Sent in place of the byte length of a response when the frame could not be converted. It is
+/// followed by the u32 byte length and the UTF-8 bytes of the reason, and no stage times.
+pub const FRAME_FAILED: u32 = u32::MAX;
+
+/// Bits of the u32 that follows `READY_MAGIC`, describing the loaded model. The flags are
+/// followed by the model's u32 output sample rate, 0 when the model doesn't state it.
+pub const MODEL_FLAG_F0: u32 = 1 << 0;
+/// The model takes 768-dim (v2) features, 256-dim (v1) otherwise.
+pub const MODEL_FLAG_V2: u32 = 1 << 1;
+/// The version came from the model's input shapes rather than the filter settings.
+pub const MODEL_FLAG_VERSION_DETECTED: u32 = 1 << 2;
+/// The model takes a speaker id. The sample rate is followed by a u32 count of speaker names, each
+/// a u32 byte length and UTF-8 bytes; the list is empty when the model doesn't name them.
+pub const MODEL_FLAG_MULTI_SPEAKER: u32 = 1 << 3;
+/// The model takes a speaker embedding and its table was found, so speakers can be blended.
+pub const MODEL_FLAG_SPEAKER_MORPH: u32 = 1 << 4;
+/// The synthesizer or the encoder that was loaded is INT8 quantized.
+pub const MODEL_FLAG_QUANTIZED: u32 = 1 << 5;
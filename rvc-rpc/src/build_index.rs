@@ -0,0 +1,135 @@
+use std::path::{Path, PathBuf};
+
+use ndarray::{concatenate, Array2, ArrayView1, ArrayView2, Axis};
+use rubato::{FftFixedIn, Resampler};
+use rvc::{build_index, load_feature_dump, RvcInfer, SessionConfig};
+use rvc_common::enums::{FeatureEncoder, RvcModelVersion};
+
+pub const USAGE: &str = "rvc-rpc [--execution-provider=<name>] [--encoder=<name>] [--encoder-model=<path>] [--contentvec-layers=<n>] build-index <version> <data> <input_dir> <output.index>";
+
+// audio encoded at once, keeps the encoder's memory flat on long recordings
+const ENCODE_CHUNK: usize = 16000 * 10;
+// recordings shorter than one encoder frame add nothing
+const MIN_ENCODE_LENGTH: usize = 320;
+const RESAMPLE_CHUNK: usize = 1024;
+
+pub struct EncoderOptions {
+    pub encoder: FeatureEncoder,
+    pub encoder_path: Option<PathBuf>,
+    pub contentvec_layers: Option<usize>,
+    pub session_config: SessionConfig,
+}
+
+/// Builds an IVF-Flat index from every `.npy` feature dump and `.wav` recording in a folder,
+/// encoding the recordings with the same encoder the filter would load.
+pub fn run(args: &[String], options: EncoderOptions) -> Result<(), String> {
+    let [version, data_path, input_dir, output_path] = args else {
+        return Err(format!("Usage: {}", USAGE));
+    };
+    let model_version = RvcModelVersion::from(version.as_str());
+
+    let mut inputs: Vec<PathBuf> = std::fs::read_dir(input_dir)
+        .map_err(|e| format!("{}: {}", input_dir, e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| has_extension(path, "npy") || has_extension(path, "wav"))
+        .collect();
+    inputs.sort();
+    if inputs.is_empty() {
+        return Err(format!("no .npy or .wav files in {}", input_dir));
+    }
+
+    // only recordings need the encoder, feature dumps are taken as they are
+    let mut encoder: Option<RvcInfer> = None;
+    let mut features = Vec::with_capacity(inputs.len());
+    for (i, path) in inputs.iter().enumerate() {
+        let file_features = if has_extension(path, "npy") {
+            load_feature_dump(path).map_err(|e| format!("{:?}", e))?
+        } else {
+            if encoder.is_none() {
+                let mut rvc = RvcInfer::new(PathBuf::from(data_path));
+                rvc.set_session_config(options.session_config.clone());
+                rvc.load_encoder(options.encoder, model_version, options.contentvec_layers, options.encoder_path.clone())
+                    .map_err(|e| format!("Error loading encoder model: {:?}", e))?;
+                encoder = Some(rvc);
+            }
+            encode_recording(encoder.as_ref().unwrap(), &read_wav_16k(path)?)?
+        };
+        eprintln!("[{}/{}] {:?}: {} frames", i + 1, inputs.len(), path, file_features.nrows());
+        features.push(file_features);
+    }
+
+    let views: Vec<ArrayView2<f32>> = features.iter().map(Array2::view).collect();
+    let features = concatenate(Axis(0), &views)
+        .map_err(|_| "feature dimensions differ between files".to_string())?;
+    if features.nrows() == 0 {
+        return Err("no features to build an index from".to_string());
+    }
+
+    eprintln!("Training index on {} frames of {} channels", features.nrows(), features.ncols());
+    let bytes = build_index(features.view());
+    std::fs::write(output_path, bytes).map_err(|e| format!("{}: {}", output_path, e))?;
+    eprintln!("Index written to {}", output_path);
+    Ok(())
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+fn encode_recording(rvc: &RvcInfer, audio: &[f32]) -> Result<Array2<f32>, String> {
+    let mut features = Vec::new();
+    for chunk in audio.chunks(ENCODE_CHUNK) {
+        if chunk.len() < MIN_ENCODE_LENGTH {
+            continue;
+        }
+        features.push(rvc.extract_features(ArrayView1::from(chunk)).map_err(|e| format!("{:?}", e))?);
+    }
+    if features.is_empty() {
+        return Ok(Array2::zeros((0, 0)));
+    }
+    let views: Vec<ArrayView2<f32>> = features.iter().map(Array2::view).collect();
+    concatenate(Axis(0), &views).map_err(|e| e.to_string())
+}
+
+/// A recording mixed down to mono and resampled to 16 kHz.
+fn read_wav_16k(path: &Path) -> Result<Vec<f32>, String> {
+    let error = |e: hound::Error| format!("{}: {}", path.display(), e);
+    let mut reader = hound::WavReader::open(path).map_err(error)?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>().map_err(error)?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 * scale))
+                .collect::<Result<_, _>>()
+                .map_err(error)?
+        }
+    };
+
+    let channels = usize::from(spec.channels).max(1);
+    let mono: Vec<f32> = samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    if spec.sample_rate == 16000 {
+        return Ok(mono);
+    }
+
+    let resample_error = |e: rubato::ResampleError| format!("{}: {}", path.display(), e);
+    let mut resampler = FftFixedIn::<f32>::new(spec.sample_rate as usize, 16000, RESAMPLE_CHUNK, 2, 1)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut resampled = Vec::with_capacity(mono.len() * 16000 / spec.sample_rate as usize + RESAMPLE_CHUNK);
+    let mut chunks = mono.chunks_exact(RESAMPLE_CHUNK);
+    for chunk in &mut chunks {
+        resampled.extend_from_slice(&resampler.process(&[chunk], None).map_err(resample_error)?[0]);
+    }
+    resampled.extend_from_slice(&resampler.process_partial(Some(&[chunks.remainder()]), None).map_err(resample_error)?[0]);
+    // flush what the resampler still holds back
+    resampled.extend_from_slice(&resampler.process_partial::<&[f32]>(None, None).map_err(resample_error)?[0]);
+
+    let delay = usize::min(resampler.output_delay(), resampled.len());
+    resampled.drain(..delay);
+    Ok(resampled)
+}
@@ -0,0 +1,31 @@
+use std::{env, ffi::OsString, process::Command};
+
+pub const USAGE: &str = "rvc-rpc convert <model.pth> [output.onnx] [--rvc-dir=<path to RVC>]";
+
+// shipped next to rvc-rpc, see tools/export_onnx.py
+const EXPORTER: &str = "export_onnx.py";
+
+/// Runs the bundled PyTorch exporter on a checkpoint. Exporting needs the model definitions and
+/// PyTorch, so this is only a wrapper; `RVC_PYTHON` picks the interpreter, `python` by default.
+pub fn run(args: &[String]) -> Result<(), String> {
+    if args.is_empty() {
+        return Err(format!("Usage: {}", USAGE));
+    }
+
+    let exe = env::current_exe().map_err(|e| e.to_string())?;
+    let exporter = exe.with_file_name(EXPORTER);
+    if !exporter.exists() {
+        return Err(format!("{} not found next to rvc-rpc", EXPORTER));
+    }
+
+    let python = env::var_os("RVC_PYTHON").unwrap_or_else(|| OsString::from("python"));
+    let status = Command::new(&python)
+        .arg(&exporter)
+        .args(args)
+        .status()
+        .map_err(|e| format!("{}: {}", python.to_string_lossy(), e))?;
+    if !status.success() {
+        return Err(format!("{} exited with {}", EXPORTER, status));
+    }
+    Ok(())
+}
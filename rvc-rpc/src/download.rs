@@ -0,0 +1,166 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+pub const USAGE: &str = "rvc-rpc download <url|huggingface repo id[@revision]> <models_folder> [sha256]";
+
+const HUGGINGFACE: &str = "https://huggingface.co";
+// bytes between progress lines, often enough for a smooth percentage on voice sized files
+const PROGRESS_STEP: u64 = 1 << 20;
+const READ_CHUNK: usize = 1 << 16;
+
+struct RemoteFile {
+    url: String,
+    /// Where the file goes, relative to the models folder.
+    path: PathBuf,
+    size: Option<u64>,
+    sha256: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TreeEntry {
+    #[serde(rename = "type")]
+    kind: String,
+    path: String,
+    size: Option<u64>,
+    lfs: Option<LfsPointer>,
+}
+
+#[derive(Deserialize)]
+struct LfsPointer {
+    // the SHA-256 of the file content
+    oid: String,
+}
+
+/// Downloads a voice into the models folder: a single file by URL, or the models and indices
+/// of a HuggingFace repository into a folder named after it. Reports on stdout, one line each:
+/// `file <path> <size, 0 if unknown>`, `progress <bytes>` and `done <path>`.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let (source, models_folder, sha256) = match args {
+        [source, models_folder] => (source, models_folder, None),
+        [source, models_folder, sha256] => (source, models_folder, Some(sha256.to_lowercase())),
+        _ => return Err(format!("Usage: {}", USAGE)),
+    };
+
+    let files = if source.starts_with("http://") || source.starts_with("https://") {
+        vec![remote_url(source, sha256)?]
+    } else {
+        // checksums of repository files come from their LFS pointers
+        list_repository(source)?
+    };
+
+    for file in files {
+        download(&file, &Path::new(models_folder).join(&file.path))?;
+    }
+    Ok(())
+}
+
+fn remote_url(url: &str, sha256: Option<String>) -> Result<RemoteFile, String> {
+    let name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| format!("{}: no file name in the URL", url))?;
+    Ok(RemoteFile {
+        url: url.to_string(),
+        path: PathBuf::from(name),
+        size: None,
+        sha256,
+    })
+}
+
+fn list_repository(source: &str) -> Result<Vec<RemoteFile>, String> {
+    let (repo, revision) = source.split_once('@').unwrap_or((source, "main"));
+    let Some((_, repo_name)) = repo.split_once('/') else {
+        return Err(format!("{}: neither a URL nor a repository id like user/voice", source));
+    };
+
+    let url = format!("{}/api/models/{}/tree/{}?recursive=true", HUGGINGFACE, repo, revision);
+    let tree: Vec<TreeEntry> = ureq::get(&url)
+        .call()
+        .map_err(|e| format!("{}: {}", url, e))?
+        .into_json()
+        .map_err(|e| format!("{}: {}", url, e))?;
+
+    let files: Vec<RemoteFile> = tree
+        .into_iter()
+        .filter(|entry| entry.kind == "file" && is_voice_file(&entry.path))
+        .map(|entry| RemoteFile {
+            url: format!("{}/{}/resolve/{}/{}", HUGGINGFACE, repo, revision, entry.path),
+            path: Path::new(repo_name).join(&entry.path),
+            size: entry.size,
+            sha256: entry.lfs.map(|lfs| lfs.oid.to_lowercase()),
+        })
+        .collect();
+    if files.is_empty() {
+        return Err(format!("no .onnx or .index files in {}", source));
+    }
+    Ok(files)
+}
+
+fn is_voice_file(path: &str) -> bool {
+    let path = path.to_lowercase();
+    path.ends_with(".onnx") || path.ends_with(".index")
+}
+
+/// Streams the file into `<destination>.part` and only moves it in place once the checksum
+/// matched, so that the models folder never lists a broken download.
+fn download(file: &RemoteFile, destination: &Path) -> Result<(), String> {
+    let name = file.path.display();
+    let error = |e: std::io::Error| format!("{}: {}", destination.display(), e);
+
+    let response = ureq::get(&file.url).call().map_err(|e| format!("{}: {}", file.url, e))?;
+    let size = file
+        .size
+        .or_else(|| response.header("Content-Length").and_then(|length| length.parse().ok()));
+    println!("file {} {}", name, size.unwrap_or(0));
+
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent).map_err(error)?;
+    }
+    let mut part_path = OsString::from(destination);
+    part_path.push(".part");
+    let part_path = PathBuf::from(part_path);
+
+    let mut reader = response.into_reader();
+    let mut writer = BufWriter::new(File::create(&part_path).map_err(error)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; READ_CHUNK];
+    let mut received = 0u64;
+    let mut reported = 0u64;
+    loop {
+        let read = reader.read(&mut buffer).map_err(|e| format!("{}: {}", file.url, e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        writer.write_all(&buffer[..read]).map_err(error)?;
+        received += read as u64;
+        if received - reported >= PROGRESS_STEP {
+            println!("progress {}", received);
+            reported = received;
+        }
+    }
+    writer.flush().map_err(error)?;
+    drop(writer);
+    println!("progress {}", received);
+
+    let digest: String = hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect();
+    if let Some(expected) = &file.sha256 {
+        if *expected != digest {
+            let _ = std::fs::remove_file(&part_path);
+            return Err(format!("{}: checksum mismatch, expected {} but got {}", name, expected, digest));
+        }
+    }
+
+    std::fs::rename(&part_path, destination).map_err(error)?;
+    println!("done {}", name);
+    Ok(())
+}
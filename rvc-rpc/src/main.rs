@@ -1,102 +1,511 @@
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
 use std::{env, io::Read};
 use std::path::PathBuf;
 use ndarray::Array1;
-use rvc_common::enums::{PitchAlgorithm, RvcModelVersion};
-use rvc::RvcInfer;
+use rvc_common::{enums::{AutotuneScale, FeatureEncoder, InferenceDevice, PitchAlgorithm, RetrievalMetric, RvcModelVersion}, protocol::{MODEL_FLAG_F0, MODEL_FLAG_MULTI_SPEAKER, MODEL_FLAG_QUANTIZED, MODEL_FLAG_SPEAKER_MORPH, MODEL_FLAG_V2, MODEL_FLAG_VERSION_DETECTED, FRAME_FAILED, LOAD_FAILED_MAGIC, READY_MAGIC, STAGE_TIME_COUNT, STREAM_CLOSED, STREAM_PARKED, STREAM_WOKEN}};
+use rvc::{usable_devices, RvcInfer, SessionConfig, StreamHistory};
 
-fn main() {
-    #[cfg(debug_assertions)]
-    tracing_subscriber::fmt::fmt().with_max_level(tracing::Level::DEBUG).with_writer(std::io::stderr).init();
+mod build_index;
+mod convert;
+mod download;
 
-    let args: Vec<String> = env::args().collect();
+// requests decoded ahead of the one being converted
+const QUEUED_REQUESTS: usize = 2;
 
-    if args.len() < 4 {
-        eprintln!("Usage: rvc-rpc <version> <f0_algorithm> <model> <data>");
-        return;
+/// What the filter sends, one per frame or change of a stream.
+enum Request {
+    Closed(u32),
+    Parked(u32),
+    Woken(u32),
+    Frame(FrameRequest),
+}
+
+struct FrameRequest {
+    stream_id: u32,
+    input: Array1<f32>,
+    sample_frame_16k_size: usize,
+    pitch_shift: i32,
+    skip_head: u32,
+    return_length: u32,
+    index_rate: f32,
+    index_weights: Vec<f32>,
+    f0_filter_radius: usize,
+    autotune: (AutotuneScale, i32, f32),
+    f0_range: (f32, f32),
+    voicing_sensitivity: f32,
+    feature_cache: bool,
+    speaker_id: u32,
+    speaker_morph: (u32, f32),
+}
+
+fn read_le_bytes(reader: &mut impl Read) -> std::io::Result<[u8; 4]> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_request(reader: &mut impl Read) -> std::io::Result<Request> {
+    let stream_id = u32::from_le_bytes(read_le_bytes(reader)?);
+
+    let input_bytes_length = u32::from_le_bytes(read_le_bytes(reader)?);
+    match input_bytes_length {
+        STREAM_CLOSED => return Ok(Request::Closed(stream_id)),
+        STREAM_PARKED => return Ok(Request::Parked(stream_id)),
+        STREAM_WOKEN => return Ok(Request::Woken(stream_id)),
+        _ => (),
     }
-    
-    let model_version = RvcModelVersion::from(args[1].as_str());
-    let pitch_algorithm = PitchAlgorithm::from(args[2].as_str());
-    let model_path = PathBuf::from(&args[3]);
-    let data_path = PathBuf::from(&args[4]);
+    let input_bytes_length = input_bytes_length as usize;
+
+    let mut input_bytes = vec![0u8; input_bytes_length];
+    reader.read_exact(&mut input_bytes)?;
+    let input = Array1::from_shape_fn(input_bytes_length / 4, |i| {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&input_bytes[i * 4..(i + 1) * 4]);
+        f32::from_le_bytes(bytes)
+    });
+
+    let sample_frame_16k_size = u32::from_le_bytes(read_le_bytes(reader)?) as usize;
+    let pitch_shift = i32::from_le_bytes(read_le_bytes(reader)?);
+    let skip_head = u32::from_le_bytes(read_le_bytes(reader)?);
+    let return_length = u32::from_le_bytes(read_le_bytes(reader)?);
+    let index_rate = f32::from_le_bytes(read_le_bytes(reader)?);
+
+    let index_count = u32::from_le_bytes(read_le_bytes(reader)?) as usize;
+    let mut index_weights = vec![0f32; index_count];
+    for weight in index_weights.iter_mut() {
+        *weight = f32::from_le_bytes(read_le_bytes(reader)?);
+    }
+
+    let f0_filter_radius = u32::from_le_bytes(read_le_bytes(reader)?) as usize;
+
+    let autotune_scale = AutotuneScale::from(u32::from_le_bytes(read_le_bytes(reader)?) as i64);
+    let autotune_key = i32::from_le_bytes(read_le_bytes(reader)?);
+    let autotune_strength = f32::from_le_bytes(read_le_bytes(reader)?);
+
+    let f0_floor = f32::from_le_bytes(read_le_bytes(reader)?);
+    let f0_ceil = f32::from_le_bytes(read_le_bytes(reader)?);
+
+    let voicing_sensitivity = f32::from_le_bytes(read_le_bytes(reader)?);
+    let feature_cache = u32::from_le_bytes(read_le_bytes(reader)?) != 0;
+    let speaker_id = u32::from_le_bytes(read_le_bytes(reader)?);
+
+    let morph_speaker_id = u32::from_le_bytes(read_le_bytes(reader)?);
+    let speaker_morph = f32::from_le_bytes(read_le_bytes(reader)?);
+
+    Ok(Request::Frame(FrameRequest {
+        stream_id,
+        input,
+        sample_frame_16k_size,
+        pitch_shift,
+        skip_head,
+        return_length,
+        index_rate,
+        index_weights,
+        f0_filter_radius,
+        autotune: (autotune_scale, autotune_key, autotune_strength),
+        f0_range: (f0_floor, f0_ceil),
+        voicing_sensitivity,
+        feature_cache,
+        speaker_id,
+        speaker_morph: (morph_speaker_id, speaker_morph),
+    }))
+}
+
+/// What the sessions were loaded from, to load them again once a parked stream wakes up.
+struct SessionSource {
+    model_version: RvcModelVersion,
+    pitch_algorithm: PitchAlgorithm,
+    model_path: PathBuf,
+    encoder: FeatureEncoder,
+    encoder_path: Option<PathBuf>,
+    contentvec_layers: Option<usize>,
+}
+
+/// Loads the model, its encoder and the pitch extractor it needs. Returns the version the model
+/// runs with, or what went wrong.
+fn load_sessions(rvc: &mut RvcInfer, source: &SessionSource) -> Result<RvcModelVersion, String> {
+    rvc.load_model(source.model_path.clone())
+        .map_err(|e| format!("Error loading model: {:?}", e))?;
+
+    // the model knows which ContentVec it was trained on, the argument only covers dynamic exports
+    let detected_model_version = rvc.detected_model_version();
+    let model_version = detected_model_version.unwrap_or(source.model_version);
+    rvc.load_encoder(source.encoder, model_version, source.contentvec_layers, source.encoder_path.clone())
+        .map_err(|e| format!("Error loading encoder model: {:?}", e))?;
+
+    // models without f0 conditioning don't need a pitch extractor at all
+    if rvc.is_f0_conditioned() {
+        rvc.load_f0(source.pitch_algorithm)
+            .map_err(|e| format!("Error loading f0 model: {:?}", e))?;
+    } else {
+        eprintln!("Model has no f0 conditioning, skipping pitch extraction");
+    }
+
+    Ok(model_version)
+}
+
+/// Tells the filter why the sessions could not be loaded, in place of the handshake, and exits.
+fn fail_loading(stdout: &mut impl Write, reason: &str) -> ! {
+    eprintln!("{}", reason);
+    let _ = stdout
+        .write_all(&LOAD_FAILED_MAGIC.to_le_bytes())
+        .and_then(|_| stdout.write_all(&(reason.len() as u32).to_le_bytes()))
+        .and_then(|_| stdout.write_all(reason.as_bytes()))
+        .and_then(|_| stdout.flush());
+    std::process::exit(1);
+}
+
+/// Runs frames of the `--warm-up` shape through the loaded sessions.
+fn warm_up_sessions(rvc: &mut RvcInfer, (input_len, sample_frame_16k_size, skip_head, return_length): (usize, usize, u32, u32)) {
+    let start_time = std::time::Instant::now();
+    match rvc.warm_up(input_len, sample_frame_16k_size, skip_head, return_length) {
+        Ok(_) => eprintln!("Warmed up in {:?}", start_time.elapsed()),
+        Err(e) => eprintln!("Error warming up, the first frames may be slow: {:?}", e),
+    }
+}
+
+/// Writes `READY_MAGIC` and the description of the loaded model.
+fn write_ready(stdout: &mut impl Write, rvc: &RvcInfer, model_version: RvcModelVersion) -> std::io::Result<()> {
+    stdout.write_all(&READY_MAGIC.to_le_bytes())?;
+    let mut model_flags = 0;
+    if rvc.is_f0_conditioned() {
+        model_flags |= MODEL_FLAG_F0;
+    }
+    if model_version == RvcModelVersion::V2 {
+        model_flags |= MODEL_FLAG_V2;
+    }
+    if rvc.detected_model_version().is_some() {
+        model_flags |= MODEL_FLAG_VERSION_DETECTED;
+    }
+    if rvc.is_multi_speaker() {
+        model_flags |= MODEL_FLAG_MULTI_SPEAKER;
+    }
+    if rvc.can_morph_speakers() {
+        model_flags |= MODEL_FLAG_SPEAKER_MORPH;
+    }
+    if rvc.is_quantized() {
+        model_flags |= MODEL_FLAG_QUANTIZED;
+    }
+    stdout.write_all(&model_flags.to_le_bytes())?;
+    stdout.write_all(&rvc.output_sample_rate().unwrap_or(0).to_le_bytes())?;
+    if rvc.is_multi_speaker() {
+        let speaker_names = rvc.speaker_names();
+        stdout.write_all(&(speaker_names.len() as u32).to_le_bytes())?;
+        for name in speaker_names {
+            stdout.write_all(&(name.len() as u32).to_le_bytes())?;
+            stdout.write_all(name.as_bytes())?;
+        }
+    }
+    stdout.flush()
+}
 
+/// Replies to a frame that could not be converted.
+fn write_frame_failed(stdout: &mut impl Write, reason: &str) -> std::io::Result<()> {
+    stdout.write_all(&FRAME_FAILED.to_le_bytes())?;
+    stdout.write_all(&(reason.len() as u32).to_le_bytes())?;
+    stdout.write_all(reason.as_bytes())?;
+    stdout.flush()
+}
+
+/// Whether some stream is parked and every other one the sessions converted for is as well.
+fn all_parked(current_stream: Option<u32>, streams: &HashMap<u32, StreamHistory>, parked: &HashSet<u32>) -> bool {
+    !parked.is_empty() && current_stream.iter().chain(streams.keys()).all(|stream_id| parked.contains(stream_id))
+}
+
+fn init_onnxruntime() {
     let cwd = env::current_dir().unwrap();
-    let ort_path = cwd.join("onnxruntime.dll");
+    // onnxruntime.dll, libonnxruntime.dylib or libonnxruntime.so
+    let ort_path = cwd.join(format!("{}onnxruntime{}", env::consts::DLL_PREFIX, env::consts::DLL_SUFFIX));
     match ort::init_from(ort_path.to_string_lossy()).commit() {
         Ok(_) => (),
         Err(e) => {
-            panic!("Error loading onnxruntime: {:?}", e);
+            // the filter reads the last line of stderr as the reason, so no panic note after it
+            eprintln!("Error loading onnxruntime: {:?}", e);
+            std::process::exit(1);
         }
     }
+}
 
-    let mut rvc = RvcInfer::new(data_path);
+fn main() {
+    #[cfg(debug_assertions)]
+    tracing_subscriber::fmt::fmt().with_max_level(tracing::Level::DEBUG).with_writer(std::io::stderr).init();
 
-    match rvc.load_contentvec(model_version) {
-        Ok(_) => (),
-        Err(e) => {
-            panic!("Error loading contentvec model: {:?}", e);
+    let mut args: Vec<String> = Vec::new();
+    let mut session_config = SessionConfig::default();
+    let mut encoder_path = None;
+    let mut contentvec_layers = None;
+    let mut encoder = FeatureEncoder::ContentVec;
+    let mut index_top_k = None;
+    let mut index_weight_exponent = None;
+    let mut index_metric = RetrievalMetric::Auto;
+    let mut warm_up = None;
+
+    for arg in env::args().skip(1) {
+        if let Some(value) = arg.strip_prefix("--intra-threads=") {
+            session_config.intra_threads = value.parse().unwrap_or(0);
+        } else if let Some(value) = arg.strip_prefix("--execution-provider=") {
+            session_config.device = InferenceDevice::from(value);
+        } else if arg == "--tensorrt" {
+            session_config.tensorrt = true;
+        } else if arg == "--cuda-graph" {
+            session_config.cuda_graph = true;
+        } else if arg == "--fp16" {
+            session_config.fp16 = true;
+        } else if arg == "--int8" {
+            session_config.int8 = true;
+        } else if let Some(value) = arg.strip_prefix("--device-id=") {
+            session_config.device_id = value.parse().unwrap_or(0);
+        } else if let Some(value) = arg.strip_prefix("--execution-provider-priority=") {
+            // unknown names parse as `Auto`
+            session_config.device_priority = value
+                .split(',')
+                .map(|name| InferenceDevice::from(name.trim()))
+                .filter(|device| *device != InferenceDevice::Auto)
+                .collect();
+        } else if let Some(value) = arg.strip_prefix("--encoder-model=") {
+            encoder_path = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("--encoder=") {
+            encoder = FeatureEncoder::from(value);
+        } else if let Some(value) = arg.strip_prefix("--contentvec-layers=") {
+            contentvec_layers = value.parse().ok();
+        } else if let Some(value) = arg.strip_prefix("--index-top-k=") {
+            index_top_k = value.parse().ok();
+        } else if let Some(value) = arg.strip_prefix("--index-weight-exponent=") {
+            index_weight_exponent = value.parse().ok();
+        } else if let Some(value) = arg.strip_prefix("--index-metric=") {
+            index_metric = RetrievalMetric::from(value);
+        } else if let Some(value) = arg.strip_prefix("--warm-up=") {
+            // input length, 16 kHz frame size, skip head and return length of the frames to come
+            let shape: Vec<usize> = value.split(',').filter_map(|value| value.parse().ok()).collect();
+            if let [input_len, sample_frame_16k_size, skip_head, return_length] = shape[..] {
+                warm_up = Some((input_len, sample_frame_16k_size, skip_head as u32, return_length as u32));
+            }
+        } else {
+            args.push(arg);
         }
     }
 
-    match rvc.load_f0(pitch_algorithm) {
-        Ok(_) => (),
-        Err(e) => {
-            panic!("Error loading f0 model: {:?}", e);
+    // run by the filter when the module loads: fails when onnxruntime can't be loaded, and lists
+    // the execution providers that work otherwise, one per line
+    if args.first().map(String::as_str) == Some("probe") {
+        init_onnxruntime();
+        for device in usable_devices(&session_config) {
+            println!("{}", device.to_string());
         }
+        return;
     }
 
-    match rvc.load_model(model_path) {
-        Ok(_) => (),
-        Err(e) => {
-            panic!("Error loading model: {:?}", e);
+    // doesn't need onnxruntime, the filter runs it to install voices into its models folder
+    if args.first().map(String::as_str) == Some("download") {
+        if let Err(e) = download::run(&args[1..]) {
+            eprintln!("Error downloading: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args.first().map(String::as_str) == Some("convert") {
+        if let Err(e) = convert::run(&args[1..]) {
+            eprintln!("Error converting model: {}", e);
+            std::process::exit(1);
         }
+        return;
     }
 
-    let stdin = std::io::stdin().lock();
+    if args.first().map(String::as_str) == Some("build-index") {
+        init_onnxruntime();
+        let options = build_index::EncoderOptions {
+            encoder,
+            encoder_path,
+            contentvec_layers,
+            session_config,
+        };
+        if let Err(e) = build_index::run(&args[1..], options) {
+            eprintln!("Error building index: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args.len() < 4 {
+        eprintln!("Usage: rvc-rpc [--intra-threads=<n>] [--execution-provider=<auto|cuda|rocm|openvino|directml|coreml|cpu>] [--execution-provider-priority=<name,...>] [--device-id=<n>] [--tensorrt] [--cuda-graph] [--fp16] [--int8] [--encoder=<name>] [--encoder-model=<path>] [--contentvec-layers=<n>] [--index-top-k=<n>] [--index-weight-exponent=<x>] [--index-metric=<auto|l2|cosine>] [--warm-up=<input>,<frame>,<skip_head>,<return_length>] <version> <f0_algorithm> <model> <data> [index...]");
+        eprintln!("       {}", build_index::USAGE);
+        eprintln!("       rvc-rpc [--device-id=<n>] probe");
+        eprintln!("       {}", download::USAGE);
+        eprintln!("       {}", convert::USAGE);
+        return;
+    }
+    
+    let model_version = RvcModelVersion::from(args[0].as_str());
+    let pitch_algorithm = PitchAlgorithm::from(args[1].as_str());
+    let model_path = PathBuf::from(&args[2]);
+    let data_path = PathBuf::from(&args[3]);
+    let index_paths: Vec<PathBuf> = args[4..].iter().map(PathBuf::from).collect();
+
+    init_onnxruntime();
+
+    let mut rvc = RvcInfer::new(data_path);
+    rvc.set_session_config(session_config);
+
+    let sessions = SessionSource {
+        model_version,
+        pitch_algorithm,
+        model_path,
+        encoder,
+        encoder_path,
+        contentvec_layers,
+    };
     let stdout = std::io::stdout().lock();
 
-    let mut buffered_stdin = std::io::BufReader::with_capacity(1024 * 1024, stdin);
     let mut buffered_stdout = std::io::BufWriter::with_capacity(1024 * 1024, stdout);
 
+    let model_version = match load_sessions(&mut rvc, &sessions) {
+        Ok(model_version) => model_version,
+        Err(reason) => fail_loading(&mut buffered_stdout, &reason),
+    };
+
+    if let Some(index_top_k) = index_top_k {
+        rvc.set_index_top_k(index_top_k);
+    }
+    if let Some(index_weight_exponent) = index_weight_exponent {
+        rvc.set_index_weight_exponent(index_weight_exponent);
+    }
+    rvc.set_index_metric(index_metric);
+
+    // big indices take a while, convert without retrieval until they are there
+    for index_path in index_paths {
+        rvc.load_index_in_background(index_path);
+    }
+
+    if let Some(shape) = warm_up {
+        warm_up_sessions(&mut rvc, shape);
+    }
+
+    // let the filter know that the sessions are warm before it starts sending frames
+    write_ready(&mut buffered_stdout, &rvc, model_version).unwrap();
+
     eprintln!("Ready to receive input");
 
-    loop {
-        let mut input_bytes_length = [0u8; 4];
-        buffered_stdin.read_exact(&mut input_bytes_length).unwrap();
-        let input_bytes_length = u32::from_le_bytes(input_bytes_length) as usize;
+    // the loaded sessions serve every stream, only the history of the one being converted is in
+    // `rvc`, the others wait here
+    let mut streams: HashMap<u32, StreamHistory> = HashMap::new();
+    let mut current_stream = None;
+    // the sessions are dropped while every stream is parked, and loaded again for the first one
+    // that wakes up
+    let mut parked: HashSet<u32> = HashSet::new();
+    let mut sessions_loaded = true;
 
-        let mut input_bytes = vec![0u8; input_bytes_length];
-        buffered_stdin.read_exact(&mut input_bytes).unwrap();
-        let input = Array1::from_shape_fn(input_bytes_length / 4, |i| {
-            let mut bytes = [0u8; 4];
-            bytes.copy_from_slice(&input_bytes[i * 4..(i + 1) * 4]);
-            f32::from_le_bytes(bytes)
-        });
+    let (request_sender, requests) = crossbeam::channel::bounded(QUEUED_REQUESTS);
+    // decoded next to the conversion, so that a frame the filter sends ahead doesn't sit in the
+    // pipe while the one before it converts
+    std::thread::spawn(move || {
+        let mut buffered_stdin = std::io::BufReader::with_capacity(1024 * 1024, std::io::stdin().lock());
+        // ends with the pipe, once the filter is gone
+        while let Ok(request) = read_request(&mut buffered_stdin) {
+            if request_sender.send(request).is_err() {
+                break;
+            }
+        }
+    });
 
-        let mut sample_frame_16k_size = [0u8; 4];
-        buffered_stdin.read_exact(&mut sample_frame_16k_size).unwrap();
-        let sample_frame_16k_size = u32::from_le_bytes(sample_frame_16k_size) as usize;
+    for request in requests {
+        let request = match request {
+            Request::Closed(stream_id) => {
+                streams.remove(&stream_id);
+                if current_stream == Some(stream_id) {
+                    rvc.swap_history(&mut StreamHistory::new());
+                    current_stream = None;
+                }
+                parked.remove(&stream_id);
+                // the one that went away may have been the last that was still converting
+                if sessions_loaded && all_parked(current_stream, &streams, &parked) {
+                    rvc.unload_sessions();
+                    eprintln!("Every stream is parked, sessions unloaded");
+                    sessions_loaded = false;
+                }
+                continue;
+            }
+            Request::Parked(stream_id) => {
+                parked.insert(stream_id);
+                if sessions_loaded && all_parked(current_stream, &streams, &parked) {
+                    rvc.unload_sessions();
+                    eprintln!("Every stream is parked, sessions unloaded");
+                    sessions_loaded = false;
+                }
+                continue;
+            }
+            Request::Woken(stream_id) => {
+                parked.remove(&stream_id);
+                if !sessions_loaded {
+                    if let Err(reason) = load_sessions(&mut rvc, &sessions) {
+                        fail_loading(&mut buffered_stdout, &reason);
+                    }
+                    if let Some(shape) = warm_up {
+                        warm_up_sessions(&mut rvc, shape);
+                    }
+                    sessions_loaded = true;
+                }
+                write_ready(&mut buffered_stdout, &rvc, model_version).unwrap();
+                continue;
+            }
+            Request::Frame(request) => request,
+        };
+        let stream_id = request.stream_id;
 
-        let mut pitch_shift = [0u8; 4];
-        buffered_stdin.read_exact(&mut pitch_shift).unwrap();
-        let pitch_shift = i32::from_le_bytes(pitch_shift);
+        // a filter that joined while the others were parked sends its frames straight away
+        if !sessions_loaded {
+            eprintln!("Frame for stream {} while parked, loading sessions again", stream_id);
+            if let Err(reason) = load_sessions(&mut rvc, &sessions) {
+                // the filter waits for a reply here, not for a handshake
+                eprintln!("{}", reason);
+                write_frame_failed(&mut buffered_stdout, &reason).unwrap();
+                std::process::exit(1);
+            }
+            sessions_loaded = true;
+        }
+        parked.remove(&stream_id);
 
-        let mut skip_head = [0u8; 4];
-        buffered_stdin.read_exact(&mut skip_head).unwrap();
-        let skip_head = u32::from_le_bytes(skip_head);
+        if current_stream != Some(stream_id) {
+            let mut history = streams.remove(&stream_id).unwrap_or_else(StreamHistory::new);
+            rvc.swap_history(&mut history);
+            if let Some(previous_stream) = current_stream {
+                streams.insert(previous_stream, history);
+            }
+            current_stream = Some(stream_id);
+        }
 
-        let mut return_length = [0u8; 4];
-        buffered_stdin.read_exact(&mut return_length).unwrap();
-        let return_length = u32::from_le_bytes(return_length);
+        rvc.set_index_weights(&request.index_weights);
+        rvc.set_f0_filter_radius(request.f0_filter_radius);
+        let (autotune_scale, autotune_key, autotune_strength) = request.autotune;
+        rvc.set_autotune(autotune_scale, autotune_key, autotune_strength);
+        rvc.set_f0_range(request.f0_range.0, request.f0_range.1);
+        rvc.set_voicing_sensitivity(request.voicing_sensitivity);
+        rvc.set_feature_cache(request.feature_cache);
+        rvc.set_speaker_id(request.speaker_id);
+        rvc.set_speaker_morph(request.speaker_morph.0, request.speaker_morph.1);
 
-        let output = rvc.infer(input.view(), sample_frame_16k_size, Some(pitch_shift), skip_head, return_length).unwrap();
+        let FrameRequest { input, sample_frame_16k_size, pitch_shift, skip_head, return_length, index_rate, .. } = request;
+        let output = match rvc.infer(input.view(), sample_frame_16k_size, Some(pitch_shift), skip_head, return_length, index_rate) {
+            Ok(output) => output,
+            Err(e) => {
+                // the filter passes the frame through and keeps sending
+                let reason = format!("{:?}", e);
+                eprintln!("Error converting frame: {}", reason);
+                write_frame_failed(&mut buffered_stdout, &reason).unwrap();
+                continue;
+            }
+        };
 
         let output_bytes: Vec<u8> = output.iter().flat_map(|&x| x.to_le_bytes().to_vec()).collect();
         let output_bytes_length = output_bytes.len();
 
         buffered_stdout.write_all(&(output_bytes_length as u32).to_le_bytes()).unwrap();
         buffered_stdout.write_all(&output_bytes).unwrap();
+        let stage_times: [std::time::Duration; STAGE_TIME_COUNT] = rvc.stage_times();
+        for stage_time in stage_times {
+            buffered_stdout.write_all(&(stage_time.as_micros() as u32).to_le_bytes()).unwrap();
+        }
         buffered_stdout.flush().unwrap();
     }
 
@@ -0,0 +1,61 @@
+use rvc_common::errors::RvcInferError;
+
+use super::PendingEncode;
+use crate::models::run_with_pinned_outputs;
+
+// the convolutional front end takes 400 samples for its first frame and moves by 320
+const RECEPTIVE_FIELD: usize = 400;
+const HOP: usize = 320;
+
+pub struct ContentVec {
+    session: ort::Session,
+    // CUDA device whose page-locked memory the features are read back through. They have to
+    // come back to the host anyway: retrieval, the feature cache and the frame doubling all work
+    // on host arrays before the synthesizer sees them.
+    pinned_output_device: Option<i32>,
+}
+
+impl ContentVec {
+    /// Custom exports have to follow the tensor names of the bundled ones.
+    pub fn new(session: ort::Session, pinned_output_device: Option<i32>) -> Result<Self, RvcInferError> {
+        if !session.inputs.iter().any(|input| input.name == "source") {
+            return Err(RvcInferError::Encoder("model has no \"source\" input".to_string()));
+        }
+        if !session.outputs.iter().any(|output| output.name == "embed") {
+            return Err(RvcInferError::Encoder("model has no \"embed\" output".to_string()));
+        }
+        Ok(ContentVec { session, pinned_output_device })
+    }
+
+    pub fn encode(&self, input: ndarray::ArrayView1<f32>) -> Result<ndarray::Array3<f32>, RvcInferError> {
+        let input_len = input.len();
+        let feats = ort::Tensor::from_array(input.into_shape((1, 1, input_len))?.to_owned())?.into_dyn();
+
+        match self.pinned_output_device {
+            Some(device_id) => run_with_pinned_outputs(&self.session, vec![("source", feats)], device_id, Self::extract_embed),
+            None => Self::extract_embed(&self.session.run(vec![("source", feats)])?),
+        }
+    }
+
+    /// Like `encode`, but leaves the run to onnxruntime's threads. Pinned outputs go through an
+    /// IO binding, which onnxruntime has no asynchronous run for, so with those it has finished
+    /// on return.
+    pub fn encode_async(&self, input: ndarray::ArrayView1<f32>) -> Result<PendingEncode<'_>, RvcInferError> {
+        if self.pinned_output_device.is_some() {
+            return Ok(PendingEncode::ready(self.encode(input)?));
+        }
+
+        let input_len = input.len();
+        let frames = input_len.saturating_sub(RECEPTIVE_FIELD) / HOP + 1;
+        let feats = ort::Tensor::from_array(input.into_shape((1, 1, input_len))?.to_owned())?.into_dyn();
+        let running = self.session.run_async(vec![("source", feats)])?;
+        Ok(PendingEncode::running(frames, async move { Self::extract_embed(&running.await?) }))
+    }
+
+    fn extract_embed(output: &ort::SessionOutputs) -> Result<ndarray::Array3<f32>, RvcInferError> {
+        let embed = output["embed"]
+            .try_extract_tensor::<f32>()?
+            .into_dimensionality::<ndarray::Ix3>()?;
+        Ok(embed.permuted_axes([0, 2, 1]).to_owned())
+    }
+}
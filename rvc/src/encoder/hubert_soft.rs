@@ -0,0 +1,74 @@
+use rvc_common::errors::RvcInferError;
+
+use super::PendingEncode;
+
+// the convolutional front end takes 400 samples for its first frame and moves by 320
+const RECEPTIVE_FIELD: usize = 400;
+const HOP: usize = 320;
+// soft-vc pads by half the difference between the receptive field and the hop, so that the
+// frames come out centered like ContentVec's
+const PADDING: usize = (RECEPTIVE_FIELD - HOP) / 2;
+
+pub struct HubertSoft {
+    session: ort::Session,
+}
+
+fn pad_input(input: ndarray::ArrayView1<f32>) -> ndarray::Array1<f32> {
+    let mut padded = ndarray::Array1::zeros(input.len() + 2 * PADDING);
+    padded
+        .slice_mut(ndarray::s![PADDING..PADDING + input.len()])
+        .assign(&input);
+    padded
+}
+
+impl HubertSoft {
+    pub fn new(session: ort::Session) -> Result<Self, RvcInferError> {
+        if session.inputs.is_empty() || session.outputs.is_empty() {
+            return Err(RvcInferError::Encoder("model has no inputs or outputs".to_string()));
+        }
+        Ok(HubertSoft { session })
+    }
+
+    pub fn encode(&self, input: ndarray::ArrayView1<f32>) -> Result<ndarray::Array3<f32>, RvcInferError> {
+        let padded = pad_input(input);
+        let padded_len = padded.len();
+        let wav = padded.into_shape((1, 1, padded_len))?;
+
+        // exported hubert-soft models don't agree on tensor names, so go by position
+        let output = self.session.run(ort::inputs![wav]?)?;
+        Self::extract_units(&output)
+    }
+
+    /// Like `encode`, but leaves the run to onnxruntime's threads.
+    pub fn encode_async(&self, input: ndarray::ArrayView1<f32>) -> Result<PendingEncode<'_>, RvcInferError> {
+        let padded = pad_input(input);
+        let padded_len = padded.len();
+        let frames = padded_len.saturating_sub(RECEPTIVE_FIELD) / HOP + 1;
+        let wav = ort::Tensor::from_array(padded.into_shape((1, 1, padded_len))?)?.into_dyn();
+        let running = self.session.run_async(vec![(self.session.inputs[0].name.clone(), wav)])?;
+        Ok(PendingEncode::running(frames, async move { Self::extract_units(&running.await?) }))
+    }
+
+    fn extract_units(output: &ort::SessionOutputs) -> Result<ndarray::Array3<f32>, RvcInferError> {
+        // (1, frames, 256) soft units, already projected, so nothing to do but transpose
+        let units = output[0]
+            .try_extract_tensor::<f32>()?
+            .into_dimensionality::<ndarray::Ix3>()?;
+        Ok(units.permuted_axes([0, 2, 1]).to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pad_input() {
+        let padded = pad_input(ndarray::arr1(&[1.0, 2.0]).view());
+        assert_eq!(padded.len(), 2 + 2 * PADDING);
+        assert_eq!(padded[PADDING - 1], 0.0);
+        assert_eq!(padded[PADDING], 1.0);
+        assert_eq!(padded[PADDING + 1], 2.0);
+        assert_eq!(padded[PADDING + 2], 0.0);
+    }
+}
@@ -0,0 +1,67 @@
+use std::{future::Future, pin::Pin};
+
+use ndarray::Axis;
+use rvc_common::errors::RvcInferError;
+
+use self::{contentvec::ContentVec, hubert_soft::HubertSoft, whisper::Whisper};
+use crate::executor::block_on;
+
+pub mod contentvec;
+pub mod hubert_soft;
+pub mod whisper;
+
+/// A loaded speech encoder. Each one takes 16 kHz audio and returns `(1, channels, frames)`
+/// features at 50 frames per second, handling its own input and output conventions.
+pub enum Encoder {
+    ContentVec(ContentVec),
+    HubertSoft(HubertSoft),
+    Whisper(Whisper),
+}
+
+/// Features on their way, with the frame count they will have known up front.
+pub struct PendingEncode<'s> {
+    pub frames: usize,
+    features: Pin<Box<dyn Future<Output = Result<ndarray::Array3<f32>, RvcInferError>> + 's>>,
+}
+
+impl<'s> PendingEncode<'s> {
+    pub fn ready(features: ndarray::Array3<f32>) -> Self {
+        PendingEncode {
+            frames: features.len_of(Axis(2)),
+            features: Box::pin(std::future::ready(Ok(features))),
+        }
+    }
+
+    pub(crate) fn running(
+        frames: usize,
+        features: impl Future<Output = Result<ndarray::Array3<f32>, RvcInferError>> + 's,
+    ) -> Self {
+        PendingEncode { frames, features: Box::pin(features) }
+    }
+
+    /// Blocks until onnxruntime is done.
+    pub fn wait(self) -> Result<ndarray::Array3<f32>, RvcInferError> {
+        block_on(self.features)
+    }
+}
+
+impl Encoder {
+    pub fn encode(&self, input: ndarray::ArrayView1<f32>) -> Result<ndarray::Array3<f32>, RvcInferError> {
+        match self {
+            Encoder::ContentVec(contentvec) => contentvec.encode(input),
+            Encoder::HubertSoft(hubert_soft) => hubert_soft.encode(input),
+            Encoder::Whisper(whisper) => whisper.encode(input),
+        }
+    }
+
+    /// Starts encoding on onnxruntime's threads and returns without waiting for it. ContentVec
+    /// with page-locked outputs is the exception: onnxruntime only runs IO bindings
+    /// synchronously, so it has finished when this returns.
+    pub fn encode_async(&self, input: ndarray::ArrayView1<f32>) -> Result<PendingEncode<'_>, RvcInferError> {
+        match self {
+            Encoder::ContentVec(contentvec) => contentvec.encode_async(input),
+            Encoder::HubertSoft(hubert_soft) => hubert_soft.encode_async(input),
+            Encoder::Whisper(whisper) => whisper.encode_async(input),
+        }
+    }
+}
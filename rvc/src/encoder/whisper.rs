@@ -0,0 +1,103 @@
+use mel_spec::mel;
+use ndarray::{s, Axis};
+
+use rvc_common::errors::RvcInferError;
+
+use super::PendingEncode;
+use crate::f0::rmvpe::{get_hann_window_periodic, stft};
+
+// whisper's front end: 25 ms windows every 10 ms at 16 kHz
+const N_FFT: usize = 400;
+const HOP_LENGTH: usize = 160;
+const DEFAULT_N_MELS: usize = 80;
+
+pub struct Whisper {
+    session: ort::Session,
+    mel_basis: ndarray::Array2<f32>,
+    window: ndarray::Array1<f32>,
+}
+
+/// Whisper's log-mel normalisation: log10 power, floored 8 decades below the peak, then
+/// scaled to roughly [-1, 1].
+fn normalize_log_mel(mel: ndarray::Array2<f32>) -> ndarray::Array2<f32> {
+    let log_mel = mel.mapv(|x| x.max(1e-10).log10());
+    let floor = log_mel.fold(f32::NEG_INFINITY, |max, &x| max.max(x)) - 8.0;
+    log_mel.mapv(|x| (x.max(floor) + 4.0) / 4.0)
+}
+
+impl Whisper {
+    pub fn new(session: ort::Session) -> Result<Self, RvcInferError> {
+        if session.inputs.is_empty() || session.outputs.is_empty() {
+            return Err(RvcInferError::Encoder("model has no inputs or outputs".to_string()));
+        }
+
+        // large-v3 takes 128 mel bins, everything before it 80
+        let n_mels = match &session.inputs[0].input_type {
+            ort::ValueType::Tensor { dimensions, .. } => dimensions
+                .get(1)
+                .and_then(|&n_mels| usize::try_from(n_mels).ok())
+                .unwrap_or(DEFAULT_N_MELS),
+            _ => DEFAULT_N_MELS,
+        };
+
+        // librosa's defaults, slaney scale and norm, as whisper uses them
+        let mel_basis = mel::mel(16000.0, N_FFT, n_mels, None, None, false, true).mapv(|x| x as f32);
+
+        Ok(Whisper {
+            session,
+            mel_basis,
+            window: get_hann_window_periodic(N_FFT),
+        })
+    }
+
+    pub fn encode(&self, input: ndarray::ArrayView1<f32>) -> Result<ndarray::Array3<f32>, RvcInferError> {
+        let log_mel = self.log_mel(input);
+
+        // exported whisper encoders don't agree on tensor names, so go by position
+        let output = self.session.run(ort::inputs![log_mel.insert_axis(Axis(0))]?)?;
+        Self::extract_features(&output)
+    }
+
+    /// Like `encode`, but leaves the run to onnxruntime's threads. The log-mel spectrogram is
+    /// still computed up front.
+    pub fn encode_async(&self, input: ndarray::ArrayView1<f32>) -> Result<PendingEncode<'_>, RvcInferError> {
+        let log_mel = self.log_mel(input);
+        // the stride 2 convolution rounds up
+        let frames = (log_mel.len_of(Axis(1)) + 1) / 2;
+        let mel = ort::Tensor::from_array(log_mel.insert_axis(Axis(0)))?.into_dyn();
+        let running = self.session.run_async(vec![(self.session.inputs[0].name.clone(), mel)])?;
+        Ok(PendingEncode::running(frames, async move { Self::extract_features(&running.await?) }))
+    }
+
+    fn log_mel(&self, input: ndarray::ArrayView1<f32>) -> ndarray::Array2<f32> {
+        let magnitude = stft(input, N_FFT, HOP_LENGTH, self.window.view(), true);
+        // whisper drops the last frame of the centered stft
+        let frames = magnitude.len_of(Axis(1)) - 1;
+        let power = magnitude.slice(s![.., ..frames]).mapv(|x| x * x);
+        normalize_log_mel(self.mel_basis.dot(&power))
+    }
+
+    fn extract_features(output: &ort::SessionOutputs) -> Result<ndarray::Array3<f32>, RvcInferError> {
+        // (1, frames, dims) at half the mel frame rate, the same 50 fps as ContentVec
+        let features = output[0]
+            .try_extract_tensor::<f32>()?
+            .into_dimensionality::<ndarray::Ix3>()?;
+        Ok(features.permuted_axes([0, 2, 1]).to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_log_mel() {
+        let mel = ndarray::arr2(&[[1.0, 1e-12], [1e-4, 100.0]]);
+        let normalized = normalize_log_mel(mel);
+        assert!((normalized[[0, 0]] - 1.0).abs() < 1e-5);
+        // floored at 8 decades below the peak of 100
+        assert!((normalized[[0, 1]] - (-6.0 + 4.0) / 4.0).abs() < 1e-5);
+        assert!((normalized[[1, 0]] - 0.0).abs() < 1e-5);
+        assert!((normalized[[1, 1]] - 1.5).abs() < 1e-5);
+    }
+}
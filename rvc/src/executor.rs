@@ -0,0 +1,61 @@
+use std::{
+    future::Future,
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::Thread,
+};
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls `future` on the calling thread, parking it in between until onnxruntime's completion
+/// callback wakes it. The runs it waits for are the only futures here, so there is no need for
+/// a runtime.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut context = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // pending until another thread has set the flag and woken the waker
+    struct Flag(Arc<std::sync::atomic::AtomicBool>);
+
+    impl Future for Flag {
+        type Output = u32;
+
+        fn poll(self: std::pin::Pin<&mut Self>, context: &mut Context<'_>) -> Poll<u32> {
+            if self.0.load(std::sync::atomic::Ordering::Acquire) {
+                return Poll::Ready(42);
+            }
+            let flag = self.0.clone();
+            let waker = context.waker().clone();
+            std::thread::spawn(move || {
+                flag.store(true, std::sync::atomic::Ordering::Release);
+                waker.wake();
+            });
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn test_block_on() {
+        assert_eq!(block_on(async { 1 + 1 }), 2);
+        assert_eq!(block_on(Flag(Arc::new(std::sync::atomic::AtomicBool::new(false)))), 42);
+    }
+}
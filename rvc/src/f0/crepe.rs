@@ -0,0 +1,94 @@
+use ndarray::s;
+
+use rvc_common::errors::RvcInferError;
+
+use super::{f0_extractor_frame, rmvpe::to_local_average_cents};
+
+// crepe looks at 1024 samples of 16k audio per frame, one frame every 10 ms
+const FRAME_LENGTH: usize = 1024;
+const HOP_LENGTH: usize = 160;
+
+pub struct Crepe {
+    session: ort::Session,
+    cents_mapping: ndarray::Array1<f32>,
+}
+
+impl Crepe {
+    pub fn new(session: ort::Session) -> Self {
+        // same 20 cent bins as rmvpe, padded for the local average
+        let cents_mapping = {
+            let mut field = ndarray::Array1::zeros(360 + 2 * 4);
+            field.indexed_iter_mut().for_each(|(i, x)| *x = (i as f32 - 4.) * 20. + 1997.3794084376191);
+            field
+        };
+
+        Crepe {
+            session,
+            cents_mapping,
+        }
+    }
+
+    /// Centered frames, each normalized to zero mean and unit variance as crepe was trained on.
+    fn frames(input: ndarray::ArrayView1<f32>) -> ndarray::Array2<f32> {
+        let n_frames = 1 + input.len() / HOP_LENGTH;
+        let mut padded = ndarray::Array1::zeros(input.len() + FRAME_LENGTH);
+        padded.slice_mut(s![FRAME_LENGTH / 2..FRAME_LENGTH / 2 + input.len()]).assign(&input);
+
+        let mut frames = ndarray::Array2::from_shape_fn((n_frames, FRAME_LENGTH), |(i, j)| {
+            padded[i * HOP_LENGTH + j]
+        });
+
+        for mut frame in frames.rows_mut() {
+            let mean = frame.mean().unwrap_or(0.0);
+            frame.mapv_inplace(|x| x - mean);
+            let std = frame.mapv(|x| x * x).mean().unwrap_or(0.0).sqrt().max(1e-10);
+            frame.mapv_inplace(|x| x / std);
+        }
+
+        frames
+    }
+
+    fn decode(&self, probabilities: ndarray::ArrayView2<f32>, threshold: f32) -> ndarray::Array1<f32> {
+        let cents_pred = to_local_average_cents(probabilities, self.cents_mapping.view(), threshold);
+        let mut f0 = cents_pred.mapv(|x| 10.0f32 * (2.0f32.powf(x / 1200.0)));
+        f0.mapv_inplace(|x| if x == 10.0f32 { 0.0 } else { x });
+        f0
+    }
+
+    pub fn pitch(
+        &mut self,
+        input: ndarray::ArrayView1<f32>,
+        sample_frame_16k_size: usize,
+        threshold: f32,
+    ) -> Result<ndarray::Array1<f32>, RvcInferError> {
+        let f0_extractor_frame = f0_extractor_frame(sample_frame_16k_size);
+        let input = input.slice(s![input.len() - f0_extractor_frame..]);
+        let frames = Self::frames(input);
+
+        // exported crepe models don't agree on tensor names, so go by position
+        let output = self.session.run(ort::inputs![frames]?)?;
+        let probabilities = output[0]
+            .try_extract_tensor::<f32>()?
+            .into_dimensionality::<ndarray::Ix2>()?;
+
+        Ok(self.decode(probabilities.view(), threshold))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Axis;
+
+    use super::*;
+
+    #[test]
+    fn test_frames() {
+        let input = ndarray::Array1::from_shape_fn(1600, |i| (i as f32 * 0.1).sin() * 0.2 + 0.5);
+        let frames = Crepe::frames(input.view());
+        assert_eq!(frames.shape(), &[11, FRAME_LENGTH]);
+
+        let row = frames.index_axis(Axis(0), 5);
+        assert!(row.mean().unwrap().abs() < 1e-4);
+        assert!((row.mapv(|x| x * x).mean().unwrap() - 1.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,80 @@
+use ndarray::{s, Axis};
+
+use rvc_common::errors::RvcInferError;
+
+use super::{
+    f0_extractor_frame,
+    rmvpe::{to_local_average_cents, MelSpectrogram},
+};
+
+// fcpe predicts 360 bins spread evenly in cents between these two frequencies
+const F0_MIN: f32 = 32.70;
+const F0_MAX: f32 = 1975.5;
+const N_BINS: usize = 360;
+
+fn f0_to_cent(f0: f32) -> f32 {
+    1200. * (f0 / 10.).log2()
+}
+
+pub struct Fcpe {
+    session: ort::Session,
+    mel_extractor: MelSpectrogram,
+    cents_mapping: ndarray::Array1<f32>,
+}
+
+impl Fcpe {
+    pub fn new(session: ort::Session) -> Self {
+        let cent_min = f0_to_cent(F0_MIN);
+        let cent_step = (f0_to_cent(F0_MAX) - cent_min) / (N_BINS - 1) as f32;
+        // padded for the local average the same way rmvpe's mapping is
+        let cents_mapping = ndarray::Array1::from_shape_fn(N_BINS + 2 * 4, |i| {
+            cent_min + (i as f32 - 4.) * cent_step
+        });
+
+        Fcpe {
+            session,
+            mel_extractor: MelSpectrogram::new(1024, 16000, 128, 1024, 160, Some(0.0), Some(8000.0), 1e-5),
+            cents_mapping,
+        }
+    }
+
+    fn decode(&self, latent: ndarray::ArrayView2<f32>, threshold: f32) -> ndarray::Array1<f32> {
+        let cents_pred = to_local_average_cents(latent, self.cents_mapping.view(), threshold);
+        let mut f0 = cents_pred.mapv(|x| 10.0f32 * (2.0f32.powf(x / 1200.0)));
+        f0.mapv_inplace(|x| if x == 10.0f32 { 0.0 } else { x });
+        f0
+    }
+
+    pub fn pitch(
+        &mut self,
+        input: ndarray::ArrayView1<f32>,
+        sample_frame_16k_size: usize,
+        threshold: f32,
+    ) -> Result<ndarray::Array1<f32>, RvcInferError> {
+        let f0_extractor_frame = f0_extractor_frame(sample_frame_16k_size);
+        let input = input.slice(s![input.len() - f0_extractor_frame..]);
+
+        // (mels, frames) => (1, frames, mels)
+        let mel = self.mel_extractor.mel_extract(input, None, None, Some(true));
+        let mel = mel.reversed_axes().as_standard_layout().insert_axis(Axis(0)).to_owned();
+
+        // exported fcpe models don't agree on tensor names, so go by position
+        let output = self.session.run(ort::inputs![mel]?)?;
+        let latent = output[0]
+            .try_extract_tensor::<f32>()?
+            .into_dimensionality::<ndarray::Ix3>()?;
+
+        Ok(self.decode(latent.index_axis(Axis(0), 0), threshold))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_f0_to_cent() {
+        assert!((f0_to_cent(20.0) - 1200.0).abs() < 1e-3);
+        assert!((f0_to_cent(440.0) - 6551.3179).abs() < 1e-2);
+    }
+}
@@ -1,9 +1,20 @@
 use ndarray::Array1;
+use rvc_common::enums::AutotuneScale;
 
-use self::rmvpe::Rmvpe;
+use self::{crepe::Crepe, fcpe::Fcpe, rmvpe::Rmvpe, world::World};
 
+pub mod crepe;
+pub mod fcpe;
 pub mod rmvpe;
+pub mod world;
  
+/// Samples every extractor analyses to cover a new block of `sample_frame_16k_size`, with some
+/// context before it. Rounded so that rmvpe sees a multiple of 32 frames; sharing it keeps all
+/// outputs aligned with the pitch cache.
+pub(crate) fn f0_extractor_frame(sample_frame_16k_size: usize) -> usize {
+    5120 * ((sample_frame_16k_size + 800 - 1) / 5120 + 1) - 160
+}
+
 pub fn get_f0_post(f0: ndarray::Array1<f32>, f0_mel_min: f32, f0_mel_max: f32) -> (Array1<i32>, Array1<f32>) {
     let f0_coarse = f0.mapv(|x| (x / 700.0 + 1.).ln() * 1127.)
         .mapv(|x| if x <= 0. { x } else { (x - f0_mel_min) * 254. / (f0_mel_max - f0_mel_min) + 1. })
@@ -11,6 +22,151 @@ pub fn get_f0_post(f0: ndarray::Array1<f32>, f0_mel_min: f32, f0_mel_max: f32) -
     (f0_coarse, f0)
 }
 
+/// Limits raw f0 to `f0_range` and shifts it by `pitch_shift` semitones.
+pub fn shape_f0(mut f0: Array1<f32>, pitch_shift: i32, f0_range: (f32, f32)) -> Array1<f32> {
+    let (f0_floor, f0_ceil) = f0_range;
+    // detections outside the range are treated as unvoiced, e.g. sub-harmonics on plosives
+    f0.mapv_inplace(|x| if x < f0_floor || x > f0_ceil { 0.0 } else { x });
+
+    let uppower = 2f32.powf(pitch_shift as f32 / 12.0);
+    f0 * uppower
+}
+
+/// Median over a window of `2 * radius + 1` frames, clamped at the edges. Removes single
+/// frame octave jumps that the extractors produce around onsets.
+pub fn median_filter(f0: ndarray::ArrayView1<f32>, radius: usize) -> Array1<f32> {
+    if radius == 0 || f0.is_empty() {
+        return f0.to_owned();
+    }
+
+    let mut window = Vec::with_capacity(2 * radius + 1);
+    Array1::from_shape_fn(f0.len(), |i| {
+        window.clear();
+        for j in i.saturating_sub(radius)..usize::min(i + radius + 1, f0.len()) {
+            window.push(f0[j]);
+        }
+        window.sort_by(|a, b| a.total_cmp(b));
+        window[window.len() / 2]
+    })
+}
+
+/// Pulls every voiced frame towards the nearest note of `scale` in `key` (0 = C). A strength of
+/// 1 snaps hard, anything below only moves that fraction of the way.
+pub fn snap_to_scale(f0: &mut Array1<f32>, scale: AutotuneScale, key: i32, strength: f32) {
+    let degrees = scale.degrees();
+    if degrees.is_empty() || strength <= 0. {
+        return;
+    }
+
+    f0.mapv_inplace(|x| {
+        if x <= 0. {
+            return x;
+        }
+        let note = 69. + 12. * (x / 440.).log2();
+        let octave = ((note - key as f32) / 12.).floor();
+        let target = (-1..=1)
+            .flat_map(|offset| {
+                degrees
+                    .iter()
+                    .map(move |degree| (octave + offset as f32) * 12. + (key + degree) as f32)
+            })
+            .min_by(|a, b| (a - note).abs().total_cmp(&(b - note).abs()))
+            .unwrap();
+        let note = note + strength.min(1.) * (target - note);
+        440. * 2f32.powf((note - 69.) / 12.)
+    });
+}
+
+// both voiced but further apart than this counts as a disagreement, usually an octave error
+const FUSE_MAX_CENTS: f32 = 100.0;
+
+/// Fuses two contours of the same framing. Where only one is voiced it wins. Where both are,
+/// they are averaged in log frequency, unless they disagree, in which case the one closer to
+/// the previous fused frame is kept.
+pub fn fuse_f0(a: ndarray::ArrayView1<f32>, b: ndarray::ArrayView1<f32>) -> Array1<f32> {
+    let len = usize::min(a.len(), b.len());
+    let mut fused = Array1::zeros(len);
+    let mut previous = 0.0f32;
+    for i in 0..len {
+        let (a, b) = (a[i], b[i]);
+        fused[i] = match (a > 0., b > 0.) {
+            (false, false) => 0.0,
+            (true, false) => a,
+            (false, true) => b,
+            (true, true) => {
+                if (1200. * (a / b).log2()).abs() <= FUSE_MAX_CENTS {
+                    (a * b).sqrt()
+                } else if previous > 0. && (b / previous).log2().abs() < (a / previous).log2().abs() {
+                    b
+                } else {
+                    a
+                }
+            }
+        };
+        if fused[i] > 0. {
+            previous = fused[i];
+        }
+    }
+    fused
+}
+
 pub enum F0Algorithm {
     Rmvpe(Rmvpe),
+    Crepe(Crepe),
+    Fcpe(Fcpe),
+    World(World),
+    Hybrid(Rmvpe, Fcpe),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snap_to_scale() {
+        // a little sharp of A4, and C#4 which is not in C major
+        let mut f0 = ndarray::arr1(&[0.0, 445.0, 277.18]);
+        snap_to_scale(&mut f0, AutotuneScale::Major, 0, 1.0);
+        assert_eq!(f0[0], 0.0);
+        assert!((f0[1] - 440.0).abs() < 1e-2);
+        assert!((f0[2] - 261.63).abs() < 1e-1 || (f0[2] - 293.66).abs() < 1e-1);
+
+        let mut f0 = ndarray::arr1(&[445.0]);
+        snap_to_scale(&mut f0, AutotuneScale::Chromatic, 0, 0.5);
+        assert!(f0[0] > 440.0 && f0[0] < 445.0);
+    }
+
+    #[test]
+    fn test_fuse_f0() {
+        let a = ndarray::arr1(&[0.0, 100.0, 100.0, 200.0, 0.0]);
+        let b = ndarray::arr1(&[150.0, 0.0, 106.0, 100.0, 0.0]);
+        let fused = fuse_f0(a.view(), b.view());
+        assert_eq!(fused[0], 150.0);
+        assert_eq!(fused[1], 100.0);
+        assert!((fused[2] - (100.0f32 * 106.0).sqrt()).abs() < 1e-3);
+        // an octave apart, the one continuing the contour wins
+        assert_eq!(fused[3], 100.0);
+        assert_eq!(fused[4], 0.0);
+    }
+
+    #[test]
+    fn test_shape_f0() {
+        let f0 = ndarray::arr1(&[0.0, 30.0, 220.0, 440.0, 2000.0]);
+        // a fifth up
+        let shaped = shape_f0(f0, 7, (50.0, 1100.0));
+        assert_eq!(&shaped.to_vec()[..2], &[0.0, 0.0]);
+        assert!((shaped[2] - 329.63).abs() < 1e-2);
+        assert!((shaped[3] - 659.26).abs() < 1e-2);
+        assert_eq!(shaped[4], 0.0);
+
+        let shaped = shape_f0(ndarray::arr1(&[220.0]), -12, (50.0, 1100.0));
+        assert!((shaped[0] - 110.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_median_filter() {
+        let f0 = ndarray::arr1(&[100.0, 100.0, 200.0, 100.0, 100.0, 0.0, 100.0]);
+        assert_eq!(median_filter(f0.view(), 0), f0);
+        assert_eq!(median_filter(f0.view(), 1), ndarray::arr1(&[100.0, 100.0, 100.0, 100.0, 100.0, 100.0, 100.0]));
+    }
 }
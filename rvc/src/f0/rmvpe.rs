@@ -8,6 +8,8 @@ use rustfft::{num_traits::Zero, FftPlanner};
 
 use rvc_common::errors::RvcInferError;
 
+use super::f0_extractor_frame;
+
 
 pub struct Rmvpe {
     session: ort::Session,
@@ -15,7 +17,7 @@ pub struct Rmvpe {
     cents_mapping: ndarray::Array1<f32>,
 }
 
-struct MelSpectrogram {
+pub(super) struct MelSpectrogram {
     mel_basis: ndarray::Array2<f32>,
     fft_size: usize,
     win_length: usize,
@@ -30,7 +32,7 @@ fn get_hann_window(window_length: usize) -> ndarray::Array1<f32> {
     })
 }
 
-fn get_hann_window_periodic(window_length: usize) -> ndarray::Array1<f32> {
+pub(crate) fn get_hann_window_periodic(window_length: usize) -> ndarray::Array1<f32> {
     ndarray::Array1::from_shape_fn(window_length, |i| {
         0.5 * (1.0 - (2.0 * std::f64::consts::PI * i as f64 / ((window_length + 1) as f64 - 1.0)).cos() as f32)
     })
@@ -77,7 +79,7 @@ fn unfold_permute_forward_transform(input_data: ndarray::ArrayView1<f32>, filter
     unfolded
 }
 
-fn stft(signal: ndarray::ArrayView1<f32>, fft_size: usize, hop_length: usize, window: ndarray::ArrayView1<f32>, center: bool) -> ndarray::Array2<f32> {
+pub(crate) fn stft(signal: ndarray::ArrayView1<f32>, fft_size: usize, hop_length: usize, window: ndarray::ArrayView1<f32>, center: bool) -> ndarray::Array2<f32> {
     let mut planner = FftPlanner::new();
     let fft = planner.plan_fft_forward(fft_size);
 
@@ -115,7 +117,7 @@ fn stft(signal: ndarray::ArrayView1<f32>, fft_size: usize, hop_length: usize, wi
     input.slice(s![..N, ..]).mapv(|x| x.norm_sqr().sqrt())
 }
 
-fn to_local_average_cents(salience: ndarray::ArrayView2<f32>, cents_mapping: ndarray::ArrayView1<f32>, threshold: f32) -> ndarray::Array1<f32> {
+pub(super) fn to_local_average_cents(salience: ndarray::ArrayView2<f32>, cents_mapping: ndarray::ArrayView1<f32>, threshold: f32) -> ndarray::Array1<f32> {
     let mut salience_padded = ndarray::Array2::zeros((salience.nrows(), salience.ncols() + 8));
     salience_padded.slice_mut(s![.., 4..-4]).assign(&salience);
 
@@ -133,7 +135,7 @@ fn to_local_average_cents(salience: ndarray::ArrayView2<f32>, cents_mapping: nda
 }
 
 impl MelSpectrogram {
-    fn new(
+    pub(super) fn new(
         fft_size: usize,
         sample_rate: usize,
         n_mels: usize,
@@ -156,7 +158,7 @@ impl MelSpectrogram {
         }
     }
 
-    fn mel_extract(
+    pub(super) fn mel_extract(
         &mut self,
         input: ndarray::ArrayView1<f32>,
         keyshift: Option<i32>,
@@ -253,7 +255,7 @@ impl Rmvpe {
         sample_frame_16k_size: usize,
         threshold: f32
     ) -> Result<ndarray::Array1<f32>, RvcInferError> {
-        let f0_extractor_frame = 5120 * ((sample_frame_16k_size + 800 - 1) / 5120 + 1) - 160;
+        let f0_extractor_frame = f0_extractor_frame(sample_frame_16k_size);
         let input = input.slice(s![input.len() - f0_extractor_frame..]);
         let mel = self.mel_extractor.mel_extract(input, None, None, Some(true));
         let hidden = self.mel2hidden(mel)?.remove_axis(Axis(0));
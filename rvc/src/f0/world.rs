@@ -0,0 +1,64 @@
+use ndarray::s;
+use rsworld_sys::{DioOption, HarvestOption};
+
+use rvc_common::errors::RvcInferError;
+
+use super::f0_extractor_frame;
+
+const SAMPLE_RATE: i32 = 16000;
+// one f0 value every 10 ms, the frame rate of every other extractor
+const FRAME_PERIOD: f64 = 10.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WorldMethod {
+    Dio,
+    Harvest,
+}
+
+/// Pitch extraction with the WORLD vocoder's estimators. Runs on the CPU and needs no model.
+pub struct World {
+    method: WorldMethod,
+}
+
+impl World {
+    pub fn new(method: WorldMethod) -> Self {
+        World { method }
+    }
+
+    pub fn pitch(
+        &mut self,
+        input: ndarray::ArrayView1<f32>,
+        sample_frame_16k_size: usize,
+        f0_floor: f64,
+        f0_ceil: f64,
+    ) -> Result<ndarray::Array1<f32>, RvcInferError> {
+        let f0_extractor_frame = f0_extractor_frame(sample_frame_16k_size);
+        let input: Vec<f64> = input
+            .slice(s![input.len() - f0_extractor_frame..])
+            .iter()
+            .map(|&x| x as f64)
+            .collect();
+
+        let f0 = match self.method {
+            WorldMethod::Dio => {
+                let mut option = DioOption::new();
+                option.frame_period = FRAME_PERIOD;
+                option.f0_floor = f0_floor;
+                option.f0_ceil = f0_ceil;
+                let (temporal_positions, f0) = rsworld::dio(&input, SAMPLE_RATE, &option);
+                // dio alone is coarse, stonemask refines it around each estimate
+                rsworld::stonemask(&input, SAMPLE_RATE, &temporal_positions, &f0)
+            }
+            WorldMethod::Harvest => {
+                let mut option = HarvestOption::new();
+                option.frame_period = FRAME_PERIOD;
+                option.f0_floor = f0_floor;
+                option.f0_ceil = f0_ceil;
+                let (_, f0) = rsworld::harvest(&input, SAMPLE_RATE, &option);
+                f0
+            }
+        };
+
+        Ok(f0.into_iter().map(|x| x as f32).collect())
+    }
+}
@@ -0,0 +1,110 @@
+use ndarray::{Array1, Array2, ArrayView2, Axis};
+use ndarray_rand::rand::{rngs::SmallRng, seq::index::sample, Rng, SeedableRng};
+
+use super::ivf_flat::write_ivf_flat;
+
+// faiss trains each centroid on at most this many points
+const MAX_POINTS_PER_CENTROID: usize = 256;
+const KMEANS_ITERATIONS: usize = 10;
+// rows assigned per matrix product, bounds the (rows, lists) distance matrix
+const ASSIGN_CHUNK: usize = 4096;
+
+/// The list count RVC WebUI trains for `n` features.
+fn ivf_list_count(n: usize) -> usize {
+    usize::min((16.0 * (n as f64).sqrt()) as usize, n / 39).max(1)
+}
+
+/// Trains an `IVF<n>,Flat` index on `(frames, channels)` features and serializes it the way
+/// faiss' `write_index` does, so the file loads in RVC WebUI as well.
+pub fn build_index(features: ArrayView2<f32>) -> Vec<u8> {
+    let nlist = usize::min(ivf_list_count(features.nrows()), features.nrows().max(1));
+    let centroids = kmeans(features, nlist, KMEANS_ITERATIONS);
+    let assignments = assign(features, centroids.view());
+    write_ivf_flat(features, centroids.view(), &assignments)
+}
+
+fn kmeans(data: ArrayView2<f32>, k: usize, iterations: usize) -> Array2<f32> {
+    let mut rng = SmallRng::seed_from_u64(1234);
+    let n = data.nrows();
+    if n == 0 {
+        return Array2::zeros((k, data.ncols()));
+    }
+
+    let training = if n > k * MAX_POINTS_PER_CENTROID {
+        data.select(Axis(0), &sample(&mut rng, n, k * MAX_POINTS_PER_CENTROID).into_vec())
+    } else {
+        data.to_owned()
+    };
+
+    let mut centroids = training.select(Axis(0), &sample(&mut rng, training.nrows(), k).into_vec());
+    for _ in 0..iterations {
+        let assignments = assign(training.view(), centroids.view());
+
+        let mut sums = Array2::<f32>::zeros(centroids.raw_dim());
+        let mut counts = vec![0usize; k];
+        for (row, &list) in training.rows().into_iter().zip(&assignments) {
+            let mut sum = sums.row_mut(list);
+            sum += &row;
+            counts[list] += 1;
+        }
+
+        for (list, count) in counts.into_iter().enumerate() {
+            if count > 0 {
+                let mean = &sums.row(list) / count as f32;
+                centroids.row_mut(list).assign(&mean);
+            } else {
+                // an empty list restarts from a random point
+                centroids.row_mut(list).assign(&training.row(rng.gen_range(0..training.nrows())));
+            }
+        }
+    }
+    centroids
+}
+
+/// The nearest centroid of every row by L2 distance.
+fn assign(data: ArrayView2<f32>, centroids: ArrayView2<f32>) -> Vec<usize> {
+    let centroid_norms: Array1<f32> = centroids.rows().into_iter().map(|centroid| centroid.dot(&centroid)).collect();
+
+    let mut assignments = Vec::with_capacity(data.nrows());
+    for chunk in data.axis_chunks_iter(Axis(0), ASSIGN_CHUNK) {
+        // |x - c|^2 = |x|^2 - 2 x.c + |c|^2, and |x|^2 doesn't change the order
+        let products = chunk.dot(&centroids.t());
+        for row in products.rows() {
+            let (nearest, _) = row
+                .iter()
+                .zip(centroid_norms.iter())
+                .map(|(product, norm)| norm - 2.0 * product)
+                .enumerate()
+                .fold((0, f32::INFINITY), |best, (list, distance)| if distance < best.1 { (list, distance) } else { best });
+            assignments.push(nearest);
+        }
+    }
+    assignments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::ivf_flat::IvfFlatIndex;
+    use rvc_common::enums::RetrievalMetric;
+
+    #[test]
+    fn test_build_index_round_trip() {
+        // two well separated blobs
+        let features = Array2::from_shape_fn((160, 4), |(row, col)| {
+            let center = if row % 2 == 0 { 0.0 } else { 10.0 };
+            center + ((row * 7 + col * 3) % 11) as f32 * 0.01
+        });
+
+        let bytes = build_index(features.view());
+        let index = IvfFlatIndex::new(bytes.as_slice()).unwrap();
+        assert_eq!(index.dim(), 4);
+
+        for id in 0..features.nrows() {
+            assert_eq!(index.vector(id).collect::<Vec<_>>(), features.row(id).to_vec());
+        }
+        // the probed list lies in the blob of the query
+        let nearest = index.search(features.row(57), 1, RetrievalMetric::Auto);
+        assert!(nearest[0].1 < 1.0);
+    }
+}
@@ -0,0 +1,216 @@
+use ndarray::{Array1, ArrayView2};
+use ort::Session;
+
+use rvc_common::errors::RvcInferError;
+
+use crate::models::load_model_from_memory_on_cuda;
+
+use super::ivf_flat::IvfFlatIndex;
+
+// below this many vectors the CPU search is quick enough to not be worth the upload
+pub(super) const GPU_SEARCH_MIN_VECTORS: usize = 20_000;
+const IR_VERSION: i64 = 7;
+const OPSET_VERSION: i64 = 13;
+// TensorProto.DataType
+const FLOAT: i64 = 1;
+const INT64: i64 = 7;
+// AttributeProto.AttributeType
+const ATTRIBUTE_INT: i64 = 2;
+const ATTRIBUTE_INTS: i64 = 7;
+
+/// Exhaustive nearest neighbour search on CUDA, as an ONNX graph holding every stored vector:
+/// one matrix product of the queries against all of them, then TopK. Unlike the CPU search it
+/// compares against every list rather than the probed ones.
+pub struct GpuSearch {
+    session: Session,
+    len: usize,
+}
+
+impl GpuSearch {
+    /// On CUDA device `device_id`, `None` when CUDA is not available.
+    pub fn new<B: AsRef<[u8]>>(index: &IvfFlatIndex<B>, device_id: i32) -> Result<Option<Self>, RvcInferError> {
+        let model = search_model(index);
+        Ok(load_model_from_memory_on_cuda(&model, device_id)?.map(|session| GpuSearch {
+            session,
+            len: index.len(),
+        }))
+    }
+
+    /// The `k` nearest stored vectors of every row of `query` as `(id, squared distance)`,
+    /// nearest first.
+    pub fn search(&self, query: ArrayView2<f32>, k: usize) -> Result<Vec<Vec<(usize, f32)>>, RvcInferError> {
+        let k = usize::min(k, self.len);
+        let outputs = self.session.run(ort::inputs![
+            "query" => query.as_standard_layout().into_owned(),
+            "k" => Array1::from_elem(1, k as i64),
+        ]?)?;
+        let distances = outputs["distances"].try_extract_tensor::<f32>()?.into_dimensionality::<ndarray::Ix2>()?;
+        let ids = outputs["ids"].try_extract_tensor::<i64>()?.into_dimensionality::<ndarray::Ix2>()?;
+
+        Ok(ids
+            .rows()
+            .into_iter()
+            .zip(distances.rows())
+            .map(|(ids, distances)| {
+                ids.iter()
+                    .zip(distances.iter())
+                    .map(|(&id, &distance)| (id as usize, distance.max(0.0)))
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+/// `|q - x|^2 = |q|^2 - 2 q.x + |x|^2` for every query and stored vector, with the stored side
+/// transposed and its norms precomputed as initializers.
+fn search_model<B: AsRef<[u8]>>(index: &IvfFlatIndex<B>) -> Vec<u8> {
+    let (len, dim) = (index.len(), index.dim());
+
+    let mut vectors_t = vec![0f32; dim * len];
+    let mut norms = Vec::with_capacity(len);
+    for id in 0..len {
+        let mut norm = 0.0;
+        for (channel, value) in index.vector(id).enumerate() {
+            vectors_t[channel * len + id] = value;
+            norm += value * value;
+        }
+        norms.push(norm);
+    }
+
+    let mut graph = Vec::new();
+    node(&mut graph, "MatMul", &["query", "vectors_t"], &["products"], &[]);
+    node(&mut graph, "Mul", &["products", "minus_two"], &["scaled"], &[]);
+    node(&mut graph, "Add", &["scaled", "norms"], &["partial"], &[]);
+    node(&mut graph, "TopK", &["partial", "k"], &["nearest", "ids"], &[("axis", Attribute::Int(1)), ("largest", Attribute::Int(0)), ("sorted", Attribute::Int(1))]);
+    node(&mut graph, "ReduceSumSquare", &["query"], &["query_norms"], &[("axes", Attribute::Ints(&[1])), ("keepdims", Attribute::Int(1))]);
+    node(&mut graph, "Add", &["nearest", "query_norms"], &["distances"], &[]);
+    string_field(&mut graph, 2, "index_search");
+    tensor(&mut graph, "vectors_t", &[dim as i64, len as i64], &vectors_t);
+    tensor(&mut graph, "norms", &[len as i64], &norms);
+    tensor(&mut graph, "minus_two", &[], &[-2.0]);
+    value_info(&mut graph, 11, "query", FLOAT, &[Dim::Param("frames"), Dim::Value(dim as i64)]);
+    value_info(&mut graph, 11, "k", INT64, &[Dim::Value(1)]);
+    value_info(&mut graph, 12, "distances", FLOAT, &[Dim::Param("frames"), Dim::Param("k")]);
+    value_info(&mut graph, 12, "ids", INT64, &[Dim::Param("frames"), Dim::Param("k")]);
+
+    let mut model = Vec::new();
+    int_field(&mut model, 1, IR_VERSION);
+    string_field(&mut model, 2, "obs-rvc");
+    message_field(&mut model, 7, &graph);
+    let mut opset = Vec::new();
+    string_field(&mut opset, 1, "");
+    int_field(&mut opset, 2, OPSET_VERSION);
+    message_field(&mut model, 8, &opset);
+    model
+}
+
+// just enough protobuf to write an ONNX ModelProto
+
+fn varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn int_field(buf: &mut Vec<u8>, field: u64, value: i64) {
+    varint(buf, field << 3);
+    varint(buf, value as u64);
+}
+
+fn bytes_field(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    varint(buf, (field << 3) | 2);
+    varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn string_field(buf: &mut Vec<u8>, field: u64, value: &str) {
+    bytes_field(buf, field, value.as_bytes());
+}
+
+fn message_field(buf: &mut Vec<u8>, field: u64, message: &[u8]) {
+    bytes_field(buf, field, message);
+}
+
+enum Attribute<'a> {
+    Int(i64),
+    Ints(&'a [i64]),
+}
+
+fn node(graph: &mut Vec<u8>, op_type: &str, inputs: &[&str], outputs: &[&str], attributes: &[(&str, Attribute)]) {
+    let mut node = Vec::new();
+    inputs.iter().for_each(|input| string_field(&mut node, 1, input));
+    outputs.iter().for_each(|output| string_field(&mut node, 2, output));
+    string_field(&mut node, 4, op_type);
+    for (name, value) in attributes {
+        let mut attribute = Vec::new();
+        string_field(&mut attribute, 1, name);
+        match value {
+            Attribute::Int(value) => {
+                int_field(&mut attribute, 3, *value);
+                int_field(&mut attribute, 20, ATTRIBUTE_INT);
+            }
+            Attribute::Ints(values) => {
+                values.iter().for_each(|value| int_field(&mut attribute, 8, *value));
+                int_field(&mut attribute, 20, ATTRIBUTE_INTS);
+            }
+        }
+        message_field(&mut node, 5, &attribute);
+    }
+    message_field(graph, 1, &node);
+}
+
+fn tensor(graph: &mut Vec<u8>, name: &str, dims: &[i64], values: &[f32]) {
+    let mut tensor = Vec::new();
+    dims.iter().for_each(|dim| int_field(&mut tensor, 1, *dim));
+    int_field(&mut tensor, 2, FLOAT);
+    string_field(&mut tensor, 8, name);
+    let raw: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes()).collect();
+    bytes_field(&mut tensor, 9, &raw);
+    message_field(graph, 5, &tensor);
+}
+
+enum Dim<'a> {
+    Value(i64),
+    Param(&'a str),
+}
+
+fn value_info(graph: &mut Vec<u8>, field: u64, name: &str, elem_type: i64, dims: &[Dim]) {
+    let mut shape = Vec::new();
+    for dim in dims {
+        let mut dimension = Vec::new();
+        match dim {
+            Dim::Value(value) => int_field(&mut dimension, 1, *value),
+            Dim::Param(param) => string_field(&mut dimension, 2, param),
+        }
+        message_field(&mut shape, 1, &dimension);
+    }
+    let mut tensor_type = Vec::new();
+    int_field(&mut tensor_type, 1, elem_type);
+    message_field(&mut tensor_type, 2, &shape);
+    let mut type_proto = Vec::new();
+    message_field(&mut type_proto, 1, &tensor_type);
+
+    let mut value_info = Vec::new();
+    string_field(&mut value_info, 1, name);
+    message_field(&mut value_info, 2, &type_proto);
+    message_field(graph, field, &value_info);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protobuf_encoding() {
+        let mut buf = Vec::new();
+        varint(&mut buf, 300);
+        assert_eq!(buf, [0xac, 0x02]);
+
+        let mut buf = Vec::new();
+        int_field(&mut buf, 1, IR_VERSION);
+        string_field(&mut buf, 2, "ab");
+        assert_eq!(buf, [0x08, 0x07, 0x12, 0x02, b'a', b'b']);
+    }
+}
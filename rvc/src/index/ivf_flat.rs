@@ -0,0 +1,475 @@
+use ndarray::{Array2, ArrayView1, ArrayView2, Axis};
+
+use rvc_common::enums::RetrievalMetric;
+
+// faiss tags every serialized object with a fourcc
+const FOURCC_IVF_FLAT: &[u8; 4] = b"IwFl";
+const FOURCC_FLAT_L2: &[u8; 4] = b"IxF2";
+const FOURCC_FLAT_IP: &[u8; 4] = b"IxFI";
+const FOURCC_ARRAY_INVLISTS: &[u8; 4] = b"ilar";
+const FOURCC_FULL_LIST_SIZES: &[u8; 4] = b"full";
+const FOURCC_SPARSE_LIST_SIZES: &[u8; 4] = b"sprs";
+const METRIC_INNER_PRODUCT: i32 = 0;
+const METRIC_L2: i32 = 1;
+// ids of the hash table direct map follow its (empty) array
+const DIRECT_MAP_HASHTABLE: u8 = 2;
+
+/// The `IVF<n>,Flat` L2 index RVC trains, in faiss's `write_index` format, or the same built
+/// with the inner product metric. A bare `Flat` index or a plain feature matrix is searched as
+/// one list holding every vector. Only the headers, the quantizer and the ids are read up front; the vectors are decoded from `bytes` as searches reach them, so a memory
+/// mapped file is only paged in where it is used.
+pub struct IvfFlatIndex<B> {
+    bytes: B,
+    dim: usize,
+    centroids: Array2<f32>,
+    // offset of the first vector and the ids of every inverted list
+    lists: Vec<(usize, Vec<usize>)>,
+    // offset of every stored vector by its id
+    vector_offsets: Vec<usize>,
+    nprobe: usize,
+    // what the index was built with, inner product indices are searched by cosine similarity
+    metric: RetrievalMetric,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| format!("truncated at byte {}", self.position))?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn fourcc(&mut self) -> Result<[u8; 4], String> {
+        Ok(self.take(4)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn size(&mut self) -> Result<usize, String> {
+        usize::try_from(self.u64()?).map_err(|e| e.to_string())
+    }
+
+    // sizes and element counts are u64, so a corrupt one must not turn into a huge allocation
+    fn len_of(&mut self, element_size: usize) -> Result<usize, String> {
+        let len = self.size()?;
+        if len.saturating_mul(element_size) > self.bytes.len() - self.position {
+            return Err(format!("length {} out of range at byte {}", len, self.position));
+        }
+        Ok(len)
+    }
+
+    fn f32s(&mut self, len: usize) -> Result<Vec<f32>, String> {
+        Ok(self
+            .take(len * 4)?
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect())
+    }
+
+    fn u64s(&mut self, len: usize) -> Result<Vec<u64>, String> {
+        Ok(self
+            .take(len * 8)?
+            .chunks_exact(8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+            .collect())
+    }
+
+    /// `read_index_header`: dimension, vector count and metric, the rest is skipped.
+    fn index_header(&mut self) -> Result<(usize, usize, RetrievalMetric), String> {
+        let dim = usize::try_from(self.i32()?).map_err(|e| e.to_string())?;
+        let ntotal = self.size()?;
+        // two unused words and `is_trained`
+        self.take(8 + 8 + 1)?;
+        let metric = match self.i32()? {
+            METRIC_L2 => RetrievalMetric::L2,
+            METRIC_INNER_PRODUCT => RetrievalMetric::Cosine,
+            metric_type => return Err(format!("unsupported metric type {}, expected L2 or inner product", metric_type)),
+        };
+        Ok((dim, ntotal, metric))
+    }
+
+    /// An `IndexFlatL2` or `IndexFlatIP` quantizer as `(nlist, dim)` centroids.
+    fn flat_quantizer(&mut self) -> Result<Array2<f32>, String> {
+        let fourcc = self.fourcc()?;
+        if &fourcc != FOURCC_FLAT_L2 && &fourcc != FOURCC_FLAT_IP {
+            return Err(format!("unsupported flat index {:?}", String::from_utf8_lossy(&fourcc)));
+        }
+        let (dim, ntotal, _) = self.index_header()?;
+        let len = self.len_of(4)?;
+        if len != dim * ntotal {
+            return Err(format!("{} floats for {} vectors of {}", len, ntotal, dim));
+        }
+        Array2::from_shape_vec((ntotal, dim), self.f32s(len)?).map_err(|e| e.to_string())
+    }
+}
+
+impl<B: AsRef<[u8]>> IvfFlatIndex<B> {
+    pub fn new(bytes: B) -> Result<Self, String> {
+        let (dim, centroids, lists, vector_offsets, nprobe, metric) = Self::parse(bytes.as_ref())?;
+        Ok(IvfFlatIndex {
+            bytes,
+            dim,
+            centroids,
+            lists,
+            vector_offsets,
+            nprobe,
+            metric,
+        })
+    }
+
+    /// Brute-force search over `ntotal` row-major vectors of `dim` floats from `data_start`,
+    /// by L2 unless searched otherwise.
+    pub fn flat(bytes: B, data_start: usize, ntotal: usize, dim: usize) -> Result<Self, String> {
+        if bytes.as_ref().len().saturating_sub(data_start) < ntotal * dim * 4 {
+            return Err(format!("data ends before {} vectors of {}", ntotal, dim));
+        }
+        let (centroids, lists, vector_offsets) = Self::flat_layout(data_start, ntotal, dim);
+        Ok(IvfFlatIndex {
+            bytes,
+            dim,
+            centroids,
+            lists,
+            vector_offsets,
+            nprobe: 1,
+            metric: RetrievalMetric::L2,
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn flat_layout(start: usize, ntotal: usize, dim: usize) -> (Array2<f32>, Vec<(usize, Vec<usize>)>, Vec<usize>) {
+        let vector_offsets = (0..ntotal).map(|id| start + id * dim * 4).collect();
+        (Array2::zeros((1, dim)), vec![(start, (0..ntotal).collect())], vector_offsets)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn parse(bytes: &[u8]) -> Result<(usize, Array2<f32>, Vec<(usize, Vec<usize>)>, Vec<usize>, usize, RetrievalMetric), String> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        let fourcc = reader.fourcc()?;
+        if &fourcc == FOURCC_FLAT_L2 || &fourcc == FOURCC_FLAT_IP {
+            let (dim, ntotal, metric) = reader.index_header()?;
+            if reader.len_of(4)? != dim * ntotal {
+                return Err(format!("vector data differs from {} vectors of {}", ntotal, dim));
+            }
+            let start = reader.position;
+            reader.take(ntotal * dim * 4)?;
+            let (centroids, lists, vector_offsets) = Self::flat_layout(start, ntotal, dim);
+            return Ok((dim, centroids, lists, vector_offsets, 1, metric));
+        }
+        if &fourcc != FOURCC_IVF_FLAT {
+            return Err(format!(
+                "unsupported index type {:?}, only IVF-Flat and Flat indices can be read",
+                String::from_utf8_lossy(&fourcc)
+            ));
+        }
+
+        let (dim, ntotal, metric) = reader.index_header()?;
+        let nlist = reader.size()?;
+        let nprobe = reader.size()?;
+        let centroids = reader.flat_quantizer()?;
+        if centroids.nrows() != nlist || centroids.ncols() != dim {
+            return Err(format!("quantizer of {:?} for {} lists of {}", centroids.dim(), nlist, dim));
+        }
+
+        // direct map: type, id array and, for a hash table, its (id, offset) pairs
+        let direct_map_type = reader.u8()?;
+        let direct_map_len = reader.len_of(8)?;
+        reader.take(direct_map_len * 8)?;
+        if direct_map_type == DIRECT_MAP_HASHTABLE {
+            let pairs = reader.len_of(16)?;
+            reader.take(pairs * 16)?;
+        }
+
+        let fourcc = reader.fourcc()?;
+        if &fourcc != FOURCC_ARRAY_INVLISTS {
+            return Err(format!("unsupported inverted lists {:?}", String::from_utf8_lossy(&fourcc)));
+        }
+        if reader.size()? != nlist {
+            return Err("inverted list count differs from the quantizer".to_string());
+        }
+        let code_size = reader.size()?;
+        if code_size != dim * 4 {
+            return Err(format!("code size {} is not a flat vector of {}", code_size, dim));
+        }
+
+        let mut list_sizes = vec![0; nlist];
+        let size_layout = reader.fourcc()?;
+        let sizes_len = reader.len_of(8)?;
+        let sizes = reader.u64s(sizes_len)?;
+        match &size_layout {
+            FOURCC_FULL_LIST_SIZES if sizes.len() == nlist => {
+                for (list_size, size) in list_sizes.iter_mut().zip(sizes) {
+                    *list_size = size as usize;
+                }
+            }
+            // (list, size) pairs of the non-empty lists
+            FOURCC_SPARSE_LIST_SIZES if sizes.len() % 2 == 0 => {
+                for pair in sizes.chunks_exact(2) {
+                    let list = pair[0] as usize;
+                    *list_sizes.get_mut(list).ok_or_else(|| format!("list {} out of range", list))? = pair[1] as usize;
+                }
+            }
+            _ => return Err(format!("malformed list sizes {:?}", String::from_utf8_lossy(&size_layout))),
+        }
+
+        let mut vector_offsets = vec![0; ntotal];
+        let mut lists = Vec::with_capacity(nlist);
+        for list_size in list_sizes {
+            // all vectors of a list, then all its ids
+            if list_size.saturating_mul(code_size + 8) > bytes.len() - reader.position {
+                return Err(format!("list of {} vectors out of range", list_size));
+            }
+            let start = reader.position;
+            reader.take(list_size * code_size)?;
+
+            let mut ids = Vec::with_capacity(list_size);
+            for (i, id) in reader.u64s(list_size)?.into_iter().enumerate() {
+                let id = usize::try_from(id).ok().filter(|&id| id < ntotal).ok_or_else(|| format!("id {} out of range", id))?;
+                vector_offsets[id] = start + i * code_size;
+                ids.push(id);
+            }
+            lists.push((start, ids));
+        }
+
+        Ok((dim, centroids, lists, vector_offsets, nprobe.clamp(1, nlist.max(1)), metric))
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// The metric the index was built with, `L2` for a feature matrix.
+    pub fn metric(&self) -> RetrievalMetric {
+        self.metric
+    }
+
+    /// How many vectors are stored.
+    pub fn len(&self) -> usize {
+        self.vector_offsets.len()
+    }
+
+    fn vector_at(&self, offset: usize) -> impl Iterator<Item = f32> + '_ {
+        self.bytes.as_ref()[offset..offset + self.dim * 4]
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// The stored vector of a search result, what faiss' `reconstruct` returns.
+    pub fn vector(&self, id: usize) -> impl Iterator<Item = f32> + '_ {
+        self.vector_at(self.vector_offsets[id])
+    }
+
+    /// The `k` nearest stored vectors in the `nprobe` closest lists as `(id, distance)`, nearest
+    /// first: the squared distance for `L2`, one minus the cosine similarity for `Cosine`, and
+    /// `Auto` the metric of the index. Fewer come back when the probed lists hold fewer vectors.
+    pub fn search(&self, query: ArrayView1<f32>, k: usize, metric: RetrievalMetric) -> Vec<(usize, f32)> {
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let metric = match metric {
+            RetrievalMetric::Auto => self.metric,
+            metric => metric,
+        };
+        let query_norm = query.dot(&query).sqrt();
+        let distance_to = |vector: &mut dyn Iterator<Item = f32>| match metric {
+            RetrievalMetric::Cosine => cosine_distance(query, query_norm, vector),
+            _ => squared_distance(query, vector),
+        };
+
+        let mut probes: Vec<(usize, f32)> = self
+            .centroids
+            .axis_iter(Axis(0))
+            .map(|centroid| distance_to(&mut centroid.iter().copied()))
+            .enumerate()
+            .collect();
+        probes.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        let code_size = self.dim * 4;
+        let mut nearest: Vec<(usize, f32)> = Vec::with_capacity(k + 1);
+        for &(list, _) in probes.iter().take(self.nprobe) {
+            let (start, ids) = &self.lists[list];
+            for (i, &id) in ids.iter().enumerate() {
+                let distance = distance_to(&mut self.vector_at(start + i * code_size));
+                if nearest.len() == k && distance >= nearest[k - 1].1 {
+                    continue;
+                }
+                let position = nearest.partition_point(|&(_, nearer)| nearer <= distance);
+                nearest.insert(position, (id, distance));
+                nearest.truncate(k);
+            }
+        }
+        nearest
+    }
+}
+
+fn write_index_header(bytes: &mut Vec<u8>, dim: usize, ntotal: usize) {
+    bytes.extend((dim as i32).to_le_bytes());
+    bytes.extend((ntotal as u64).to_le_bytes());
+    // the two unused words faiss writes, then `is_trained`
+    bytes.extend((1u64 << 20).to_le_bytes());
+    bytes.extend((1u64 << 20).to_le_bytes());
+    bytes.push(1);
+    bytes.extend(METRIC_L2.to_le_bytes());
+}
+
+fn write_floats<'a>(bytes: &mut Vec<u8>, values: impl IntoIterator<Item = &'a f32>) {
+    values.into_iter().for_each(|value| bytes.extend(value.to_le_bytes()));
+}
+
+/// Serializes an IVF-Flat index of `features` with `assignments[id]` the list of each row,
+/// in faiss's `write_index` format.
+pub(super) fn write_ivf_flat(features: ArrayView2<f32>, centroids: ArrayView2<f32>, assignments: &[usize]) -> Vec<u8> {
+    let (ntotal, dim) = features.dim();
+    let nlist = centroids.nrows();
+
+    let mut bytes = FOURCC_IVF_FLAT.to_vec();
+    write_index_header(&mut bytes, dim, ntotal);
+    bytes.extend((nlist as u64).to_le_bytes());
+    // nprobe, what RVC WebUI sets
+    bytes.extend(1u64.to_le_bytes());
+
+    bytes.extend(FOURCC_FLAT_L2);
+    write_index_header(&mut bytes, dim, nlist);
+    bytes.extend(((nlist * dim) as u64).to_le_bytes());
+    write_floats(&mut bytes, centroids.iter());
+
+    // no direct map
+    bytes.push(0);
+    bytes.extend(0u64.to_le_bytes());
+
+    let mut lists = vec![Vec::new(); nlist];
+    for (id, &list) in assignments.iter().enumerate() {
+        lists[list].push(id);
+    }
+
+    bytes.extend(FOURCC_ARRAY_INVLISTS);
+    bytes.extend((nlist as u64).to_le_bytes());
+    bytes.extend(((dim * 4) as u64).to_le_bytes());
+    bytes.extend(FOURCC_FULL_LIST_SIZES);
+    bytes.extend((nlist as u64).to_le_bytes());
+    for list in &lists {
+        bytes.extend((list.len() as u64).to_le_bytes());
+    }
+    for list in &lists {
+        for &id in list {
+            write_floats(&mut bytes, features.row(id).iter());
+        }
+        for &id in list {
+            bytes.extend((id as u64).to_le_bytes());
+        }
+    }
+    bytes
+}
+
+fn squared_distance(a: ArrayView1<f32>, b: impl Iterator<Item = f32>) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+fn cosine_distance(a: ArrayView1<f32>, a_norm: f32, b: impl Iterator<Item = f32>) -> f32 {
+    let (dot, b_norm_squared) = a.iter().zip(b).fold((0.0, 0.0), |(dot, norm), (a, b)| (dot + a * b, norm + b * b));
+    1.0 - dot / (a_norm * b_norm_squared.sqrt()).max(1e-12)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index_header(bytes: &mut Vec<u8>, dim: i32, ntotal: u64) {
+        bytes.extend(dim.to_le_bytes());
+        bytes.extend(ntotal.to_le_bytes());
+        bytes.extend([0u8; 17]);
+        bytes.extend(METRIC_L2.to_le_bytes());
+    }
+
+    fn floats(bytes: &mut Vec<u8>, values: &[f32]) {
+        values.iter().for_each(|value| bytes.extend(value.to_le_bytes()));
+    }
+
+    #[test]
+    fn test_parse_and_search_ivf_flat() {
+        // two lists around 0 and 10, the second one holding ids 1 and 2
+        let mut bytes = FOURCC_IVF_FLAT.to_vec();
+        index_header(&mut bytes, 2, 3);
+        bytes.extend(2u64.to_le_bytes());
+        bytes.extend(1u64.to_le_bytes());
+        bytes.extend(FOURCC_FLAT_L2);
+        index_header(&mut bytes, 2, 2);
+        bytes.extend(4u64.to_le_bytes());
+        floats(&mut bytes, &[0.0, 0.0, 10.0, 10.0]);
+        bytes.push(0);
+        bytes.extend(0u64.to_le_bytes());
+        bytes.extend(FOURCC_ARRAY_INVLISTS);
+        bytes.extend(2u64.to_le_bytes());
+        bytes.extend(8u64.to_le_bytes());
+        bytes.extend(FOURCC_FULL_LIST_SIZES);
+        bytes.extend(2u64.to_le_bytes());
+        bytes.extend(1u64.to_le_bytes());
+        bytes.extend(2u64.to_le_bytes());
+        floats(&mut bytes, &[1.0, 0.0]);
+        bytes.extend(0u64.to_le_bytes());
+        floats(&mut bytes, &[9.0, 10.0, 12.0, 10.0]);
+        bytes.extend(1u64.to_le_bytes());
+        bytes.extend(2u64.to_le_bytes());
+
+        let index = IvfFlatIndex::new(bytes.as_slice()).unwrap();
+        assert_eq!(index.dim(), 2);
+        assert_eq!(index.vector(2).collect::<Vec<_>>(), vec![12.0, 10.0]);
+
+        assert_eq!(index.metric(), RetrievalMetric::L2);
+        let nearest = index.search(ArrayView1::from(&[11.5, 10.0]), 2, RetrievalMetric::Auto);
+        assert_eq!(nearest, vec![(2, 0.25), (1, 6.25)]);
+        // only the closest list is probed
+        assert_eq!(index.search(ArrayView1::from(&[4.0, 4.0]), 2, RetrievalMetric::Auto), vec![(0, 25.0)]);
+
+        assert!(IvfFlatIndex::new(&bytes[..bytes.len() - 1]).is_err());
+        assert!(IvfFlatIndex::new(b"IxPQ".as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_search_feature_matrix() {
+        let bytes = crate::npy::npy_bytes((3, 2), &[0.0, 0.0, 3.0, 4.0, 1.0, 1.0]);
+        let (data_start, (ntotal, dim)) = crate::npy::parse_npy_header(&bytes).unwrap();
+        let index = IvfFlatIndex::flat(bytes.as_slice(), data_start, ntotal, dim).unwrap();
+
+        assert_eq!(index.search(ArrayView1::from(&[3.0, 3.0]), 2, RetrievalMetric::Auto), vec![(1, 1.0), (2, 8.0)]);
+        assert!(IvfFlatIndex::flat(bytes.as_slice(), data_start, ntotal + 1, dim).is_err());
+    }
+
+    #[test]
+    fn test_search_inner_product_index() {
+        let mut bytes = FOURCC_FLAT_IP.to_vec();
+        bytes.extend(2i32.to_le_bytes());
+        bytes.extend(2u64.to_le_bytes());
+        bytes.extend([0u8; 17]);
+        bytes.extend(METRIC_INNER_PRODUCT.to_le_bytes());
+        bytes.extend(4u64.to_le_bytes());
+        floats(&mut bytes, &[10.0, 0.0, 1.0, 1.0]);
+
+        let index = IvfFlatIndex::new(bytes.as_slice()).unwrap();
+        assert_eq!(index.metric(), RetrievalMetric::Cosine);
+        // by angle the far vector along the query is nearest, by L2 the short diagonal one
+        let query = ArrayView1::from(&[1.0, 0.0]);
+        assert_eq!(index.search(query, 1, RetrievalMetric::Auto)[0], (0, 0.0));
+        assert_eq!(index.search(query, 1, RetrievalMetric::L2)[0], (1, 1.0));
+    }
+}
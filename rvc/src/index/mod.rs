@@ -0,0 +1,106 @@
+use std::{fs::File, path::Path};
+
+use memmap2::Mmap;
+use ndarray::{Array2, ArrayView2, Axis};
+
+use rvc_common::{enums::RetrievalMetric, errors::RvcInferError};
+
+use crate::npy::{parse_npy, parse_npy_header};
+
+use self::{
+    gpu::{GpuSearch, GPU_SEARCH_MIN_VECTORS},
+    ivf_flat::IvfFlatIndex,
+};
+
+pub use self::build::build_index;
+
+mod build;
+mod gpu;
+mod ivf_flat;
+
+/// A `(frames, channels)` float32 feature dump as RVC's feature extraction saves it.
+pub fn load_feature_dump(path: &Path) -> Result<Array2<f32>, RvcInferError> {
+    let bytes = std::fs::read(path).map_err(|e| RvcInferError::Index(format!("{}: {}", path.display(), e)))?;
+    parse_npy(&bytes).map_err(|e| RvcInferError::Index(format!("{}: {}", path.display(), e)))
+}
+
+pub struct FeatureIndex {
+    index: IvfFlatIndex<Mmap>,
+    // large indices are searched on the GPU when CUDA is available
+    gpu: Option<GpuSearch>,
+}
+
+impl FeatureIndex {
+    /// Large indices are searched on `cuda_device` if there is one.
+    pub fn load(path: &Path, cuda_device: Option<i32>) -> Result<Self, RvcInferError> {
+        let file = File::open(path)
+            .map_err(|e| RvcInferError::Index(format!("{}: {}", path.display(), e)))?;
+        // indices run to hundreds of megabytes, mapping them only pages in the lists searched.
+        // Training writes them once, nothing changes them while they are mapped.
+        let bytes = unsafe { Mmap::map(&file) }
+            .map_err(|e| RvcInferError::Index(format!("{}: {}", path.display(), e)))?;
+        // feature dumps (`total_fea.npy`, a.k.a. `big_npy`) are searched exhaustively
+        let index = if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("npy")) {
+            parse_npy_header(&bytes)
+                .and_then(|(data_start, (ntotal, dim))| IvfFlatIndex::flat(bytes, data_start, ntotal, dim))
+        } else {
+            IvfFlatIndex::new(bytes)
+        }
+        .map_err(|e| RvcInferError::Index(format!("{}: {}", path.display(), e)))?;
+
+        // the upload reads the whole file, which small indices can spare
+        let gpu = match cuda_device {
+            Some(device_id) if index.len() >= GPU_SEARCH_MIN_VECTORS => {
+                GpuSearch::new(&index, device_id).unwrap_or_else(|e| {
+                    eprintln!("GPU index search unavailable, searching {} on the CPU: {:?}", path.display(), e);
+                    None
+                })
+            }
+            _ => None,
+        };
+
+        Ok(FeatureIndex { index, gpu })
+    }
+
+    pub fn dim(&self) -> usize {
+        self.index.dim()
+    }
+
+    /// For every row of `query`, the mean of its `k` nearest neighbours by `metric` weighted by
+    /// inverse distance to the power of `weight_exponent`. Rows without a full set of neighbours
+    /// are returned unchanged.
+    pub fn retrieve(&self, query: ArrayView2<f32>, k: usize, weight_exponent: f32, metric: RetrievalMetric) -> Result<Array2<f32>, RvcInferError> {
+        let metric = match metric {
+            RetrievalMetric::Auto => self.index.metric(),
+            metric => metric,
+        };
+        // the search graph ranks by L2
+        let neighbours = match &self.gpu {
+            Some(gpu) if metric == RetrievalMetric::L2 => gpu.search(query, k)?,
+            _ => query.axis_iter(Axis(0)).map(|query| self.index.search(query, k, metric)).collect(),
+        };
+
+        let mut retrieved = Array2::zeros(query.raw_dim());
+        for ((query, mut output), nearest) in query.axis_iter(Axis(0)).zip(retrieved.axis_iter_mut(Axis(0))).zip(neighbours) {
+            if nearest.len() < k {
+                output.assign(&query);
+                continue;
+            }
+
+            let weights: Vec<f32> = nearest
+                .iter()
+                .map(|(_, distance)| 1.0 / distance.max(1e-12).powf(weight_exponent))
+                .collect();
+            let weight_sum: f32 = weights.iter().sum();
+
+            // the stored features, a.k.a. `big_npy`
+            for ((id, _), weight) in nearest.iter().zip(weights) {
+                for (output, feature) in output.iter_mut().zip(self.index.vector(*id)) {
+                    *output += weight / weight_sum * feature;
+                }
+            }
+        }
+
+        Ok(retrieved)
+    }
+}
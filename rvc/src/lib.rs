@@ -1,8 +1,17 @@
 mod rvc;
 mod models;
 mod f0;
+mod encoder;
+mod index;
+mod speaker;
+mod model_config;
+mod npy;
+mod quantization;
+mod executor;
 mod ndarray_ext;
 pub use rvc::*;
+pub use models::{usable_devices, SessionConfig};
+pub use index::{build_index, load_feature_dump};
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,56 @@
+use std::path::Path;
+
+// custom model metadata carrying the synthesizer's output sample rate
+const SAMPLE_RATE_METADATA_KEYS: [&str; 2] = ["sample_rate", "sr"];
+
+/// The output sample rate stored in the model's custom metadata.
+pub(crate) fn sample_rate_from_metadata(session: &ort::Session) -> Option<u32> {
+    let metadata = session.metadata().ok()?;
+    SAMPLE_RATE_METADATA_KEYS
+        .into_iter()
+        .find_map(|key| metadata.custom(key).ok().flatten())
+        .and_then(|value| parse_sample_rate_text(&value))
+        .and_then(|sample_rate| u32::try_from(sample_rate).ok())
+        .filter(|&sample_rate| sample_rate > 0)
+}
+
+/// The output sample rate from the JSON saved next to the model, `voice.onnx` comes with
+/// `voice.json`. Either a plain `{"sample_rate": 40000}` or RVC's training config, which keeps
+/// it under `data.sampling_rate`.
+pub(crate) fn sample_rate_from_sidecar(model_path: &Path) -> Option<u32> {
+    let content = std::fs::read_to_string(model_path.with_extension("json")).ok()?;
+    parse_sidecar_sample_rate(&content)
+}
+
+fn parse_sidecar_sample_rate(content: &str) -> Option<u32> {
+    let config: serde_json::Value = serde_json::from_str(content).ok()?;
+    [&config["sample_rate"], &config["sr"], &config["data"]["sampling_rate"]]
+        .into_iter()
+        .find_map(|value| value.as_u64().or_else(|| value.as_str().and_then(parse_sample_rate_text)))
+        .and_then(|sample_rate| u32::try_from(sample_rate).ok())
+        .filter(|&sample_rate| sample_rate > 0)
+}
+
+// RVC names its configs and checkpoints by rate, "40k"
+fn parse_sample_rate_text(text: &str) -> Option<u64> {
+    let text = text.trim();
+    match text.strip_suffix('k') {
+        Some(khz) => khz.parse::<u64>().ok().map(|khz| khz * 1000),
+        None => text.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sidecar_sample_rate() {
+        assert_eq!(parse_sidecar_sample_rate(r#"{"sample_rate": 48000}"#), Some(48000));
+        assert_eq!(parse_sidecar_sample_rate(r#"{"train": {}, "data": {"sampling_rate": 40000}}"#), Some(40000));
+        assert_eq!(parse_sidecar_sample_rate(r#"{"sr": "32k"}"#), Some(32000));
+        assert_eq!(parse_sidecar_sample_rate(r#"{"sr": "44100"}"#), Some(44100));
+        assert_eq!(parse_sidecar_sample_rate(r#"{"version": "v2"}"#), None);
+        assert_eq!(parse_sidecar_sample_rate("not json"), None);
+    }
+}
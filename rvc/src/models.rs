@@ -1,52 +1,271 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use ort::*;
 
-use rvc_common::enums::PitchAlgorithm;
-
-fn get_onnx_session(cache_path: PathBuf, use_tensorrt: bool, use_cudagraph: bool) -> Result<ort::SessionBuilder, ort::Error> {
-    #[cfg(feature = "tensorrt")]
-    if use_tensorrt {
-        return Session::builder()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_execution_providers([
-                TensorRTExecutionProvider::default()
-                    .with_timing_cache(true)
-                    .with_engine_cache(true)
-                    .with_fp16(true)
-                    .with_engine_cache_path(cache_path.to_string_lossy())
-                    .build(),
-                CUDAExecutionProvider::default()
-                    .with_copy_in_default_stream(false)
-                    .with_cuda_graph()
-                    .build(),
-                CPUExecutionProvider::default().build(),
-            ]);
-    } 
-
-    if use_cudagraph {
-        return Session::builder()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_execution_providers([
-                CUDAExecutionProvider::default()
-                    .with_cuda_graph()
-                    .build(),
-                CPUExecutionProvider::default().build(),
-            ]);
+use rvc_common::{
+    enums::{InferenceDevice, DEFAULT_DEVICE_PRIORITY},
+    errors::RvcInferError,
+};
+
+use crate::quantization::{inspect_model, Quantization};
+
+// OpenVINO picks the first of these it finds, the integrated GPU handles dynamic shapes best
+const OPENVINO_DEVICE_TYPE: &str = "AUTO:GPU,NPU,CPU";
+
+/// Options applied to every session the engine builds.
+#[derive(Clone, Debug)]
+pub struct SessionConfig {
+    /// Threads used by onnxruntime within an operator, 0 leaves it to onnxruntime.
+    pub intra_threads: usize,
+    pub device: InferenceDevice,
+    /// Execution providers `Auto` chains, in order.
+    pub device_priority: Vec<InferenceDevice>,
+    /// GPU the CUDA, TensorRT, ROCm and DirectML providers run on, by their own numbering.
+    pub device_id: i32,
+    /// Puts TensorRT ahead of CUDA wherever CUDA runs.
+    pub tensorrt: bool,
+    /// Replays the CUDA kernels of a run as one graph, which only works for models whose shapes
+    /// stay the same between runs.
+    pub cuda_graph: bool,
+    /// Loads the `<name>.fp16.onnx` variant of the synthesizer and the encoder where there is one.
+    pub fp16: bool,
+    /// Loads the `<name>.int8.onnx` variant instead, ahead of an FP16 one.
+    pub int8: bool,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            intra_threads: 0,
+            device: InferenceDevice::Auto,
+            device_priority: DEFAULT_DEVICE_PRIORITY.to_vec(),
+            device_id: 0,
+            tensorrt: false,
+            cuda_graph: false,
+            fp16: false,
+            int8: false,
+        }
     }
+}
 
-    Session::builder()?
-    .with_optimization_level(GraphOptimizationLevel::Level3)?
-    .with_execution_providers([
-        CUDAExecutionProvider::default()
+impl SessionConfig {
+    /// The CUDA device to run on, `None` when the device setting rules CUDA out.
+    pub(crate) fn cuda_device(&self) -> Option<i32> {
+        self.device.allows_cuda().then_some(self.device_id)
+    }
+}
+
+/// Whether the loaded onnxruntime was built with the execution provider of `device`.
+fn is_device_available(device: InferenceDevice) -> Result<bool, ort::Error> {
+    match device {
+        #[cfg(feature = "tensorrt")]
+        InferenceDevice::TensorRt => TensorRTExecutionProvider::default().is_available(),
+        #[cfg(not(feature = "tensorrt"))]
+        InferenceDevice::TensorRt => Ok(false),
+        InferenceDevice::Cuda => CUDAExecutionProvider::default().is_available(),
+        InferenceDevice::Rocm => ROCmExecutionProvider::default().is_available(),
+        InferenceDevice::DirectMl => DirectMLExecutionProvider::default().is_available(),
+        InferenceDevice::CoreMl => CoreMLExecutionProvider::default().is_available(),
+        InferenceDevice::OpenVino => OpenVINOExecutionProvider::default().is_available(),
+        InferenceDevice::Cpu | InferenceDevice::Auto => Ok(true),
+    }
+}
+
+/// The GPU and NPU providers that not only are in the loaded onnxruntime but also register, which
+/// fails when e.g. the CUDA runtime next to it is missing. The CPU always works.
+pub fn usable_devices(config: &SessionConfig) -> Vec<InferenceDevice> {
+    [
+        InferenceDevice::Cuda,
+        InferenceDevice::TensorRt,
+        InferenceDevice::Rocm,
+        InferenceDevice::DirectMl,
+        InferenceDevice::CoreMl,
+        InferenceDevice::OpenVino,
+    ]
+    .into_iter()
+    .filter(|&device| {
+        is_device_available(device).unwrap_or(false)
+            && Session::builder()
+                .and_then(|builder| {
+                    let provider = execution_provider(device, &std::env::temp_dir(), config);
+                    builder.with_execution_providers([provider.error_on_failure()])
+                })
+                .is_ok()
+    })
+    .collect()
+}
+
+/// The providers sessions are built with ahead of the CPU: the available ones of the priority
+/// list for `Auto`, so that nodes one of them cannot run fall through to the next, or else the
+/// selected one. A device the onnxruntime build lacks leaves the CPU alone.
+fn session_devices(config: &SessionConfig) -> Result<Vec<InferenceDevice>, ort::Error> {
+    let candidates = match config.device {
+        InferenceDevice::Auto => config.device_priority.clone(),
+        InferenceDevice::Cpu => Vec::new(),
+        InferenceDevice::TensorRt => vec![InferenceDevice::TensorRt, InferenceDevice::Cuda],
+        device => vec![device],
+    };
+
+    let mut devices = Vec::new();
+    for device in candidates {
+        if devices.contains(&device) || matches!(device, InferenceDevice::Auto | InferenceDevice::Cpu) {
+            continue;
+        }
+        if is_device_available(device)? {
+            devices.push(device);
+        } else if config.device != InferenceDevice::Auto {
+            eprintln!("{} execution provider is not available, running on the CPU", device.to_string());
+        }
+    }
+
+    if config.tensorrt && !devices.contains(&InferenceDevice::TensorRt) {
+        if let Some(cuda) = devices.iter().position(|&device| device == InferenceDevice::Cuda) {
+            if is_device_available(InferenceDevice::TensorRt)? {
+                devices.insert(cuda, InferenceDevice::TensorRt);
+            } else {
+                eprintln!("TensorRT execution provider is not available, running on CUDA");
+            }
+        }
+    }
+    Ok(devices)
+}
+
+#[cfg_attr(not(feature = "tensorrt"), allow(unused_variables))]
+fn execution_provider(device: InferenceDevice, cache_path: &Path, config: &SessionConfig) -> ExecutionProviderDispatch {
+    match device {
+        #[cfg(feature = "tensorrt")]
+        InferenceDevice::TensorRt => TensorRTExecutionProvider::default()
+            .with_device_id(config.device_id)
+            .with_timing_cache(true)
+            .with_engine_cache(true)
+            .with_fp16(true)
+            .with_engine_cache_path(cache_path.to_string_lossy())
+            .build(),
+        // `is_device_available` rules it out
+        #[cfg(not(feature = "tensorrt"))]
+        InferenceDevice::TensorRt => unreachable!(),
+        InferenceDevice::Cuda => {
+            let cuda = CUDAExecutionProvider::default().with_device_id(config.device_id);
+            if config.cuda_graph { cuda.with_cuda_graph() } else { cuda }.build()
+        }
+        // a provider built in whose runtime is missing fails to register, the next one takes over
+        InferenceDevice::Rocm => ROCmExecutionProvider::default()
+            .with_device_id(config.device_id)
             .build(),
-        CPUExecutionProvider::default().build(),
-    ])
+        InferenceDevice::DirectMl => DirectMLExecutionProvider::default()
+            .with_device_id(config.device_id)
+            .build(),
+        // models with dynamic shapes run partly on the CPU, which CoreML hands over by itself
+        InferenceDevice::CoreMl => CoreMLExecutionProvider::default().build(),
+        InferenceDevice::OpenVino => OpenVINOExecutionProvider::default()
+            .with_device_type(OPENVINO_DEVICE_TYPE)
+            .build(),
+        InferenceDevice::Cpu | InferenceDevice::Auto => CPUExecutionProvider::default().build(),
+    }
+}
+
+fn get_onnx_session(cache_path: PathBuf, config: &SessionConfig) -> Result<ort::SessionBuilder, ort::Error> {
+    let builder = Session::builder()?
+        .with_optimization_level(GraphOptimizationLevel::Level3)?;
+    let builder = if config.intra_threads > 0 {
+        builder.with_intra_threads(config.intra_threads)?
+    } else {
+        builder
+    };
 
+    let devices = session_devices(config)?;
+    // DirectML supports neither memory patterns nor parallel execution
+    let builder = if devices.contains(&InferenceDevice::DirectMl) {
+        builder
+            .with_memory_pattern(false)?
+            .with_parallel_execution(false)?
+    } else {
+        builder
+    };
+
+    let mut providers: Vec<ExecutionProviderDispatch> = devices
+        .into_iter()
+        .map(|device| execution_provider(device, &cache_path, config))
+        .collect();
+    providers.push(CPUExecutionProvider::default().build());
+    builder.with_execution_providers(providers)
 }
 
-pub fn load_model_from_file(model_path: PathBuf, cache_path: PathBuf) -> Result<Session, ort::Error> {
-    get_onnx_session(cache_path, false, false)?.commit_from_file(model_path)
+/// The CUDA device the sessions run on first, whose outputs can be read back through page-locked
+/// memory. `None` off CUDA.
+pub(crate) fn pinned_output_device(config: &SessionConfig) -> Option<i32> {
+    match session_devices(config).ok()?.first()? {
+        InferenceDevice::Cuda | InferenceDevice::TensorRt => Some(config.device_id),
+        _ => None,
+    }
+}
+
+/// Runs `session` through an IOBinding that puts every output in page-locked host memory of CUDA
+/// device `device_id`, which the GPU writes into directly instead of through a staging copy. The
+/// outputs borrow the binding, so they are read within `extract`.
+pub(crate) fn run_with_pinned_outputs<T>(
+    session: &Session,
+    inputs: Vec<(&str, DynValue)>,
+    device_id: i32,
+    extract: impl FnOnce(&SessionOutputs) -> Result<T, RvcInferError>,
+) -> Result<T, RvcInferError> {
+    let memory = MemoryInfo::new(AllocationDevice::CUDAPinned, device_id, AllocatorType::Device, MemoryType::CPUOutput)?;
+    let mut binding = session.create_binding()?;
+    for (name, value) in &inputs {
+        binding.bind_input(*name, value)?;
+    }
+    for output in &session.outputs {
+        binding.bind_output_to_device(&output.name, &memory)?;
+    }
+    let outputs = binding.run()?;
+    extract(&outputs)
+}
+
+/// `voice.int8.onnx` or `voice.fp16.onnx` for `voice.onnx` if INT8 or FP16 is on and the file
+/// exists. They are converted with their inputs and outputs kept in float32 so that they are
+/// drop-in replacements.
+pub(crate) fn precision_variant(model_path: PathBuf, config: &SessionConfig) -> PathBuf {
+    let variants = [(config.int8, "int8.onnx"), (config.fp16, "fp16.onnx")];
+    for (enabled, extension) in variants {
+        let variant = model_path.with_extension(extension);
+        if enabled && variant.exists() {
+            eprintln!("Loading {}", variant.display());
+            return variant;
+        }
+    }
+    model_path
+}
+
+/// Loads a synthesizer or encoder, along with what it is quantized with.
+pub fn load_model_from_file(
+    model_path: PathBuf,
+    cache_path: PathBuf,
+    config: &SessionConfig,
+) -> Result<(Session, Quantization), ort::Error> {
+    let model_path = precision_variant(model_path, config);
+    let quantization = inspect_model(&model_path);
+    if quantization.contrib_ops && config.device != InferenceDevice::Cpu {
+        eprintln!(
+            "{} uses operators only onnxruntime's CPU kernels implement, they will run on the CPU",
+            model_path.display()
+        );
+    }
+    let session = get_onnx_session(cache_path, config)?.commit_from_file(model_path)?;
+    Ok((session, quantization))
+}
+
+/// A model built in memory, on CUDA device `device_id` only. `None` when CUDA is not available.
+pub fn load_model_from_memory_on_cuda(model: &[u8], device_id: i32) -> Result<Option<Session>, ort::Error> {
+    let cuda = CUDAExecutionProvider::default();
+    if !cuda.is_available()? {
+        return Ok(None);
+    }
+
+    Session::builder()?
+        .with_optimization_level(GraphOptimizationLevel::Level3)?
+        .with_execution_providers([cuda.with_device_id(device_id).build()])?
+        .commit_from_memory(model)
+        .map(Some)
 }
 
 pub fn load_contentvec_from_file(
@@ -54,23 +273,21 @@ pub fn load_contentvec_from_file(
     cache_path: PathBuf,
     text_encoder_in_channels: usize,
     output_layers: usize,
-) -> Result<Session, ort::Error> {
+    config: &SessionConfig,
+) -> Result<(Session, Quantization), ort::Error> {
     let filename = format!(
         "vec-{}-layer-{}.onnx",
         text_encoder_in_channels, output_layers
     );
-    let model_path = path.join(filename);
-    get_onnx_session(cache_path, false, false)?.commit_from_file(model_path)
+    load_model_from_file(path.join(filename), cache_path, config)
 }
 
+/// Loads the f0 model `filename` from `path`, one of the `model_file_names` of a pitch algorithm.
 pub fn load_f0_from_file(
     path: PathBuf,
     cache_path: PathBuf,
-    pitch_algoritm: PitchAlgorithm,
+    filename: &str,
+    config: &SessionConfig,
 ) -> Result<Session, ort::Error> {
-    let filename = match pitch_algoritm {
-        PitchAlgorithm::Rmvpe => "rmvpe.onnx",
-    };
-
-    get_onnx_session(cache_path, false, false)?.commit_from_file(path.join(filename))
+    get_onnx_session(cache_path, config)?.commit_from_file(path.join(filename))
 }
@@ -0,0 +1,77 @@
+use ndarray::Array2;
+
+pub(crate) const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Where the data of a C-order float32 `.npy` matrix starts and its `(rows, cols)` shape.
+pub(crate) fn parse_npy_header(bytes: &[u8]) -> Result<(usize, (usize, usize)), String> {
+    if bytes.len() < 10 || &bytes[..6] != NPY_MAGIC {
+        return Err("not a .npy file".to_string());
+    }
+
+    // version 1 stores the header length in two bytes, later versions in four
+    let (header_len, header_start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        _ if bytes.len() >= 12 => (u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize, 12),
+        _ => return Err("truncated header".to_string()),
+    };
+    let data_start = header_start + header_len;
+    let header = bytes
+        .get(header_start..data_start)
+        .map(String::from_utf8_lossy)
+        .ok_or("truncated header")?;
+
+    if !header.contains("'descr': '<f4'") || !header.contains("'fortran_order': False") {
+        return Err(format!("expected a C-order float32 array, got {}", header.trim()));
+    }
+
+    let shape = header
+        .split("'shape': (")
+        .nth(1)
+        .and_then(|rest| rest.split(')').next())
+        .ok_or("header has no shape")?;
+    let shape: Vec<usize> = shape
+        .split(',')
+        .map(str::trim)
+        .filter(|dim| !dim.is_empty())
+        .map(|dim| dim.parse().map_err(|_| format!("bad dimension {}", dim)))
+        .collect::<Result<_, _>>()?;
+    let [rows, cols] = shape[..] else {
+        return Err(format!("expected a 2-d array, got shape {:?}", shape));
+    };
+
+    if bytes.len() - data_start != rows * cols * 4 {
+        return Err("data length doesn't match the shape".to_string());
+    }
+    Ok((data_start, (rows, cols)))
+}
+
+/// A C-order float32 `.npy` matrix, as `np.save` writes it.
+pub(crate) fn parse_npy(bytes: &[u8]) -> Result<Array2<f32>, String> {
+    let (data_start, shape) = parse_npy_header(bytes)?;
+    let values = bytes[data_start..]
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect();
+    Array2::from_shape_vec(shape, values).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+pub(crate) fn npy_bytes(shape: (usize, usize), values: &[f32]) -> Vec<u8> {
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        shape.0, shape.1
+    );
+    while (10 + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    let mut bytes = NPY_MAGIC.to_vec();
+    bytes.extend_from_slice(&[1, 0]);
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    for value in values {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
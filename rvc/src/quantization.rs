@@ -0,0 +1,72 @@
+use std::path::Path;
+
+// operators only quantized graphs use, QDQ as well as QOperator and dynamic quantization
+const QUANTIZED_OPS: [&[u8]; 6] = [
+    b"QuantizeLinear",
+    b"DequantizeLinear",
+    b"MatMulInteger",
+    b"ConvInteger",
+    b"QLinearConv",
+    b"QLinearMatMul",
+];
+// fused quantized operators (QGemm, QAttention, ...) live in onnxruntime's own domain and only
+// have CPU kernels
+const CONTRIB_DOMAIN: &[u8] = b"com.microsoft";
+
+/// What loading an INT8 model needs to know about it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Quantization {
+    pub quantized: bool,
+    pub contrib_ops: bool,
+}
+
+/// Operator types and domains are plain strings in the serialized graph, so the file is searched
+/// for them instead of being parsed.
+pub(crate) fn inspect_model(model_path: &Path) -> Quantization {
+    match std::fs::read(model_path) {
+        Ok(bytes) => inspect_graph(&bytes),
+        Err(_) => Quantization::default(),
+    }
+}
+
+fn inspect_graph(bytes: &[u8]) -> Quantization {
+    let contains = |needle: &[u8]| bytes.windows(needle.len()).any(|window| window == needle);
+    let quantized = QUANTIZED_OPS.iter().any(|op| contains(op));
+    Quantization {
+        quantized,
+        contrib_ops: quantized && contains(CONTRIB_DOMAIN),
+    }
+}
+
+/// Quantized exports have to keep float32 inputs and outputs to stand in for the float model.
+/// Checks the tensors in `names` the session has.
+pub(crate) fn check_float_io(session: &ort::Session, names: &[&str]) -> Result<(), String> {
+    let inputs = session.inputs.iter().map(|input| (&input.name, &input.input_type));
+    let outputs = session.outputs.iter().map(|output| (&output.name, &output.output_type));
+    for (name, value_type) in inputs.chain(outputs) {
+        if !names.contains(&name.as_str()) {
+            continue;
+        }
+        match value_type {
+            ort::ValueType::Tensor { ty: ort::TensorElementType::Float32, .. } => (),
+            other => return Err(format!("{} is {:?}, a quantized model must keep it float32", name, other)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_graph() {
+        assert_eq!(inspect_graph(b"\x0aConv\x12Relu"), Quantization::default());
+
+        let dynamic = inspect_graph(b"\x0aDynamicQuantizeLinear\x12MatMulInteger");
+        assert!(dynamic.quantized && !dynamic.contrib_ops);
+
+        let fused = inspect_graph(b"\x0aQGemm\x12com.microsoft\x1aDequantizeLinear");
+        assert!(fused.quantized && fused.contrib_ops);
+    }
+}
@@ -1,133 +1,747 @@
-use std::path::{Path, PathBuf};
+use std::{path::{Path, PathBuf}, thread::JoinHandle};
 
 use ndarray::{s, Axis};
 use ndarray_rand::{rand_distr::Normal, RandomExt};
 use ort::Session;
-use crate::{f0::F0Algorithm, ndarray_ext::CopyWithin};
+use crate::{
+    encoder::{contentvec::ContentVec, hubert_soft::HubertSoft, whisper::Whisper, Encoder, PendingEncode},
+    f0::F0Algorithm,
+    index::FeatureIndex,
+    model_config,
+    speaker::SpeakerEmbeddings,
+    ndarray_ext::CopyWithin,
+};
 
 use super::{
-    f0::{get_f0_post, rmvpe::Rmvpe},
-    models::{load_contentvec_from_file, load_f0_from_file, load_model_from_file},
+    f0::{crepe::Crepe, f0_extractor_frame, fcpe::Fcpe, fuse_f0, get_f0_post, median_filter, rmvpe::Rmvpe, shape_f0, snap_to_scale, world::{World, WorldMethod}},
+    models::{
+        load_contentvec_from_file, load_f0_from_file, load_model_from_file, pinned_output_device,
+        run_with_pinned_outputs, SessionConfig,
+    },
+    quantization::check_float_io,
 };
 
 use rvc_common::{
-    enums::{PitchAlgorithm, RvcModelVersion},
+    enums::{AutotuneScale, FeatureEncoder, PitchAlgorithm, RetrievalMetric, RvcModelVersion},
     errors::RvcInferError,
 };
 
 pub struct RvcInfer {
     data_path: PathBuf,
+    session_config: SessionConfig,
     session: Option<Session>,
-    contentvec_session: Option<Session>,
+    // set on CUDA, the encoder and synthesizer outputs are read back through page-locked memory
+    pinned_output_device: Option<i32>,
+    // what the synthesizer and the encoder were loaded from, to reload them without FP16
+    model_path: Option<PathBuf>,
+    // either of them is INT8 quantized
+    quantized_model: bool,
+    quantized_encoder: bool,
+    encoder_source: Option<(FeatureEncoder, RvcModelVersion, Option<usize>, Option<PathBuf>)>,
+    // models exported without f0 conditioning take no pitch inputs
+    f0_conditioned: bool,
+    // from the feature dimension of the "phone" input, when it is static
+    detected_model_version: Option<RvcModelVersion>,
+    // from the model metadata or the JSON next to it
+    output_sample_rate: Option<u32>,
+    // name of the speaker id input of multi-speaker exports
+    speaker_input: Option<&'static str>,
+    speaker_names: Vec<String>,
+    speaker_id: i64,
+    // for exports that take the speaker embedding "g" instead of an id
+    speaker_embeddings: Option<SpeakerEmbeddings>,
+    takes_speaker_embedding: bool,
+    morph_speaker_id: i64,
+    speaker_morph: f32,
+    encoder: Option<Encoder>,
     f0_algorithm: Option<F0Algorithm>,
     f0_mel_min: f32,
     f0_mel_max: f32,
 
+    // one slot per index in load order, empty while it is loading or when it failed to load
+    indices: Vec<Option<FeatureIndex>>,
+    index_weights: Vec<f32>,
+    pending_indices: Vec<PendingIndex>,
+    index_top_k: usize,
+    index_weight_exponent: f32,
+    index_metric: RetrievalMetric,
+
+    // raw f0 of the recent past, before range limits and pitch shift
+    cache_pitchf: ndarray::Array1<f32>,
+    // trailing frames of the cache that hold f0 from the current extractor
+    cached_f0_frames: usize,
+    f0_filter_radius: usize,
+    autotune_scale: AutotuneScale,
+    autotune_key: i32,
+    autotune_strength: f32,
+    // (floor, ceiling) of detected f0 in Hz, before the pitch shift
+    f0_range: (f32, f32),
+    // multiplies each extractor's voicing threshold
+    voicing_threshold_scale: f32,
+
+    feature_cache_enabled: bool,
+    // encoder output of the previous window and the window length it was computed for
+    feature_cache: Option<(usize, ndarray::Array3<f32>)>,
+    // encoding, pitch extraction with retrieval and synthesis of the last frame
+    stage_times: [std::time::Duration; 3],
+}
+
+/// What an audio stream carries over from one frame to the next. Streams served by the same
+/// sessions take turns, swapping theirs in around their frames.
+pub struct StreamHistory {
     cache_pitchf: ndarray::Array1<f32>,
+    cached_f0_frames: usize,
+    feature_cache_enabled: bool,
+    feature_cache: Option<(usize, ndarray::Array3<f32>)>,
+}
+
+impl StreamHistory {
+    pub fn new() -> Self {
+        StreamHistory {
+            cache_pitchf: ndarray::Array1::zeros(PITCH_CACHE_FRAMES),
+            cached_f0_frames: 0,
+            feature_cache_enabled: false,
+            feature_cache: None,
+        }
+    }
 }
 
+struct PendingIndex {
+    slot: usize,
+    path: PathBuf,
+    started: std::time::Instant,
+    loading: JoinHandle<Result<FeatureIndex, RvcInferError>>,
+}
+
+// neighbours blended per frame during feature retrieval and the power of the inverse squared
+// distance they are weighted by, as in RVC WebUI
+const INDEX_SEARCH_K: usize = 8;
+const INDEX_WEIGHT_EXPONENT: f32 = 2.0;
+// share of the index rate applied to unvoiced frames
+const UNVOICED_INDEX_RATE_SCALE: f32 = 0.25;
+// pitch frames on either side of a frame that have to be voiced and steady for full retrieval
+const F0_STABILITY_RADIUS: usize = 2;
+// f0 spread in cents across those frames at which a frame no longer counts as steady
+const UNSTABLE_F0_CENTS: f32 = 100.0;
+// speaker id input names of multi-speaker exports, RVC's own exporter calls it "ds"
+const SPEAKER_INPUT_NAMES: [&str; 2] = ["sid", "ds"];
+// the speaker embedding input of exports that look the embedding up outside the model
+const SPEAKER_EMBEDDING_INPUT_NAME: &str = "g";
+// custom model metadata listing the speaker names in id order, separated by commas
+const SPEAKERS_METADATA_KEY: &str = "speakers";
+// synthesizer tensors a quantized export has to keep in float32, the integer ones are indices
+const SYNTHESIZER_FLOAT_IO: [&str; 5] = ["phone", "pitchf", "rnd", "g", "audio"];
+// samples per encoder frame
+const FEATURE_HOP: usize = 320;
+// audio encoded ahead of the new block when reusing cached features; the frames it yields only
+// give the new ones context and are not kept
+const FEATURE_CACHE_CONTEXT: usize = 25 * FEATURE_HOP;
+// f0 frames of history kept, enough for the longest window
+const PITCH_CACHE_FRAMES: usize = 1024;
+// the first run catches the pitch history up over the whole window, the second is a regular one
+const WARM_UP_RUNS: usize = 2;
+
 impl RvcInfer {
     pub fn new(data_path: PathBuf) -> Self {
+        // the range the coarse pitch was quantized over in training, which stays the same
+        // whatever `f0_range` lets through
         const F0_MIN: f32 = 50.0;
-        const F0_MAX: f32 = 500.0f32;
+        const F0_MAX: f32 = 1100.0f32;
         let f0_mel_min = (F0_MIN / 700.0 + 1.).ln() * 1127.;
         let f0_mel_max = (F0_MAX / 700.0 + 1.).ln() * 1127.;
         RvcInfer {
             data_path,
+            session_config: SessionConfig::default(),
             session: None,
-            contentvec_session: None,
+            pinned_output_device: None,
+            model_path: None,
+            quantized_model: false,
+            quantized_encoder: false,
+            encoder_source: None,
+            f0_conditioned: true,
+            detected_model_version: None,
+            output_sample_rate: None,
+            speaker_input: None,
+            speaker_names: Vec::new(),
+            speaker_id: 0,
+            speaker_embeddings: None,
+            takes_speaker_embedding: false,
+            morph_speaker_id: 0,
+            speaker_morph: 0.0,
+            encoder: None,
             f0_algorithm: None,
             f0_mel_min,
             f0_mel_max,
-            cache_pitchf: ndarray::Array1::zeros(1024),
+            indices: Vec::new(),
+            index_weights: Vec::new(),
+            pending_indices: Vec::new(),
+            index_top_k: INDEX_SEARCH_K,
+            index_weight_exponent: INDEX_WEIGHT_EXPONENT,
+            index_metric: RetrievalMetric::Auto,
+            cache_pitchf: ndarray::Array1::zeros(PITCH_CACHE_FRAMES),
+            cached_f0_frames: 0,
+            f0_filter_radius: 0,
+            autotune_scale: AutotuneScale::Off,
+            autotune_key: 0,
+            autotune_strength: 1.0,
+            f0_range: (50.0, 1100.0),
+            voicing_threshold_scale: 1.0,
+            feature_cache_enabled: false,
+            feature_cache: None,
+            stage_times: Default::default(),
         }
     }
 
-    pub fn load_contentvec(&mut self, model_version: RvcModelVersion) -> Result<(), ort::Error> {
-        self.contentvec_session = Some(load_contentvec_from_file(
-            self.data_path.join("contentvec"),
-            self.data_path.join("cache"),
-            model_version.text_encoder_in_channels(),
-            model_version.output_layers(),
-        )?);
+    /// Applies to sessions loaded after this call.
+    pub fn set_session_config(&mut self, session_config: SessionConfig) {
+        self.pinned_output_device = pinned_output_device(&session_config);
+        self.session_config = session_config;
+    }
+
+    /// Loads the bundled export of `encoder`, or the one at `model_path`. The bundled ContentVec
+    /// follows `model_version`, and `output_layers` picks it by feature layer instead.
+    pub fn load_encoder(
+        &mut self,
+        encoder: FeatureEncoder,
+        model_version: RvcModelVersion,
+        output_layers: Option<usize>,
+        model_path: Option<PathBuf>,
+    ) -> Result<(), RvcInferError> {
+        let cache_path = self.data_path.join("cache");
+        self.encoder_source = Some((encoder, model_version, output_layers, model_path.clone()));
+        let (session, quantization) = match (model_path, encoder) {
+            (Some(model_path), _) => load_model_from_file(model_path, cache_path, &self.session_config)?,
+            (None, FeatureEncoder::ContentVec) => load_contentvec_from_file(
+                self.data_path.join("contentvec"),
+                cache_path,
+                model_version.text_encoder_in_channels(),
+                output_layers.unwrap_or(model_version.output_layers()),
+                &self.session_config,
+            )?,
+            (None, FeatureEncoder::HubertSoft) => load_model_from_file(
+                self.data_path.join("encoders").join("hubert-soft.onnx"),
+                cache_path,
+                &self.session_config,
+            )?,
+            (None, FeatureEncoder::Whisper) => load_model_from_file(
+                self.data_path.join("encoders").join("whisper.onnx"),
+                cache_path,
+                &self.session_config,
+            )?,
+        };
+        if quantization.quantized {
+            let names: Vec<&str> = session
+                .inputs
+                .iter()
+                .map(|input| input.name.as_str())
+                .chain(session.outputs.iter().map(|output| output.name.as_str()))
+                .collect();
+            check_float_io(&session, &names).map_err(RvcInferError::Model)?;
+        }
+        self.quantized_encoder = quantization.quantized;
+
+        self.feature_cache = None;
+        self.encoder = Some(match encoder {
+            FeatureEncoder::ContentVec => Encoder::ContentVec(ContentVec::new(session, self.pinned_output_device)?),
+            FeatureEncoder::HubertSoft => Encoder::HubertSoft(HubertSoft::new(session)?),
+            FeatureEncoder::Whisper => Encoder::Whisper(Whisper::new(session)?),
+        });
         Ok(())
     }
 
-    pub fn load_model(&mut self, model_path: PathBuf) -> Result<(), ort::Error> {
+    pub fn load_model(&mut self, model_path: PathBuf) -> Result<(), RvcInferError> {
         let cache_path = self.data_path.join("cache");
-        self.session = Some(load_model_from_file(model_path, cache_path)?);
+        let (session, quantization) = load_model_from_file(model_path.clone(), cache_path, &self.session_config)?;
+        if quantization.quantized {
+            check_float_io(&session, &SYNTHESIZER_FLOAT_IO).map_err(RvcInferError::Model)?;
+        }
+        self.quantized_model = quantization.quantized;
+        self.f0_conditioned = session.inputs.iter().any(|input| input.name == "pitchf");
+        self.detected_model_version = session
+            .inputs
+            .iter()
+            .find(|input| input.name == "phone")
+            .and_then(|input| match &input.input_type {
+                ort::ValueType::Tensor { dimensions, .. } => dimensions.last().copied(),
+                _ => None,
+            })
+            .and_then(|channels| usize::try_from(channels).ok())
+            .and_then(RvcModelVersion::from_text_encoder_in_channels);
+        self.output_sample_rate = model_config::sample_rate_from_metadata(&session)
+            .or_else(|| model_config::sample_rate_from_sidecar(&model_path));
+        self.speaker_input = SPEAKER_INPUT_NAMES
+            .into_iter()
+            .find(|name| session.inputs.iter().any(|input| input.name == *name));
+        self.speaker_names = session
+            .metadata()
+            .ok()
+            .and_then(|metadata| metadata.custom(SPEAKERS_METADATA_KEY).ok().flatten())
+            .map(|names| {
+                names
+                    .split(',')
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.takes_speaker_embedding = session
+            .inputs
+            .iter()
+            .any(|input| input.name == SPEAKER_EMBEDDING_INPUT_NAME);
+        self.speaker_embeddings = None;
+        if self.takes_speaker_embedding {
+            // saved next to the model, `voice.onnx` comes with `voice.emb_g.npy`
+            match SpeakerEmbeddings::load(&model_path.with_extension("emb_g.npy")) {
+                Ok(embeddings) => self.speaker_embeddings = Some(embeddings),
+                Err(e) => eprintln!("Error loading speaker embeddings, using a silent speaker: {:?}", e),
+            }
+        }
+        self.session = Some(session);
+        self.model_path = Some(model_path);
+        Ok(())
+    }
+
+    /// Reloads the synthesizer and the encoder from their float32 exports, for when the FP16
+    /// ones turn out to overflow.
+    fn fall_back_to_fp32(&mut self) -> Result<(), RvcInferError> {
+        eprintln!("FP16 inference produced NaN or infinite samples, reloading in FP32");
+        self.session_config.fp16 = false;
+        if let Some(model_path) = self.model_path.clone() {
+            self.load_model(model_path)?;
+        }
+        if let Some((encoder, model_version, output_layers, model_path)) = self.encoder_source.clone() {
+            self.load_encoder(encoder, model_version, output_layers, model_path)?;
+        }
         Ok(())
     }
 
+    /// The sample rate the loaded model synthesizes at, when the model says so. Valid after
+    /// `load_model`.
+    pub fn output_sample_rate(&self) -> Option<u32> {
+        self.output_sample_rate
+    }
+
+    /// Whether the synthesizer or the encoder loaded is INT8 quantized, which costs some quality.
+    pub fn is_quantized(&self) -> bool {
+        self.quantized_model || self.quantized_encoder
+    }
+
+    /// What encoding, pitch extraction with retrieval and synthesis took in the last frame.
+    pub fn stage_times(&self) -> [std::time::Duration; 3] {
+        self.stage_times
+    }
+
+    /// Whether the loaded model expects a pitch contour. Valid after `load_model`.
+    pub fn is_f0_conditioned(&self) -> bool {
+        self.f0_conditioned
+    }
+
+    /// Whether the loaded model takes a speaker id. Valid after `load_model`.
+    pub fn is_multi_speaker(&self) -> bool {
+        self.speaker_input.is_some() || self.takes_speaker_embedding
+    }
+
+    /// Whether speakers can be blended, which needs the embedding table. Valid after `load_model`.
+    pub fn can_morph_speakers(&self) -> bool {
+        self.speaker_embeddings.is_some()
+    }
+
+    /// Moves the speaker embedding `amount` of the way towards `target`. Models that take a
+    /// speaker id can't be blended and always use the speaker id.
+    pub fn set_speaker_morph(&mut self, target: u32, amount: f32) {
+        self.morph_speaker_id = target as i64;
+        self.speaker_morph = amount;
+    }
+
+    /// Speaker names from the model metadata, in id order. Often empty even for multi-speaker
+    /// models.
+    pub fn speaker_names(&self) -> &[String] {
+        &self.speaker_names
+    }
+
+    /// Ignored by single-speaker models.
+    pub fn set_speaker_id(&mut self, speaker_id: u32) {
+        self.speaker_id = speaker_id as i64;
+    }
+
+    /// The version implied by the loaded model's feature dimension, `None` when the export
+    /// leaves it dynamic. Valid after `load_model`.
+    pub fn detected_model_version(&self) -> Option<RvcModelVersion> {
+        self.detected_model_version
+    }
+
     pub fn load_f0(&mut self, pitch_algorithm: PitchAlgorithm) -> Result<(), ort::Error> {
-        match pitch_algorithm {
-            PitchAlgorithm::Rmvpe => {
-                let f0_session = load_f0_from_file(
-                    self.data_path.join("f0"),
-                    self.data_path.join("cache"),
-                    pitch_algorithm,
-                )?;
-                self.f0_algorithm =
-                    Some(F0Algorithm::Rmvpe(Rmvpe::new(f0_session)));
+        self.cached_f0_frames = 0;
+        let files = pitch_algorithm.model_file_names();
+        let load = |file: &str| load_f0_from_file(
+            self.data_path.join("f0"),
+            self.data_path.join("cache"),
+            file,
+            &self.session_config,
+        );
+        let f0_algorithm = match pitch_algorithm {
+            PitchAlgorithm::Rmvpe => F0Algorithm::Rmvpe(Rmvpe::new(load(files[0])?)),
+            PitchAlgorithm::CrepeTiny | PitchAlgorithm::CrepeFull => F0Algorithm::Crepe(Crepe::new(load(files[0])?)),
+            PitchAlgorithm::Fcpe => F0Algorithm::Fcpe(Fcpe::new(load(files[0])?)),
+            PitchAlgorithm::Dio => F0Algorithm::World(World::new(WorldMethod::Dio)),
+            PitchAlgorithm::Harvest => F0Algorithm::World(World::new(WorldMethod::Harvest)),
+            PitchAlgorithm::Hybrid => {
+                F0Algorithm::Hybrid(Rmvpe::new(load(files[0])?), Fcpe::new(load(files[1])?))
             }
+        };
+        self.f0_algorithm = Some(f0_algorithm);
+        Ok(())
+    }
+
+    /// Adds a retrieval index. Several can be loaded; their results are blended by weight.
+    pub fn load_index(&mut self, index_path: PathBuf) -> Result<(), RvcInferError> {
+        self.indices.push(Some(FeatureIndex::load(&index_path, self.session_config.cuda_device())?));
+        self.index_weights.push(1.0);
+        Ok(())
+    }
+
+    /// Like `load_index`, but loads on another thread so that inference can start right away.
+    /// The index takes no part in retrieval until it has loaded; if it fails, it never does.
+    pub fn load_index_in_background(&mut self, index_path: PathBuf) {
+        let slot = self.indices.len();
+        self.indices.push(None);
+        self.index_weights.push(1.0);
+
+        eprintln!("Loading index {:?} in the background", index_path);
+        let path = index_path.clone();
+        let cuda_device = self.session_config.cuda_device();
+        self.pending_indices.push(PendingIndex {
+            slot,
+            path: index_path,
+            started: std::time::Instant::now(),
+            loading: std::thread::spawn(move || FeatureIndex::load(&path, cuda_device)),
+        });
+    }
+
+    /// Whether any index is still loading.
+    pub fn is_loading_indices(&self) -> bool {
+        !self.pending_indices.is_empty()
+    }
+
+    fn collect_loaded_indices(&mut self) {
+        while let Some(position) = self
+            .pending_indices
+            .iter()
+            .position(|pending| pending.loading.is_finished())
+        {
+            let pending = self.pending_indices.swap_remove(position);
+            match pending.loading.join() {
+                Ok(Ok(index)) => {
+                    eprintln!("Index {:?} loaded in {:?}", pending.path, pending.started.elapsed());
+                    self.indices[pending.slot] = Some(index);
+                }
+                Ok(Err(e)) => eprintln!("Error loading index {:?}: {:?}", pending.path, e),
+                Err(_) => eprintln!("Error loading index {:?}: loader panicked", pending.path),
+            }
+        }
+    }
+
+    /// Encoder features of 16 kHz audio as `(frames, channels)`, what a retrieval index holds.
+    pub fn extract_features(&self, input: ndarray::ArrayView1<f32>) -> Result<ndarray::Array2<f32>, RvcInferError> {
+        let encoder = self.encoder.as_ref().ok_or(RvcInferError::ContentvecNotLoaded)?;
+        let features = encoder.encode(input)?;
+        Ok(features.index_axis_move(Axis(0), 0).reversed_axes().as_standard_layout().into_owned())
+    }
+
+    /// How many neighbours retrieval blends per frame.
+    pub fn set_index_top_k(&mut self, top_k: usize) {
+        self.index_top_k = top_k.max(1);
+    }
+
+    /// The exponent of the inverse squared distance neighbours are weighted by, 0 averages them
+    /// evenly.
+    pub fn set_index_weight_exponent(&mut self, weight_exponent: f32) {
+        self.index_weight_exponent = weight_exponent.max(0.0);
+    }
+
+    /// How retrieval compares features. `Auto` uses the metric each index was built with, which
+    /// a feature dump does not record and is searched by L2.
+    pub fn set_index_metric(&mut self, metric: RetrievalMetric) {
+        self.index_metric = metric;
+    }
+
+    pub fn set_index_weights(&mut self, weights: &[f32]) {
+        self.index_weights
+            .iter_mut()
+            .zip(weights)
+            .for_each(|(weight, new_weight)| *weight = new_weight.max(0.0));
+    }
+
+    /// Median filters the pitch contour over `2 * radius + 1` frames, 0 disables it.
+    pub fn set_f0_filter_radius(&mut self, radius: usize) {
+        self.f0_filter_radius = radius;
+    }
+
+    /// Snaps the shifted pitch to `scale` in `key` (0 = C) before synthesis.
+    pub fn set_autotune(&mut self, scale: AutotuneScale, key: i32, strength: f32) {
+        self.autotune_scale = scale;
+        self.autotune_key = key.rem_euclid(12);
+        self.autotune_strength = strength.clamp(0., 1.);
+    }
+
+    /// Limits the detected f0 to `floor..=ceil` Hz, measured before the pitch shift.
+    pub fn set_f0_range(&mut self, floor: f32, ceil: f32) {
+        let floor = floor.max(1.0);
+        self.f0_range = (floor, ceil.max(floor));
+    }
+
+    /// How eagerly frames are declared unvoiced, 0.5 keeps each extractor's own threshold.
+    /// WORLD's estimators have no confidence to threshold and ignore this.
+    pub fn set_voicing_sensitivity(&mut self, sensitivity: f32) {
+        self.voicing_threshold_scale = 2. * sensitivity.clamp(0., 1.);
+    }
+
+    /// Reuses the encoder output of the overlapping part of the previous window and only
+    /// encodes the new block with some context. The encoders attend to the whole window, so
+    /// this trades a little accuracy for a much cheaper encoder pass.
+    pub fn set_feature_cache(&mut self, enabled: bool) {
+        if self.feature_cache_enabled != enabled {
+            self.feature_cache_enabled = enabled;
+            self.feature_cache = None;
+        }
+    }
+
+    /// Encodes `input`, reusing the cached features of the previous window when it is enabled
+    /// and the windows line up. The window moves by `sample_frame_16k_size` every call.
+    fn encode_incremental(
+        &mut self,
+        encoder: &Encoder,
+        input: ndarray::ArrayView1<f32>,
+        sample_frame_16k_size: usize,
+    ) -> Result<ndarray::Array3<f32>, RvcInferError> {
+        let input_len = input.len();
+        let shift_frames = sample_frame_16k_size / FEATURE_HOP;
+        let reusable = self.feature_cache_enabled
+            && sample_frame_16k_size % FEATURE_HOP == 0
+            && matches!(&self.feature_cache, Some((cached_len, _)) if *cached_len == input_len);
+
+        // the tail has to start on a frame boundary of the whole window for its frames to line up
+        let tail_len = sample_frame_16k_size + FEATURE_CACHE_CONTEXT + FEATURE_HOP;
+        let tail_start = input_len.saturating_sub(tail_len) / FEATURE_HOP * FEATURE_HOP;
+        let features = match self.feature_cache.take() {
+            Some((_, cached)) if reusable && tail_start > 0 => {
+                let tail = encoder.encode(input.slice(s![tail_start..]))?;
+                let frames = cached.len_of(Axis(2));
+                let keep_from = tail_start / FEATURE_HOP + FEATURE_CACHE_CONTEXT / FEATURE_HOP;
+                if tail_start / FEATURE_HOP + tail.len_of(Axis(2)) != frames || keep_from + shift_frames > frames {
+                    encoder.encode(input)?
+                } else {
+                    let mut features = cached;
+                    let retained = features.slice(s![.., .., shift_frames..]).to_owned();
+                    features.slice_mut(s![.., .., ..frames - shift_frames]).assign(&retained);
+                    features
+                        .slice_mut(s![.., .., keep_from..])
+                        .assign(&tail.slice(s![.., .., FEATURE_CACHE_CONTEXT / FEATURE_HOP..]));
+                    features
+                }
+            }
+            _ => encoder.encode(input)?,
+        };
+
+        if self.feature_cache_enabled {
+            self.feature_cache = Some((input_len, features.clone()));
+        }
+        Ok(features)
+    }
+
+    /// Runs a few frames of silence shaped like the real ones, so that kernel selection, JIT
+    /// compilation and TensorRT engine builds happen now instead of stalling the first real frame.
+    /// The pitch and feature history they leave behind is dropped again.
+    pub fn warm_up(
+        &mut self,
+        input_len: usize,
+        sample_frame_16k_size: usize,
+        skip_head: u32,
+        return_length: u32,
+    ) -> Result<(), RvcInferError> {
+        let silence = ndarray::Array1::zeros(input_len);
+        for _ in 0..WARM_UP_RUNS {
+            self.infer(silence.view(), sample_frame_16k_size, None, skip_head, return_length, 0.0)?;
         }
+        self.cache_pitchf.fill(0.0);
+        self.cached_f0_frames = 0;
+        self.feature_cache = None;
         Ok(())
     }
 
+    /// Puts `history` in place of the current stream's, which is left in `history`.
+    pub fn swap_history(&mut self, history: &mut StreamHistory) {
+        std::mem::swap(&mut self.cache_pitchf, &mut history.cache_pitchf);
+        std::mem::swap(&mut self.cached_f0_frames, &mut history.cached_f0_frames);
+        std::mem::swap(&mut self.feature_cache_enabled, &mut history.feature_cache_enabled);
+        std::mem::swap(&mut self.feature_cache, &mut history.feature_cache);
+    }
+
     pub fn unload_model(&mut self) {
         self.session = None;
     }
 
+    /// Drops the synthesizer, the encoder and the pitch extractor, and the device memory they
+    /// hold. The indices and stream histories stay; `load_model`, `load_encoder` and `load_f0`
+    /// bring the sessions back.
+    pub fn unload_sessions(&mut self) {
+        self.session = None;
+        self.encoder = None;
+        self.f0_algorithm = None;
+        self.feature_cache = None;
+    }
+
     pub fn hubert(
         &self,
         input: ndarray::ArrayView1<f32>,
     ) -> Result<ndarray::Array3<f32>, RvcInferError> {
-        let contentvec = self
-            .contentvec_session
+        self.encoder
             .as_ref()
-            .ok_or(RvcInferError::ContentvecNotLoaded)?;
-        let input_len = input.len();
-        let feats = input.into_shape((1, 1, input_len))?;
-
-        let hubert_output = contentvec.run(ort::inputs!["source" => feats]?)?;
-        let hubert = hubert_output["embed"]
-            .try_extract_tensor::<f32>()?
-            .into_dimensionality::<ndarray::Ix3>()?;
-        Ok(hubert.permuted_axes([0, 2, 1]).to_owned())
+            .ok_or(RvcInferError::ContentvecNotLoaded)?
+            .encode(input)
     }
 
     pub fn extract_feature(&self, input: ndarray::ArrayView1<f32>) -> Result<ndarray::Array3<f32>, RvcInferError> {
-        let raw_hubert = self.hubert(input)?;
+        Ok(Self::extend_feature(self.hubert(input)?))
+    }
+
+    fn extend_feature(raw_hubert: ndarray::Array3<f32>) -> ndarray::Array3<f32> {
         let extended_hubert_shape = {
             let raw_h = raw_hubert.shape();
             [raw_h[0], raw_h[1], raw_h[2] * 2 + 1]
         };
         let max_k = raw_hubert.len_of(Axis(2)) - 1;
-        Ok(ndarray::Array3::from_shape_fn(extended_hubert_shape, 
+        ndarray::Array3::from_shape_fn(extended_hubert_shape, 
             |(i, j, k)| raw_hubert[[i, j, usize::min(k / 2, max_k)]]
-        ).permuted_axes([0, 2, 1]))
+        ).permuted_axes([0, 2, 1])
     }
 
-    pub fn pitch(
+    /// How steadily voiced a pitch frame is: 0 when it or a close neighbour is unvoiced, which
+    /// covers consonants and the transients around them, up to 1 for a flat contour.
+    fn voicing_confidence(pitchf: ndarray::ArrayView1<f32>, index: usize) -> f32 {
+        let start = index.saturating_sub(F0_STABILITY_RADIUS);
+        let end = usize::min(index + F0_STABILITY_RADIUS + 1, pitchf.len());
+        let window = pitchf.slice(s![start..end]);
+        if window.iter().any(|&f0| f0 <= 0.) {
+            return 0.;
+        }
+
+        let (low, high) = window
+            .iter()
+            .fold((f32::INFINITY, 0f32), |(low, high), &f0| (low.min(f0), high.max(f0)));
+        let spread_cents = 1200. * (high / low).log2();
+        (1. - spread_cents / UNSTABLE_F0_CENTS).clamp(0., 1.)
+    }
+
+    /// The index rate of every raw feature frame from `skip_frames` on. `pitchf` covers the
+    /// returned window, which starts `skip_head` frames into the extended features. Frames get
+    /// the full rate when steadily voiced and fall to a fraction of it on unvoiced and unsteady
+    /// ones, so that consonants keep most of their own features and don't get smeared.
+    fn frame_index_rates(
+        pitchf: Option<ndarray::ArrayView1<f32>>,
+        index_rate: f32,
+        frames: usize,
+        skip_frames: usize,
+        skip_head: usize,
+    ) -> Vec<f32> {
+        (skip_frames..frames)
+            .map(|frame| match pitchf {
+                Some(pitchf) if !pitchf.is_empty() => {
+                    let pitch_index = usize::min((2 * frame).saturating_sub(skip_head), pitchf.len() - 1);
+                    let confidence = Self::voicing_confidence(pitchf, pitch_index);
+                    index_rate * (UNVOICED_INDEX_RATE_SCALE + (1. - UNVOICED_INDEX_RATE_SCALE) * confidence)
+                }
+                _ => index_rate,
+            })
+            .collect()
+    }
+
+    /// Blends the raw (channels, frames) hubert features from `skip_frames` onwards towards
+    /// their nearest neighbours in the loaded indices, by one rate per frame.
+    fn retrieve_feature(
+        &mut self,
+        raw_hubert: &mut ndarray::Array3<f32>,
+        index_rates: &[f32],
+        skip_frames: usize,
+    ) -> Result<(), RvcInferError> {
+        let channels = raw_hubert.len_of(Axis(1));
+        let skip_frames = usize::min(skip_frames, raw_hubert.len_of(Axis(2)));
+
+        // indices built for the other model version have a different dimension
+        let total_weight: f32 = self
+            .indices
+            .iter()
+            .zip(self.index_weights.iter())
+            .filter_map(|(index, weight)| index.as_ref().map(|index| (index, weight)))
+            .filter(|(index, _)| index.dim() == channels)
+            .map(|(_, weight)| *weight)
+            .sum();
+
+        if total_weight <= 0. {
+            return Ok(());
+        }
+
+        let mut feats = raw_hubert.index_axis_mut(Axis(0), 0).reversed_axes();
+        let mut feats = feats.slice_mut(s![skip_frames.., ..]);
+        let query = feats.to_owned();
+
+        let mut retrieved = ndarray::Array2::<f32>::zeros(query.raw_dim());
+        for (index, weight) in self.indices.iter().zip(self.index_weights.iter()) {
+            let Some(index) = index else {
+                continue;
+            };
+            if index.dim() != channels || *weight <= 0. {
+                continue;
+            }
+            retrieved.scaled_add(*weight / total_weight, &index.retrieve(query.view(), self.index_top_k, self.index_weight_exponent, self.index_metric)?);
+        }
+
+        for ((mut feat, retrieved), index_rate) in feats.rows_mut().into_iter().zip(retrieved.rows()).zip(index_rates) {
+            ndarray::Zip::from(&mut feat).and(&retrieved)
+                .for_each(|feat, retrieved| *feat = *retrieved * index_rate + *feat * (1. - index_rate));
+        }
+
+        Ok(())
+    }
+
+    /// Raw f0 of the last `f0_extractor_frame(sample_frame_16k_size)` samples of `input`.
+    fn extract_f0(
         &mut self,
         input: ndarray::ArrayView1<f32>,
-        pitch_shift: i32,
         sample_frame_16k_size: usize,
     ) -> Result<ndarray::Array1<f32>, RvcInferError> {
-        // return pitch, pitchf
-
+        let (f0_floor, f0_ceil) = self.f0_range;
+        let scale = self.voicing_threshold_scale;
         let f0 = match &mut self.f0_algorithm {
-            Some(F0Algorithm::Rmvpe(rmvpe)) => {
-                let uppower = 2.0f32.powi(pitch_shift / 12);
-                let f0 = rmvpe.pitch(input, sample_frame_16k_size, 0.03)? * uppower;
-                f0
+            Some(F0Algorithm::Rmvpe(rmvpe)) => rmvpe.pitch(input, sample_frame_16k_size, 0.03 * scale)?,
+            Some(F0Algorithm::Crepe(crepe)) => crepe.pitch(input, sample_frame_16k_size, 0.1 * scale)?,
+            Some(F0Algorithm::Fcpe(fcpe)) => fcpe.pitch(input, sample_frame_16k_size, 0.006 * scale)?,
+            Some(F0Algorithm::World(world)) => {
+                world.pitch(input, sample_frame_16k_size, f0_floor as f64, f0_ceil as f64)?
+            }
+            Some(F0Algorithm::Hybrid(rmvpe, fcpe)) => {
+                let rmvpe_f0 = rmvpe.pitch(input, sample_frame_16k_size, 0.03 * scale)?;
+                let fcpe_f0 = fcpe.pitch(input, sample_frame_16k_size, 0.006 * scale)?;
+                fuse_f0(rmvpe_f0.view(), fcpe_f0.view())
             }
-            _ => unreachable!(),
+            None => return Err(RvcInferError::F0NotLoaded),
         };
-
         Ok(f0)
+    }
 
-        // Ok(get_f0_post(f0, self.f0_mel_min, self.f0_mel_max))
+    /// Limits raw f0 to the configured range and applies the pitch shift.
+    fn shape_f0(&self, f0: ndarray::Array1<f32>, pitch_shift: i32) -> ndarray::Array1<f32> {
+        shape_f0(f0, pitch_shift, self.f0_range)
+    }
+
+    pub fn pitch(
+        &mut self,
+        input: ndarray::ArrayView1<f32>,
+        pitch_shift: i32,
+        sample_frame_16k_size: usize,
+    ) -> Result<ndarray::Array1<f32>, RvcInferError> {
+        let f0 = self.extract_f0(input, sample_frame_16k_size)?;
+        Ok(self.shape_f0(f0, pitch_shift))
     }
 
     pub fn infer(
@@ -137,51 +751,135 @@ impl RvcInfer {
         pitch_shift: Option<i32>,
         skip_head: u32,
         return_length: u32,
+        index_rate: f32,
     ) -> Result<ndarray::Array1<f32>, RvcInferError> {
         if self.session.is_none() {
             return Err(RvcInferError::ModelNotLoaded);
         }
 
+        // moved out for the frame so that the pitch can be extracted while it runs
+        let encoder = self.encoder.take().ok_or(RvcInferError::ContentvecNotLoaded)?;
+        let result = self.infer_frame(
+            &encoder,
+            input,
+            sample_frame_16k_size,
+            pitch_shift,
+            skip_head,
+            return_length,
+            index_rate,
+        );
+        // unless falling back to FP32 has replaced it
+        if self.encoder.is_none() {
+            self.encoder = Some(encoder);
+        }
+        result
+    }
+
+    fn infer_frame(
+        &mut self,
+        encoder: &Encoder,
+        input: ndarray::ArrayView1<f32>,
+        sample_frame_16k_size: usize,
+        pitch_shift: Option<i32>,
+        skip_head: u32,
+        return_length: u32,
+        index_rate: f32,
+    ) -> Result<ndarray::Array1<f32>, RvcInferError> {
         let start_time = std::time::Instant::now();
+
+        if self.is_loading_indices() {
+            self.collect_loaded_indices();
+        }
         
         let skip_head = skip_head as usize;
         let return_length = return_length as usize;
 
-        // let hubert_output = self.hubert(input)?;
-        let hubert_output = self.extract_feature(input)?;
-
-        let hubert_length = usize::min(input.len() / 160, hubert_output.len_of(Axis(1)));
-        // let hubert_output = hubert_output.slice(s![.., ..hubert_length, ..]);
-        let hubert_output = hubert_output.slice(s![.., skip_head..skip_head + return_length, ..]);
-
-        let hubert_time = start_time.elapsed();
+        // a full pass over the window runs on onnxruntime's threads during pitch extraction, the
+        // short one of the feature cache is done up front
+        let encoding = if self.feature_cache_enabled {
+            PendingEncode::ready(self.encode_incremental(encoder, input, sample_frame_16k_size)?)
+        } else {
+            encoder.encode_async(input)?
+        };
 
-        // TODO: index search
-        
+        // extend_feature doubles the frame rate
+        let hubert_length = usize::min(input.len() / 160, encoding.frames * 2 + 1);
 
-        // if f0
+        let pitch_start = std::time::Instant::now();
         let pitch_shift = pitch_shift.unwrap_or(0);
-        let (pitch, pitchf) = {
-            let pitchf = self.pitch(input, pitch_shift, sample_frame_16k_size)?;
-
-            let pitch_len = pitchf.len();
+        let pitch = if !self.f0_conditioned {
+            None
+        } else {
+            let cache_len = self.cache_pitchf.len();
             let shift = sample_frame_16k_size / 160;
-            
             self.cache_pitchf.copy_within(shift.., 0);
+            let retained_frames = usize::min(self.cached_f0_frames, cache_len - shift) + shift;
 
-            let cache_pitch_start = self.cache_pitchf.len() + 4 - pitch_len;
-
-            self.cache_pitchf.slice_mut(s![cache_pitch_start..]).assign(&pitchf.slice(s![3..pitch_len - 1]));
-
-            let cached_range_start = self.cache_pitchf.len() - hubert_length + skip_head;
+            let cached_range_start = cache_len - hubert_length + skip_head;
             let cached_range_end = cached_range_start + return_length;
 
-            let result_pitchf = self.cache_pitchf.slice(s![cached_range_start..cached_range_end]).to_owned();
+            // the window overlaps the previous one, so normally only the new tail is analysed and
+            // the rest comes from the cache. Without usable history, e.g. right after loading,
+            // analyse everything this frame returns once.
+            let needed_frames = cache_len - cached_range_start;
+            let mut extract_size = sample_frame_16k_size;
+            let tail_frames = f0_extractor_frame(sample_frame_16k_size) / 160 - 3;
+            if usize::max(retained_frames, tail_frames) < needed_frames {
+                let catch_up_size = needed_frames * 160;
+                let catch_up_frame = f0_extractor_frame(catch_up_size);
+                if catch_up_frame <= input.len() && catch_up_frame / 160 - 3 <= cache_len {
+                    extract_size = catch_up_size;
+                }
+            }
+
+            let pitchf = self.extract_f0(input, extract_size)?;
+            let pitch_len = pitchf.len();
+            let cache_pitch_start = cache_len + 4 - pitch_len;
+            self.cache_pitchf.slice_mut(s![cache_pitch_start..]).assign(&pitchf.slice(s![3..pitch_len - 1]));
+            self.cached_f0_frames = usize::max(retained_frames, pitch_len - 4);
+
+            // filter with the cached history around the window so its edges see real neighbours
+            let result_pitchf = {
+                let radius = usize::min(self.f0_filter_radius, cached_range_start);
+                let filter_end = usize::min(cached_range_end + radius, cache_len);
+                let window = self.shape_f0(
+                    self.cache_pitchf.slice(s![cached_range_start - radius..filter_end]).to_owned(),
+                    pitch_shift,
+                );
+                let filtered = median_filter(window.view(), self.f0_filter_radius);
+                filtered.slice(s![radius..radius + return_length]).to_owned()
+            };
+            let mut result_pitchf = result_pitchf;
+            snap_to_scale(&mut result_pitchf, self.autotune_scale, self.autotune_key, self.autotune_strength);
             let (pitch, pitchf) = get_f0_post(result_pitchf, self.f0_mel_min, self.f0_mel_max);
-            (pitch.insert_axis(Axis(0)), pitchf.insert_axis(Axis(0)))
+            Some((pitch.insert_axis(Axis(0)), pitchf.insert_axis(Axis(0))))
         };
 
-        let pitch_time = start_time.elapsed() - hubert_time;
+        let pitch_time = pitch_start.elapsed();
+
+        // the encoder ran alongside the pitch extraction, so its time is until its features were
+        // in, which may well cover the pitch extraction as well
+        let mut raw_hubert = encoding.wait()?;
+        let hubert_time = start_time.elapsed();
+
+        // raw features run at half the frame rate of skip_head
+        if index_rate > 0. && self.indices.iter().any(Option::is_some) {
+            let skip_frames = skip_head / 2;
+            let index_rates = Self::frame_index_rates(
+                pitch.as_ref().map(|(_, pitchf)| pitchf.row(0)),
+                index_rate,
+                raw_hubert.len_of(Axis(2)),
+                skip_frames,
+                skip_head,
+            );
+            self.retrieve_feature(&mut raw_hubert, &index_rates, skip_frames)?;
+        }
+
+        let hubert_output = Self::extend_feature(raw_hubert);
+        // let hubert_output = hubert_output.slice(s![.., ..hubert_length, ..]);
+        let hubert_output = hubert_output.slice(s![.., skip_head..skip_head + return_length, ..]);
+
+        let retrieval_time = start_time.elapsed() - hubert_time;
 
         // let ds = 0;
         // let ds = ndarray::Array1::from_elem(1, ds as i32);
@@ -190,31 +888,61 @@ impl RvcInfer {
         // let skip_head = ndarray::Array1::from_elem(1, skip_head as i64);
         // let return_length = ndarray::Array1::from_elem(1, return_length as i64);
 
-        let output = {
+        let out = {
             let session = self.session.as_ref().unwrap();
-            session.run(ort::inputs![
-                "phone" => hubert_output, 
-                // "phone_lengths" => hubert_length_arr, 
-                "pitch" => pitch,
-                "pitchf" => pitchf,
-                // "ds" => ds,
-                // "rnd" => rnd
-                // "skip_head" => skip_head,
-                // "max_len" => return_length,
-            ]?)?
-        };
+            let mut inputs: Vec<(&str, ort::DynValue)> = vec![
+                ("phone", ort::Tensor::from_array(hubert_output.to_owned())?.into_dyn()),
+                // ("phone_lengths", hubert_length_arr),
+                // ("rnd", rnd),
+                // ("skip_head", skip_head),
+                // ("max_len", return_length),
+            ];
+            if let Some((pitch, pitchf)) = pitch {
+                inputs.push(("pitch", ort::Tensor::from_array(pitch)?.into_dyn()));
+                inputs.push(("pitchf", ort::Tensor::from_array(pitchf)?.into_dyn()));
+            }
+            if let Some(speaker_input) = self.speaker_input {
+                let speaker_id = ndarray::Array1::from_elem(1, self.speaker_id);
+                inputs.push((speaker_input, ort::Tensor::from_array(speaker_id)?.into_dyn()));
+            }
+            if self.takes_speaker_embedding {
+                let embedding = match &self.speaker_embeddings {
+                    Some(embeddings) => embeddings.blend(
+                        self.speaker_id as usize,
+                        self.morph_speaker_id as usize,
+                        self.speaker_morph,
+                    ),
+                    // gin_channels of every RVC config
+                    None => ndarray::Array3::zeros((1, 256, 1)),
+                };
+                inputs.push((SPEAKER_EMBEDDING_INPUT_NAME, ort::Tensor::from_array(embedding)?.into_dyn()));
+            }
 
-        let output_tensor = output["audio"]
-            .try_extract_tensor::<f32>()?
-            .into_dimensionality::<ndarray::Ix1>()?;
+            let extract = |output: &ort::SessionOutputs| -> Result<ndarray::Array1<f32>, RvcInferError> {
+                let output_tensor = output["audio"]
+                    .try_extract_tensor::<f32>()?
+                    .into_dimensionality::<ndarray::Ix1>()?;
+                Ok(output_tensor
+                    // .remove_axis(Axis(0))
+                    // .remove_axis(Axis(0))
+                    .to_owned())
+            };
+            match self.pinned_output_device {
+                Some(device_id) => run_with_pinned_outputs(session, inputs, device_id, extract)?,
+                None => extract(&session.run(inputs)?)?,
+            }
+        };
 
-        let out = output_tensor
-            // .remove_axis(Axis(0))
-            // .remove_axis(Axis(0))
-            .to_owned();
-            // .mapv(|x| x * 32767.0f32);
+        // half precision overflows on some models; this frame fails and goes out unconverted,
+        // the next ones run in FP32
+        if self.session_config.fp16 && out.iter().any(|sample| !sample.is_finite()) {
+            self.fall_back_to_fp32()?;
+            return Err(RvcInferError::NonFiniteOutput);
+        }
 
-        eprintln!("hubert: {:?}, pitch: {:?}, inference: {:?}", hubert_time, pitch_time, start_time.elapsed() - pitch_time - hubert_time);
+        let inference_time = start_time.elapsed() - hubert_time - retrieval_time;
+        // reported per frame through stage_times, not on stderr where it would end up in the log
+        self.stage_times = [hubert_time, pitch_time + retrieval_time, inference_time];
 
         Ok(out)
     }
@@ -0,0 +1,58 @@
+use std::path::Path;
+
+use ndarray::{Array2, Array3, Axis};
+
+use rvc_common::errors::RvcInferError;
+
+use crate::npy::parse_npy;
+
+/// The speaker embedding table of a multi-speaker model, `emb_g.weight` saved with `np.save`
+/// next to the model. Models exported with a `g` input take the embedding directly, which is
+/// what makes blending two speakers possible.
+pub struct SpeakerEmbeddings {
+    // (speakers, gin_channels)
+    table: Array2<f32>,
+}
+
+impl SpeakerEmbeddings {
+    pub fn load(path: &Path) -> Result<Self, RvcInferError> {
+        let bytes = std::fs::read(path).map_err(|e| RvcInferError::Speaker(format!("{}: {}", path.display(), e)))?;
+        let table = parse_npy(&bytes).map_err(|e| RvcInferError::Speaker(format!("{}: {}", path.display(), e)))?;
+        Ok(SpeakerEmbeddings { table })
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.nrows()
+    }
+
+    /// The `(1, gin_channels, 1)` embedding `amount` of the way from `speaker` to `target`.
+    /// Out of range ids fall back to the last speaker.
+    pub fn blend(&self, speaker: usize, target: usize, amount: f32) -> Array3<f32> {
+        let last = self.len().saturating_sub(1);
+        let from = self.table.row(usize::min(speaker, last));
+        let to = self.table.row(usize::min(target, last));
+        let amount = amount.clamp(0., 1.);
+        let embedding = &from * (1. - amount) + &to * amount;
+        embedding.insert_axis(Axis(0)).insert_axis(Axis(2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::npy::npy_bytes;
+
+    #[test]
+    fn test_speaker_embedding_blend() {
+        let table = parse_npy(&npy_bytes((2, 3), &[0.0, 1.0, 2.0, 4.0, 5.0, 6.0])).unwrap();
+        assert_eq!(table.shape(), &[2, 3]);
+
+        let embeddings = SpeakerEmbeddings { table };
+        let halfway = embeddings.blend(0, 1, 0.5);
+        assert_eq!(halfway.shape(), &[1, 3, 1]);
+        assert_eq!(halfway.into_raw_vec(), vec![2.0, 3.0, 4.0]);
+        assert_eq!(embeddings.blend(1, 7, 0.3).into_raw_vec(), vec![4.0, 5.0, 6.0]);
+
+        assert!(parse_npy(b"not an array").is_err());
+    }
+}
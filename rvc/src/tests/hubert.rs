@@ -11,7 +11,7 @@ mod tests {
     fn test_hubert_v2() {
         init_ort();
         let mut rvc = get_rvc();
-        rvc.load_contentvec(rvc_common::enums::RvcModelVersion::V2).unwrap();
+        rvc.load_encoder(rvc_common::enums::FeatureEncoder::ContentVec, rvc_common::enums::RvcModelVersion::V2, None, None).unwrap();
         let input: Array1<f32> = read_npy("D:\\obs-rvc\\rvc\\src\\tests\\input_wav.npy").unwrap();
         let feats: Array3<f32> = read_npy("D:\\obs-rvc\\rvc\\src\\tests\\feats.npy").unwrap();
         let output = rvc.extract_feature(input.view()).unwrap();
@@ -0,0 +1,136 @@
+"""Exports an RVC .pth checkpoint to the ONNX layout the plugin loads.
+
+The exported synthesizer takes `phone` (1, frames, 256 or 768), `pitch` and `pitchf`
+(1, frames) for models with pitch, and `ds` (1,) for the speaker of multi-speaker models, and
+returns `audio` as a flat array. The noise and the feature lengths are made inside the graph. The output sample rate is
+stored in the model metadata under `sample_rate`, next to the `version` it was detected as.
+
+Needs PyTorch, onnx and a checkout of RVC for the model definitions:
+
+    python export_onnx.py voice.pth [voice.onnx] [--rvc-dir=<path to RVC>]
+"""
+
+import os
+import sys
+
+import torch
+
+FEATURE_CHANNELS = {"v1": 256, "v2": 768}
+# frames of the dummy input traced through the model, the frame axis stays dynamic
+TRACE_FRAMES = 200
+
+
+def import_synthesizers(rvc_dir):
+    if rvc_dir:
+        sys.path.insert(0, rvc_dir)
+    # the module moved between RVC releases
+    for module_name in ("infer.lib.infer_pack.models", "lib.infer_pack.models", "infer_pack.models"):
+        try:
+            return __import__(module_name, fromlist=["models"])
+        except ImportError:
+            continue
+    sys.exit("RVC's model definitions not found, pass --rvc-dir=<path to RVC>")
+
+
+def detect_version(checkpoint):
+    # checkpoints of older RVC releases don't say, the feature projection does
+    channels = checkpoint["weight"]["enc_p.emb_phone.weight"].shape[1]
+    for version, version_channels in FEATURE_CHANNELS.items():
+        if channels == version_channels:
+            return version
+    sys.exit(f"unexpected feature dimension {channels}")
+
+
+def detect_sample_rate(checkpoint):
+    sample_rate = checkpoint.get("sr", checkpoint["config"][-1])
+    if isinstance(sample_rate, str):
+        # "40k"
+        sample_rate = int(sample_rate.rstrip("k")) * 1000 if sample_rate.endswith("k") else int(sample_rate)
+    return int(sample_rate)
+
+
+class ExportedSynthesizer(torch.nn.Module):
+    def __init__(self, net_g, multi_speaker):
+        super().__init__()
+        self.net_g = net_g
+        self.multi_speaker = multi_speaker
+
+    # (pitch, pitchf) with pitch, followed by ds for multi-speaker models
+    def forward(self, phone, *conditions):
+        phone_lengths = torch._shape_as_tensor(phone)[1:2].long()
+        if not self.multi_speaker:
+            # the only speaker is built into the graph, an input would have the plugin offer a choice
+            conditions = (*conditions, torch.zeros(1, dtype=torch.long))
+        audio = self.net_g.infer(phone, phone_lengths, *conditions)[0]
+        return audio.reshape(-1)
+
+
+def main():
+    args = [arg for arg in sys.argv[1:] if not arg.startswith("--rvc-dir=")]
+    rvc_dir = next((arg.split("=", 1)[1] for arg in sys.argv[1:] if arg.startswith("--rvc-dir=")), None)
+    if not args:
+        sys.exit(__doc__)
+    input_path = args[0]
+    output_path = args[1] if len(args) > 1 else os.path.splitext(input_path)[0] + ".onnx"
+
+    models = import_synthesizers(rvc_dir)
+    checkpoint = torch.load(input_path, map_location="cpu")
+    version = detect_version(checkpoint)
+    f0 = bool(checkpoint.get("f0", 1))
+    sample_rate = detect_sample_rate(checkpoint)
+
+    # the speaker count is only right in the weights
+    speakers = checkpoint["weight"]["emb_g.weight"].shape[0]
+    checkpoint["config"][-3] = speakers
+    synthesizer = {
+        ("v1", True): models.SynthesizerTrnMs256NSFsid,
+        ("v1", False): models.SynthesizerTrnMs256NSFsid_nono,
+        ("v2", True): models.SynthesizerTrnMs768NSFsid,
+        ("v2", False): models.SynthesizerTrnMs768NSFsid_nono,
+    }[(version, f0)]
+    net_g = synthesizer(*checkpoint["config"], is_half=False) if f0 else synthesizer(*checkpoint["config"])
+    # the posterior encoder is only used in training
+    del net_g.enc_q
+    net_g.load_state_dict(checkpoint["weight"], strict=False)
+    net_g.eval()
+
+    phone = torch.rand(1, TRACE_FRAMES, FEATURE_CHANNELS[version])
+    pitch = torch.randint(low=5, high=255, size=(1, TRACE_FRAMES), dtype=torch.long)
+    pitchf = torch.rand(1, TRACE_FRAMES) * 500
+    ds = torch.zeros(1, dtype=torch.long)
+
+    if f0:
+        inputs, input_names = (phone, pitch, pitchf), ["phone", "pitch", "pitchf"]
+        dynamic_axes = {"phone": {1: "frames"}, "pitch": {1: "frames"}, "pitchf": {1: "frames"}}
+    else:
+        inputs, input_names = (phone,), ["phone"]
+        dynamic_axes = {"phone": {1: "frames"}}
+    if speakers > 1:
+        inputs, input_names = (*inputs, ds), [*input_names, "ds"]
+    dynamic_axes["audio"] = {0: "samples"}
+
+    with torch.no_grad():
+        torch.onnx.export(
+            ExportedSynthesizer(net_g, speakers > 1),
+            inputs,
+            output_path,
+            input_names=input_names,
+            output_names=["audio"],
+            dynamic_axes=dynamic_axes,
+            opset_version=17,
+            do_constant_folding=True,
+        )
+
+    import onnx
+
+    model = onnx.load(output_path)
+    for key, value in (("sample_rate", str(sample_rate)), ("version", version)):
+        entry = model.metadata_props.add()
+        entry.key, entry.value = key, value
+    onnx.save(model, output_path)
+
+    print(f"{output_path}: {version}, {'with' if f0 else 'without'} pitch, {sample_rate} Hz, {speakers} speakers")
+
+
+if __name__ == "__main__":
+    main()